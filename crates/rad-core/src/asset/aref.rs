@@ -1,21 +1,30 @@
 use std::{
+	any::Any,
 	fmt::{Debug, Display},
-	hash::Hash,
+	hash::{Hash, Hasher},
 	io,
 	marker::PhantomData,
 	ops::Deref,
-	sync::{Arc, OnceLock, RwLock},
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc,
+		Mutex,
+		OnceLock,
+		RwLock,
+	},
 };
 
 use bevy_reflect::{Reflect, ReflectDeserialize, ReflectSerialize, TypePath};
 use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
-use rustc_hash::FxHashMap;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rustc_hash::{FxHashMap, FxHasher};
 use serde::{Deserialize, Serialize};
+use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-	asset::{Asset, AssetView},
+	asset::{Asset, AssetView, LiveView, ViewReport},
 	Engine,
 };
 
@@ -81,6 +90,38 @@ impl<T> AssetId<T> {
 struct ARefData<T: AssetView> {
 	id: AssetId<<T::Base as Asset>::Root>,
 	data: OnceLock<T>,
+	last_used: AtomicU64,
+	stale: AtomicBool,
+	/// Set while a background load is running.
+	loading: AtomicBool,
+	/// Set if the last background load failed.
+	failed: AtomicBool,
+}
+
+impl<T: AssetView> ARefData<T> {
+	fn touch(&self) { self.last_used.store(Engine::get().assets.tick(), Ordering::Relaxed); }
+
+	/// End a background load.
+	fn finish_loading(&self, res: Result<(), io::Error>) {
+		if let Err(e) = res {
+			error!("failed to load asset {:?}: {e}", self.id);
+			self.failed.store(true, Ordering::Relaxed);
+		}
+		self.loading.store(false, Ordering::Release);
+	}
+}
+
+impl<T: AssetView> Drop for ARefData<T> {
+	fn drop(&mut self) {
+		// Invalidated views are no longer in the cache, so the last reference hands them off for deletion.
+		if *self.stale.get_mut() {
+			if let Some(view) = self.data.take() {
+				let assets = &Engine::get().assets;
+				assets.untrack(view.size());
+				assets.cache::<T>().evicted.lock().unwrap().push(view);
+			}
+		}
+	}
 }
 
 // TODO: support weak references.
@@ -98,13 +139,109 @@ impl<T: AssetView> ARef<T> {
 		Engine::get().assets.cache::<T>().loaded(id)
 	}
 
+	/// Create loaded references to several asset views, loading the ones that aren't loaded yet in one batch with
+	/// [`AssetView::load_many`]. This function will block until all of them are loaded.
+	pub fn loaded_many(ids: &[AssetId<<T::Base as Asset>::Root>]) -> Vec<Result<LARef<T>, io::Error>>
+	where
+		T::Base: Send,
+	{
+		Engine::get().assets.cache::<T>().loaded_many(ids)
+	}
+
 	/// Load the asset view. This function will block until the asset view is loaded.
 	pub fn load(self) -> Result<LARef<T>, io::Error> {
 		Engine::get().assets.cache::<T>().load(&self.inner)?;
 		Ok(LARef { inner: self })
 	}
 
+	/// Start loading the asset view in the background, if it isn't loaded or loading already. Use
+	/// [`try_loaded`](Self::try_loaded) to check if it is done.
+	///
+	/// The asset is read on a worker thread, and the view is then created by
+	/// [`Engine::run_staged_loads`](crate::Engine::run_staged_loads), so that its GPU work is recorded on the thread
+	/// that drives frames.
+	pub fn load_in_background(&self)
+	where
+		T::Base: Send,
+	{
+		if self.inner.data.get().is_some() || self.inner.loading.swap(true, Ordering::AcqRel) {
+			return;
+		}
+		self.inner.failed.store(false, Ordering::Relaxed);
+		let inner = self.inner.clone();
+		rayon::spawn(move || {
+			let assets = &Engine::get().assets;
+			match assets.cache::<T>().read(&inner) {
+				Ok(base) => assets.stage(move || {
+					let res = assets.cache::<T>().create(&inner, base).map(|_| ());
+					inner.finish_loading(res);
+				}),
+				Err(e) => inner.finish_loading(Err(e)),
+			}
+		});
+	}
+
+	/// Like [`Self::load_in_background`] for several asset views, creating them in one batch with
+	/// [`AssetView::load_many`]. Views that are loaded or loading already are left out.
+	pub fn load_many_in_background<'a>(refs: impl IntoIterator<Item = &'a Self>)
+	where
+		T::Base: Send,
+	{
+		let todo: Vec<_> = refs
+			.into_iter()
+			.filter(|x| x.inner.data.get().is_none() && !x.inner.loading.swap(true, Ordering::AcqRel))
+			.map(|x| {
+				x.inner.failed.store(false, Ordering::Relaxed);
+				x.inner.clone()
+			})
+			.collect();
+		if todo.is_empty() {
+			return;
+		}
+		rayon::spawn(move || {
+			let assets = &Engine::get().assets;
+			let bases: Vec<_> = todo.par_iter().map(|x| assets.cache::<T>().read(x)).collect();
+			let mut loading = Vec::with_capacity(todo.len());
+			let mut loaded = Vec::with_capacity(todo.len());
+			for (inner, base) in todo.into_iter().zip(bases) {
+				match base {
+					Ok(x) => {
+						loading.push(inner);
+						loaded.push(x);
+					},
+					Err(e) => inner.finish_loading(Err(e)),
+				}
+			}
+			if loading.is_empty() {
+				return;
+			}
+			assets.stage(move || {
+				let refs: Vec<_> = loading.iter().collect();
+				for (inner, res) in loading.iter().zip(assets.cache::<T>().create_many(&refs, loaded)) {
+					inner.finish_loading(res);
+				}
+			});
+		});
+	}
+
+	/// A loaded reference, if the asset view has finished loading.
+	pub fn try_loaded(&self) -> Option<LARef<T>> {
+		self.inner.data.get()?;
+		Some(LARef {
+			inner: ARef {
+				inner: self.inner.clone(),
+			},
+		})
+	}
+
+	/// Whether the last background load failed.
+	pub fn failed(&self) -> bool { self.inner.failed.load(Ordering::Relaxed) }
+
 	pub fn id(&self) -> AssetId<<T::Base as Asset>::Root> { self.inner.id }
+
+	/// Whether the asset has been invalidated since this reference was created. A fresh reference to the same ID will
+	/// load the new version.
+	pub fn is_stale(&self) -> bool { self.inner.stale.load(Ordering::Relaxed) }
 }
 
 /// A loaded asset view
@@ -116,6 +253,23 @@ impl<T: AssetView> LARef<T> {
 	pub fn into_inner(self) -> ARef<T> { self.inner }
 
 	pub fn id(&self) -> AssetId<<T::Base as Asset>::Root> { self.inner.id() }
+
+	pub fn is_stale(&self) -> bool { self.inner.is_stale() }
+
+	/// Load the new version of a stale view. Returns `None` if the view isn't stale, or if loading the new version
+	/// failed before and the asset hasn't changed since, so that a broken asset isn't reloaded every frame.
+	pub fn reload(&self) -> Option<Result<LARef<T>, io::Error>> {
+		if !self.is_stale() {
+			return None;
+		}
+		// A failed load stays in the cache until the asset is invalidated again.
+		let new = ARef::unloaded(self.id());
+		if new.failed() {
+			return None;
+		}
+		let inner = new.inner.clone();
+		Some(new.load().inspect_err(|_| inner.failed.store(true, Ordering::Relaxed)))
+	}
 }
 
 impl<T: AssetView> Deref for LARef<T> {
@@ -124,32 +278,55 @@ impl<T: AssetView> Deref for LARef<T> {
 	fn deref(&self) -> &Self::Target { unsafe { self.inner.inner.data.get().unwrap_unchecked() } }
 }
 
+/// The number of separately locked parts of a cache, so that threads using different assets rarely wait on each other.
+const SHARDS: usize = 16;
+
+type Shard<T> = RwLock<FxHashMap<AssetId<<<T as AssetView>::Base as Asset>::Root>, Arc<ARefData<T>>>>;
+
+/// The views of one type. Loads run outside of any lock, and concurrent loads of the same asset wait on the first
+/// one instead of loading it again.
 pub struct AssetCache<T: AssetView> {
-	context: T::Ctx,
-	loaded: RwLock<FxHashMap<AssetId<<T::Base as Asset>::Root>, Arc<ARefData<T>>>>,
+	pub(crate) context: T::Ctx,
+	loaded: [Shard<T>; SHARDS],
+	evicted: Mutex<Vec<T>>,
 }
 
 impl<T: AssetView> AssetCache<T> {
 	pub fn new() -> Self {
 		Self {
 			context: T::Ctx::default(),
-			loaded: RwLock::new(FxHashMap::default()),
+			loaded: std::array::from_fn(|_| RwLock::new(FxHashMap::default())),
+			evicted: Mutex::new(Vec::new()),
 		}
 	}
 
+	fn shard(&self, id: AssetId<<T::Base as Asset>::Root>) -> &Shard<T> {
+		let mut hasher = FxHasher::default();
+		id.hash(&mut hasher);
+		&self.loaded[hasher.finish() as usize % SHARDS]
+	}
+
 	pub fn unloaded(&self, id: AssetId<<T::Base as Asset>::Root>) -> ARef<T> {
-		let read = self.loaded.read().unwrap();
+		let shard = self.shard(id);
+		let read = shard.read().unwrap();
 		match read.get(&id) {
-			Some(data) => ARef { inner: data.clone() },
+			Some(data) => {
+				data.touch();
+				ARef { inner: data.clone() }
+			},
 			None => {
 				drop(read);
-				let mut write = self.loaded.write().unwrap();
+				let mut write = shard.write().unwrap();
 				let inner = write
 					.entry(id)
 					.or_insert_with(|| {
 						Arc::new(ARefData {
 							id,
 							data: OnceLock::new(),
+							last_used: AtomicU64::new(Engine::get().assets.tick()),
+							stale: AtomicBool::new(false),
+							loading: AtomicBool::new(false),
+							failed: AtomicBool::new(false),
 						})
 					})
 					.clone();
@@ -164,10 +341,269 @@ impl<T: AssetView> AssetCache<T> {
 		Ok(LARef { inner })
 	}
 
+	pub fn loaded_many(&'static self, ids: &[AssetId<<T::Base as Asset>::Root>]) -> Vec<Result<LARef<T>, io::Error>>
+	where
+		T::Base: Send,
+	{
+		let refs: Vec<_> = ids.iter().map(|&id| self.unloaded(id)).collect();
+		let mut todo: Vec<_> = refs
+			.iter()
+			.map(|x| &x.inner)
+			.filter(|x| x.data.get().is_none())
+			.collect();
+		todo.sort_unstable_by_key(|x| Arc::as_ptr(x));
+		todo.dedup_by_key(|x| Arc::as_ptr(x));
+
+		let bases: Vec<_> = todo.par_iter().map(|x| self.read(x)).collect();
+		let mut errors = FxHashMap::default();
+		let mut loading = Vec::with_capacity(todo.len());
+		let mut loaded = Vec::with_capacity(todo.len());
+		for (inner, base) in todo.into_iter().zip(bases) {
+			match base {
+				Ok(x) => {
+					loading.push(inner);
+					loaded.push(x);
+				},
+				Err(e) => {
+					errors.insert(inner.id, e);
+				},
+			}
+		}
+
+		for (inner, res) in loading.iter().zip(self.create_many(&loading, loaded)) {
+			if let Err(e) = res {
+				errors.insert(inner.id, e);
+			}
+		}
+
+		refs.into_iter()
+			.map(|inner| match errors.get(&inner.id()) {
+				Some(e) => Err(io::Error::new(e.kind(), e.to_string())),
+				None => {
+					self.load(&inner.inner)?;
+					Ok(LARef { inner })
+				},
+			})
+			.collect()
+	}
+
 	fn load<'a>(&'static self, inner: &'a ARefData<T>) -> Result<&'a T, io::Error> {
-		inner.data.get_or_try_init(|| {
+		inner.touch();
+		let mut loaded = false;
+		let data = inner.data.get_or_try_init(|| {
 			let asset = Engine::get().assets.load_asset(inner.id)?;
+			Self::add_dependencies(inner.id, &asset);
+			loaded = true;
 			T::load(&self.context, asset)
-		})
+		})?;
+		if loaded {
+			Engine::get().assets.track(data.size());
+		}
+		Ok(data)
+	}
+
+	/// Read the asset a view is created from. This is the part of a load that doesn't touch the GPU.
+	fn read(&self, inner: &ARefData<T>) -> Result<T::Base, io::Error> {
+		let asset = Engine::get().assets.load_asset(inner.id)?;
+		Self::add_dependencies(inner.id, &asset);
+		Ok(asset)
+	}
+
+	/// Create a view from its asset, unless it has been loaded in the meantime.
+	fn create<'a>(&'static self, inner: &'a ARefData<T>, base: T::Base) -> Result<&'a T, io::Error> {
+		let mut loaded = false;
+		let data = inner.data.get_or_try_init(|| {
+			loaded = true;
+			T::load(&self.context, base)
+		})?;
+		if loaded {
+			Engine::get().assets.track(data.size());
+		}
+		Ok(data)
+	}
+
+	/// Create several views from their assets in one batch with [`AssetView::load_many`].
+	fn create_many(&'static self, inners: &[&Arc<ARefData<T>>], bases: Vec<T::Base>) -> Vec<Result<(), io::Error>> {
+		inners
+			.iter()
+			.zip(T::load_many(&self.context, bases))
+			.map(|(inner, view)| {
+				let view = view?;
+				let size = view.size();
+				match inner.data.set(view) {
+					Ok(()) => Engine::get().assets.track(size),
+					// Loaded by someone else in the meantime.
+					Err(view) => self.evicted.lock().unwrap().push(view),
+				}
+				Ok(())
+			})
+			.collect()
+	}
+
+	fn add_dependencies(id: AssetId<<T::Base as Asset>::Root>, base: &T::Base) {
+		let mut deps = Vec::new();
+		T::dependencies(base, &mut deps);
+		if !deps.is_empty() {
+			Engine::get().assets.add_dependencies(id.to_untyped(), &deps);
+		}
+	}
+
+	pub(crate) fn take_evicted(&self) -> Vec<T> { std::mem::take(&mut *self.evicted.lock().unwrap()) }
+}
+
+/// A type-erased [`AssetCache`], used for budget eviction across all views.
+pub(crate) trait ErasedCache: Send + Sync {
+	fn as_any(&self) -> &dyn Any;
+
+	/// Collect the last use and ID of every loaded view that is only referenced by the cache.
+	fn released(&self, out: &mut Vec<(u64, UntypedAssetId)>);
+
+	/// Evict a released view, returning its size. Returns `None` if the view has been referenced again.
+	fn evict(&self, id: UntypedAssetId) -> Option<u64>;
+
+	/// Remove a view from the cache, marking existing references as stale.
+	fn invalidate(&self, id: UntypedAssetId);
+
+	/// Report the views in the cache, without names.
+	fn report(&self) -> ViewReport;
+}
+
+impl<T: AssetView> ErasedCache for AssetCache<T> {
+	fn as_any(&self) -> &dyn Any { self }
+
+	fn released(&self, out: &mut Vec<(u64, UntypedAssetId)>) {
+		for shard in self.loaded.iter() {
+			let read = shard.read().unwrap();
+			out.extend(read.iter().filter_map(|(id, data)| {
+				let size = data.data.get()?.size();
+				(Arc::strong_count(data) == 1 && size > 0)
+					.then(|| (data.last_used.load(Ordering::Relaxed), id.to_untyped()))
+			}));
+		}
+	}
+
+	fn evict(&self, id: UntypedAssetId) -> Option<u64> {
+		let id = unsafe { id.typed() };
+		let mut write = self.shard(id).write().unwrap();
+		// No new references can be created while the lock is held.
+		if write
+			.get(&id)
+			.is_none_or(|data| Arc::strong_count(data) != 1 || data.data.get().is_none())
+		{
+			return None;
+		}
+
+		let mut data = Arc::into_inner(write.remove(&id).unwrap()).unwrap();
+		drop(write);
+		let view = data.data.take().unwrap();
+		let size = view.size();
+		self.evicted.lock().unwrap().push(view);
+		Some(size)
+	}
+
+	fn invalidate(&self, id: UntypedAssetId) {
+		let id = unsafe { id.typed() };
+		let data = self.shard(id).write().unwrap().remove(&id);
+		if let Some(data) = data {
+			data.stale.store(true, Ordering::Relaxed);
+		}
+	}
+
+	fn report(&self) -> ViewReport {
+		let mut live = Vec::new();
+		for shard in self.loaded.iter() {
+			let read = shard.read().unwrap();
+			live.extend(read.iter().map(|(id, data)| LiveView {
+				id: id.to_untyped(),
+				name: None,
+				refs: Arc::strong_count(data) - 1,
+				loaded: data.data.get().is_some(),
+			}));
+		}
+		live.sort_unstable_by_key(|x| std::cmp::Reverse(x.refs));
+		ViewReport {
+			ty: std::any::type_name::<T>(),
+			live,
+			pending_deletion: self.evicted.lock().unwrap().len(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{sync::atomic::AtomicU32, thread, time::Duration};
+
+	use super::*;
+	use crate::asset::{AssetRead, AssetSource, AssetWrite};
+
+	struct Data;
+
+	impl Asset for Data {
+		const UUID: Uuid = uuid::uuid!("4c3f8f5e-2a4b-4d0e-9a57-3f0f6d1b2c7a");
+
+		fn load(_: Box<dyn AssetRead>) -> Result<Self, io::Error> { Ok(Self) }
+
+		fn save(&self, _: &mut dyn AssetWrite) -> Result<(), io::Error> { Ok(()) }
+	}
+
+	impl AssetRead for io::Empty {}
+
+	struct Source;
+
+	impl AssetSource for Source {
+		fn load(&self, _: UntypedAssetId, _: Uuid) -> Result<Box<dyn AssetRead>, io::Error> {
+			Ok(Box::new(io::empty()))
+		}
+	}
+
+	static LOADS: AtomicU32 = AtomicU32::new(0);
+
+	struct View;
+
+	impl AssetView for View {
+		type Base = Data;
+		type Ctx = ();
+
+		fn load(_: &'static Self::Ctx, _: Self::Base) -> Result<Self, io::Error> {
+			LOADS.fetch_add(1, Ordering::Relaxed);
+			// Long enough for the other threads to pile up on the same load.
+			thread::sleep(Duration::from_millis(20));
+			Ok(Self)
+		}
+	}
+
+	#[test]
+	fn concurrent_loads() {
+		let mut engine = Engine::builder();
+		engine.asset_source(Source);
+		engine.asset::<Data>();
+		engine.asset_view::<View>();
+		engine.build();
+
+		let shared = AssetId::<Data>::new();
+		let ids: Vec<_> = (0..32).map(|_| AssetId::<Data>::new()).collect();
+		let refs: Vec<_> = thread::scope(|s| {
+			let threads: Vec<_> = ids
+				.iter()
+				.map(|&id| {
+					s.spawn(move || {
+						let a = ARef::<View>::loaded(shared).unwrap();
+						let b = ARef::<View>::loaded(id).unwrap();
+						(a, b)
+					})
+				})
+				.collect();
+			threads.into_iter().map(|t| t.join().unwrap()).collect()
+		});
+
+		assert_eq!(LOADS.load(Ordering::Relaxed), ids.len() as u32 + 1);
+		let report = Engine::get().asset_report();
+		let live = &report.views[0].live;
+		assert_eq!(live.len(), ids.len() + 1);
+		assert_eq!(
+			live.iter().find(|x| x.id == shared.to_untyped()).unwrap().refs,
+			ids.len()
+		);
+		drop(refs);
 	}
 }
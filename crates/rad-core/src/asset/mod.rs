@@ -1,9 +1,16 @@
 use std::{
 	alloc::Layout,
-	any::{Any, TypeId},
+	any::TypeId,
+	collections::VecDeque,
+	fmt::{self, Display},
 	io::{self, Read, Write},
 	mem::MaybeUninit,
-	sync::Arc,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+		Mutex,
+	},
+	time::{Duration, Instant},
 };
 
 use bincode::{
@@ -11,11 +18,11 @@ use bincode::{
 	Decode,
 	Encode,
 };
-use rustc_hash::FxHashMap;
-use tracing::{trace_span, warn};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{trace, trace_span, warn};
 pub use uuid::Uuid;
 
-use crate::asset::aref::{AssetCache, AssetId, UntypedAssetId};
+use crate::asset::aref::{AssetCache, AssetId, ErasedCache, UntypedAssetId};
 
 pub mod aref;
 
@@ -78,15 +85,90 @@ pub trait AssetView: Sized + Send + Sync + 'static {
 	type Ctx: Default + Send + Sync + 'static;
 
 	fn load(ctx: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error>;
+
+	/// Load several views at once, so that they can share work such as GPU submissions. A failure should only fail
+	/// the views it affects. Every view is loaded on its own by default.
+	fn load_many(ctx: &'static Self::Ctx, bases: Vec<Self::Base>) -> Vec<Result<Self, io::Error>> {
+		bases.into_iter().map(|x| Self::load(ctx, x)).collect()
+	}
+
+	/// The approximate amount of GPU memory owned by this view, counted against the asset budget.
+	fn size(&self) -> u64 { 0 }
+
+	/// The assets whose views are used by the view of `base`. Invalidating any of them also invalidates this view, so
+	/// that it is reloaded with their new versions.
+	fn dependencies(base: &Self::Base, out: &mut Vec<UntypedAssetId>) { let _ = (base, out); }
 }
 
 pub trait AssetSource: Send + Sync + 'static {
 	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error>;
+
+	/// A human-readable name for the asset, such as the path it was imported to. Only used for diagnostics.
+	fn name(&self, id: UntypedAssetId) -> Option<String> {
+		let _ = id;
+		None
+	}
 }
 impl<T: AssetSource> AssetSource for Arc<T> {
 	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error> {
 		T::load(self.as_ref(), id, ty)
 	}
+
+	fn name(&self, id: UntypedAssetId) -> Option<String> { T::name(self.as_ref(), id) }
+}
+
+/// The asset views alive in an [`AssetRegistry`], for tracking down leaks.
+pub struct AssetReport {
+	pub views: Vec<ViewReport>,
+}
+
+/// The views of one [`AssetView`] type.
+pub struct ViewReport {
+	pub ty: &'static str,
+	/// Views in the cache, whether or not they are referenced.
+	pub live: Vec<LiveView>,
+	/// Views evicted or invalidated whose resources haven't been taken for deletion yet.
+	pub pending_deletion: usize,
+}
+
+pub struct LiveView {
+	pub id: UntypedAssetId,
+	pub name: Option<String>,
+	/// References held outside of the cache.
+	pub refs: usize,
+	pub loaded: bool,
+}
+
+impl AssetReport {
+	/// Views that are referenced outside of the cache.
+	pub fn referenced(&self) -> usize { self.views.iter().flat_map(|v| &v.live).filter(|x| x.refs > 0).count() }
+
+	pub fn pending_deletion(&self) -> usize { self.views.iter().map(|v| v.pending_deletion).sum() }
+}
+
+impl Display for AssetReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for view in self.views.iter() {
+			if view.live.is_empty() && view.pending_deletion == 0 {
+				continue;
+			}
+			writeln!(
+				f,
+				"{}: {} live, {} pending deletion",
+				view.ty,
+				view.live.len(),
+				view.pending_deletion
+			)?;
+			for x in view.live.iter() {
+				write!(f, "\t{}", x.id)?;
+				if let Some(name) = &x.name {
+					write!(f, " ({name})")?;
+				}
+				writeln!(f, ": {} refs{}", x.refs, if x.loaded { "" } else { ", not loaded" })?;
+			}
+		}
+		Ok(())
+	}
 }
 
 struct Kitchen {
@@ -95,25 +177,41 @@ struct Kitchen {
 	cook: fn(base: *const (), out: *mut ()),
 }
 type ErasedAssetLoad = fn(from: Box<dyn AssetRead>, out: *mut ()) -> Result<(), io::Error>;
+type StagedLoad = Box<dyn FnOnce() + Send>;
 
 pub struct AssetRegistry {
 	cook_at_runtime: bool,
+	background_loads: bool,
 	sources: Vec<Box<dyn AssetSource>>,
 	source_to_index: FxHashMap<TypeId, usize>,
 	assets: FxHashMap<Uuid, ErasedAssetLoad>,
-	views: FxHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+	views: FxHashMap<TypeId, Box<dyn ErasedCache>>,
 	kitchens: FxHashMap<Uuid, Kitchen>,
+	budget: Option<u64>,
+	resident: AtomicU64,
+	clock: AtomicU64,
+	/// The assets whose views depend on each asset. Edges are only added, so an asset that stopped depending on
+	/// another is at worst reloaded once too often.
+	dependents: Mutex<FxHashMap<UntypedAssetId, FxHashSet<UntypedAssetId>>>,
+	/// Background loads whose assets have been read, waiting for their views to be created on the main thread.
+	staged: Mutex<VecDeque<StagedLoad>>,
 }
 
 impl AssetRegistry {
 	pub fn new() -> Self {
 		Self {
 			cook_at_runtime: false,
+			background_loads: false,
 			sources: Vec::new(),
 			source_to_index: FxHashMap::default(),
 			assets: FxHashMap::default(),
 			views: FxHashMap::default(),
 			kitchens: FxHashMap::default(),
+			budget: None,
+			resident: AtomicU64::new(0),
+			clock: AtomicU64::new(0),
+			dependents: Mutex::new(FxHashMap::default()),
+			staged: Mutex::new(VecDeque::new()),
 		}
 	}
 
@@ -157,6 +255,46 @@ impl AssetRegistry {
 
 	pub fn cook_at_runtime(&mut self) { self.cook_at_runtime = true; }
 
+	/// Load the assets used by scenes on worker threads, instead of blocking the frame that first uses them.
+	pub fn load_in_background(&mut self) { self.background_loads = true; }
+
+	pub fn loads_in_background(&self) -> bool { self.background_loads }
+
+	/// Queue the rest of a background load, to run on the next call to [`Self::run_staged`].
+	pub(crate) fn stage(&self, load: impl FnOnce() + Send + 'static) {
+		self.staged.lock().unwrap().push_back(Box::new(load));
+	}
+
+	/// Finish background loads in the order they were queued, until `budget` has been spent. At least one is always
+	/// finished, so that loads make progress however long each takes. Returns how many are still queued.
+	///
+	/// This is where views record and submit their GPU work, so it must be called from the thread that drives frames.
+	pub fn run_staged(&self, budget: Duration) -> usize {
+		let start = Instant::now();
+		let mut finished = 0;
+		loop {
+			let load = {
+				let mut staged = self.staged.lock().unwrap();
+				if finished > 0 && start.elapsed() >= budget {
+					return staged.len();
+				}
+				match staged.pop_front() {
+					Some(x) => x,
+					None => return 0,
+				}
+			};
+			load();
+			finished += 1;
+		}
+	}
+
+	/// Limit the memory used by loaded asset views. When the budget is exceeded, the least recently used views that
+	/// are no longer referenced are evicted.
+	pub fn set_budget(&mut self, max_bytes: u64) { self.budget = Some(max_bytes); }
+
+	/// The memory used by all loaded asset views.
+	pub fn resident_bytes(&self) -> u64 { self.resident.load(Ordering::Relaxed) }
+
 	pub fn source<T: AssetSource>(&self) -> &T {
 		match self.source_to_index.get(&TypeId::of::<T>()) {
 			Some(&source) => unsafe { &*(self.sources[source].as_ref() as *const dyn AssetSource as *const T) },
@@ -168,13 +306,107 @@ impl AssetRegistry {
 		match self
 			.views
 			.get(&TypeId::of::<T>())
-			.and_then(|cache| cache.downcast_ref::<AssetCache<T>>())
+			.and_then(|cache| cache.as_any().downcast_ref::<AssetCache<T>>())
 		{
 			Some(cache) => cache,
 			None => panic!("view `{}` not registered", std::any::type_name::<T>()),
 		}
 	}
 
+	/// The context shared by every view of type `T`.
+	pub fn context<T: AssetView>(&self) -> &T::Ctx { &self.cache::<T>().context }
+
+	/// Take the views evicted or invalidated since the last call. Their resources must be destroyed by the caller once
+	/// the GPU is done with them.
+	pub fn take_evicted<T: AssetView>(&self) -> Vec<T> { self.cache::<T>().take_evicted() }
+
+	/// The name given to an asset by the first source that knows it.
+	pub fn name(&self, id: UntypedAssetId) -> Option<String> { self.sources.iter().rev().find_map(|s| s.name(id)) }
+
+	/// Report the views alive in every cache, and the views waiting to be deleted.
+	pub fn report(&self) -> AssetReport {
+		let mut views: Vec<_> = self.views.values().map(|cache| cache.report()).collect();
+		views.sort_unstable_by_key(|v| v.ty);
+		for x in views.iter_mut().flat_map(|v| &mut v.live) {
+			x.name = self.name(x.id);
+		}
+		AssetReport { views }
+	}
+
+	pub(crate) fn tick(&self) -> u64 { self.clock.fetch_add(1, Ordering::Relaxed) }
+
+	pub(crate) fn track(&self, size: u64) {
+		let resident = self.resident.fetch_add(size, Ordering::Relaxed) + size;
+		if let Some(budget) = self.budget {
+			if resident > budget {
+				self.evict(budget);
+			}
+		}
+	}
+
+	pub(crate) fn untrack(&self, size: u64) { self.resident.fetch_sub(size, Ordering::Relaxed); }
+
+	/// Record that the views of `dependent` use the views of `dependencies`.
+	pub(crate) fn add_dependencies(&self, dependent: UntypedAssetId, dependencies: &[UntypedAssetId]) {
+		let mut dependents = self.dependents.lock().unwrap();
+		for &dep in dependencies {
+			dependents.entry(dep).or_default().insert(dependent);
+		}
+	}
+
+	/// Drop the cached views of an asset and of every asset that transitively depends on it, so that the next load
+	/// reads them from their sources again.
+	///
+	/// Existing references keep the old view until they are dropped, and report themselves as stale.
+	pub fn invalidate(&self, id: UntypedAssetId) {
+		let s = trace_span!("invalidate asset", id = %id);
+		let _e = s.enter();
+
+		let mut seen = FxHashSet::default();
+		let mut todo = vec![id];
+		let dependents = self.dependents.lock().unwrap();
+		while let Some(id) = todo.pop() {
+			if !seen.insert(id) {
+				continue;
+			}
+			if let Some(deps) = dependents.get(&id) {
+				todo.extend(deps.iter().copied());
+			}
+		}
+		drop(dependents);
+
+		if seen.len() > 1 {
+			trace!("invalidating {} dependents", seen.len() - 1);
+		}
+		for id in seen {
+			for cache in self.views.values() {
+				cache.invalidate(id);
+			}
+		}
+	}
+
+	fn evict(&self, budget: u64) {
+		let s = trace_span!("evict assets");
+		let _e = s.enter();
+
+		let mut released = Vec::new();
+		let mut ids = Vec::new();
+		for cache in self.views.values() {
+			cache.released(&mut ids);
+			released.extend(ids.drain(..).map(|(last_used, id)| (last_used, id, cache)));
+		}
+		released.sort_unstable_by_key(|&(last_used, ..)| last_used);
+
+		for (_, id, cache) in released {
+			if self.resident.load(Ordering::Relaxed) <= budget {
+				break;
+			}
+			if let Some(size) = cache.evict(id) {
+				self.untrack(size);
+			}
+		}
+	}
+
 	#[inline(always)]
 	fn cook_dynamic(&self, id: UntypedAssetId, ty: Uuid, base: *const (), into: *mut ()) {
 		let s = trace_span!("cook asset", id = %id, ty = %ty);
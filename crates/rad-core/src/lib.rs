@@ -6,11 +6,20 @@
 use std::{
 	any::{Any, TypeId},
 	sync::OnceLock,
+	time::Duration,
 };
 
 use rustc_hash::FxHashMap;
 
-use crate::asset::{aref::AssetId, Asset, AssetRegistry, AssetSource, AssetView, CookedAsset};
+use crate::asset::{
+	aref::{AssetId, UntypedAssetId},
+	Asset,
+	AssetRegistry,
+	AssetReport,
+	AssetSource,
+	AssetView,
+	CookedAsset,
+};
 
 pub mod asset;
 
@@ -42,6 +51,28 @@ impl Engine {
 		self.assets.cook_asset(id)
 	}
 
+	/// Whether scenes should load their assets with
+	/// [`ARef::load_in_background`](asset::aref::ARef::load_in_background).
+	pub fn loads_assets_in_background(&self) -> bool { self.assets.loads_in_background() }
+
+	/// Create the views of background loads whose assets have been read, spending about `budget` on them. Views
+	/// record and submit their GPU work here, so call this once a frame from the thread that drives frames. Returns
+	/// how many loads are still waiting.
+	pub fn run_staged_loads(&self, budget: Duration) -> usize { self.assets.run_staged(budget) }
+
+	/// Drop the cached views of an asset and of the assets depending on it, so that they are reloaded from their
+	/// sources.
+	pub fn invalidate_asset(&self, id: UntypedAssetId) { self.assets.invalidate(id); }
+
+	/// Take the views of type `T` evicted to stay within the asset budget, or invalidated.
+	pub fn take_evicted<T: AssetView>(&self) -> Vec<T> { self.assets.take_evicted() }
+
+	/// The context shared by every view of type `T`.
+	pub fn asset_context<T: AssetView>(&self) -> &T::Ctx { self.assets.context::<T>() }
+
+	/// Report the asset views that are alive or waiting to be deleted, with the names their sources give them.
+	pub fn asset_report(&self) -> AssetReport { self.assets.report() }
+
 	pub unsafe fn destroy() { std::ptr::drop_in_place(&ENGINE as *const _ as *mut OnceLock<Engine>); }
 }
 
@@ -69,6 +100,10 @@ impl EngineBuilder {
 
 	pub fn asset_view<T: AssetView>(&mut self) { self.inner.assets.register_view::<T>(); }
 
+	pub fn asset_budget(&mut self, max_bytes: u64) { self.inner.assets.set_budget(max_bytes); }
+
+	pub fn background_asset_loads(&mut self) { self.inner.assets.load_in_background(); }
+
 	pub fn get_global<T: Any + Send + Sync>(&mut self) -> &mut T { self.inner.globals.get_mut().unwrap() }
 
 	pub fn module<M: Module>(mut self) -> Self {
@@ -0,0 +1,256 @@
+//! Bundles of a world and every asset it uses, loaded with one read instead of a file per asset.
+//!
+//! A bundle is [`BUNDLE_MAGIC`], the number of assets, a [`BundleEntry`] for every asset, and then the compressed data
+//! of every asset as it is stored in its `.radass` file.
+
+use std::{
+	fs,
+	io::{self, Cursor, Read, Write},
+	ops::Range,
+	path::Path,
+	sync::Arc,
+};
+
+use bytemuck::{Pod, Zeroable};
+use rad_core::{
+	asset::{
+		aref::{AssetId, UntypedAssetId},
+		AssetRead,
+		AssetSource,
+	},
+	Engine,
+};
+use rad_renderer::{
+	assets::{material::Material, mesh::Mesh},
+	components::{
+		environment::{EnvironmentComponent, EnvironmentType},
+		mesh::MeshComponent,
+	},
+};
+use rad_world::{Uuid, World};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{info, trace_span};
+use zstd::Decoder;
+
+use crate::asset::fs::{AssetHeader, FsAssetSystem};
+
+const BUNDLE_MAGIC: [u8; 8] = *b"radbndl1";
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct BundleEntry {
+	header: AssetHeader,
+	/// From the start of the bundle.
+	offset: u64,
+	len: u64,
+}
+
+/// Write `world`, saved as `id`, and the assets it transitively references to a bundle at `path`.
+pub fn export(id: AssetId<World>, world: &mut World, path: &Path) -> Result<(), io::Error> {
+	let s = trace_span!("export bundle", path = %path.display());
+	let _e = s.enter();
+
+	let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+	let assets = dependencies(id, world)?;
+	let data = assets
+		.iter()
+		.map(|&id| fs.read_raw(id))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let mut offset =
+		(BUNDLE_MAGIC.len() + std::mem::size_of::<u64>() + std::mem::size_of::<BundleEntry>() * data.len()) as u64;
+	let entries: Vec<_> = data
+		.iter()
+		.map(|(header, data)| {
+			let entry = BundleEntry {
+				header: *header,
+				offset,
+				len: data.len() as u64,
+			};
+			offset += data.len() as u64;
+			entry
+		})
+		.collect();
+
+	let mut out = io::BufWriter::new(fs::File::create(path)?);
+	out.write_all(&BUNDLE_MAGIC)?;
+	out.write_all(&(entries.len() as u64).to_le_bytes())?;
+	out.write_all(bytemuck::cast_slice(&entries))?;
+	for (_, data) in data.iter() {
+		out.write_all(data)?;
+	}
+	out.flush()?;
+
+	info!("bundled {} assets ({} MiB)", entries.len(), offset / (1024 * 1024));
+	Ok(())
+}
+
+/// `id` and every asset used by `world`, with dependencies after the assets using them.
+fn dependencies(id: AssetId<World>, world: &mut World) -> Result<Vec<UntypedAssetId>, io::Error> {
+	let mut seen = FxHashSet::default();
+	let mut out = vec![id.to_untyped()];
+	let mut add = |id: UntypedAssetId, out: &mut Vec<_>| {
+		let new = seen.insert(id);
+		if new {
+			out.push(id);
+		}
+		new
+	};
+
+	if let EnvironmentType::Map(map) = EnvironmentComponent::of(world).ty {
+		add(map.to_untyped(), &mut out);
+	}
+	let meshes: Vec<_> = world
+		.query::<&MeshComponent>()
+		.iter(world)
+		.flat_map(|m| m.meshes().iter().copied())
+		.collect();
+	let mut materials: Vec<_> = world
+		.query::<&MeshComponent>()
+		.iter(world)
+		.flat_map(|m| m.material_overrides().iter().flatten().copied())
+		.collect();
+	for mesh in meshes {
+		if add(mesh.to_untyped(), &mut out) {
+			let mesh: Mesh = Engine::get().load_asset(mesh)?;
+			materials.push(mesh.material);
+		}
+	}
+	for material in materials {
+		if add(material.to_untyped(), &mut out) {
+			let material: Material = Engine::get().load_asset(material)?;
+			for image in [
+				material.base_color,
+				material.metallic_roughness,
+				material.normal,
+				material.emissive,
+			]
+			.into_iter()
+			.flatten()
+			{
+				add(image.to_untyped(), &mut out);
+			}
+		}
+	}
+
+	Ok(out)
+}
+
+/// An asset source serving the assets of a bundle from memory.
+pub struct BundleSource {
+	data: Arc<[u8]>,
+	index: FxHashMap<UntypedAssetId, (Uuid, Range<usize>)>,
+}
+
+impl BundleSource {
+	pub fn open(path: &Path) -> Result<Self, io::Error> {
+		let s = trace_span!("open bundle", path = %path.display());
+		let _e = s.enter();
+
+		let data: Arc<[u8]> = fs::read(path)?.into();
+		let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid asset bundle");
+		let (magic, rest) = data.split_first_chunk::<8>().ok_or_else(invalid)?;
+		if *magic != BUNDLE_MAGIC {
+			return Err(invalid());
+		}
+		let (&count, rest) = rest.split_first_chunk::<8>().ok_or_else(invalid)?;
+		// Sizes come from the file, so they may overflow if it is corrupt.
+		let entries_len = usize::try_from(u64::from_le_bytes(count))
+			.ok()
+			.and_then(|count| count.checked_mul(std::mem::size_of::<BundleEntry>()))
+			.ok_or_else(invalid)?;
+		let entries = rest.get(..entries_len).ok_or_else(invalid)?;
+		let entries: Vec<BundleEntry> = bytemuck::pod_collect_to_vec(entries);
+
+		let mut index = FxHashMap::default();
+		for entry in entries {
+			let end = entry.offset.checked_add(entry.len).ok_or_else(invalid)?;
+			if end > data.len() as u64 {
+				return Err(invalid());
+			}
+			index.insert(entry.header.id, (entry.header.ty, entry.offset as usize..end as usize));
+		}
+
+		info!("opened bundle with {} assets", index.len());
+		Ok(Self { data, index })
+	}
+}
+
+impl AssetSource for BundleSource {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error> {
+		let (asset_ty, range) = self
+			.index
+			.get(&id)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		if *asset_ty != ty {
+			return Err(io::Error::new(io::ErrorKind::NotFound, "asset type mismatch"));
+		}
+		let bytes = BundleBytes {
+			data: self.data.clone(),
+			range: range.clone(),
+		};
+		Ok(Box::new(BundleAssetRead {
+			read: Decoder::with_buffer(Cursor::new(bytes))?,
+		}))
+	}
+}
+
+/// The bytes of one asset in a bundle.
+struct BundleBytes {
+	data: Arc<[u8]>,
+	range: Range<usize>,
+}
+
+impl AsRef<[u8]> for BundleBytes {
+	fn as_ref(&self) -> &[u8] { &self.data[self.range.clone()] }
+}
+
+struct BundleAssetRead {
+	read: Decoder<'static, Cursor<BundleBytes>>,
+}
+impl Read for BundleAssetRead {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.read.read(buf) }
+}
+impl AssetRead for BundleAssetRead {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Open a bundle of `entries` whose header says it has `count` of them, and no asset data.
+	fn open(count: u64, entries: &[BundleEntry]) -> Result<BundleSource, io::Error> {
+		let mut data = BUNDLE_MAGIC.to_vec();
+		data.extend(count.to_le_bytes());
+		data.extend(bytemuck::cast_slice(entries));
+		let path = std::env::temp_dir().join(format!("radiance-bundle-{}", Uuid::new_v4()));
+		fs::write(&path, data)?;
+		let res = BundleSource::open(&path);
+		let _ = fs::remove_file(&path);
+		res
+	}
+
+	fn entry(offset: u64, len: u64) -> BundleEntry {
+		BundleEntry {
+			header: AssetHeader::zeroed(),
+			offset,
+			len,
+		}
+	}
+
+	#[test]
+	fn corrupt_bundles() {
+		let header = (BUNDLE_MAGIC.len() + 8 + std::mem::size_of::<BundleEntry>()) as u64;
+		assert!(open(1, &[entry(header, 0)]).is_ok());
+
+		for (count, entries) in [
+			(u64::MAX, vec![]),
+			(u64::MAX / std::mem::size_of::<BundleEntry>() as u64 + 1, vec![]),
+			(1, vec![entry(u64::MAX, 1)]),
+			(1, vec![entry(1, u64::MAX)]),
+			(1, vec![entry(header, 1)]),
+		] {
+			let err = open(count, &entries).err().expect("corrupt bundle opened");
+			assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{count} entries");
+		}
+	}
+}
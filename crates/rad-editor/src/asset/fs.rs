@@ -5,22 +5,33 @@ use std::{
 	ops::Deref,
 	path::{Path, PathBuf},
 	sync::Arc,
+	time::Duration,
 };
 
 use bytemuck::{Pod, Zeroable};
-use parking_lot::RwLock;
-use rad_core::asset::{
-	aref::{AssetId, UntypedAssetId},
-	Asset,
-	AssetRead,
-	AssetSource,
-	AssetWrite,
+use notify_debouncer_full::{
+	new_debouncer,
+	notify::{EventKind, RecommendedWatcher, RecursiveMode},
+	DebounceEventResult,
+	Debouncer,
+	RecommendedCache,
+};
+use parking_lot::{Mutex, RwLock};
+use rad_core::{
+	asset::{
+		aref::{AssetId, UntypedAssetId},
+		Asset,
+		AssetRead,
+		AssetSource,
+		AssetWrite,
+	},
+	Engine,
 };
 use rad_world::Uuid;
 use rustc_hash::{FxHashMap, FxHashSet};
-use tracing::trace_span;
+use tracing::{trace_span, warn};
 use walkdir::WalkDir;
-use zstd::{stream::AutoFinishEncoder, Decoder, Encoder};
+use zstd::{Decoder, Encoder};
 
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
@@ -69,6 +80,7 @@ pub struct FsAssetSystem {
 	assets: RwLock<FxHashMap<UntypedAssetId, PathBuf>>,
 	by_type: RwLock<FxHashMap<Uuid, FxHashSet<UntypedAssetId>>>,
 	dir: RwLock<Dir>,
+	watcher: Mutex<Option<Debouncer<RecommendedWatcher, RecommendedCache>>>,
 }
 
 impl FsAssetSystem {
@@ -85,12 +97,52 @@ impl FsAssetSystem {
 				a.rescan();
 				std::thread::sleep(std::time::Duration::from_secs(5));
 			});
+		if let Some(root) = this.root.read().as_deref() {
+			this.watch(root);
+		}
 		this
 	}
 
 	pub fn root(&self) -> impl Deref<Target = Option<PathBuf>> + '_ { self.root.read() }
 
-	pub fn open(&self, root: PathBuf) { *self.root.write() = Some(root) }
+	pub fn open(&self, root: PathBuf) {
+		self.watch(&root);
+		*self.root.write() = Some(root)
+	}
+
+	/// Watch `root` for changed assets, invalidating them so they are reloaded from disk.
+	fn watch(&self, root: &Path) {
+		let watcher = new_debouncer(Duration::from_secs_f32(0.5), None, |res: DebounceEventResult| {
+			let Ok(evs) = res else {
+				return;
+			};
+			for ev in evs {
+				if !matches!(ev.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+					continue;
+				}
+				for path in ev.paths.iter() {
+					if path.extension().and_then(|x| x.to_str()) != Some("radass") {
+						continue;
+					}
+					if let Ok(mut view) = FsAssetRead::open(path) {
+						Engine::get().invalidate_asset(view.header().id);
+					}
+				}
+			}
+		});
+		let mut watcher = match watcher {
+			Ok(x) => x,
+			Err(e) => {
+				warn!("failed to create asset watcher: {e}");
+				return;
+			},
+		};
+		if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+			warn!("failed to watch {}: {e}", root.display());
+			return;
+		}
+		*self.watcher.lock() = Some(watcher);
+	}
 
 	pub fn create<T: Asset>(&self, rel_path: &Path, id: AssetId<T>) -> Result<FsAssetWrite, io::Error> {
 		let s = trace_span!("create asset", path = %rel_path.display(), id = %id);
@@ -113,11 +165,63 @@ impl FsAssetSystem {
 		view
 	}
 
+	/// Overwrite an existing asset with what `write` writes. It is written to a temporary file that then replaces the
+	/// asset, so a failed write leaves the old asset intact.
+	pub fn overwrite<T: Asset>(
+		&self, id: AssetId<T>, write: impl FnOnce(&mut FsAssetWrite) -> Result<(), io::Error>,
+	) -> Result<(), io::Error> {
+		let path = self
+			.assets
+			.read()
+			.get(&id.to_untyped())
+			.cloned()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		// Not a `radass`, so the watcher and rescans ignore it.
+		let tmp = path.with_extension("radass.tmp");
+		let res = FsAssetWrite::create(&tmp, id).and_then(|mut to| {
+			write(&mut to)?;
+			to.finish()
+		});
+		match res {
+			Ok(()) => fs::rename(&tmp, &path),
+			Err(e) => {
+				let _ = fs::remove_file(&tmp);
+				Err(e)
+			},
+		}
+	}
+
+	/// Read the header and still compressed data of an asset.
+	pub fn read_raw(&self, id: UntypedAssetId) -> Result<(AssetHeader, Vec<u8>), io::Error> {
+		let path = self
+			.assets
+			.read()
+			.get(&id)
+			.cloned()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		let mut file = fs::File::open(path)?;
+		let mut header = AssetHeader::zeroed();
+		file.read_exact(bytemuck::bytes_of_mut(&mut header))?;
+		let mut data = Vec::new();
+		file.read_to_end(&mut data)?;
+		Ok((header, data))
+	}
+
 	pub fn dir(&self) -> impl Deref<Target = Dir> + '_ { self.dir.read() }
 
-	// pub fn assets_of_type(&self, ty: Uuid) -> FxHashSet<AssetId> {
-	// 	self.by_type.read().get(&ty).cloned().unwrap_or_default()
-	// }
+	/// The assets of type `T` along with their paths, sorted by path.
+	pub fn assets_of_type<T: Asset>(&self) -> Vec<(String, AssetId<T>)> {
+		let mut out: Vec<_> = self
+			.by_type
+			.read()
+			.get(&T::UUID)
+			.into_iter()
+			.flatten()
+			.filter_map(|&id| Some((self.name(id)?, unsafe { id.typed() })))
+			.collect();
+		out.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+		out
+	}
 
 	fn rescan(&self) {
 		let s = trace_span!("rescan assets");
@@ -196,6 +300,11 @@ impl AssetSource for FsAssetSystem {
 		}
 		Ok(Box::new(view))
 	}
+
+	fn name(&self, id: UntypedAssetId) -> Option<String> {
+		let path = self.assets.read().get(&id)?.clone();
+		Some(self.rel_path(&path).unwrap_or(path).display().to_string())
+	}
 }
 
 pub struct FsAssetRead {
@@ -222,25 +331,41 @@ impl FsAssetRead {
 }
 
 pub struct FsAssetWrite {
-	write: AutoFinishEncoder<'static, fs::File>,
+	write: Option<Encoder<'static, fs::File>>,
 }
 impl Write for FsAssetWrite {
-	fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.write.write(buf) }
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.encoder().write(buf) }
 
-	fn flush(&mut self) -> io::Result<()> { self.write.flush() }
+	fn flush(&mut self) -> io::Result<()> { self.encoder().flush() }
+}
+impl Drop for FsAssetWrite {
+	fn drop(&mut self) {
+		if let Some(Err(e)) = self.write.take().map(|x| x.finish()) {
+			warn!("failed to finish writing asset: {e}");
+		}
+	}
 }
 impl AssetWrite for FsAssetWrite {}
 
 impl FsAssetWrite {
 	fn create<T: Asset>(path: &Path, id: AssetId<T>) -> Result<Self, io::Error> {
-		let mut file = fs::OpenOptions::new().write(true).create(true).open(path)?;
+		let mut file = fs::OpenOptions::new()
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(path)?;
 		let header = AssetHeader {
 			id: id.to_untyped(),
 			ty: T::UUID,
 		};
 		file.write_all(bytemuck::bytes_of(&header))?;
 		Ok(Self {
-			write: Encoder::new(file, 5)?.auto_finish(),
+			write: Some(Encoder::new(file, 5)?),
 		})
 	}
+
+	fn encoder(&mut self) -> &mut Encoder<'static, fs::File> { self.write.as_mut().unwrap() }
+
+	/// Finish writing the asset and flush it to disk, instead of only logging errors when dropped.
+	fn finish(mut self) -> Result<(), io::Error> { self.write.take().unwrap().finish()?.sync_all() }
 }
@@ -0,0 +1,66 @@
+use std::{
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use image::{ImageFormat, ImageReader, Rgba32FImage};
+use rad_core::{
+	asset::{aref::AssetId, Asset},
+	Engine,
+};
+use rad_graph::ash::vk;
+use rad_renderer::{assets::image::ImageAsset, vek::Vec3};
+use tracing::trace_span;
+
+use crate::asset::fs::FsAssetSystem;
+
+/// Imports standalone Radiance HDR and OpenEXR images as `R32G32B32A32_SFLOAT` image assets, keeping their full
+/// range. These can be used as environment maps.
+pub struct HdrImporter {
+	name: String,
+	image: Rgba32FImage,
+}
+
+impl HdrImporter {
+	pub fn initialize(path: &Path) -> Option<Result<Self, io::Error>> {
+		let format = match path.extension().and_then(|x| x.to_str()) {
+			Some("hdr") => ImageFormat::Hdr,
+			Some("exr") => ImageFormat::OpenExr,
+			_ => return None,
+		};
+
+		let s = trace_span!("load hdr image");
+		let _e = s.enter();
+		let name = path
+			.file_stem()
+			.map(|x| x.to_string_lossy().into_owned())
+			.unwrap_or_else(|| AssetId::<ImageAsset>::new().to_string());
+		let image = ImageReader::open(path).and_then(|mut x| {
+			x.set_format(format);
+			x.decode().map_err(io::Error::other)
+		});
+		Some(image.map(|x| Self {
+			name,
+			image: x.into_rgba32f(),
+		}))
+	}
+
+	pub fn import(self, progress: impl Fn(f32)) -> Result<(), io::Error> {
+		progress(0.0);
+		let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		let s = trace_span!("save");
+		let _e = s.enter();
+
+		let path = PathBuf::from("images").join(&self.name);
+		ImageAsset {
+			size: Vec3::new(self.image.width(), self.image.height(), 1),
+			format: vk::Format::R32G32B32A32_SFLOAT.as_raw(),
+			data: bytemuck::cast_slice(self.image.as_raw()).to_vec(),
+		}
+		.save(&mut sys.create(&path, AssetId::<ImageAsset>::new())?)?;
+		progress(1.0);
+
+		Ok(())
+	}
+}
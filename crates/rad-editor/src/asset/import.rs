@@ -1,5 +1,6 @@
 use std::{
 	collections::hash_map::Entry,
+	fmt::{self, Display},
 	fs::File,
 	io::{self, BufReader},
 	path::{Path, PathBuf},
@@ -13,6 +14,8 @@ use gltf::{
 	buffer,
 	camera::Projection,
 	image::{self, Source},
+	mesh::Mode,
+	texture::{MagFilter, MinFilter, WrappingMode},
 	Document,
 	Gltf,
 };
@@ -25,8 +28,8 @@ use rad_graph::ash::vk;
 use rad_renderer::{
 	assets::{
 		image::ImageAsset,
-		material::Material,
-		mesh::{GpuVertex, Mesh},
+		material::{AlphaMode, Filter, Material, TexCoord, TextureSampler, Wrap},
+		mesh::{virtual_mesh::MeshletConfig, GpuVertex, Mesh},
 	},
 	components::{
 		camera::CameraComponent,
@@ -35,18 +38,77 @@ use rad_renderer::{
 	},
 	vek::{Mat4, Quaternion, Vec2, Vec3, Vec4},
 };
-use rad_world::{transform::Transform, World};
-use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use rad_world::{name::Name, transform::Transform, World};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelBridge, ParallelIterator};
 use rustc_hash::FxHashMap;
-use tracing::{span, trace_span, Level};
+use tracing::{span, trace_span, warn, Level};
 
-use crate::asset::fs::FsAssetSystem;
+use crate::asset::{
+	fs::FsAssetSystem,
+	ktx2::{self, TextureKind},
+};
 
 pub struct GltfImporter {
 	gltf: Document,
 	base: PathBuf,
 	buffers: Vec<buffer::Data>,
-	image_cache: Mutex<FxHashMap<(usize, bool), AssetId<ImageAsset>>>,
+	image_cache: Mutex<FxHashMap<(usize, TextureKind), AssetId<ImageAsset>>>,
+	meshlet_config: MeshletConfig,
+	warnings: Mutex<Vec<ImportWarning>>,
+}
+
+/// Something in the imported file that isn't supported, and was imported differently or skipped instead.
+#[derive(Clone, Debug)]
+pub enum ImportWarning {
+	/// A light of a kind that isn't supported, imported as a light of the closest supported kind.
+	UnsupportedLight { node: String, kind: &'static str },
+	/// A primitive without tangents, for which tangents couldn't be generated.
+	MissingTangents { mesh: String, primitive: usize },
+	/// A primitive that wasn't imported.
+	SkippedPrimitive {
+		mesh: String,
+		primitive: usize,
+		reason: String,
+	},
+	/// A primitive without a material, which uses the default material instead.
+	DefaultMaterial { mesh: String, primitive: usize },
+	/// Triangles that repeat a vertex or have no area, which were removed.
+	DegenerateTriangles {
+		mesh: String,
+		primitive: usize,
+		count: usize,
+	},
+}
+
+impl Display for ImportWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnsupportedLight { node, kind } => write!(f, "{node}: {kind} lights are imported as point lights"),
+			Self::MissingTangents { mesh, primitive } => {
+				write!(
+					f,
+					"{mesh}/{primitive}: has no tangents, and they could not be generated"
+				)
+			},
+			Self::SkippedPrimitive {
+				mesh,
+				primitive,
+				reason,
+			} => write!(f, "{mesh}/{primitive}: skipped, {reason}"),
+			Self::DefaultMaterial { mesh, primitive } => {
+				write!(f, "{mesh}/{primitive}: has no material, using default")
+			},
+			Self::DegenerateTriangles { mesh, primitive, count } => {
+				write!(f, "{mesh}/{primitive}: removed {count} degenerate triangles")
+			},
+		}
+	}
+}
+
+/// The warnings collected while importing a file. Malformed files fail to import instead.
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+	pub warnings: Vec<ImportWarning>,
 }
 
 #[derive(Copy, Clone)]
@@ -64,17 +126,22 @@ impl ImportProgress {
 
 impl GltfImporter {
 	pub fn initialize(path: &Path) -> Option<Result<Self, io::Error>> {
-		if path.extension().and_then(|x| x.to_str()) != Some("gltf") {
+		if !matches!(path.extension().and_then(|x| x.to_str()), Some("gltf" | "glb")) {
 			return None;
 		}
 
 		let s = span!(Level::TRACE, "load gltf");
 		let _e = s.enter();
-		let base = path.parent().unwrap_or_else(|| Path::new("."));
+		// `.glb` files usually embed everything, but may still reference external URIs relative to themselves.
+		let base = path
+			.parent()
+			.filter(|x| !x.as_os_str().is_empty())
+			.unwrap_or_else(|| Path::new("."));
 		let file = match File::open(path) {
 			Ok(x) => x,
 			Err(e) => return Some(Err(e)),
 		};
+		// Handles both JSON and the binary container, which populates `blob`.
 		let Gltf { document: gltf, blob } = match Gltf::from_reader(BufReader::new(file)) {
 			Ok(x) => x,
 			Err(e) => return Some(Err(io::Error::other(e))),
@@ -83,7 +150,13 @@ impl GltfImporter {
 		Some(Self::new(base, gltf, blob).map_err(|e| io::Error::other(e)))
 	}
 
-	pub fn import(self, progress: impl Fn(f32) + Send + Sync) -> Result<(), io::Error> {
+	/// Set the meshlet limits used when cooking the imported meshes.
+	pub fn with_meshlet_config(mut self, config: MeshletConfig) -> Self {
+		self.meshlet_config = config;
+		self
+	}
+
+	pub fn import(self, progress: impl Fn(f32) + Send + Sync) -> Result<ImportReport, io::Error> {
 		let total = ImportProgress {
 			materials: self.gltf.materials().count() as _,
 			meshes: self.gltf.meshes().count() as _,
@@ -115,24 +188,55 @@ impl GltfImporter {
 						Material {
 							base_color: m
 								.base_color_texture()
-								.map(|x| self.image(x.texture().source(), true))
+								.map(|x| self.image(x.texture().source(), TextureKind::Color))
 								.transpose()?,
 							base_color_factor: m.base_color_factor().into(),
 							metallic_roughness: m
 								.metallic_roughness_texture()
-								.map(|x| self.image(x.texture().source(), false))
+								.map(|x| self.image(x.texture().source(), TextureKind::Data))
 								.transpose()?,
 							metallic_factor: m.metallic_factor(),
 							roughness_factor: m.roughness_factor(),
 							normal: mat
 								.normal_texture()
-								.map(|x| self.image(x.texture().source(), false))
+								.map(|x| self.image(x.texture().source(), TextureKind::Normal))
 								.transpose()?,
+							normal_scale: mat.normal_texture().map_or(1.0, |x| x.scale()),
 							emissive: mat
 								.emissive_texture()
-								.map(|x| self.image(x.texture().source(), true))
+								.map(|x| self.image(x.texture().source(), TextureKind::Color))
 								.transpose()?,
 							emissive_factor: mat.emissive_factor().map(|x| x * es).into(),
+							alpha_mode: match mat.alpha_mode() {
+								gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+								gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+								gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+							},
+							alpha_cutoff: mat.alpha_cutoff().unwrap_or(0.5),
+							double_sided: mat.double_sided(),
+							base_color_sampler: m
+								.base_color_texture()
+								.map(|x| sampler(x.texture()))
+								.unwrap_or_default(),
+							metallic_roughness_sampler: m
+								.metallic_roughness_texture()
+								.map(|x| sampler(x.texture()))
+								.unwrap_or_default(),
+							normal_sampler: mat.normal_texture().map(|x| sampler(x.texture())).unwrap_or_default(),
+							emissive_sampler: mat.emissive_texture().map(|x| sampler(x.texture())).unwrap_or_default(),
+							base_color_uv: m.base_color_texture().map(|x| tex_coord(&x)).unwrap_or_default(),
+							metallic_roughness_uv: m
+								.metallic_roughness_texture()
+								.map(|x| tex_coord(&x))
+								.unwrap_or_default(),
+							normal_uv: mat
+								.normal_texture()
+								.map(|x| TexCoord {
+									set: x.tex_coord(),
+									..Default::default()
+								})
+								.unwrap_or_default(),
+							emissive_uv: mat.emissive_texture().map(|x| tex_coord(&x)).unwrap_or_default(),
 						}
 						.save(&mut sys.create(&path, id)?)?;
 					}
@@ -149,11 +253,12 @@ impl GltfImporter {
 
 					Ok::<_, io::Error>(id)
 				})
-				.chain(Some({
+				// Primitives without a material use the default material, which comes after every other material.
+				.chain(self.needs_default_material().then(|| {
 					let id = AssetId::new();
-					let path = Path::new("materials").join("default");
+					let path = Path::new("materials").join(self.default_material_name());
 					self.default_material().save(&mut sys.create(&path, id)?)?;
-					Ok(id)
+					Ok::<_, io::Error>(id)
 				}))
 				.collect::<Result<_, _>>()?
 		};
@@ -171,10 +276,10 @@ impl GltfImporter {
 					let s = trace_span!("import mesh", name = name);
 					let _e = s.enter();
 
-					let prims = self.conv_to_meshes(mesh, &materials).map_err(io::Error::other)?;
+					let prims = self.conv_to_meshes(mesh, &materials);
 					let c = prims.len();
 					let ids = prims
-						.into_iter()
+						.into_par_iter()
 						.enumerate()
 						.map(|(i, m)| {
 							let id = AssetId::new();
@@ -184,7 +289,14 @@ impl GltfImporter {
 							} else {
 								format!("{name}-{i}")
 							};
-							let s = trace_span!("save primitive", i = i);
+							let s = trace_span!(
+								"save primitive",
+								i = i,
+								tris = m.tri_count(),
+								vertex_bytes = m.vertex_bytes(),
+								index_bytes = m.index_bytes(),
+								blas_bytes = m.blas_size_estimate(),
+							);
 							let _e = s.enter();
 
 							let path = Path::new("meshes").join(&name);
@@ -237,9 +349,18 @@ impl GltfImporter {
 					.ratio(total),
 				);
 
-				Ok(())
-			})
+				Ok::<_, io::Error>(())
+			})?;
 		}
+
+		Ok(ImportReport {
+			warnings: self.warnings.into_inner(),
+		})
+	}
+
+	fn warn(&self, warning: ImportWarning) {
+		warn!("{warning}");
+		self.warnings.lock().push(warning);
 	}
 
 	fn new(base: &Path, gltf: Document, mut blob: Option<Vec<u8>>) -> Result<Self, gltf::Error> {
@@ -262,6 +383,8 @@ impl GltfImporter {
 			base: base.to_path_buf(),
 			buffers,
 			image_cache: Mutex::new(FxHashMap::default()),
+			meshlet_config: MeshletConfig::default(),
+			warnings: Mutex::new(Vec::new()),
 		})
 	}
 
@@ -278,12 +401,13 @@ impl GltfImporter {
 	}
 
 	fn node(&self, node: gltf::Node, transform: Mat4<f32>, meshes: &[Vec<AssetId<Mesh>>], out: &mut World) {
-		// let name = node.name().unwrap_or("unnamed node").to_string();
-
 		let this_transform = Mat4::from_col_arrays(node.transform().matrix());
 		let transform = transform * this_transform;
 
 		let mut entity = out.spawn_empty();
+		if let Some(name) = node.name() {
+			entity.insert(Name(name.to_string()));
+		}
 
 		// gltf is X- right, Y up, Z in
 		// we are X right, Y in, Z up
@@ -312,7 +436,16 @@ impl GltfImporter {
 				ty: match light.kind() {
 					gltf::khr_lights_punctual::Kind::Directional => LightType::Directional,
 					gltf::khr_lights_punctual::Kind::Point => LightType::Point,
-					_ => LightType::Directional,
+					gltf::khr_lights_punctual::Kind::Spot { .. } => {
+						self.warn(ImportWarning::UnsupportedLight {
+							node: node
+								.name()
+								.map(|x| x.to_string())
+								.unwrap_or_else(|| format!("node {}", node.index())),
+							kind: "spot",
+						});
+						LightType::Point
+					},
 				},
 				radiance: Vec3::from(light.color()) * light.intensity(),
 			});
@@ -322,6 +455,7 @@ impl GltfImporter {
 			entity.insert(CameraComponent {
 				fov: p.yfov(),
 				near: p.znear(),
+				far: p.zfar(),
 			});
 		}
 
@@ -330,9 +464,9 @@ impl GltfImporter {
 		}
 	}
 
-	fn image(&self, image: gltf::Image, srgb: bool) -> Result<AssetId<ImageAsset>, io::Error> {
+	fn image(&self, image: gltf::Image, kind: TextureKind) -> Result<AssetId<ImageAsset>, io::Error> {
 		let mut cache = self.image_cache.lock();
-		let id = match cache.entry((image.index(), srgb)) {
+		let id = match cache.entry((image.index(), kind)) {
 			Entry::Occupied(x) => return Ok(*x.get()),
 			Entry::Vacant(x) => *x.insert(AssetId::new()),
 		};
@@ -352,6 +486,46 @@ impl GltfImporter {
 		let _e = s.enter();
 
 		let path = Path::new("images").join(&name);
+		let asset = match self.ktx2_data(&image)? {
+			Some(data) => {
+				let s = trace_span!("transcode");
+				let _e = s.enter();
+				ktx2::load(&data, kind)?
+			},
+			None => self.decode_image(&image, kind == TextureKind::Color)?,
+		};
+
+		{
+			let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
+			let s = trace_span!("save");
+			let _e = s.enter();
+			asset.save(&mut sys.create(&path, id)?)?;
+		}
+
+		Ok::<_, io::Error>(id)
+	}
+
+	/// The contents of `image` if it is a KTX2 file, which `image::Data` can't decode.
+	fn ktx2_data(&self, image: &gltf::Image) -> Result<Option<Vec<u8>>, io::Error> {
+		let data = match image.source() {
+			Source::View { view, mime_type } => {
+				if mime_type != "image/ktx2" {
+					return Ok(None);
+				}
+				let start = view.offset();
+				self.buffers[view.buffer().index()][start..start + view.length()].to_vec()
+			},
+			Source::Uri { uri, mime_type } => {
+				if uri.starts_with("data:") || (mime_type != Some("image/ktx2") && !uri.ends_with(".ktx2")) {
+					return Ok(None);
+				}
+				std::fs::read(self.base.join(uri))?
+			},
+		};
+		Ok(ktx2::is_ktx2(&data).then_some(data))
+	}
+
+	fn decode_image(&self, image: &gltf::Image, srgb: bool) -> Result<ImageAsset, io::Error> {
 		let mut d = {
 			let s = trace_span!("load");
 			let _e = s.enter();
@@ -378,73 +552,111 @@ impl GltfImporter {
 			d.format = image::Format::R8G8B8A8;
 		}
 
-		{
-			let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
-			let s = trace_span!("save");
-			let _e = s.enter();
-			ImageAsset {
-				size: Vec3::new(d.width, d.height, 1),
-				format: match (d.format, srgb) {
-					(image::Format::R8, false) => vk::Format::R8_UNORM,
-					(image::Format::R8G8, false) => vk::Format::R8G8_UNORM,
-					(image::Format::R8G8B8A8, false) => vk::Format::R8G8B8A8_UNORM,
-					(image::Format::R8, true) => vk::Format::R8_SRGB,
-					(image::Format::R8G8B8A8, true) => vk::Format::R8G8B8A8_SRGB,
-					(image::Format::R16, _) => vk::Format::R16_UNORM,
-					(image::Format::R16G16, _) => vk::Format::R16G16_UNORM,
-					(image::Format::R16G16B16, _) => vk::Format::R16G16B16_UNORM,
-					(image::Format::R16G16B16A16, _) => vk::Format::R16G16B16A16_UNORM,
-					(image::Format::R32G32B32FLOAT, _) => vk::Format::R32G32B32_SFLOAT,
-					(image::Format::R32G32B32A32FLOAT, _) => vk::Format::R32G32B32A32_SFLOAT,
-					_ => return Err(io::Error::other("unsupported image format")),
-				}
-				.as_raw(),
-				data: d.pixels,
+		Ok(ImageAsset {
+			size: Vec3::new(d.width, d.height, 1),
+			format: match (d.format, srgb) {
+				(image::Format::R8, false) => vk::Format::R8_UNORM,
+				(image::Format::R8G8, false) => vk::Format::R8G8_UNORM,
+				(image::Format::R8G8B8A8, false) => vk::Format::R8G8B8A8_UNORM,
+				(image::Format::R8, true) => vk::Format::R8_SRGB,
+				(image::Format::R8G8B8A8, true) => vk::Format::R8G8B8A8_SRGB,
+				(image::Format::R16, _) => vk::Format::R16_UNORM,
+				(image::Format::R16G16, _) => vk::Format::R16G16_UNORM,
+				(image::Format::R16G16B16, _) => vk::Format::R16G16B16_UNORM,
+				(image::Format::R16G16B16A16, _) => vk::Format::R16G16B16A16_UNORM,
+				(image::Format::R32G32B32FLOAT, _) => vk::Format::R32G32B32_SFLOAT,
+				(image::Format::R32G32B32A32FLOAT, _) => vk::Format::R32G32B32A32_SFLOAT,
+				_ => return Err(io::Error::other("unsupported image format")),
 			}
-			.save(&mut sys.create(&path, id)?)?;
-		}
+			.as_raw(),
+			data: d.pixels,
+		})
+	}
 
-		Ok::<_, io::Error>(id)
+	fn needs_default_material(&self) -> bool {
+		self.gltf
+			.meshes()
+			.flat_map(|x| x.primitives())
+			.any(|x| x.material().index().is_none())
+	}
+
+	/// A name for the default material that no material in the file uses.
+	fn default_material_name(&self) -> String {
+		let taken = |name: &str| self.gltf.materials().any(|x| x.name() == Some(name));
+		std::iter::once("default".to_string())
+			.chain((1..).map(|i| format!("default-{i}")))
+			.find(|x| !taken(x))
+			.unwrap()
 	}
 
 	fn default_material(&self) -> Material {
 		Material {
 			base_color: None,
-			base_color_factor: Vec4::new(1.0, 1.0, 1.0, 1.0),
+			base_color_factor: Vec4::new(0.8, 0.8, 0.8, 1.0),
 			metallic_roughness: None,
-			metallic_factor: 1.0,
+			metallic_factor: 0.0,
 			roughness_factor: 1.0,
 			normal: None,
+			normal_scale: 1.0,
 			emissive: None,
 			emissive_factor: Vec3::zero(),
+			alpha_mode: AlphaMode::Opaque,
+			alpha_cutoff: 0.5,
+			double_sided: false,
+			base_color_sampler: TextureSampler::default(),
+			metallic_roughness_sampler: TextureSampler::default(),
+			normal_sampler: TextureSampler::default(),
+			emissive_sampler: TextureSampler::default(),
+			base_color_uv: TexCoord::default(),
+			metallic_roughness_uv: TexCoord::default(),
+			normal_uv: TexCoord::default(),
+			emissive_uv: TexCoord::default(),
 		}
 	}
 
-	fn conv_to_meshes(&self, mesh: gltf::Mesh, materials: &[AssetId<Material>]) -> Result<Vec<Mesh>, io::Error> {
+	/// Convert the primitives of `mesh`, skipping the ones that can't be imported.
+	fn conv_to_meshes(&self, mesh: gltf::Mesh, materials: &[AssetId<Material>]) -> Vec<Mesh> {
 		let s = trace_span!("load mesh");
 		let _e = s.enter();
 
-		let out = mesh
-			.primitives()
-			.map(|prim| {
+		let mesh_name = mesh
+			.name()
+			.map(|x| x.to_string())
+			.unwrap_or_else(|| format!("mesh {}", mesh.index()));
+
+		// Every primitive becomes its own mesh, with its own material, so they can be converted in parallel. Meshlets
+		// are built when each mesh is cooked, which is also per primitive.
+		let prims: Vec<_> = mesh.primitives().collect();
+		prims
+			.into_par_iter()
+			.filter_map(|prim| {
+				let skip = |reason: &str| {
+					self.warn(ImportWarning::SkippedPrimitive {
+						mesh: mesh_name.clone(),
+						primitive: prim.index(),
+						reason: reason.to_string(),
+					});
+					None
+				};
 				let reader = prim.reader(|x| Some(&self.buffers[x.index()]));
-				let positions = reader
-					.read_positions()
-					.ok_or_else(|| io::Error::other("invalid gltf"))?
-					.map(|x| x.into());
-				let normals = reader
-					.read_normals()
-					.ok_or_else(|| io::Error::other("invalid gltf"))?
-					.map(|x| x.into());
+				let Some(positions) = reader.read_positions() else {
+					return skip("it has no positions");
+				};
+				let Some(normals) = reader.read_normals() else {
+					return skip("it has no normals");
+				};
+				let positions = positions.map(|x| x.into());
+				let normals = normals.map(|x| x.into());
 				let mut uvs = reader.read_tex_coords(0).map(|x| x.into_f32());
+				let has_uvs = uvs.is_some();
+				let mut uvs1 = reader.read_tex_coords(1).map(|x| x.into_f32());
+				let mut tangents = reader.read_tangents();
+				let has_tangents = tangents.is_some();
+				let mut colors = reader.read_colors(0).map(|x| x.into_rgba_u8());
 
-				let indices = reader
-					.read_indices()
-					.ok_or_else(|| io::Error::other("invalid gltf"))?
-					.into_u32()
-					.collect();
+				let indices = reader.read_indices().map(|x| x.into_u32());
 
-				let vertices = positions
+				let mut vertices: Vec<_> = positions
 					.zip(normals)
 					.zip(std::iter::from_fn(move || {
 						if let Some(ref mut uvs) = uvs {
@@ -453,17 +665,339 @@ impl GltfImporter {
 							Some(Vec2::new(0.0, 0.0))
 						}
 					}))
-					.map(|((position, normal), uv)| GpuVertex { position, normal, uv })
+					.zip(std::iter::from_fn(move || {
+						// Meshes without a second set reuse the first.
+						if let Some(ref mut uvs1) = uvs1 {
+							uvs1.next().map(|x| Some(x.into()))
+						} else {
+							Some(None)
+						}
+					}))
+					.zip(std::iter::from_fn(move || {
+						if let Some(ref mut tangents) = tangents {
+							tangents.next().map(|x| GpuVertex::pack_tangent(x.into()))
+						} else {
+							Some(Vec4::zero())
+						}
+					}))
+					.zip(std::iter::from_fn(move || {
+						if let Some(ref mut colors) = colors {
+							colors.next().map(Into::into)
+						} else {
+							Some(Vec4::broadcast(255))
+						}
+					}))
+					.map(|(((((position, normal), uv), uv1), tangent), color)| GpuVertex {
+						position,
+						normal,
+						uv,
+						uv1: uv1.unwrap_or(uv),
+						tangent,
+						color,
+					})
 					.collect();
 
-				Ok::<_, io::Error>(Mesh {
+				// Non-indexed primitives draw their vertices in order.
+				let indices = match indices {
+					Some(indices) => triangulate(prim.mode(), indices),
+					None => triangulate(prim.mode(), 0..vertices.len() as u32),
+				};
+				let mut indices = match indices {
+					Ok(x) => x,
+					Err(e) => return skip(&e.to_string()),
+				};
+				if indices.iter().any(|&x| x as usize >= vertices.len()) {
+					return skip("it has out of range indices");
+				}
+				let removed = remove_degenerate(&vertices, &mut indices);
+				if removed > 0 {
+					self.warn(ImportWarning::DegenerateTriangles {
+						mesh: mesh_name.clone(),
+						primitive: prim.index(),
+						count: removed,
+					});
+				}
+
+				// Tangents are only needed for normal maps, which also need UVs to generate them from.
+				let needs_tangents = !has_tangents && prim.material().normal_texture().is_some();
+				let generated = !has_tangents && has_uvs && {
+					let s = trace_span!("generate tangents");
+					let _e = s.enter();
+					let mut generator = TangentGenerator {
+						vertices: &vertices,
+						indices: &indices,
+						tangents: vec![None; indices.len()],
+					};
+					let generated = mikktspace::generate_tangents(&mut generator);
+					if generated {
+						let tangents = generator.tangents;
+						split_tangents(&mut vertices, &mut indices, &tangents);
+					}
+					generated
+				};
+				if needs_tangents && !generated {
+					self.warn(ImportWarning::MissingTangents {
+						mesh: mesh_name.clone(),
+						primitive: prim.index(),
+					});
+				}
+
+				let material = match prim.material().index() {
+					Some(x) => x,
+					None => {
+						self.warn(ImportWarning::DefaultMaterial {
+							mesh: mesh_name.clone(),
+							primitive: prim.index(),
+						});
+						materials.len() - 1
+					},
+				};
+				Some(Mesh {
 					vertices,
 					indices,
-					material: materials[prim.material().index().unwrap_or(materials.len() - 1)].clone(),
+					material: materials[material].clone(),
+					meshlet_config: self.meshlet_config,
 				})
 			})
-			.collect::<Result<Vec<_>, _>>()?;
+			.collect()
+	}
+}
 
-		Ok(out)
+/// Convert the sampler state of a texture. Unspecified filters are linear. Images have no mips, so the mipmap mode of
+/// the minification filter is ignored.
+fn sampler(texture: gltf::Texture) -> TextureSampler {
+	let s = texture.sampler();
+	let wrap = |w| match w {
+		WrappingMode::Repeat => Wrap::Repeat,
+		WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+		WrappingMode::ClampToEdge => Wrap::Clamp,
+	};
+	TextureSampler {
+		wrap_u: wrap(s.wrap_s()),
+		wrap_v: wrap(s.wrap_t()),
+		mag: match s.mag_filter() {
+			Some(MagFilter::Nearest) => Filter::Nearest,
+			Some(MagFilter::Linear) | None => Filter::Linear,
+		},
+		min: match s.min_filter() {
+			Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest | MinFilter::NearestMipmapLinear) => {
+				Filter::Nearest
+			},
+			_ => Filter::Linear,
+		},
+	}
+}
+
+/// Get the UV set and `KHR_texture_transform` of a texture reference. The transform may override the UV set.
+fn tex_coord(info: &gltf::texture::Info) -> TexCoord {
+	let set = info.tex_coord();
+	match info.texture_transform() {
+		Some(t) => TexCoord {
+			set: t.tex_coord().unwrap_or(set),
+			offset: t.offset().into(),
+			rotation: t.rotation(),
+			scale: t.scale().into(),
+		},
+		None => TexCoord {
+			set,
+			..Default::default()
+		},
+	}
+}
+
+/// Convert the indices of a primitive into a triangle list.
+fn triangulate(mode: Mode, indices: impl Iterator<Item = u32>) -> Result<Vec<u32>, io::Error> {
+	let indices: Vec<_> = indices.collect();
+	Ok(match mode {
+		Mode::Triangles => indices,
+		// Every other triangle is flipped to keep the winding consistent.
+		Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+			.flat_map(|i| {
+				let flip = i % 2;
+				[indices[i], indices[i + 1 + flip], indices[i + 2 - flip]]
+			})
+			.collect(),
+		Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+			.flat_map(|i| [indices[i], indices[i + 1], indices[0]])
+			.collect(),
+		x => return Err(io::Error::other(format!("unsupported primitive mode `{x:?}`"))),
+	})
+}
+
+/// Triangles with less area than this are dropped on import.
+const MIN_TRI_AREA: f32 = 1e-12;
+
+/// Remove triangles that repeat a vertex or have (almost) no area, which make meshlet building and the meshlet
+/// graph misbehave. Returns the number of triangles removed.
+fn remove_degenerate(vertices: &[GpuVertex], indices: &mut Vec<u32>) -> usize {
+	let before = indices.len() / 3;
+	let tris: Vec<_> = indices
+		.chunks_exact(3)
+		.filter(|t| {
+			let (a, b, c) = (t[0], t[1], t[2]);
+			if a == b || b == c || c == a {
+				return false;
+			}
+			let [a, b, c] = [a, b, c].map(|x| vertices[x as usize].position);
+			(b - a).cross(c - a).magnitude() * 0.5 >= MIN_TRI_AREA
+		})
+		.flatten()
+		.copied()
+		.collect();
+	*indices = tris;
+	before - indices.len() / 3
+}
+
+struct TangentGenerator<'a> {
+	vertices: &'a [GpuVertex],
+	indices: &'a [u32],
+	/// The tangent of every corner, as faces sharing a vertex may give it different tangents.
+	tangents: Vec<Option<Vec4<i16>>>,
+}
+
+impl TangentGenerator<'_> {
+	fn vertex(&self, face: usize, vert: usize) -> &GpuVertex { &self.vertices[self.indices[face * 3 + vert] as usize] }
+}
+
+impl mikktspace::Geometry for TangentGenerator<'_> {
+	fn num_faces(&self) -> usize { self.indices.len() / 3 }
+
+	fn num_vertices_of_face(&self, _: usize) -> usize { 3 }
+
+	fn position(&self, face: usize, vert: usize) -> [f32; 3] { self.vertex(face, vert).position.into_array() }
+
+	fn normal(&self, face: usize, vert: usize) -> [f32; 3] { self.vertex(face, vert).normal.into_array() }
+
+	fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] { self.vertex(face, vert).uv.into_array() }
+
+	fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+		self.tangents[face * 3 + vert] = Some(GpuVertex::pack_tangent(tangent.into()));
+	}
+}
+
+/// Give every vertex the tangent generated for its corners, splitting vertices whose corners were given different
+/// tangents or signs, like MikkTSpace expects vertices to be welded. Corners without a tangent are left alone.
+/// Returns the number of vertices added.
+fn split_tangents(vertices: &mut Vec<GpuVertex>, indices: &mut [u32], tangents: &[Option<Vec4<i16>>]) -> usize {
+	let before = vertices.len();
+	let mut assigned = vec![false; before];
+	let mut splits = FxHashMap::default();
+	for (index, &tangent) in indices.iter_mut().zip(tangents) {
+		let Some(tangent) = tangent else {
+			continue;
+		};
+		let i = *index as usize;
+		if !assigned[i] {
+			assigned[i] = true;
+			vertices[i].tangent = tangent;
+		} else if vertices[i].tangent != tangent {
+			*index = *splits.entry((*index, tangent.into_array())).or_insert_with(|| {
+				let mut split = vertices[i];
+				split.tangent = tangent;
+				vertices.push(split);
+				(vertices.len() - 1) as u32
+			});
+		}
+	}
+	vertices.len() - before
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use rad_core::asset::{AssetWrite, CookedAsset};
+	use rad_renderer::assets::mesh::virtual_mesh::VirtualMesh;
+
+	use super::*;
+
+	struct Bytes(Vec<u8>);
+
+	impl Write for Bytes {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.write(buf) }
+
+		fn flush(&mut self) -> io::Result<()> { Ok(()) }
+	}
+	impl AssetWrite for Bytes {}
+
+	fn bytes(asset: &impl Asset) -> Vec<u8> {
+		let mut out = Bytes(Vec::new());
+		asset.save(&mut out).unwrap();
+		out.0
+	}
+
+	#[test]
+	fn deterministic_import() {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/bumpy_grid.glb");
+		// The material ids are chosen by the caller, so both imports share them.
+		let materials = [AssetId::new(), AssetId::new()];
+		let import = || {
+			let importer = GltfImporter::initialize(&path).unwrap().unwrap();
+			let meshes: Vec<_> = importer
+				.gltf
+				.meshes()
+				.flat_map(|m| importer.conv_to_meshes(m, &materials))
+				.collect();
+			let cooked: Vec<_> = meshes.iter().map(|m| bytes(&VirtualMesh::cook(m))).collect();
+			(meshes.iter().map(bytes).collect::<Vec<_>>(), cooked)
+		};
+
+		let (meshes, cooked) = import();
+		assert!(!meshes.is_empty());
+		let (again, cooked_again) = import();
+		assert!(meshes == again, "importing the same file twice gave different meshes");
+		assert!(
+			cooked == cooked_again,
+			"cooking the same import twice gave different bytes"
+		);
+	}
+
+	#[test]
+	fn non_indexed_strip() {
+		// Every odd triangle has its winding flipped.
+		let indexed = [0, 1, 2, 1, 3, 2, 2, 3, 4];
+		assert_eq!(triangulate(Mode::TriangleStrip, 0..5).unwrap(), indexed);
+		assert_eq!(triangulate(Mode::Triangles, indexed.into_iter()).unwrap(), indexed);
+	}
+
+	#[test]
+	fn non_indexed_fan() {
+		assert_eq!(
+			triangulate(Mode::TriangleFan, 0..5).unwrap(),
+			[1, 2, 0, 2, 3, 0, 3, 4, 0]
+		);
+	}
+
+	#[test]
+	fn degenerate_tris() {
+		let vertex = |x: f32, y: f32| GpuVertex {
+			position: Vec3::new(x, y, 0.0),
+			..Default::default()
+		};
+		let vertices = [vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0), vertex(2.0, 0.0)];
+		// A valid triangle, one repeating a vertex, and one with collinear vertices.
+		let mut indices = vec![0, 1, 2, 0, 0, 1, 0, 1, 3];
+		assert_eq!(remove_degenerate(&vertices, &mut indices), 2);
+		assert_eq!(indices, [0, 1, 2]);
+	}
+
+	#[test]
+	fn split_tangent_seams() {
+		let mut vertices = vec![GpuVertex::default(); 4];
+		// Two triangles sharing the edge 1-2, with the second mirrored in UV space.
+		let mut indices = vec![0, 1, 2, 2, 1, 3];
+		let t = Vec4::new(i16::MAX, 0, 0, i16::MAX);
+		let flipped = Vec4::new(i16::MAX, 0, 0, -i16::MAX);
+		let tangents = [t, t, t, flipped, flipped, flipped].map(Some);
+		assert_eq!(split_tangents(&mut vertices, &mut indices, &tangents), 2);
+		assert_eq!(indices, [0, 1, 2, 4, 5, 3]);
+		assert!(indices[..3].iter().all(|&i| vertices[i as usize].tangent == t));
+		assert!(indices[3..].iter().all(|&i| vertices[i as usize].tangent == flipped));
+	}
+
+	#[test]
+	fn degenerate_strip() {
+		assert!(triangulate(Mode::TriangleStrip, 0..2).unwrap().is_empty());
+		assert!(triangulate(Mode::Lines, 0..2).is_err());
 	}
 }
@@ -0,0 +1,188 @@
+//! Loading of KTX2 textures.
+//!
+//! Textures already in a GPU format are stored as is, and UASTC Basis Universal textures are transcoded to a BC format
+//! picked by how the texture is used. ETC1S (BasisLZ) textures, arrays, and cubemaps are not supported. Only the
+//! first mip level is kept, as images have no mips.
+
+use std::{io, ops::Range, sync::Once};
+
+use basis_universal::{
+	transcoder_init,
+	DecodeFlags,
+	LowLevelUastcTranscoder,
+	SliceParametersUastc,
+	TranscoderBlockFormat,
+};
+use rad_graph::ash::vk;
+use rad_renderer::{assets::image::ImageAsset, vek::Vec3};
+
+const IDENTIFIER: [u8; 12] = [
+	0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+const DF_MODEL_UASTC: u8 = 166;
+const DF_CHANNEL_UASTC_RGBA: u8 = 3;
+const DF_CHANNEL_UASTC_RRRG: u8 = 5;
+
+/// How a texture is used by a material, which picks the BC format Basis Universal textures are transcoded to.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TextureKind {
+	/// sRGB color, transcoded to BC7.
+	Color,
+	/// Tangent-space normals in RG, transcoded to BC5.
+	Normal,
+	/// Linear data such as metallic and roughness, transcoded to BC1.
+	Data,
+}
+
+pub fn is_ktx2(data: &[u8]) -> bool { data.starts_with(&IDENTIFIER) }
+
+#[derive(Debug)]
+struct Header {
+	format: i32,
+	size: Vec3<u32>,
+	supercompression: u32,
+	color_model: u8,
+	has_alpha: bool,
+	level: Range<usize>,
+	uncompressed_len: usize,
+}
+
+fn invalid() -> io::Error { io::Error::other("invalid ktx2 file") }
+
+fn parse(data: &[u8]) -> Result<Header, io::Error> {
+	if !is_ktx2(data) {
+		return Err(invalid());
+	}
+	let u8_at = |o: usize| data.get(o).copied().ok_or_else(invalid);
+	let u32_at = |o: usize| {
+		data.get(o..o + 4)
+			.map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+			.ok_or_else(invalid)
+	};
+	let u64_at = |o: usize| {
+		data.get(o..o + 8)
+			.map(|x| u64::from_le_bytes(x.try_into().unwrap()) as usize)
+			.ok_or_else(invalid)
+	};
+
+	let layers = u32_at(32)?;
+	let faces = u32_at(36)?;
+	if layers > 1 || faces > 1 {
+		return Err(io::Error::other("ktx2 arrays and cubemaps are not supported"));
+	}
+
+	// The color model and first sample of the basic data format descriptor block.
+	let dfd = u32_at(48)? as usize + 4;
+	let channel = u8_at(dfd + 27)? & 0xf;
+	let level = u64_at(80)?;
+	let level = level..level + u64_at(88)?;
+	if level.end > data.len() {
+		return Err(invalid());
+	}
+
+	Ok(Header {
+		format: u32_at(12)? as i32,
+		size: Vec3::new(u32_at(20)?, u32_at(24)?.max(1), u32_at(28)?.max(1)),
+		supercompression: u32_at(44)?,
+		color_model: u8_at(dfd + 8)?,
+		has_alpha: matches!(channel, DF_CHANNEL_UASTC_RGBA | DF_CHANNEL_UASTC_RRRG),
+		level,
+		uncompressed_len: u64_at(96)?,
+	})
+}
+
+pub fn load(data: &[u8], kind: TextureKind) -> Result<ImageAsset, io::Error> {
+	let h = parse(data)?;
+	let level = &data[h.level.clone()];
+	let level = match h.supercompression {
+		SUPERCOMPRESSION_NONE => level.to_vec(),
+		SUPERCOMPRESSION_ZSTD => zstd::bulk::decompress(level, h.uncompressed_len)?,
+		SUPERCOMPRESSION_BASIS_LZ => return Err(io::Error::other("ETC1S ktx2 textures are not supported")),
+		x => {
+			return Err(io::Error::other(format!(
+				"unsupported ktx2 supercompression scheme `{x}`"
+			)))
+		},
+	};
+
+	if h.format != vk::Format::UNDEFINED.as_raw() {
+		return Ok(ImageAsset {
+			size: h.size,
+			format: h.format,
+			data: level,
+		});
+	}
+	if h.color_model != DF_MODEL_UASTC || h.size.z > 1 {
+		return Err(io::Error::other("only 2D UASTC ktx2 textures are supported"));
+	}
+
+	static INIT: Once = Once::new();
+	INIT.call_once(transcoder_init);
+	let (block, format) = match kind {
+		TextureKind::Color => (TranscoderBlockFormat::BC7, vk::Format::BC7_SRGB_BLOCK),
+		TextureKind::Normal => (TranscoderBlockFormat::BC5, vk::Format::BC5_UNORM_BLOCK),
+		TextureKind::Data => (TranscoderBlockFormat::BC1, vk::Format::BC1_RGB_UNORM_BLOCK),
+	};
+	let data = LowLevelUastcTranscoder::new()
+		.transcode_slice(
+			&level,
+			SliceParametersUastc {
+				num_blocks_x: h.size.x.div_ceil(4),
+				num_blocks_y: h.size.y.div_ceil(4),
+				has_alpha: h.has_alpha,
+				original_width: h.size.x,
+				original_height: h.size.y,
+			},
+			DecodeFlags::HIGH_QUALITY,
+			block,
+		)
+		.map_err(|e| io::Error::other(format!("failed to transcode ktx2 texture: {e:?}")))?;
+
+	Ok(ImageAsset {
+		size: h.size,
+		format: format.as_raw(),
+		data,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn file(format: vk::Format, layers: u32, level: &[u8]) -> Vec<u8> {
+		let mut out = IDENTIFIER.to_vec();
+		let dfd = 104u32;
+		let data = dfd + 44;
+		for x in [format.as_raw() as u32, 1, 8, 4, 0, layers, 1, 1, 0, dfd, 44, 0, 0] {
+			out.extend(x.to_le_bytes());
+		}
+		for x in [0u64, 0, data as u64, level.len() as u64, level.len() as u64] {
+			out.extend(x.to_le_bytes());
+		}
+		out.resize(data as usize, 0);
+		out[dfd as usize + 12] = DF_MODEL_UASTC;
+		out.extend(level);
+		out
+	}
+
+	#[test]
+	fn gpu_format_is_kept() {
+		let level = [7; 16];
+		let img = load(&file(vk::Format::BC1_RGB_UNORM_BLOCK, 0, &level), TextureKind::Color).unwrap();
+		assert_eq!(img.size, Vec3::new(8, 4, 1));
+		assert_eq!(img.format, vk::Format::BC1_RGB_UNORM_BLOCK.as_raw());
+		assert_eq!(img.data, level);
+	}
+
+	#[test]
+	fn rejects_invalid() {
+		assert!(!is_ktx2(b"\x89PNG\r\n\x1a\n"));
+		assert!(load(&file(vk::Format::BC1_RGB_UNORM_BLOCK, 2, &[0; 16]), TextureKind::Data).is_err());
+		let mut f = file(vk::Format::BC1_RGB_UNORM_BLOCK, 0, &[0; 16]);
+		f.truncate(f.len() - 1);
+		assert!(load(&f, TextureKind::Data).is_err());
+	}
+}
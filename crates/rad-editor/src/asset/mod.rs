@@ -3,25 +3,35 @@ use std::{path::PathBuf, sync::Arc};
 use rad_core::{asset::Asset, Engine};
 use rad_renderer::assets::{image::ImageAsset, material::Material, mesh::Mesh};
 use rad_ui::{
-	egui::{Button, Context, Grid, Key, KeyboardShortcut, Modifiers, RichText, ScrollArea, TopBottomPanel},
+	egui::{Button, Context, Grid, Key, KeyboardShortcut, Modifiers, RichText, ScrollArea, TopBottomPanel, Window},
 	icons::{self, icon},
 };
 use rad_world::World;
 use tracing::{error, info};
 
 use crate::{
-	asset::{fs::FsAssetSystem, image_preview::ImagePreviewer, import::GltfImporter},
+	asset::{
+		fs::FsAssetSystem,
+		hdr::HdrImporter,
+		image_preview::ImagePreviewer,
+		import::{GltfImporter, ImportReport},
+	},
 	world::WorldContext,
 };
 
+pub mod bundle;
 pub mod fs;
+mod hdr;
 mod image_preview;
 mod import;
+mod ktx2;
 
 pub struct AssetTray {
 	open: bool,
 	cursor: PathBuf,
 	image_previewer: ImagePreviewer,
+	/// The file and warnings of the last import that had any.
+	report: Option<(PathBuf, ImportReport)>,
 }
 
 impl AssetTray {
@@ -30,11 +40,13 @@ impl AssetTray {
 			open: false,
 			cursor: PathBuf::new(),
 			image_previewer: ImagePreviewer::new(),
+			report: None,
 		}
 	}
 
 	pub fn render(&mut self, ctx: &Context, world: &mut WorldContext) {
 		self.image_previewer.render(ctx);
+		self.render_report(ctx);
 
 		self.open =
 			self.open ^ ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Space)));
@@ -60,14 +72,18 @@ impl AssetTray {
 
 					let dropped = ctx.input_mut(|x| std::mem::take(&mut x.raw.dropped_files));
 					for file in dropped {
-						if let Some(x) = GltfImporter::initialize(&file.path.unwrap()) {
-							if let Err(e) = x.and_then(|x| {
-								x.import(|x| {
-									info!("import: {:.2}%", x * 100.0);
-								})
-							}) {
-								error!("import error: {:?}", e);
-							}
+						let path = file.path.unwrap();
+						let progress = |x: f32| info!("import: {:.2}%", x * 100.0);
+						let res = if let Some(x) = GltfImporter::initialize(&path) {
+							Some(x.and_then(|x| x.import(progress)))
+						} else {
+							HdrImporter::initialize(&path)
+								.map(|x| x.and_then(|x| x.import(progress)).map(|_| ImportReport::default()))
+						};
+						match res {
+							Some(Ok(report)) if !report.warnings.is_empty() => self.report = Some((path, report)),
+							Some(Err(e)) => error!("import error: {:?}", e),
+							_ => {},
 						}
 					}
 
@@ -209,4 +225,27 @@ impl AssetTray {
 				});
 		}
 	}
+
+	fn render_report(&mut self, ctx: &Context) {
+		let Some((path, report)) = &self.report else {
+			return;
+		};
+		let mut open = true;
+		Window::new("import warnings").open(&mut open).show(ctx, |ui| {
+			ui.label(format!(
+				"{} imported with {} warnings",
+				path.file_name().unwrap_or_default().to_string_lossy(),
+				report.warnings.len()
+			));
+			ui.separator();
+			ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+				for w in &report.warnings {
+					ui.label(w.to_string());
+				}
+			});
+		});
+		if !open {
+			self.report = None;
+		}
+	}
 }
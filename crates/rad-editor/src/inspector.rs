@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use rad_core::{
+	asset::{aref::AssetId, Asset, AssetSource},
+	Engine,
+};
+use rad_renderer::{
+	assets::image::ImageAsset,
+	components::{
+		camera::CameraComponent,
+		environment::{EnvironmentComponent, EnvironmentType},
+		light::{LightComponent, LightType},
+		mesh::MeshComponent,
+	},
+	env::EnvironmentMap,
+	vek::{Quaternion, Vec3},
+};
+use rad_ui::egui::{CollapsingHeader, ComboBox, Context, DragValue, Grid, ScrollArea, SidePanel, Ui};
+use rad_world::{
+	bevy_ecs::{component::Component, entity::Entity},
+	name::Name,
+	serde::DoNotSerialize,
+	transform::Transform,
+	World,
+};
+use rustc_hash::FxHashMap;
+
+use crate::{asset::fs::FsAssetSystem, world::WorldContext};
+
+/// Lists the entities of the world being edited, and edits the components of the selected one.
+pub struct Inspector {
+	/// Whether each image asset can be an environment map, so that only their headers are read, and only once.
+	env_maps: FxHashMap<AssetId<ImageAsset>, bool>,
+}
+
+impl Inspector {
+	pub fn new() -> Self {
+		Self {
+			env_maps: FxHashMap::default(),
+		}
+	}
+
+	pub fn render(&mut self, ctx: &Context, world: &mut WorldContext, selected: &mut Option<Entity>) {
+		let world = world.world_mut();
+		if selected.is_some_and(|e| world.get_entity(e).is_err()) {
+			*selected = None;
+		}
+
+		SidePanel::right("inspector")
+			.resizable(true)
+			.default_width(250.0)
+			.show(ctx, |ui| {
+				ui.heading("hierarchy");
+				ScrollArea::vertical()
+					.id_salt("hierarchy")
+					.max_height(ui.available_height() * 0.5)
+					.auto_shrink([false, false])
+					.show(ui, |ui| {
+						let mut entities: Vec<_> = world
+							.iter_entities()
+							.filter(|x| !x.contains::<DoNotSerialize>())
+							.map(|x| (x.id(), label(x.id(), x.get::<Name>())))
+							.collect();
+						entities.sort_by_key(|x| x.0);
+						for (e, label) in entities {
+							if ui.selectable_label(*selected == Some(e), label).clicked() {
+								*selected = Some(e);
+							}
+						}
+					});
+
+				ui.separator();
+				ui.heading("inspector");
+				let Some(e) = *selected else {
+					ui.label("nothing selected");
+					return;
+				};
+				ScrollArea::vertical()
+					.id_salt("inspector")
+					.auto_shrink([false, false])
+					.show(ui, |ui| Self::components(ui, world, e, &mut self.env_maps));
+			});
+	}
+
+	fn components(ui: &mut Ui, world: &mut World, e: Entity, env_maps: &mut FxHashMap<AssetId<ImageAsset>, bool>) {
+		ui.label(label(e, world.get::<Name>(e)));
+		if world.get::<MeshComponent>(e).is_some() {
+			ui.label("has mesh");
+		}
+
+		edit::<Transform>(ui, world, e, "transform", |ui, t| {
+			ui.label("position");
+			vec3(ui, &mut t.position, 0.1);
+			ui.end_row();
+
+			ui.label("rotation");
+			let mut euler = to_euler(t.rotation).map(f32::to_degrees);
+			if vec3(ui, &mut euler, 1.0) {
+				t.rotation = from_euler(euler.map(f32::to_radians));
+			}
+			ui.end_row();
+
+			ui.label("scale");
+			vec3(ui, &mut t.scale, 0.01);
+			ui.end_row();
+		});
+
+		edit::<LightComponent>(ui, world, e, "light", |ui, l| {
+			ui.label("type");
+			ComboBox::from_id_salt("light type")
+				.selected_text(match l.ty {
+					LightType::Point => "point",
+					LightType::Directional => "directional",
+				})
+				.show_ui(ui, |ui| {
+					ui.selectable_value(&mut l.ty, LightType::Point, "point");
+					ui.selectable_value(&mut l.ty, LightType::Directional, "directional");
+				});
+			ui.end_row();
+
+			ui.label("radiance");
+			vec3(ui, &mut l.radiance, 0.1);
+			l.radiance = l.radiance.map(|x| x.max(0.0));
+			ui.end_row();
+		});
+
+		edit::<CameraComponent>(ui, world, e, "camera", |ui, c| {
+			ui.label("fov");
+			ui.drag_angle(&mut c.fov);
+			c.fov = c.fov.clamp(1f32.to_radians(), 179f32.to_radians());
+			ui.end_row();
+
+			ui.label("near");
+			// Kept below the far plane, rather than dragging it along.
+			let max_near = c.far.map_or(f32::MAX, |f| f / 2.0);
+			ui.add(DragValue::new(&mut c.near).speed(0.001).range(0.0001..=max_near));
+			ui.end_row();
+
+			let mut enabled = c.far.is_some();
+			ui.checkbox(&mut enabled, "far");
+			let mut far = c.far.unwrap_or(1000.0).max(c.near * 2.0);
+			ui.add_enabled(
+				enabled,
+				DragValue::new(&mut far).speed(1.0).range(c.near * 2.0..=f32::MAX),
+			);
+			c.far = enabled.then_some(far);
+			ui.end_row();
+		});
+
+		edit::<EnvironmentComponent>(ui, world, e, "environment", |ui, env| {
+			ui.label("type");
+			ComboBox::from_id_salt("environment type")
+				.selected_text(match env.ty {
+					EnvironmentType::Sky => "sky",
+					EnvironmentType::Constant => "constant",
+					EnvironmentType::Map(_) => "map",
+				})
+				.show_ui(ui, |ui| {
+					ui.selectable_value(&mut env.ty, EnvironmentType::Sky, "sky");
+					ui.selectable_value(&mut env.ty, EnvironmentType::Constant, "constant");
+				});
+			ui.end_row();
+
+			ui.label("map");
+			let map = match env.ty {
+				EnvironmentType::Map(id) => Some(id),
+				_ => None,
+			};
+			let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+			ComboBox::from_id_salt("environment map")
+				.selected_text(map.map_or("none".to_string(), |id| {
+					fs.name(id.to_untyped()).unwrap_or(id.to_string())
+				}))
+				.show_ui(ui, |ui| {
+					for (name, id) in fs.assets_of_type::<ImageAsset>() {
+						let supported = *env_maps.entry(id).or_insert_with(|| {
+							fs.load(id.to_untyped(), ImageAsset::UUID)
+								.and_then(ImageAsset::read_header)
+								.is_ok_and(|h| EnvironmentMap::supports(h.size, h.format))
+						});
+						if supported {
+							ui.selectable_value(&mut env.ty, EnvironmentType::Map(id), name);
+						}
+					}
+				});
+			ui.end_row();
+
+			ui.label("ambient");
+			let mut ambient = env.ambient.into_array();
+			ui.color_edit_button_rgb(&mut ambient);
+			env.ambient = ambient.into();
+			ui.end_row();
+
+			let mut enabled = env.sun_dir.is_some();
+			ui.checkbox(&mut enabled, "sun direction");
+			let mut dir = env.sun_dir.unwrap_or(-Vec3::unit_z());
+			ui.add_enabled_ui(enabled, |ui| vec3(ui, &mut dir, 0.01));
+			env.sun_dir = enabled.then_some(dir);
+			ui.end_row();
+
+			ui.label("exposure compensation");
+			ui.add(
+				DragValue::new(&mut env.exposure_compensation)
+					.speed(0.1)
+					.range(-5.0..=5.0)
+					.suffix(" EV"),
+			);
+			ui.end_row();
+		});
+	}
+}
+
+fn label(e: Entity, name: Option<&Name>) -> String {
+	match name {
+		Some(name) => name.0.clone(),
+		None => format!("entity {}", e.index()),
+	}
+}
+
+/// Edit a copy of the component, so that it is only marked as changed if it was actually edited.
+fn edit<T: Component + Copy + PartialEq>(
+	ui: &mut Ui, world: &mut World, e: Entity, name: &str, f: impl FnOnce(&mut Ui, &mut T),
+) {
+	let Some(&old) = world.get::<T>(e) else {
+		return;
+	};
+	let mut new = old;
+	CollapsingHeader::new(name).default_open(true).show(ui, |ui| {
+		Grid::new(name).num_columns(2).show(ui, |ui| f(ui, &mut new));
+	});
+	if new != old {
+		*world.get_mut::<T>(e).unwrap() = new;
+	}
+}
+
+fn vec3(ui: &mut Ui, v: &mut Vec3<f32>, speed: f32) -> bool {
+	ui.horizontal(|ui| {
+		let x = ui.add(DragValue::new(&mut v.x).speed(speed)).changed();
+		let y = ui.add(DragValue::new(&mut v.y).speed(speed)).changed();
+		let z = ui.add(DragValue::new(&mut v.z).speed(speed)).changed();
+		x | y | z
+	})
+	.inner
+}
+
+/// The XYZ euler angles of `q`, applied in the order X, Y, then Z.
+fn to_euler(q: Quaternion<f32>) -> Vec3<f32> {
+	let x = (2.0 * (q.w * q.x + q.y * q.z)).atan2(1.0 - 2.0 * (q.x * q.x + q.y * q.y));
+	let y = (2.0 * (q.w * q.y - q.z * q.x)).clamp(-1.0, 1.0).asin();
+	let z = (2.0 * (q.w * q.z + q.x * q.y)).atan2(1.0 - 2.0 * (q.y * q.y + q.z * q.z));
+	Vec3::new(x, y, z)
+}
+
+fn from_euler(e: Vec3<f32>) -> Quaternion<f32> {
+	Quaternion::rotation_z(e.z) * Quaternion::rotation_y(e.y) * Quaternion::rotation_x(e.x)
+}
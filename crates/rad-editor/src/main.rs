@@ -1,6 +1,6 @@
 #![feature(path_add_extension)]
 
-use std::mem::ManuallyDrop;
+use std::{mem::ManuallyDrop, path::Path};
 
 use rad_core::{Engine, EngineBuilder, Module};
 use rad_graph::{graph::Frame, Result};
@@ -8,17 +8,20 @@ use rad_renderer::RendererModule;
 use rad_rhi::RhiModule;
 use rad_ui::{egui::Context, App, UiApp, UiModule};
 use rad_window::{winit::event::WindowEvent, Window, WindowModule};
-use rad_world::WorldModule;
+use rad_world::{WorldBuilderExt, WorldModule};
+use tracing::error;
 use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, EnvFilter, Layer, Registry};
 
 use crate::{
-	asset::{fs::FsAssetSystem, AssetTray},
+	asset::{bundle::BundleSource, fs::FsAssetSystem, AssetTray},
+	inspector::Inspector,
 	menu::Menu,
 	render::Renderer,
 	world::WorldContext,
 };
 
 mod asset;
+mod inspector;
 mod menu;
 mod render;
 mod world;
@@ -50,12 +53,25 @@ fn main() -> Result<()> {
 struct EditorModule;
 
 impl Module for EditorModule {
-	fn init(engine: &mut EngineBuilder) { engine.asset_source(FsAssetSystem::new()); }
+	fn init(engine: &mut EngineBuilder) {
+		engine.asset_source(FsAssetSystem::new());
+		// Assets in a bundle are loaded from it instead of their files, to test exported bundles.
+		if let Some(path) = std::env::var_os("RADBUNDLE") {
+			match BundleSource::open(Path::new(&path)) {
+				Ok(bundle) => engine.asset_source(bundle),
+				Err(e) => error!("failed to open bundle {}: {e}", path.to_string_lossy()),
+			}
+		}
+		// Keep the editor responsive while large scenes stream in.
+		engine.background_asset_loads();
+		engine.component::<render::CameraBookmark>();
+	}
 }
 
 struct EditorApp {
 	menu: Menu,
 	assets: AssetTray,
+	inspector: Inspector,
 	world: WorldContext,
 	renderer: ManuallyDrop<Renderer>,
 }
@@ -65,6 +81,7 @@ impl EditorApp {
 		Self {
 			menu: Menu::new(),
 			assets: AssetTray::new(),
+			inspector: Inspector::new(),
 			world: WorldContext::new(),
 			renderer: ManuallyDrop::new(Renderer::new().unwrap()),
 		}
@@ -73,16 +90,28 @@ impl EditorApp {
 
 impl App for EditorApp {
 	fn render<'pass>(&'pass mut self, window: &mut Window, frame: &mut Frame<'pass, '_>, ctx: &Context) -> Result<()> {
-		self.menu.render(ctx, &mut self.renderer);
+		self.menu.render(ctx, &mut self.renderer, &mut self.world);
 		self.assets.render(ctx, &mut self.world);
+		self.inspector.render(ctx, &mut self.world, &mut self.renderer.selected);
 		self.renderer.render(window, frame, ctx, &mut self.world);
 
 		Ok(())
 	}
 
+	fn sdr_white(&self) -> f32 { self.renderer.debug_window.sdr_white() }
+
 	fn window_event(&mut self, window: &mut Window, event: &WindowEvent) {
 		self.renderer.on_window_event(window, event);
 	}
+
+	fn device_lost(&mut self) {
+		// The device is global to the process, so the editor can't keep going. Save a copy of the world before it
+		// exits, so that unsaved edits aren't lost.
+		match self.world.save_recovery() {
+			Ok(path) => error!("GPU device lost, saved the world to {}", path.display()),
+			Err(e) => error!("GPU device lost, failed to save the world: {e}"),
+		}
+	}
 }
 
 impl Drop for EditorApp {
@@ -1,27 +1,122 @@
 use std::sync::Arc;
 
 use rad_core::Engine;
-use rad_ui::egui::{menu, Context, Key, KeyboardShortcut, Modifiers, TopBottomPanel};
+use rad_renderer::components::camera::CameraComponent;
+use rad_ui::egui::{menu, ComboBox, Context, DragValue, Key, KeyboardShortcut, Modifiers, TopBottomPanel};
+use rad_world::name::Name;
 use rfd::FileDialog;
+use tracing::error;
 
-use crate::{asset::fs::FsAssetSystem, render::Renderer};
+use crate::{
+	asset::fs::FsAssetSystem,
+	render::{bookmarks, scene_cameras, CameraBookmark, Easing, Renderer},
+	world::WorldContext,
+};
 
-pub struct Menu {}
+pub struct Menu {
+	bookmark_name: String,
+}
 
 impl Menu {
-	pub fn new() -> Self { Self {} }
+	pub fn new() -> Self {
+		Self {
+			bookmark_name: String::new(),
+		}
+	}
 
-	pub fn render(&mut self, ctx: &Context, renderer: &mut Renderer) {
+	pub fn render(&mut self, ctx: &Context, renderer: &mut Renderer, world: &mut WorldContext) {
 		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
 
 		let mut new = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::N)));
 		let mut open = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::O)));
+		let mut save = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::S)));
 
 		TopBottomPanel::top("menu").show(ctx, |ui| {
 			menu::bar(ui, |ui| {
 				ui.menu_button("file", |ui| {
 					new |= ui.button("new").clicked();
 					open |= ui.button("open").clicked();
+					save |= ui.button("save scene").clicked();
+					if ui.button("export bundle").clicked() {
+						ui.close_menu();
+						if let Some(path) = FileDialog::new().add_filter("bundle", &["radbundle"]).save_file() {
+							if let Err(e) = world.export_bundle(&path) {
+								error!("failed to export bundle: {e}");
+							}
+						}
+					}
+					ui.separator();
+					ui.menu_button("screenshot", |ui| {
+						renderer.screenshot.ui(ui);
+						if ui.button("save").clicked() {
+							ui.close_menu();
+							if let Some(path) = FileDialog::new()
+								.add_filter("png", &["png"])
+								.add_filter("exr", &["exr"])
+								.save_file()
+							{
+								renderer.screenshot.request(path);
+							}
+						}
+					});
+				});
+
+				ui.menu_button("camera", |ui| {
+					ui.checkbox(&mut renderer.camera.smooth, "smooth transitions");
+					ui.add_enabled_ui(renderer.camera.smooth, |ui| {
+						ui.add(
+							DragValue::new(&mut renderer.camera.duration)
+								.speed(0.01)
+								.range(0.0..=10.0)
+								.suffix(" s"),
+						);
+						ComboBox::from_id_salt("easing")
+							.selected_text(renderer.camera.easing.name())
+							.show_ui(ui, |ui| {
+								for e in Easing::ALL {
+									ui.selectable_value(&mut renderer.camera.easing, e, e.name());
+								}
+							});
+					});
+					ui.separator();
+
+					ui.menu_button("settings", |ui| renderer.camera.settings_ui(ui));
+					ui.separator();
+
+					ui.label("scene cameras");
+					for (_, name, t, c) in scene_cameras(world.world_mut()) {
+						if ui.button(name).clicked() {
+							renderer.camera.set(&t);
+							*world.editor_mut().get_mut::<CameraComponent>().unwrap() = c;
+						}
+					}
+					ui.separator();
+
+					ui.label("bookmarks");
+					ui.horizontal(|ui| {
+						ui.text_edit_singleline(&mut self.bookmark_name);
+						if ui.button("add").clicked() {
+							let world = world.world_mut();
+							let name = match std::mem::take(&mut self.bookmark_name) {
+								x if x.is_empty() => format!("bookmark {}", bookmarks(world).len() + 1),
+								x => x,
+							};
+							world
+								.spawn_empty()
+								.insert((renderer.camera.pose(), Name(name), CameraBookmark));
+						}
+					});
+					for (i, (e, name, t)) in bookmarks(world.world_mut()).into_iter().enumerate() {
+						ui.horizontal(|ui| {
+							let label = if i < 9 { format!("{}: {name}", i + 1) } else { name };
+							if ui.button(label).clicked() {
+								renderer.camera.set(&t);
+							}
+							if ui.small_button("x").on_hover_text("delete").clicked() {
+								world.world_mut().despawn(e);
+							}
+						});
+					}
 				});
 
 				ui.menu_button("window", |ui| {
@@ -30,6 +125,12 @@ impl Menu {
 			});
 		});
 
+		if save {
+			if let Err(e) = world.save() {
+				error!("failed to save scene: {e}");
+			}
+		}
+
 		if new || open {
 			if let Some(path) = FileDialog::new().pick_folder() {
 				fs.open(path);
@@ -1,12 +1,41 @@
-use rad_renderer::vek::{num_traits::FloatConst, Mat4, Quaternion, Vec2, Vec3, Vec4};
-use rad_ui::egui::Context;
+use std::{io, path::PathBuf};
+
+use rad_renderer::{
+	components::camera::{CameraComponent, PrimaryViewComponent},
+	vek::{num_traits::FloatConst, Aabb, Lerp, Mat4, Quaternion, Vec2, Vec3, Vec4},
+};
+use rad_ui::egui::{Context, DragValue, Grid, Ui};
 use rad_window::winit::{
 	dpi::PhysicalPosition,
 	event::{ElementState, MouseScrollDelta, WindowEvent},
 	keyboard::{KeyCode, PhysicalKey},
 	window::{CursorGrabMode, Window},
 };
-use rad_world::{bevy_ecs::world::EntityMut, transform::Transform};
+use rad_world::{
+	bevy_ecs::{entity::Entity, query::Without, world::EntityMut},
+	name::Name,
+	transform::Transform,
+	RadComponent,
+	World,
+};
+use serde::{Deserialize, Serialize};
+
+/// Marks an entity as a saved editor viewpoint. Bookmarks are saved with the scene, with their pose in `Transform` and
+/// their name in `Name`.
+#[derive(Copy, Clone, PartialEq, RadComponent)]
+#[uuid("d3c1e5a2-7f4b-4c6e-8a9d-2b5f1e7c3a84")]
+pub struct CameraBookmark;
+
+/// The bookmarks in `world`, in the order they were created.
+pub fn bookmarks(world: &mut World) -> Vec<(Entity, String, Transform)> {
+	let mut out: Vec<_> = world
+		.query::<(Entity, &Transform, Option<&Name>, &CameraBookmark)>()
+		.iter(world)
+		.map(|(e, t, n, _)| (e, n.map(|x| x.0.clone()).unwrap_or_default(), *t))
+		.collect();
+	out.sort_by_key(|x| x.0);
+	out
+}
 
 #[derive(Default)]
 struct MouseGrabber {
@@ -36,20 +65,124 @@ impl MouseGrabber {
 	}
 }
 
+/// The cameras in `world`, other than the editor camera.
+pub fn scene_cameras(world: &mut World) -> Vec<(Entity, String, Transform, CameraComponent)> {
+	let mut out: Vec<_> = world
+		.query_filtered::<(Entity, &Transform, &CameraComponent, Option<&Name>), Without<PrimaryViewComponent>>()
+		.iter(world)
+		.map(|(e, t, c, n)| {
+			let name = n
+				.map(|x| x.0.clone())
+				.unwrap_or_else(|| format!("camera {}", e.index()));
+			(e, name, *t, *c)
+		})
+		.collect();
+	out.sort_by_key(|x| x.0);
+	out
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum Mode {
 	Camera,
 	Default,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+	Linear,
+	SmoothStep,
+	EaseOutCubic,
+}
+
+impl Easing {
+	pub const ALL: [Self; 3] = [Self::Linear, Self::SmoothStep, Self::EaseOutCubic];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Linear => "linear",
+			Self::SmoothStep => "smooth step",
+			Self::EaseOutCubic => "ease out cubic",
+		}
+	}
+
+	fn apply(self, t: f32) -> f32 {
+		match self {
+			Self::Linear => t,
+			Self::SmoothStep => t * t * (3.0 - 2.0 * t),
+			Self::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+		}
+	}
+}
+
+/// Movement and look settings, saved between sessions.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+	/// Fly speed, in meters per second or in scene sizes per second if `scene_relative`.
+	pub fly_speed: f32,
+	/// A multiplier on how fast the mouse turns the camera.
+	pub sensitivity: f32,
+	/// The speed multiplier while shift is held.
+	pub fast_multiplier: f32,
+	/// The speed multiplier while ctrl is held.
+	pub slow_multiplier: f32,
+	/// Scale the fly speed by the diagonal of the scene bounds, so it works at any scene scale.
+	pub scene_relative: bool,
+}
+
+impl Default for CameraSettings {
+	fn default() -> Self {
+		Self {
+			fly_speed: 1.0,
+			sensitivity: 1.0,
+			fast_multiplier: 4.0,
+			slow_multiplier: 0.25,
+			scene_relative: false,
+		}
+	}
+}
+
+impl CameraSettings {
+	fn path() -> Option<PathBuf> { Some(std::env::current_exe().ok()?.with_file_name("camera_settings.json")) }
+
+	pub fn load() -> Self {
+		Self::path()
+			.and_then(|p| std::fs::read(p).ok())
+			.and_then(|x| serde_json::from_slice(&x).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		let path = Self::path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no executable path"))?;
+		std::fs::write(path, serde_json::to_vec_pretty(self)?)
+	}
+}
+
+/// An interpolation from a previous pose to the current one.
+struct Transition {
+	pos: Vec3<f32>,
+	rotation: Quaternion<f32>,
+	t: f32,
+}
+
 pub struct CameraController {
 	pub pos: Vec3<f32>,
 	states: [bool; 6],
 	pitch: f32,
 	yaw: f32,
-	move_speed: f32,
+	pub settings: CameraSettings,
+	fast: bool,
+	slow: bool,
+	/// The diagonal of the scene bounds.
+	scene_size: f32,
 	mode: Mode,
 	grabber: MouseGrabber,
+	transition: Option<Transition>,
+	/// Interpolate to poses given to `set` instead of snapping to them.
+	pub smooth: bool,
+	/// The length of a transition in seconds.
+	pub duration: f32,
+	pub easing: Easing,
 }
 
 impl CameraController {
@@ -59,9 +192,110 @@ impl CameraController {
 			states: [false; 6],
 			pitch: 0.0,
 			yaw: 0.0,
-			move_speed: 1.0,
+			settings: CameraSettings::load(),
+			fast: false,
+			slow: false,
+			scene_size: 1.0,
 			mode: Mode::Default,
 			grabber: MouseGrabber::default(),
+			transition: None,
+			smooth: true,
+			duration: 0.5,
+			easing: Easing::SmoothStep,
+		}
+	}
+
+	fn rotation(&self) -> Quaternion<f32> { Quaternion::identity().rotated_x(self.pitch).rotated_z(self.yaw) }
+
+	/// The current pose of the camera, ignoring any transition.
+	pub fn pose(&self) -> Transform {
+		Transform {
+			position: self.pos,
+			rotation: self.rotation(),
+			scale: Vec3::broadcast(1.0),
+		}
+	}
+
+	/// Move the camera to `t`. Roll is discarded, as the camera is always kept upright.
+	pub fn set(&mut self, t: &Transform) {
+		if self.smooth && self.duration > 0.0 {
+			let (pos, rotation) = self.current();
+			self.transition = Some(Transition { pos, rotation, t: 0.0 });
+		} else {
+			self.transition = None;
+		}
+
+		let forward = t.rotation * Vec3::unit_y();
+		self.pos = t.position;
+		self.pitch = forward.z.clamp(-1.0, 1.0).asin();
+		self.yaw = (-forward.x).atan2(forward.y);
+	}
+
+	fn current(&self) -> (Vec3<f32>, Quaternion<f32>) {
+		match &self.transition {
+			Some(tr) => {
+				let t = self.easing.apply(tr.t);
+				(
+					Lerp::lerp(tr.pos, self.pos, t),
+					Quaternion::slerp(tr.rotation, self.rotation(), t),
+				)
+			},
+			None => (self.pos, self.rotation()),
+		}
+	}
+
+	/// Set the size of the scene, for scene relative fly speeds.
+	pub fn set_scene_bounds(&mut self, bounds: Option<Aabb<f32>>) {
+		self.scene_size = bounds.map(|x| (x.max - x.min).magnitude()).unwrap_or(1.0).max(1e-3);
+	}
+
+	/// The current fly speed, in meters per second.
+	pub fn speed(&self) -> f32 {
+		let s = &self.settings;
+		let mut speed = s.fly_speed;
+		if s.scene_relative {
+			speed *= self.scene_size;
+		}
+		if self.fast {
+			speed *= s.fast_multiplier;
+		}
+		if self.slow {
+			speed *= s.slow_multiplier;
+		}
+		speed
+	}
+
+	pub fn settings_ui(&mut self, ui: &mut Ui) {
+		let s = &mut self.settings;
+		Grid::new("camera settings").num_columns(2).show(ui, |ui| {
+			ui.label("fly speed");
+			ui.add(
+				DragValue::new(&mut s.fly_speed)
+					.speed(0.01)
+					.range(0.0001..=f32::MAX)
+					.suffix(if s.scene_relative { " scenes/s" } else { " m/s" }),
+			);
+			ui.end_row();
+
+			ui.label("sensitivity");
+			ui.add(DragValue::new(&mut s.sensitivity).speed(0.01).range(0.01..=10.0));
+			ui.end_row();
+
+			ui.label("shift multiplier");
+			ui.add(DragValue::new(&mut s.fast_multiplier).speed(0.1).range(1.0..=100.0));
+			ui.end_row();
+
+			ui.label("ctrl multiplier");
+			ui.add(DragValue::new(&mut s.slow_multiplier).speed(0.01).range(0.01..=1.0));
+			ui.end_row();
+		});
+		if ui.checkbox(&mut s.scene_relative, "relative to scene size").changed() {
+			// Keep the same speed in meters per second.
+			if s.scene_relative {
+				s.fly_speed /= self.scene_size;
+			} else {
+				s.fly_speed *= self.scene_size;
+			}
 		}
 	}
 
@@ -73,6 +307,14 @@ impl CameraController {
 	}
 
 	pub fn control(&mut self, ctx: &Context) {
+		// Transitions advance by the real frame time, so they take the same time at any frame rate.
+		if let Some(tr) = &mut self.transition {
+			tr.t += ctx.input(|x| x.unstable_dt) / self.duration;
+			if tr.t >= 1.0 {
+				self.transition = None;
+			}
+		}
+
 		if self.mode != Mode::Camera {
 			return;
 		}
@@ -91,7 +333,7 @@ impl CameraController {
 		} else {
 			dir.normalized()
 		};
-		self.pos += dir * self.move_speed * ctx.input(|x| x.unstable_dt);
+		self.pos += dir * self.speed() * ctx.input(|x| x.unstable_dt);
 	}
 
 	pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) {
@@ -100,11 +342,15 @@ impl CameraController {
 				self.grabber.cursor_moved(window, *position);
 				let delta = Vec2::new(position.x as f32, position.y as _)
 					- Vec2::new(self.grabber.last_pos.x as _, self.grabber.last_pos.y as _);
-				let delta = Vec2::new(delta.x, delta.y) * 0.002;
+				let delta = Vec2::new(delta.x, delta.y) * 0.002 * self.settings.sensitivity;
 				self.pitch -= delta.y;
 				self.yaw -= delta.x;
 				self.pitch = self.pitch.clamp(-f32::FRAC_PI_2(), f32::FRAC_PI_2());
 			},
+			WindowEvent::ModifiersChanged(m) => {
+				self.fast = m.state().shift_key();
+				self.slow = m.state().control_key();
+			},
 			WindowEvent::KeyboardInput {
 				event,
 				is_synthetic: false,
@@ -127,15 +373,16 @@ impl CameraController {
 					MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
 				};
 				let factor = 2f32.powf(delta);
-				self.move_speed *= factor;
+				self.settings.fly_speed *= factor;
 			},
 			_ => {},
 		}
 	}
 
 	pub fn apply(&self, mut entity: EntityMut<'_>) {
+		let (position, rotation) = self.current();
 		let mut t = entity.get_mut::<Transform>().unwrap();
-		t.position = self.pos;
-		t.rotation = Quaternion::identity().rotated_x(self.pitch).rotated_z(self.yaw);
+		t.position = position;
+		t.rotation = rotation;
 	}
 }
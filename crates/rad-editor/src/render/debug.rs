@@ -1,11 +1,43 @@
 use egui_plot::{Bar, BarChart, HPlacement, Plot, VLine, VPlacement};
-use rad_graph::device::{Device, HotreloadStatus};
+use rad_graph::{
+	ash::vk,
+	device::{Device, HotreloadStatus},
+	graph::RenderGraph,
+};
 use rad_renderer::{
-	debug::mesh::DebugVis,
-	mesh::{CullStats, PassStats},
-	tonemap::exposure::{ExposureCalc, ExposureStats},
+	debug::{
+		mesh::{overdraw_heat, Background, DebugVis, NormalSpace, Wireframe},
+		meshlet_spheres::MeshletSphere,
+	},
+	env::EnvironmentMap,
+	mesh::{self, CullStats, HzbInfo, PassStats},
+	pt,
+	shadow::{ShadowInfo, MAX_CASCADES},
+	sky::SkySampler,
+	taa::TaaInfo,
+	tonemap::{
+		exposure::{ExposureCalc, ExposureStats},
+		DisplayTarget,
+	},
+	vek::Vec3,
+};
+use rad_ui::egui::{
+	vec2,
+	Align2,
+	Checkbox,
+	Color32,
+	ComboBox,
+	Context,
+	DragValue,
+	FontId,
+	Grid,
+	Key,
+	Painter,
+	Rect,
+	Rgba,
+	Ui,
+	Window,
 };
-use rad_ui::egui::{Checkbox, ComboBox, Context, DragValue, Ui, Window};
 
 #[derive(Copy, Clone)]
 pub enum RenderMode {
@@ -13,6 +45,15 @@ pub enum RenderMode {
 	Debug,
 }
 
+#[derive(Copy, Clone)]
+pub enum BackgroundMode {
+	Color,
+	Gradient,
+	Sky,
+	/// The environment map of the world, or the sky if it has none.
+	Map,
+}
+
 #[derive(Copy, Clone)]
 pub enum HdrTonemap {
 	Null,
@@ -26,6 +67,11 @@ pub enum Tonemap {
 	AgX,
 	AgXPunchy,
 	TonyMcMapface,
+	Reinhard,
+	Uncharted2,
+	/// The HDR tonemappers, displayed in SDR.
+	Null,
+	Frostbite,
 }
 
 pub struct DebugWindow {
@@ -34,8 +80,40 @@ pub struct DebugWindow {
 	tonemap: Tonemap,
 	hdr_tonemap: HdrTonemap,
 	debug_vis: DebugVis,
-	scale: f32,
+	overdraw_max: u32,
+	/// Scale overdraw to the peak read back from the GPU instead of `overdraw_max`.
+	overdraw_auto: bool,
+	overdraw_peak: Option<u32>,
+	texture_feedback: bool,
+	/// The number of textures last seen to need every power of two size, indexed by its log2.
+	texture_sizes: [u32; 16],
+	depth_range: [f32; 2],
+	show_hzb: bool,
+	normal_space: NormalSpace,
+	geometric_normals: bool,
+	wireframe: bool,
+	wire_thickness: f32,
+	wire_color: [f32; 3],
+	background_mode: BackgroundMode,
+	background_top: [f32; 3],
+	background_bottom: [f32; 3],
+	meshlet_spheres: Option<MeshletSphere>,
+	sphere_radius: u32,
+	sphere_color: [f32; 3],
+	shadows: ShadowInfo,
+	samples: vk::SampleCountFlags,
+	hzb: HzbInfo,
+	sw_edge_pixels: f32,
+	taa: bool,
+	taa_info: TaaInfo,
 	exposure_compensation: f32,
+	punchy_strength: f32,
+	sdr_white: f32,
+	max_bounces: u32,
+	rr_start_depth: u32,
+	firefly_clamp: Option<f32>,
+	aperture: f32,
+	focus_distance: f32,
 }
 
 impl DebugWindow {
@@ -46,8 +124,38 @@ impl DebugWindow {
 			tonemap: Tonemap::TonyMcMapface,
 			hdr_tonemap: HdrTonemap::AgX,
 			debug_vis: DebugVis::Meshlets,
-			scale: 0.15,
+			overdraw_max: 16,
+			overdraw_auto: true,
+			overdraw_peak: None,
+			texture_feedback: false,
+			texture_sizes: [0; 16],
+			depth_range: [0.0, 100.0],
+			show_hzb: false,
+			normal_space: NormalSpace::World,
+			geometric_normals: false,
+			wireframe: false,
+			wire_thickness: 1.0,
+			wire_color: [0.0; 3],
+			background_mode: BackgroundMode::Color,
+			background_top: [0.0; 3],
+			background_bottom: [0.0; 3],
+			meshlet_spheres: None,
+			sphere_radius: 8,
+			sphere_color: [1.0, 1.0, 0.0],
+			shadows: ShadowInfo::default(),
+			samples: vk::SampleCountFlags::TYPE_1,
+			hzb: HzbInfo::default(),
+			sw_edge_pixels: mesh::RenderInfo::DEFAULT_SW_EDGE_PIXELS,
+			taa: true,
+			taa_info: TaaInfo::default(),
 			exposure_compensation: 0.0,
+			punchy_strength: 1.0,
+			sdr_white: DisplayTarget::DEFAULT_WHITE,
+			max_bounces: pt::RenderInfo::DEFAULT_MAX_BOUNCES,
+			rr_start_depth: pt::RenderInfo::DEFAULT_RUSSIAN_ROULETTE_START_DEPTH,
+			firefly_clamp: None,
+			aperture: 0.0,
+			focus_distance: 5.0,
 		}
 	}
 
@@ -64,6 +172,29 @@ impl DebugWindow {
 			8 => "roughness",
 			9 => "metallic",
 			10 => "emissive",
+			11 => "vertex colors",
+			12 => "lod",
+			13 => "depth",
+			14 => "lit",
+			15 => "meshlet boundaries",
+			_ => unreachable!(),
+		}
+	}
+
+	fn sphere_text(sphere: usize) -> &'static str {
+		match sphere {
+			0 => "off",
+			1 => "bounding",
+			2 => "group error",
+			3 => "parent error",
+			_ => unreachable!(),
+		}
+	}
+
+	fn samples_text(samples: usize) -> &'static str {
+		match samples {
+			0 => "1x",
+			1 => "4x",
 			_ => unreachable!(),
 		}
 	}
@@ -76,11 +207,32 @@ impl DebugWindow {
 		}
 	}
 
+	fn punchy_strength_ui(&mut self, ui: &mut Ui) {
+		ui.horizontal(|ui| {
+			ui.label("look strength");
+			ui.add(DragValue::new(&mut self.punchy_strength).speed(0.01).range(0.0..=2.0));
+		});
+	}
+
+	fn background_text(background: usize) -> &'static str {
+		match background {
+			0 => "color",
+			1 => "gradient",
+			2 => "sky",
+			3 => "map",
+			_ => unreachable!(),
+		}
+	}
+
 	fn tonemap_text(tonemap: usize) -> &'static str {
 		match tonemap {
 			0 => "agx",
 			1 => "agx (punchy)",
 			2 => "tony mcmapface",
+			3 => "reinhard",
+			4 => "uncharted 2",
+			5 => "null",
+			6 => "frostbite",
 			_ => unreachable!(),
 		}
 	}
@@ -97,8 +249,12 @@ impl DebugWindow {
 
 	pub fn render(
 		&mut self, device: &Device, window: &mut rad_window::Window, ctx: &Context, stats: Option<CullStats>,
-		pt: Option<(ExposureStats, u32)>,
+		pt: Option<(ExposureStats, u32)>, graph: &RenderGraph,
 	) {
+		if !ctx.wants_keyboard_input() && ctx.input(|x| x.key_pressed(Key::F5)) {
+			device.reload_shaders();
+		}
+
 		Window::new("debug").open(&mut self.enabled).show(ctx, |ui| {
 			let mut sel = self.render_mode as usize;
 			ComboBox::from_label("render mode")
@@ -122,6 +278,38 @@ impl DebugWindow {
 
 			match self.render_mode {
 				RenderMode::Path => {
+					ui.horizontal(|ui| {
+						ui.label("max bounces");
+						ui.add(DragValue::new(&mut self.max_bounces).range(1..=64));
+					});
+					ui.horizontal(|ui| {
+						ui.label("russian roulette start");
+						ui.add(DragValue::new(&mut self.rr_start_depth).range(0..=64));
+					});
+					ui.horizontal(|ui| {
+						let mut enabled = self.firefly_clamp.is_some();
+						ui.checkbox(&mut enabled, "firefly clamp");
+						let mut clamp = self.firefly_clamp.unwrap_or(10.0);
+						ui.add_enabled(enabled, DragValue::new(&mut clamp).speed(0.1).range(0.01..=1000.0));
+						self.firefly_clamp = enabled.then_some(clamp);
+					});
+					ui.horizontal(|ui| {
+						ui.label("aperture");
+						ui.add(
+							DragValue::new(&mut self.aperture)
+								.speed(0.1)
+								.range(0.0..=64.0)
+								.prefix("f/"),
+						);
+						ui.label("focus");
+						ui.add(
+							DragValue::new(&mut self.focus_distance)
+								.speed(0.05)
+								.range(0.01..=1000.0)
+								.suffix(" m"),
+						);
+					});
+
 					if hdr {
 						let mut sel = self.hdr_tonemap as usize;
 						ComboBox::from_label("hdr tonemap")
@@ -134,48 +322,259 @@ impl DebugWindow {
 							3 => HdrTonemap::AgXPunchy,
 							_ => unreachable!(),
 						};
+						if matches!(self.hdr_tonemap, HdrTonemap::AgXPunchy) {
+							self.punchy_strength_ui(ui);
+						}
+						ui.horizontal(|ui| {
+							ui.label("sdr white");
+							ui.add(
+								DragValue::new(&mut self.sdr_white)
+									.speed(1.0)
+									.range(80.0..=1000.0)
+									.suffix(" nits"),
+							);
+						});
 					} else {
 						let mut sel = self.tonemap as usize;
 						ComboBox::from_label("tonemap")
 							.selected_text(Self::tonemap_text(sel))
-							.show_index(ui, &mut sel, 3, Self::tonemap_text);
+							.show_index(ui, &mut sel, 7, Self::tonemap_text);
 						self.tonemap = match sel {
 							0 => Tonemap::AgX,
 							1 => Tonemap::AgXPunchy,
 							2 => Tonemap::TonyMcMapface,
+							3 => Tonemap::Reinhard,
+							4 => Tonemap::Uncharted2,
+							5 => Tonemap::Null,
+							6 => Tonemap::Frostbite,
 							_ => unreachable!(),
 						};
+						if matches!(self.tonemap, Tonemap::AgXPunchy) {
+							self.punchy_strength_ui(ui);
+						}
 					}
 				},
 				RenderMode::Debug => {
 					let mut sel = self.debug_vis.to_u32() as usize;
 					ComboBox::from_label("debug vis")
 						.selected_text(Self::vis_text(sel))
-						.show_index(ui, &mut sel, 11, Self::vis_text);
+						.show_index(ui, &mut sel, 16, Self::vis_text);
 					self.debug_vis = match sel {
 						0 => DebugVis::Triangles,
 						1 => DebugVis::Meshlets,
-						2 => DebugVis::Overdraw(self.scale),
+						2 => DebugVis::Overdraw(self.overdraw_max),
 						3 => DebugVis::HwSw,
-						4 => DebugVis::Normals,
+						4 => DebugVis::Normals {
+							space: self.normal_space,
+							geometric: self.geometric_normals,
+						},
 						5 => DebugVis::Uvs,
 						6 => DebugVis::Error,
 						7 => DebugVis::BaseColor,
 						8 => DebugVis::Roughness,
 						9 => DebugVis::Metallic,
 						10 => DebugVis::Emissive,
+						11 => DebugVis::VertexColors,
+						12 => DebugVis::Lod,
+						13 => DebugVis::Depth {
+							near: self.depth_range[0],
+							far: self.depth_range[1],
+							hzb: self.show_hzb,
+						},
+						14 => DebugVis::Lit,
+						15 => DebugVis::MeshletBoundaries,
 						_ => unreachable!(),
 					};
 
+					let mut sel = self.samples.as_raw().ilog2() as usize / 2;
+					ComboBox::from_label("msaa")
+						.selected_text(Self::samples_text(sel))
+						.show_index(ui, &mut sel, 2, Self::samples_text);
+					self.samples = vk::SampleCountFlags::from_raw(1 << (sel * 2));
+
+					ui.checkbox(&mut self.taa, "taa");
+					if self.taa {
+						ui.horizontal(|ui| {
+							ui.label("blend");
+							ui.add(DragValue::new(&mut self.taa_info.blend).speed(0.005).range(0.01..=1.0));
+						});
+					}
+
+					ui.collapsing("hzb", |ui| {
+						let h = &mut self.hzb;
+						Grid::new("hzb").num_columns(2).show(ui, |ui| {
+							ui.label("mips");
+							ui.add(DragValue::new(&mut h.mips).range(1..=HzbInfo::MAX_MIPS));
+							ui.end_row();
+
+							ui.label("min tile");
+							ui.add(DragValue::new(&mut h.min_tile).range(1..=4096).suffix(" px"));
+							ui.end_row();
+						});
+						ui.checkbox(&mut h.conservative, "conservative");
+					});
+
+					ui.horizontal(|ui| {
+						ui.label("sw raster below");
+						ui.add(
+							DragValue::new(&mut self.sw_edge_pixels)
+								.speed(0.1)
+								.range(0.0..=256.0)
+								.suffix(" px"),
+						)
+						.on_hover_text(
+							"meshlets with their longest edge under this on screen are rasterized in software",
+						);
+					});
+
 					match &mut self.debug_vis {
-						DebugVis::Overdraw(s) => {
+						DebugVis::Overdraw(_) => {
 							ui.horizontal(|ui| {
-								ui.add(DragValue::new(&mut self.scale).speed(0.01).range(0.0..=1.0));
+								ui.checkbox(&mut self.overdraw_auto, "auto scale");
+								ui.add_enabled(
+									!self.overdraw_auto,
+									DragValue::new(&mut self.overdraw_max).range(1..=1024).prefix("max "),
+								);
+							});
+							match self.overdraw_peak {
+								Some(peak) => ui.label(format!("peak overdraw: {peak}")),
+								None => ui.label("peak overdraw: waiting for readback"),
+							};
+						},
+						DebugVis::Depth { near, far, hzb } => {
+							let [n, f] = &mut self.depth_range;
+							ui.horizontal(|ui| {
+								ui.label("depth range");
+								ui.add(DragValue::new(n).speed(0.1).range(0.0..=*f - 0.01).suffix(" m"));
+								ui.add(DragValue::new(f).speed(0.1).range(*n + 0.01..=10000.0).suffix(" m"));
+							});
+							ui.checkbox(&mut self.show_hzb, "show hzb");
+							*near = self.depth_range[0];
+							*far = self.depth_range[1];
+							*hzb = self.show_hzb;
+						},
+						DebugVis::Normals { space, geometric } => {
+							ComboBox::from_label("normal space")
+								.selected_text(match self.normal_space {
+									NormalSpace::World => "world",
+									NormalSpace::View => "view",
+								})
+								.show_ui(ui, |ui| {
+									ui.selectable_value(&mut self.normal_space, NormalSpace::World, "world");
+									ui.selectable_value(&mut self.normal_space, NormalSpace::View, "view");
+								});
+							ui.checkbox(&mut self.geometric_normals, "geometric");
+							*space = self.normal_space;
+							*geometric = self.geometric_normals;
+						},
+						DebugVis::Lit => {
+							let s = &mut self.shadows;
+							Grid::new("shadows").num_columns(2).show(ui, |ui| {
+								ui.label("cascades");
+								ui.add(DragValue::new(&mut s.cascades).range(1..=MAX_CASCADES as u32));
+								ui.end_row();
+
+								ui.label("resolution");
+								ComboBox::from_id_salt("shadow resolution")
+									.selected_text(s.resolution.to_string())
+									.show_ui(ui, |ui| {
+										for r in [512, 1024, 2048, 4096] {
+											ui.selectable_value(&mut s.resolution, r, r.to_string());
+										}
+									});
+								ui.end_row();
+
+								ui.label("distance");
+								ui.add(
+									DragValue::new(&mut s.distance)
+										.speed(0.5)
+										.range(1.0..=10000.0)
+										.suffix(" m"),
+								);
+								ui.end_row();
 							});
-							*s = self.scale;
 						},
 						_ => {},
 					}
+
+					let mut sel = self.background_mode as usize;
+					ComboBox::from_label("background")
+						.selected_text(Self::background_text(sel))
+						.show_index(ui, &mut sel, 4, Self::background_text);
+					self.background_mode = match sel {
+						0 => BackgroundMode::Color,
+						1 => BackgroundMode::Gradient,
+						2 => BackgroundMode::Sky,
+						3 => BackgroundMode::Map,
+						_ => unreachable!(),
+					};
+					match self.background_mode {
+						BackgroundMode::Color => {
+							ui.horizontal(|ui| {
+								ui.label("color");
+								ui.color_edit_button_rgb(&mut self.background_top);
+							});
+						},
+						BackgroundMode::Gradient => {
+							ui.horizontal(|ui| {
+								ui.label("top");
+								ui.color_edit_button_rgb(&mut self.background_top);
+								ui.label("bottom");
+								ui.color_edit_button_rgb(&mut self.background_bottom);
+							});
+						},
+						BackgroundMode::Sky | BackgroundMode::Map => {},
+					}
+
+					ui.checkbox(&mut self.wireframe, "wireframe");
+					if self.wireframe {
+						ui.horizontal(|ui| {
+							ui.add(
+								DragValue::new(&mut self.wire_thickness)
+									.speed(0.05)
+									.range(0.5..=8.0)
+									.suffix(" px"),
+							);
+							ui.color_edit_button_rgb(&mut self.wire_color);
+						});
+					}
+
+					ui.checkbox(&mut self.texture_feedback, "texture feedback")
+						.on_hover_text("read back the resolution material textures are seen at");
+					if self.texture_feedback {
+						ui.label(format!("textures seen: {}", self.texture_sizes.iter().sum::<u32>()));
+						Grid::new("texture sizes").striped(true).show(ui, |ui| {
+							for (i, &count) in self.texture_sizes.iter().enumerate().filter(|x| *x.1 > 0) {
+								ui.label(format!("{} px", 1u32 << i));
+								ui.label(count.to_string());
+								ui.end_row();
+							}
+						});
+					}
+
+					let mut sel = match self.meshlet_spheres {
+						None => 0,
+						Some(MeshletSphere::Bounding) => 1,
+						Some(MeshletSphere::GroupError) => 2,
+						Some(MeshletSphere::ParentError) => 3,
+					};
+					ComboBox::from_label("meshlet spheres")
+						.selected_text(Self::sphere_text(sel))
+						.show_index(ui, &mut sel, 4, Self::sphere_text);
+					self.meshlet_spheres = match sel {
+						0 => None,
+						1 => Some(MeshletSphere::Bounding),
+						2 => Some(MeshletSphere::GroupError),
+						3 => Some(MeshletSphere::ParentError),
+						_ => unreachable!(),
+					};
+					if self.meshlet_spheres.is_some() {
+						ui.horizontal(|ui| {
+							ui.label("around cursor");
+							ui.add(DragValue::new(&mut self.sphere_radius).range(0..=64).suffix(" px"));
+							ui.color_edit_button_rgb(&mut self.sphere_color);
+						});
+					}
 				},
 			}
 
@@ -185,26 +584,60 @@ impl DebugWindow {
 					HotreloadStatus::Waiting => ui.label("ready"),
 					HotreloadStatus::Recompiling => ui.spinner(),
 					HotreloadStatus::Errored => ui.label("errored"),
+				};
+				if ui.button("reload").on_hover_text("F5").clicked() {
+					device.reload_shaders();
+				}
+			});
+
+			let mem = device.memory_report();
+			ui.label(format!(
+				"gpu memory: {:.0} / {:.0} MiB ({} allocations)",
+				mem.allocated as f64 / (1024.0 * 1024.0),
+				mem.capacity as f64 / (1024.0 * 1024.0),
+				mem.allocations
+			));
+
+			let aliasing = graph.aliasing();
+			ui.label(format!(
+				"transient resources: {} in {} ({:.0} MiB saved)",
+				aliasing.virtual_resources,
+				aliasing.resources,
+				aliasing.bytes_saved as f64 / (1024.0 * 1024.0)
+			));
+
+			ui.collapsing("gpu timings", |ui| {
+				for (name, ms) in graph.timings() {
+					ui.label(format!("{name}: {ms:.2} ms"));
 				}
 			});
 
 			if let Some(stats) = stats {
-				ui.label("early");
-				Self::pass_stats(ui, stats.early);
-				ui.label("late");
-				Self::pass_stats(ui, stats.late);
+				Self::pass_stats(ui, "early", stats.early);
+				Self::pass_stats(ui, "late", stats.late);
 			}
 
 			if let Some((exp, samples)) = pt {
-				ui.label(format!("samples: {}", samples));
+				ui.label(format!("converging: {} spp", samples));
 
-				ui.label(format!("exposure: {:.2}", exp.exposure));
+				if exp.ready {
+					ui.label(format!(
+						"exposure: {:.2} EV (scene: {:.2} EV)",
+						exp.exposure, exp.scene_exposure
+					));
+				} else {
+					ui.label("exposure: waiting for readback");
+				}
 
-				ui.add(
-					DragValue::new(&mut self.exposure_compensation)
-						.speed(0.1)
-						.range(-5.0..=5.0),
-				);
+				ui.horizontal(|ui| {
+					ui.label("exposure compensation");
+					ui.add(
+						DragValue::new(&mut self.exposure_compensation)
+							.speed(0.1)
+							.range(-5.0..=5.0)
+							.suffix(" EV"),
+					);
+				});
 
 				Plot::new("exposure histogram")
 					.allow_zoom(false)
@@ -251,11 +684,47 @@ impl DebugWindow {
 		});
 	}
 
-	fn pass_stats(ui: &mut Ui, pass: PassStats) {
-		ui.label(format!("instances: {}", pass.instances));
-		ui.label(format!("candidate meshlets: {}", pass.candidate_meshlets));
+	fn pass_stats(ui: &mut Ui, name: &str, pass: PassStats) {
+		ui.label(name);
+		Grid::new(name).striped(true).show(ui, |ui| {
+			ui.label("");
+			ui.label("tested");
+			ui.label("culled");
+			ui.label("occluded");
+			ui.label("drawn");
+			ui.end_row();
+
+			let mut stage = |name: &str, tested: u32, culled: u32, occluded: u32| {
+				ui.label(name);
+				ui.label(tested.to_string());
+				ui.label(culled.to_string());
+				ui.label(occluded.to_string());
+				ui.label(tested.saturating_sub(culled + occluded).to_string());
+				ui.end_row();
+			};
+			stage(
+				"instances",
+				pass.instances,
+				pass.instances_culled,
+				pass.instances_occluded,
+			);
+			stage(
+				"bvh nodes",
+				pass.bvh_nodes,
+				pass.bvh_nodes_culled,
+				pass.bvh_nodes_occluded,
+			);
+			stage(
+				"meshlets",
+				pass.candidate_meshlets,
+				pass.meshlets_culled,
+				pass.meshlets_occluded,
+			);
+		});
+		ui.label(format!("meshlets too large for hzb: {}", pass.meshlets_hzb_skipped));
 		ui.label(format!("hw meshlets: {}", pass.hw_meshlets));
 		ui.label(format!("sw meshlets: {}", pass.sw_meshlets));
+		ui.label(format!("sw triangles/meshlet: {:.1}", pass.sw_triangles_per_meshlet()));
 	}
 
 	pub fn render_mode(&self) -> RenderMode { self.render_mode }
@@ -264,7 +733,122 @@ impl DebugWindow {
 
 	pub fn hdr_tonemap(&self) -> HdrTonemap { self.hdr_tonemap }
 
+	/// Applied on top of the exposure compensation of the world.
 	pub fn exposure_compensation(&self) -> f32 { self.exposure_compensation }
 
-	pub fn debug_vis(&self) -> DebugVis { self.debug_vis }
+	pub fn punchy_strength(&self) -> f32 { self.punchy_strength }
+
+	pub fn sdr_white(&self) -> f32 { self.sdr_white }
+
+	pub fn max_bounces(&self) -> u32 { self.max_bounces }
+
+	pub fn russian_roulette_start_depth(&self) -> u32 { self.rr_start_depth }
+
+	pub fn firefly_clamp(&self) -> Option<f32> { self.firefly_clamp }
+
+	pub fn aperture(&self) -> f32 { self.aperture }
+
+	pub fn focus_distance(&self) -> f32 { self.focus_distance }
+
+	/// The background of the debug view, with `sky` only called if it shows the sky. `map` is the environment map of
+	/// the world, if it has one.
+	pub fn background(&self, sky: impl FnOnce() -> SkySampler, map: Option<&EnvironmentMap>) -> Background {
+		match self.background_mode {
+			BackgroundMode::Color => Background::Color(self.background_top.into()),
+			BackgroundMode::Gradient => Background::Gradient {
+				top: self.background_top.into(),
+				bottom: self.background_bottom.into(),
+			},
+			BackgroundMode::Sky => Background::Sky(sky()),
+			BackgroundMode::Map => map.map_or_else(|| Background::Sky(sky()), Background::map),
+		}
+	}
+
+	pub fn debug_vis(&self) -> DebugVis {
+		match self.debug_vis {
+			DebugVis::Overdraw(_) => DebugVis::Overdraw(self.overdraw_scale()),
+			vis => vis,
+		}
+	}
+
+	/// The overdraw shown as red.
+	fn overdraw_scale(&self) -> u32 {
+		match (self.overdraw_auto, self.overdraw_peak) {
+			(true, Some(peak)) => peak.max(1),
+			_ => self.overdraw_max,
+		}
+	}
+
+	pub fn set_overdraw_peak(&mut self, peak: Option<u32>) { self.overdraw_peak = peak; }
+
+	pub fn texture_feedback(&self) -> bool { self.texture_feedback }
+
+	/// Count the textures needing every size, rounded up to a power of two, from the sizes read back.
+	pub fn set_texture_sizes(&mut self, sizes: impl Iterator<Item = u32>) {
+		self.texture_sizes = [0; 16];
+		for size in sizes {
+			let i = (size.next_power_of_two().ilog2() as usize).min(self.texture_sizes.len() - 1);
+			self.texture_sizes[i] += 1;
+		}
+	}
+
+	/// Paint the legend of `DebugVis::Overdraw` into the bottom left of `painter`, if it is shown.
+	pub fn paint_overdraw_legend(&self, painter: &Painter) {
+		if !matches!(self.debug_vis, DebugVis::Overdraw(_)) {
+			return;
+		}
+		let max = self.overdraw_scale();
+		let rect = painter.clip_rect();
+		let bar = Rect::from_min_size(rect.left_bottom() + vec2(12.0, -36.0), vec2(200.0, 12.0));
+		let back = Rect::from_min_max(bar.min - vec2(4.0, 4.0), bar.max + vec2(4.0, 20.0));
+		painter.rect_filled(back, 2.0, Color32::from_black_alpha(160));
+		let steps = max.min(64);
+		let w = bar.width() / steps as f32;
+		for i in 0..steps {
+			let overdraw = 1 + i * (max - 1) / (steps - 1).max(1);
+			let c = overdraw_heat(overdraw, max);
+			painter.rect_filled(
+				Rect::from_min_size(bar.min + vec2(w * i as f32, 0.0), vec2(w, bar.height())),
+				0.0,
+				Color32::from(Rgba::from_rgb(c.x, c.y, c.z)),
+			);
+		}
+		let text = |pos, align, s: String| {
+			painter.text(pos, align, s, FontId::monospace(11.0), Color32::WHITE);
+		};
+		text(bar.left_bottom() + vec2(0.0, 4.0), Align2::LEFT_TOP, "1".to_string());
+		text(
+			bar.center_bottom() + vec2(0.0, 4.0),
+			Align2::CENTER_TOP,
+			format!("{}", max.div_ceil(2)),
+		);
+		text(
+			bar.right_bottom() + vec2(0.0, 4.0),
+			Align2::RIGHT_TOP,
+			format!("{max}+"),
+		);
+	}
+
+	pub fn wireframe(&self) -> Option<Wireframe> {
+		self.wireframe.then(|| Wireframe {
+			thickness: self.wire_thickness,
+			color: self.wire_color.into(),
+		})
+	}
+
+	/// The meshlet spheres to draw, and the radius around the cursor to draw them in.
+	pub fn meshlet_spheres(&self) -> Option<(MeshletSphere, u32, Vec3<f32>)> {
+		self.meshlet_spheres
+			.map(|s| (s, self.sphere_radius, self.sphere_color.into()))
+	}
+
+	pub fn samples(&self) -> vk::SampleCountFlags { self.samples }
+
+	pub fn shadows(&self) -> ShadowInfo { self.shadows }
+
+	pub fn hzb(&self) -> HzbInfo { self.hzb }
+
+	pub fn sw_edge_pixels(&self) -> f32 { self.sw_edge_pixels }
+
+	pub fn taa(&self) -> Option<TaaInfo> { self.taa.then_some(self.taa_info) }
 }
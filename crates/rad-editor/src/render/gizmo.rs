@@ -0,0 +1,254 @@
+use rad_renderer::{
+	components::camera::CameraComponent,
+	vek::{Quaternion, Vec3},
+};
+use rad_ui::egui::{vec2, Color32, Painter, Pos2, Rect, Shape, Stroke};
+use rad_world::transform::Transform;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum GizmoMode {
+	Translate,
+	Rotate,
+	Scale,
+}
+
+/// The camera the viewport is rendered from, to project the gizmo and cast rays from the pointer.
+pub struct GizmoView {
+	pub transform: Transform,
+	pub camera: CameraComponent,
+	pub rect: Rect,
+}
+
+impl GizmoView {
+	fn wh(&self) -> (f32, f32) {
+		let h = (self.camera.fov / 2.0).tan().recip();
+		(h * self.rect.height() / self.rect.width(), h)
+	}
+
+	fn project(&self, p: Vec3<f32>) -> Option<Pos2> {
+		let v = self.transform.rotation.inverse() * (p - self.transform.position);
+		if v.y <= self.camera.near {
+			return None;
+		}
+		let (w, h) = self.wh();
+		let uv = vec2(0.5 + 0.5 * w * v.x / v.y, 0.5 - 0.5 * h * v.z / v.y);
+		Some(self.rect.min + uv * self.rect.size())
+	}
+
+	/// The ray from the camera through `pos`, in screen space.
+	pub fn ray(&self, pos: Pos2) -> Ray {
+		let (w, h) = self.wh();
+		let uv = (pos - self.rect.min) / self.rect.size();
+		let dir = Vec3::new((uv.x * 2.0 - 1.0) / w, 1.0, (1.0 - uv.y * 2.0) / h);
+		Ray {
+			origin: self.transform.position,
+			dir: (self.transform.rotation * dir).normalized(),
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct Ray {
+	pub origin: Vec3<f32>,
+	pub dir: Vec3<f32>,
+}
+
+impl Ray {
+	/// The closest points between the ray and the line through `origin` along `axis`, as distances along the line
+	/// and the ray.
+	fn closest_to_line(&self, origin: Vec3<f32>, axis: Vec3<f32>) -> Option<(f32, f32)> {
+		let w = origin - self.origin;
+		let b = axis.dot(self.dir);
+		let d = axis.dot(w);
+		let e = self.dir.dot(w);
+		let denom = 1.0 - b * b;
+		if denom < 1e-6 {
+			return None;
+		}
+		Some(((b * e - d) / denom, (e - b * d) / denom))
+	}
+
+	fn hit_plane(&self, origin: Vec3<f32>, normal: Vec3<f32>) -> Option<(Vec3<f32>, f32)> {
+		let denom = normal.dot(self.dir);
+		if denom.abs() < 1e-4 {
+			return None;
+		}
+		let t = normal.dot(origin - self.origin) / denom;
+		(t > 0.0).then(|| (self.origin + self.dir * t, t))
+	}
+}
+
+struct Drag {
+	axis: usize,
+	start: Transform,
+	/// Where the drag started, along the axis for translation and scaling, and on the rotation plane for rotation.
+	start_param: f32,
+	start_point: Vec3<f32>,
+}
+
+/// A translate, rotate, and scale gizmo for the transform of the selected entity. Translation and rotation are along
+/// world axes, and scaling is along the local axes of the entity.
+pub struct Gizmo {
+	pub mode: GizmoMode,
+	hovered: Option<usize>,
+	drag: Option<Drag>,
+}
+
+const COLORS: [Color32; 3] = [Color32::RED, Color32::GREEN, Color32::BLUE];
+
+impl Gizmo {
+	pub fn new() -> Self {
+		Self {
+			mode: GizmoMode::Translate,
+			hovered: None,
+			drag: None,
+		}
+	}
+
+	fn axes(&self, t: &Transform) -> [Vec3<f32>; 3] {
+		let axes = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()];
+		match self.mode {
+			GizmoMode::Scale => axes.map(|x| t.rotation * x),
+			_ => axes,
+		}
+	}
+
+	/// The length of the axes in world space, so that the gizmo keeps the same size on screen.
+	fn size(view: &GizmoView, t: &Transform) -> f32 { (t.position - view.transform.position).magnitude() * 0.2 }
+
+	/// The axis under `ray`, if any.
+	fn hit(&self, view: &GizmoView, t: &Transform, ray: Ray) -> Option<usize> {
+		let size = Self::size(view, t);
+		let axes = self.axes(t);
+		(0..3)
+			.filter_map(|i| {
+				let axis = axes[i];
+				match self.mode {
+					GizmoMode::Translate | GizmoMode::Scale => {
+						let (s, u) = ray.closest_to_line(t.position, axis)?;
+						let dist = (t.position + axis * s - (ray.origin + ray.dir * u)).magnitude();
+						(u > 0.0 && (0.0..=size).contains(&s) && dist < size * 0.06).then_some((i, u))
+					},
+					GizmoMode::Rotate => {
+						let (p, u) = ray.hit_plane(t.position, axis)?;
+						let r = (p - t.position).magnitude();
+						((r - size).abs() < size * 0.08).then_some((i, u))
+					},
+				}
+			})
+			.min_by(|a, b| a.1.total_cmp(&b.1))
+			.map(|(i, _)| i)
+	}
+
+	/// Update the gizmo with the pointer, editing `t` while it is dragged. Returns whether the gizmo is under or
+	/// dragged by the pointer, in which case the click should not select anything.
+	pub fn update(
+		&mut self, view: &GizmoView, t: &mut Transform, pointer: Option<Pos2>, pressed: bool, down: bool,
+	) -> bool {
+		if !down {
+			self.drag = None;
+		}
+		let Some(ray) = pointer.map(|x| view.ray(x)) else {
+			self.hovered = None;
+			return self.drag.is_some();
+		};
+
+		if let Some(drag) = &self.drag {
+			let axis = self.axes(&drag.start)[drag.axis];
+			match self.mode {
+				GizmoMode::Translate => {
+					if let Some((s, _)) = ray.closest_to_line(drag.start.position, axis) {
+						t.position = drag.start.position + axis * (s - drag.start_param);
+					}
+				},
+				GizmoMode::Scale => {
+					if let Some((s, _)) = ray.closest_to_line(drag.start.position, axis) {
+						let factor = (s / drag.start_param).max(0.01);
+						t.scale[drag.axis] = drag.start.scale[drag.axis] * factor;
+					}
+				},
+				GizmoMode::Rotate => {
+					if let Some((p, _)) = ray.hit_plane(drag.start.position, axis) {
+						let a = (drag.start_point - drag.start.position).normalized();
+						let b = (p - drag.start.position).normalized();
+						let angle = axis.dot(a.cross(b)).atan2(a.dot(b));
+						t.rotation = Quaternion::rotation_3d(angle, axis) * drag.start.rotation;
+					}
+				},
+			}
+			return true;
+		}
+
+		self.hovered = self.hit(view, t, ray);
+		if let (true, Some(axis)) = (pressed, self.hovered) {
+			let dir = self.axes(t)[axis];
+			let start_param = ray.closest_to_line(t.position, dir).map(|x| x.0).unwrap_or(1.0);
+			let start_point = ray.hit_plane(t.position, dir).map(|x| x.0).unwrap_or(t.position);
+			self.drag = Some(Drag {
+				axis,
+				start: *t,
+				start_param: if start_param.abs() < 1e-4 { 1e-4 } else { start_param },
+				start_point,
+			});
+		}
+		self.hovered.is_some()
+	}
+
+	pub fn paint(&self, view: &GizmoView, t: &Transform, painter: &Painter) {
+		let Some(center) = view.project(t.position) else {
+			return;
+		};
+		let size = Self::size(view, t);
+		let axes = self.axes(t);
+		let active = self.drag.as_ref().map(|x| x.axis).or(self.hovered);
+		for i in 0..3 {
+			let color = if active == Some(i) { Color32::YELLOW } else { COLORS[i] };
+			let stroke = Stroke::new(2.0, color);
+			match self.mode {
+				GizmoMode::Translate | GizmoMode::Scale => {
+					let Some(tip) = view.project(t.position + axes[i] * size) else {
+						continue;
+					};
+					painter.line_segment([center, tip], stroke);
+					if self.mode == GizmoMode::Translate {
+						painter.circle_filled(tip, 4.0, color);
+					} else {
+						painter.rect_filled(Rect::from_center_size(tip, vec2(8.0, 8.0)), 0.0, color);
+					}
+				},
+				GizmoMode::Rotate => {
+					let (u, v) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+					let points: Option<Vec<_>> = (0..=48)
+						.map(|x| {
+							let theta = x as f32 / 48.0 * std::f32::consts::TAU;
+							view.project(t.position + (u * theta.cos() + v * theta.sin()) * size)
+						})
+						.collect();
+					if let Some(points) = points {
+						painter.add(Shape::line(points, stroke));
+					}
+				},
+			}
+		}
+		painter.circle_filled(center, 3.0, Color32::WHITE);
+	}
+}
+
+/// Draw a grey unit box in the object space of `t`, standing in for an entity whose meshes are still loading.
+pub fn paint_placeholder(view: &GizmoView, t: &Transform, painter: &Painter) {
+	let corner = |i: usize| {
+		let c = Vec3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1).map(|x| x as f32 - 0.5);
+		view.project(t.position + t.rotation * (c * t.scale))
+	};
+	let stroke = Stroke::new(1.0, Color32::GRAY);
+	for i in 0..8 {
+		// Each edge joins corners that differ in one coordinate.
+		for bit in [1, 2, 4] {
+			if i & bit == 0 {
+				if let (Some(a), Some(b)) = (corner(i), corner(i | bit)) {
+					painter.line_segment([a, b], stroke);
+				}
+			}
+		}
+	}
+}
@@ -1,73 +1,158 @@
+use std::time::Duration;
+
 use rad_core::Engine;
-use rad_graph::{graph::Frame, Result};
+use rad_graph::{device::Device, graph::Frame, util::pipeline::DepthConvention, Result};
 use rad_renderer::{
-	debug::mesh::DebugMesh,
-	mesh::{self, VisBuffer},
+	assets::{
+		delete_evicted,
+		mesh::{virtual_mesh::VirtualMeshView, RaytracingMeshView},
+	},
+	components::{
+		camera::CameraComponent,
+		environment::{EnvironmentComponent, EnvironmentType},
+	},
+	debug::{
+		mesh::{DebugMesh, DebugVis, OverdrawPeak},
+		meshlet_spheres::{MeshletSpheres, MeshletSpheresInfo},
+	},
+	env::EnvironmentMaps,
+	mesh::{self, TextureFeedback, TransparentPass, VisBuffer},
 	pt::{self, PathTracer},
-	scene::{camera::CameraSceneInfo, WorldRenderer},
+	scene::{camera::CameraSceneInfo, virtual_scene::VirtualSceneData, LoadProgress, PendingMeshes, WorldRenderer},
+	shadow::{CascadedShadows, ShadowInfo},
 	sky::SkyLuts,
+	taa::Taa,
 	tonemap::{
 		agx::{AgXLook, AgXTonemap},
 		agx_hdr::AgxHdrTonemap,
 		exposure::ExposureCalc,
 		frostbite::FrostbiteTonemap,
 		null::NullTonemap,
+		reinhard::ReinhardTonemap,
 		tony_mc_mapface::TonyMcMapfaceTonemap,
+		uncharted2::Uncharted2Tonemap,
+		DisplayTarget,
+		TonemapOutput,
 	},
-	vek::Vec2,
+	vek::{Vec2, Vec3},
 };
 use rad_ui::{
-	egui::{CentralPanel, Context, Image, PointerButton, Sense},
+	egui::{vec2, CentralPanel, Context, Image, Key, PointerButton, ProgressBar, Rect, Sense},
 	to_texture_id,
 };
 use rad_window::{winit::event::WindowEvent, Window};
-use tracing::trace_span;
+use rad_world::{bevy_ecs::entity::Entity, transform::Transform};
+use tracing::{trace_span, warn};
 
+pub use crate::render::camera::{bookmarks, scene_cameras, CameraBookmark, Easing};
 use crate::{
 	render::{
 		camera::{CameraController, Mode},
 		debug::{DebugWindow, HdrTonemap, RenderMode, Tonemap},
+		gizmo::{paint_placeholder, Gizmo, GizmoMode, GizmoView},
+		picker::Picker,
+		raycast::{RayCaster, RayHit},
+		screenshot::Screenshot,
 	},
 	world::WorldContext,
 };
 
 mod camera;
 mod debug;
+mod gizmo;
+mod picker;
+mod raycast;
+mod screenshot;
 
 pub struct Renderer {
 	pub debug_window: DebugWindow,
+	pub screenshot: Screenshot,
 	sky: SkyLuts,
 	visbuffer: VisBuffer,
 	pt: PathTracer,
+	env_maps: EnvironmentMaps,
 	exposure: ExposureCalc,
 	agx: AgXTonemap,
 	tony_mcmapface: TonyMcMapfaceTonemap,
+	reinhard: ReinhardTonemap,
+	uncharted2: Uncharted2Tonemap,
 	null: NullTonemap,
 	frostbite: FrostbiteTonemap,
 	agx_hdr: AgxHdrTonemap,
 	debug: DebugMesh,
-	camera: CameraController,
+	overdraw_peak: OverdrawPeak,
+	feedback: TextureFeedback,
+	meshlet_spheres: MeshletSpheres,
+	shadows: CascadedShadows,
+	transparent: TransparentPass,
+	taa: Taa,
+	picker: Picker,
+	raycaster: RayCaster,
+	gizmo: Gizmo,
+	pub camera: CameraController,
+	pending_pick: Option<Vec2<u32>>,
+	pending_ray: Option<(Vec3<f32>, Vec3<f32>)>,
+	pub selected: Option<Entity>,
+	/// The surface under the last click in the path traced view.
+	pub last_hit: Option<RayHit>,
 }
 
+/// How long each frame may spend creating the views of meshes loaded in the background.
+const STAGING_BUDGET: Duration = Duration::from_millis(4);
+
+/// Jump to the first nine camera bookmarks.
+const BOOKMARK_KEYS: [Key; 9] = [
+	Key::Num1,
+	Key::Num2,
+	Key::Num3,
+	Key::Num4,
+	Key::Num5,
+	Key::Num6,
+	Key::Num7,
+	Key::Num8,
+	Key::Num9,
+];
+
 impl Renderer {
 	pub fn new() -> Result<Self> {
 		let device = Engine::get().global();
 		Ok(Self {
 			debug_window: DebugWindow::new(),
+			screenshot: Screenshot::new(),
 			sky: SkyLuts::new(device)?,
 			visbuffer: VisBuffer::new(device)?,
 			pt: PathTracer::new(device)?,
+			env_maps: EnvironmentMaps::new(),
 			exposure: ExposureCalc::new(device)?,
 			agx: AgXTonemap::new(device)?,
 			tony_mcmapface: TonyMcMapfaceTonemap::new(device)?,
+			reinhard: ReinhardTonemap::new(device)?,
+			uncharted2: Uncharted2Tonemap::new(device)?,
 			null: NullTonemap::new(device)?,
 			frostbite: FrostbiteTonemap::new(device)?,
 			agx_hdr: AgxHdrTonemap::new(device)?,
 			debug: DebugMesh::new(device)?,
+			overdraw_peak: OverdrawPeak::new(device)?,
+			feedback: TextureFeedback::new(device)?,
+			meshlet_spheres: MeshletSpheres::new(device)?,
+			shadows: CascadedShadows::new(device)?,
+			transparent: TransparentPass::new(device)?,
+			taa: Taa::new(device)?,
+			picker: Picker::new(device)?,
+			raycaster: RayCaster::new(device)?,
+			gizmo: Gizmo::new(),
 			camera: CameraController::new(),
+			pending_pick: None,
+			pending_ray: None,
+			selected: None,
+			last_hit: None,
 		})
 	}
 
+	/// Select the entity under `pos`, in viewport pixels. The selection changes once the pick is read back a few
+	/// frames later, and is cleared if there is nothing under `pos`.
+	pub fn pick(&mut self, pos: Vec2<u32>) { self.pending_pick = Some(pos); }
+
 	pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) {
 		self.camera.on_window_event(window, event);
 	}
@@ -76,94 +161,309 @@ impl Renderer {
 		&'pass mut self, window: &mut Window, frame: &mut Frame<'pass, '_>, ctx: &Context,
 		world: &'pass mut WorldContext,
 	) {
+		Engine::get().run_staged_loads(STAGING_BUDGET);
+		delete_evicted(frame);
+
 		let (stats, pt) = CentralPanel::default()
 			.show(ctx, |ui| {
 				let rect = ui.available_rect_before_wrap();
 				let size = rect.size();
-				let resp = ui.allocate_rect(rect, Sense::click());
+				let resp = ui.allocate_rect(rect, Sense::click_and_drag());
+				let hover = resp.hover_pos().map(|p| p - rect.min);
 
-				if ctx.input(|x| resp.contains_pointer() && x.pointer.button_down(PointerButton::Secondary)) {
+				let camera_mode =
+					ctx.input(|x| resp.contains_pointer() && x.pointer.button_down(PointerButton::Secondary));
+				if camera_mode {
 					self.camera.set_mode(window, Mode::Camera);
 				} else {
 					self.camera.set_mode(window, Mode::Default);
 				}
+				self.camera.set_scene_bounds(
+					world
+						.world_mut()
+						.get_resource::<VirtualSceneData>()
+						.and_then(|x| x.bounds()),
+				);
 				self.camera.control(ctx);
 				self.camera.apply(world.editor_mut());
+
+				if let Some(picked) = self.picker.take() {
+					self.selected =
+						picked.and_then(|i| world.world_mut().get_resource::<VirtualSceneData>()?.entity(i));
+				}
+				if let Some(hit) = self.raycaster.take(world.world_mut()) {
+					self.selected = hit.map(|x| x.entity);
+					self.last_hit = hit;
+				}
+				let view = {
+					let editor = world.editor_mut();
+					GizmoView {
+						transform: *editor.get::<Transform>().unwrap(),
+						camera: *editor.get::<CameraComponent>().unwrap(),
+						rect,
+					}
+				};
+				if !camera_mode {
+					if !ctx.wants_keyboard_input() {
+						ctx.input(|x| {
+							if x.key_pressed(Key::G) {
+								self.gizmo.mode = GizmoMode::Translate;
+							} else if x.key_pressed(Key::R) {
+								self.gizmo.mode = GizmoMode::Rotate;
+							} else if x.key_pressed(Key::S) {
+								self.gizmo.mode = GizmoMode::Scale;
+							}
+						});
+
+						let bookmark = ctx.input(|x| BOOKMARK_KEYS.iter().position(|&k| x.key_pressed(k)));
+						if let Some((_, _, t)) = bookmark.and_then(|i| bookmarks(world.world_mut()).get(i).cloned()) {
+							self.camera.set(&t);
+						}
+					}
+
+					let pointer = resp.hover_pos();
+					let (pressed, down) = ctx.input(|x| (x.pointer.primary_pressed(), x.pointer.primary_down()));
+					let mut used = false;
+					if let Some(mut t) = self.selected.and_then(|e| world.world_mut().get_mut::<Transform>(e)) {
+						let mut new = *t;
+						used = self.gizmo.update(&view, &mut new, pointer, pressed, down);
+						if new != *t {
+							*t = new;
+						}
+					}
+					if let (false, true, Some(p)) = (used, pressed, pointer) {
+						// The path tracer has no visbuffer to pick from, so cast a ray against its scene instead.
+						if matches!(self.debug_window.render_mode(), RenderMode::Path) {
+							let ray = view.ray(p);
+							self.pending_ray = Some((ray.origin, ray.dir));
+						} else {
+							let p = p - rect.min;
+							self.pending_pick = Some(Vec2::new(p.x as u32, p.y as u32));
+						}
+					}
+				}
+				let selected = self
+					.selected
+					.and_then(|e| world.world_mut().get::<Transform>(e))
+					.copied();
 				world.edit_tick();
+				let (progress, placeholders) = match self.debug_window.render_mode() {
+					RenderMode::Path => (
+						LoadProgress::of::<RaytracingMeshView>(world.world_mut()),
+						PendingMeshes::<RaytracingMeshView>::placeholders(world.world_mut()),
+					),
+					RenderMode::Debug => (
+						LoadProgress::of::<VirtualMeshView>(world.world_mut()),
+						PendingMeshes::<VirtualMeshView>::placeholders(world.world_mut()),
+					),
+				};
+				let env = EnvironmentComponent::of(world.world_mut());
 				let mut rend = WorldRenderer::new(world.world_mut(), frame.arena());
 
 				let s = trace_span!("render viewport");
 				let _e = s.enter();
 
+				let render_size = self
+					.screenshot
+					.render_size()
+					.unwrap_or(Vec2::new(size.x as u32, size.y as u32));
+				let taa = match self.debug_window.render_mode() {
+					RenderMode::Debug => self.debug_window.taa(),
+					RenderMode::Path => None,
+				};
 				rend.set_input(CameraSceneInfo {
-					aspect: size.x / size.y,
+					aspect: render_size.x as f32 / render_size.y as f32,
+					jitter: taa.map(|_| self.taa.jitter(render_size)).unwrap_or(Vec2::zero()),
+					depth: DepthConvention::default(),
 				});
 
 				let vis = self.debug_window.debug_vis();
-				let (img, stats, exp) = match self.debug_window.render_mode() {
+				let wireframe = self.debug_window.wireframe();
+				let (img, hdr, stats, exp) = match self.debug_window.render_mode() {
 					RenderMode::Path => {
 						let sky = self.sky.run(frame, &mut rend);
+						let environment = env.environment(frame, &mut self.env_maps);
 						let (raw, s) = self.pt.run(
 							frame,
 							&mut rend,
 							pt::RenderInfo {
 								sky,
-								size: Vec2::new(size.x as u32, size.y as u32),
+								env: environment,
+								size: render_size,
+								max_bounces: self.debug_window.max_bounces(),
+								russian_roulette_start_depth: self.debug_window.russian_roulette_start_depth(),
+								firefly_clamp: self.debug_window.firefly_clamp(),
+								aperture: self.debug_window.aperture(),
+								focus_distance: self.debug_window.focus_distance(),
+								view: None,
 							},
 						);
+						self.raycaster.run(frame, &mut rend, self.pending_ray.take());
 						let (exp, stats) = self.exposure.run(
 							frame,
 							raw,
-							self.debug_window.exposure_compensation(),
+							env.exposure_compensation + self.debug_window.exposure_compensation(),
 							ui.input(|x| x.stable_dt),
 						);
 
+						let output = DisplayTarget {
+							output: TonemapOutput::for_swapchain(window.format()),
+							white: self.debug_window.sdr_white(),
+						};
 						let img = if window.hdr_enabled() {
 							match self.debug_window.hdr_tonemap() {
-								HdrTonemap::Null => self.null.run(frame, raw, exp),
-								HdrTonemap::Frostbite => self.frostbite.run(frame, raw, exp),
-								HdrTonemap::AgX => self.agx_hdr.run(frame, raw, exp, AgXLook::default()),
-								HdrTonemap::AgXPunchy => self.agx_hdr.run(frame, raw, exp, AgXLook::punchy()),
+								HdrTonemap::Null => self.null.run(frame, raw, exp, output),
+								HdrTonemap::Frostbite => self.frostbite.run(frame, raw, exp, output),
+								HdrTonemap::AgX => self.agx_hdr.run(frame, raw, exp, AgXLook::default(), output),
+								HdrTonemap::AgXPunchy => self.agx_hdr.run(
+									frame,
+									raw,
+									exp,
+									AgXLook::punchy_by(self.debug_window.punchy_strength()),
+									output,
+								),
 							}
 						} else {
 							match self.debug_window.tonemap() {
 								Tonemap::AgX => self.agx.run(frame, raw, exp, AgXLook::default()),
-								Tonemap::AgXPunchy => self.agx.run(frame, raw, exp, AgXLook::punchy()),
+								Tonemap::AgXPunchy => self.agx.run(
+									frame,
+									raw,
+									exp,
+									AgXLook::punchy_by(self.debug_window.punchy_strength()),
+								),
 								Tonemap::TonyMcMapface => self.tony_mcmapface.run(frame, raw, exp),
+								Tonemap::Reinhard => self.reinhard.run(frame, raw, exp),
+								Tonemap::Uncharted2 => self.uncharted2.run(frame, raw, exp),
+								Tonemap::Null => self.null.run(frame, raw, exp, output),
+								Tonemap::Frostbite => self.frostbite.run(frame, raw, exp, output),
 							}
 						};
 
-						(img, None, Some((stats, s)))
+						(img, Some(raw), None, Some((stats, s)))
 					},
 					RenderMode::Debug => {
 						let visbuffer = self.visbuffer.run(
 							frame,
 							&mut rend,
 							mesh::RenderInfo {
-								size: Vec2::new(size.x as u32, size.y as u32),
+								size: render_size,
 								debug_info: vis.requires_debug_info(),
+								samples: self.debug_window.samples(),
+								view: None,
+								hzb: self.debug_window.hzb(),
+								motion_vectors: taa.is_some(),
+								sw_edge_pixels: self.debug_window.sw_edge_pixels(),
 							},
 						);
-						let img = self.debug.run(frame, vis, visbuffer, [].into_iter());
-						(img, Some(visbuffer.stats), None)
+						self.picker.run(frame, visbuffer, self.pending_pick.take());
+						if matches!(vis, DebugVis::Overdraw(_)) {
+							self.debug_window.set_overdraw_peak(self.overdraw_peak.peak());
+							self.overdraw_peak.run(frame, visbuffer);
+						}
+						if self.debug_window.texture_feedback() {
+							self.debug_window.set_texture_sizes(self.feedback.seen());
+							self.feedback.run(frame, visbuffer);
+						}
+						let shadows = matches!(vis, DebugVis::Lit)
+							.then(|| {
+								let info = ShadowInfo {
+									aspect: render_size.x as f32 / render_size.y as f32,
+									..self.debug_window.shadows()
+								};
+								self.shadows.run(frame, &mut rend, &info)
+							})
+							.flatten();
+						let map = match env.ty {
+							EnvironmentType::Map(id) => self.env_maps.get(frame, id),
+							_ => None,
+						};
+						let background = self.debug_window.background(|| self.sky.run(frame, &mut rend), map);
+						let img = self
+							.debug
+							.run(frame, vis, wireframe, visbuffer, shadows, background, [].into_iter());
+						let img = self.transparent.run(frame, &mut rend, visbuffer, img);
+						let img = match (taa, visbuffer.motion) {
+							(Some(info), Some(motion)) => self.taa.run(frame, img, motion, &info),
+							_ => {
+								self.taa.reset();
+								img
+							},
+						};
+						let img = match (self.debug_window.meshlet_spheres(), hover) {
+							(Some((sphere, radius, color)), Some(p)) => self.meshlet_spheres.run(
+								frame,
+								visbuffer,
+								MeshletSpheresInfo {
+									sphere,
+									center: Vec2::new(p.x as u32, p.y as u32),
+									radius,
+									color,
+								},
+								img,
+							),
+							_ => img,
+						};
+						(img, None, Some(visbuffer.stats), None)
 					},
 				};
+				self.screenshot.run(frame, img, hdr, exp.as_ref().map(|&(_, s)| s));
 				ui.put(rect, Image::new((to_texture_id(img), size)));
+				for t in placeholders.iter() {
+					paint_placeholder(&view, t, &ui.painter_at(rect));
+				}
+				if let Some(t) = selected {
+					self.gizmo.paint(&view, &t, &ui.painter_at(rect));
+				}
+				if matches!(self.debug_window.render_mode(), RenderMode::Debug) {
+					self.debug_window.paint_overdraw_legend(&ui.painter_at(rect));
+				}
+				if !progress.done() {
+					ui.put(
+						Rect::from_min_size(rect.min + vec2(12.0, 12.0), vec2(240.0, 18.0)),
+						ProgressBar::new(progress.ratio())
+							.text(format!("loading meshes: {}/{}", progress.loaded, progress.total)),
+					);
+				}
 
 				(stats, exp)
 			})
 			.inner;
 
-		self.debug_window.render(frame.device(), window, ctx, stats, pt);
+		self.debug_window
+			.render(frame.device(), window, ctx, stats, pt, frame.graph());
 	}
 
 	pub unsafe fn destroy(self) {
+		if let Err(e) = Engine::get().global::<Device>().save_pipeline_cache() {
+			warn!("{e}");
+		}
+		if let Err(e) = self.camera.settings.save() {
+			warn!("failed to save camera settings: {e}");
+		}
+		// Views evicted after the last frame would never have their GPU resources deleted.
+		let report = Engine::get().asset_report();
+		if report.pending_deletion() > 0 {
+			warn!("asset views still pending deletion on shutdown:\n{report}");
+		}
+
 		self.sky.destroy();
 		self.visbuffer.destroy();
 		self.pt.destroy();
+		self.env_maps.destroy(Engine::get().global::<Device>());
 		self.exposure.destroy();
 		self.agx.destroy();
 		self.tony_mcmapface.destroy();
+		self.reinhard.destroy();
+		self.uncharted2.destroy();
 		self.debug.destroy();
+		self.overdraw_peak.destroy();
+		self.feedback.destroy();
+		self.meshlet_spheres.destroy();
+		self.shadows.destroy();
+		self.transparent.destroy();
+		self.taa.destroy();
+		self.picker.destroy();
+		self.raycaster.destroy();
 	}
 }
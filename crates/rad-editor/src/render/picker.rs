@@ -0,0 +1,85 @@
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, ShaderInfo},
+	graph::{BufferDesc, BufferUsage, Frame, Persist},
+	resource::{BufferHandle, GpuPtr},
+	sync::Shader,
+	util::compute::ComputePass,
+	Result,
+};
+use rad_renderer::{
+	mesh::{GpuVisBufferReader, RenderOutput},
+	vek::Vec2,
+};
+
+/// Reads back the instance under a pixel of the visbuffer. Results arrive `FRAMES_IN_FLIGHT` frames after the pick
+/// was requested.
+pub struct Picker {
+	pass: ComputePass<PushConstants>,
+	readback: Persist<BufferHandle>,
+	result: Option<Option<u32>>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	read: GpuVisBufferReader,
+	pix: Vec2<u32>,
+	should_pick: u32,
+	_pad: u32,
+	ret: GpuPtr<u32>,
+}
+
+const NO_PICK: u32 = u32::MAX;
+const EMPTY: u32 = u32::MAX - 1;
+
+impl Picker {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "editor.mousepick.main",
+					spec: &[],
+				},
+			)?,
+			readback: Persist::new(),
+			result: None,
+		})
+	}
+
+	/// The result of a finished pick: the picked instance, or `None` if nothing was under the pixel.
+	pub fn take(&mut self) -> Option<Option<u32>> { self.result.take() }
+
+	/// Pick the instance under `pixel`, if given. Must run every frame to collect results.
+	pub fn run<'pass>(&'pass mut self, frame: &mut Frame<'pass, '_>, output: RenderOutput, pixel: Option<Vec2<u32>>) {
+		let mut pass = frame.pass("mousepick");
+		output.reader.add(&mut pass, Shader::Compute, false);
+		let ret = pass.resource(
+			BufferDesc::readback(std::mem::size_of::<u32>() as u64, self.readback),
+			BufferUsage::write(Shader::Compute),
+		);
+
+		let grid = output.reader.sample_grid;
+		pass.build(move |mut pass| {
+			if !pass.is_uninit(ret) {
+				match pass.readback::<u32>(ret, 0) {
+					NO_PICK => {},
+					EMPTY => self.result = Some(None),
+					x => self.result = Some(Some(x)),
+				}
+			}
+
+			let push = PushConstants {
+				read: output.reader.get(&mut pass),
+				pix: pixel.map(|x| x * grid).unwrap_or_default(),
+				should_pick: pixel.is_some() as u32,
+				_pad: 0,
+				ret: pass.get(ret).ptr(),
+			};
+			self.pass.dispatch(&mut pass, &push, 1, 1, 1);
+		});
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
@@ -0,0 +1,134 @@
+use bytemuck::{NoUninit, Pod, Zeroable};
+use rad_graph::{
+	device::{Device, ShaderInfo},
+	graph::{BufferDesc, BufferUsage, Frame, Persist},
+	resource::{BufferHandle, GpuPtr},
+	sync::Shader,
+	util::compute::ComputePass,
+	Result,
+};
+use rad_renderer::{
+	scene::{
+		rt_scene::{RtScene, RtSceneData},
+		WorldRenderer,
+	},
+	vek::{Vec2, Vec3},
+};
+use rad_world::{bevy_ecs::entity::Entity, World};
+
+/// Casts single rays against the TLAS of the scene, and reads back the closest hit. Results arrive `FRAMES_IN_FLIGHT`
+/// frames after the ray was cast.
+pub struct RayCaster {
+	pass: ComputePass<PushConstants>,
+	readback: Persist<BufferHandle>,
+	result: Option<Option<GpuHit>>,
+}
+
+/// The closest hit of a ray.
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+	pub entity: Entity,
+	/// The instance in the raytracing scene.
+	pub instance: u32,
+	/// The triangle in the mesh of the instance.
+	pub tri: u32,
+	/// The barycentrics of the hit on the triangle, of its second and third vertex.
+	pub bary: Vec2<f32>,
+	/// The world space position of the hit.
+	pub position: Vec3<f32>,
+	/// The distance along the ray.
+	pub t: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct GpuHit {
+	instance: u32,
+	tri: u32,
+	bary: Vec2<f32>,
+	position: Vec3<f32>,
+	t: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	as_: GpuPtr<u8>,
+	origin: Vec3<f32>,
+	should_cast: u32,
+	dir: Vec3<f32>,
+	_pad: u32,
+	ret: GpuPtr<GpuHit>,
+}
+
+const NO_CAST: u32 = u32::MAX;
+const MISS: u32 = u32::MAX - 1;
+
+impl RayCaster {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "editor.raycast.main",
+					spec: &[],
+				},
+			)?,
+			readback: Persist::new(),
+			result: None,
+		})
+	}
+
+	/// The result of a finished cast: the closest hit, or `None` if the ray hit nothing.
+	pub fn take(&mut self, world: &World) -> Option<Option<RayHit>> {
+		let hit = self.result.take()?;
+		Some(hit.and_then(|x| {
+			Some(RayHit {
+				entity: world.get_resource::<RtSceneData>()?.entity(x.instance)?,
+				instance: x.instance,
+				tri: x.tri,
+				bary: x.bary,
+				position: x.position,
+				t: x.t,
+			})
+		}))
+	}
+
+	/// Cast the ray from `origin` along `dir`, if given. Must run every frame to collect results.
+	pub fn run<'pass>(
+		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>,
+		ray: Option<(Vec3<f32>, Vec3<f32>)>,
+	) {
+		let rt = rend.get::<RtScene>(frame);
+		let mut pass = frame.pass("raycast");
+		pass.reference(rt.as_, BufferUsage::read(Shader::Compute));
+		let ret = pass.resource(
+			BufferDesc::readback(std::mem::size_of::<GpuHit>() as u64, self.readback),
+			BufferUsage::write(Shader::Compute),
+		);
+
+		pass.build(move |mut pass| {
+			if !pass.is_uninit(ret) {
+				let hit = pass.readback::<GpuHit>(ret, 0);
+				match hit.instance {
+					NO_CAST => {},
+					MISS => self.result = Some(None),
+					_ => self.result = Some(Some(hit)),
+				}
+			}
+
+			let (origin, dir) = ray.unwrap_or_default();
+			let push = PushConstants {
+				as_: pass.get(rt.as_).ptr().offset(rt.as_offset),
+				origin,
+				should_cast: ray.is_some() as u32,
+				dir,
+				_pad: 0,
+				ret: pass.get(ret).ptr(),
+			};
+			self.pass.dispatch(&mut pass, &push, 1, 1, 1);
+		});
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use image::{
+	error::{ParameterError, ParameterErrorKind},
+	ImageError,
+	Rgba32FImage,
+	RgbaImage,
+};
+use rad_graph::{
+	ash::vk,
+	graph::{BufferDesc, BufferUsage, Frame, ImageUsage, Persist, Res, FRAMES_IN_FLIGHT},
+	resource::{BufferHandle, ImageView, Subresource},
+	util::pass::ImageCopy,
+};
+use rad_renderer::vek::Vec2;
+use rad_ui::egui::{DragValue, Ui};
+use tracing::error;
+
+/// Saves the rendered image to disk, as an 8-bit PNG after tonemapping or as an EXR of the HDR image before
+/// tonemapping.
+///
+/// The image is copied into a readback buffer, which is read `FRAMES_IN_FLIGHT` frames later once the copy is done.
+pub struct Screenshot {
+	readback: Persist<BufferHandle>,
+	request: Option<PathBuf>,
+	pending: Option<Pending>,
+	/// Render the screenshot at `size` instead of the size of the viewport.
+	pub custom_size: bool,
+	pub size: Vec2<u32>,
+	/// The number of samples per pixel path traced screenshots accumulate before they are captured.
+	pub samples: u32,
+}
+
+struct Pending {
+	path: PathBuf,
+	size: Vec2<u32>,
+	format: vk::Format,
+	len: u64,
+	frames: usize,
+}
+
+fn is_exr(path: &Path) -> bool { path.extension().is_some_and(|x| x.eq_ignore_ascii_case("exr")) }
+
+impl Screenshot {
+	pub fn new() -> Self {
+		Self {
+			readback: Persist::new(),
+			request: None,
+			pending: None,
+			custom_size: false,
+			size: Vec2::new(1920, 1080),
+			samples: 64,
+		}
+	}
+
+	pub fn request(&mut self, path: PathBuf) { self.request = Some(path); }
+
+	/// The size to render this frame at, if it is captured at a custom size.
+	pub fn render_size(&self) -> Option<Vec2<u32>> {
+		(self.request.is_some() && self.pending.is_none() && self.custom_size).then_some(self.size)
+	}
+
+	pub fn ui(&mut self, ui: &mut Ui) {
+		ui.checkbox(&mut self.custom_size, "custom size");
+		ui.add_enabled_ui(self.custom_size, |ui| {
+			ui.horizontal(|ui| {
+				ui.add(DragValue::new(&mut self.size.x).range(1..=16384));
+				ui.label("x");
+				ui.add(DragValue::new(&mut self.size.y).range(1..=16384));
+			});
+		});
+		ui.horizontal(|ui| {
+			ui.add(DragValue::new(&mut self.samples).range(1..=65536));
+			ui.label("path traced samples");
+		});
+	}
+
+	/// Capture `img`, or `hdr` for EXR screenshots, if a screenshot was requested. `samples` is the number of samples
+	/// the path tracer has accumulated into `img`, if it is path traced.
+	pub fn run(&mut self, frame: &mut Frame, img: Res<ImageView>, hdr: Option<Res<ImageView>>, samples: Option<u32>) {
+		if let Some(p) = self.pending.take() {
+			if p.frames > 0 {
+				self.pending = Some(Pending {
+					frames: p.frames - 1,
+					..p
+				});
+			} else {
+				self.read(frame, p);
+			}
+			return;
+		}
+
+		let Some(path) = self.request.take() else {
+			return;
+		};
+		// Rendering at a custom size restarts accumulation, so wait for the image to converge like headless renders.
+		if samples.is_some_and(|s| s + 1 < self.samples) {
+			self.request = Some(path);
+			return;
+		}
+		let src = if is_exr(&path) {
+			match hdr {
+				Some(x) => x,
+				None => {
+					error!("EXR screenshots can only be saved of the path traced image");
+					return;
+				},
+			}
+		} else {
+			img
+		};
+
+		let mut pass = frame.pass("screenshot");
+		pass.reference(src, ImageUsage::transfer_read());
+		let desc = pass.desc(src);
+		let texel_size = match desc.format {
+			vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => 4,
+			vk::Format::R32G32B32A32_SFLOAT => 16,
+			x => {
+				error!("cannot save screenshots of images with format `{x:?}`");
+				return;
+			},
+		};
+		let size = Vec2::new(desc.size.width, desc.size.height);
+		let len = size.x as u64 * size.y as u64 * texel_size;
+		let buf = pass.resource(BufferDesc::readback(len, self.readback), BufferUsage::transfer_write());
+		pass.build(move |mut pass| {
+			// Rows are tightly packed in the buffer, so there is no row pitch to account for when reading it.
+			pass.copy_image_to_buffer(
+				src,
+				buf,
+				0,
+				ImageCopy {
+					row_stride: 0,
+					plane_stride: 0,
+					subresource: Subresource::default(),
+					offset: vk::Offset3D::default(),
+					extent: desc.size,
+				},
+			);
+		});
+
+		self.pending = Some(Pending {
+			path,
+			size,
+			format: desc.format,
+			len,
+			frames: FRAMES_IN_FLIGHT - 1,
+		});
+	}
+
+	fn read(&mut self, frame: &mut Frame, p: Pending) {
+		let mut pass = frame.pass("read screenshot");
+		let buf = pass.resource(BufferDesc::readback(p.len, self.readback), BufferUsage::none());
+		pass.build(move |mut pass| {
+			if pass.is_uninit(buf) {
+				error!("screenshot readback buffer was lost");
+				return;
+			}
+			let data = unsafe { pass.get(buf).data.as_ref()[..p.len as usize].to_vec() };
+			rayon::spawn(move || {
+				if let Err(e) = save(&p, data) {
+					error!("failed to save screenshot to {}: {e}", p.path.display());
+				}
+			});
+		});
+	}
+}
+
+fn save(p: &Pending, data: Vec<u8>) -> Result<(), ImageError> {
+	let mismatch = || ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch));
+	if p.format == vk::Format::R32G32B32A32_SFLOAT {
+		Rgba32FImage::from_raw(p.size.x, p.size.y, bytemuck::pod_collect_to_vec(&data))
+			.ok_or_else(mismatch)?
+			.save(&p.path)
+	} else {
+		RgbaImage::from_raw(p.size.x, p.size.y, data)
+			.ok_or_else(mismatch)?
+			.save(&p.path)
+	}
+}
@@ -1,22 +1,35 @@
-use std::io;
+use std::{
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
-use rad_core::{asset::aref::AssetId, Engine};
+use rad_core::{
+	asset::{aref::AssetId, Asset},
+	Engine,
+};
 use rad_renderer::{
 	assets::mesh::Mesh,
 	components::{
 		camera::{CameraComponent, PrimaryViewComponent},
+		environment::EnvironmentComponent,
 		mesh::MeshComponent,
 	},
 	scene::register_all_gpu_scenes,
 };
 use rad_world::{
 	bevy_ecs::{entity::Entity, world::EntityMut},
+	name::Name,
 	serde::DoNotSerialize,
 	tick::Tick,
 	World,
 };
 
+use crate::asset::{bundle, fs::FsAssetSystem};
+
 pub struct WorldContext {
+	/// The asset the world was opened from, if it has been saved.
+	id: Option<AssetId<World>>,
 	edit: World,
 	edit_tick: Tick,
 	editor: Entity,
@@ -25,6 +38,7 @@ pub struct WorldContext {
 impl WorldContext {
 	pub fn new() -> Self {
 		let mut this = Self {
+			id: None,
 			edit: World::new(),
 			edit_tick: Tick::new(),
 			editor: Entity::from_raw(0),
@@ -35,6 +49,7 @@ impl WorldContext {
 
 	pub fn open(&mut self, id: AssetId<World>) -> Result<(), io::Error> {
 		self.edit = Engine::get().load_asset(id)?;
+		self.id = Some(id);
 		self.setup_world();
 
 		Ok(())
@@ -42,12 +57,44 @@ impl WorldContext {
 
 	pub fn open_mesh(&mut self, id: AssetId<Mesh>) -> Result<(), io::Error> {
 		self.edit = World::new();
+		self.id = None;
 		self.edit.spawn_empty().insert(MeshComponent::new(&[id]));
 		self.setup_world();
 
 		Ok(())
 	}
 
+	/// Save the world back to the asset it was opened from, or to a new asset if it has never been saved.
+	pub fn save(&mut self) -> Result<(), io::Error> {
+		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		match self.id {
+			Some(id) => fs.overwrite(id, |to| self.edit.save(to)),
+			None => {
+				let id = AssetId::new();
+				let mut to = fs.create(&PathBuf::from(format!("worlds/{id}")), id)?;
+				self.id = Some(id);
+				self.edit.save(&mut to)
+			},
+		}
+	}
+
+	/// Save a copy of the world to a new asset, leaving the asset it was opened from untouched. Returns the path of the
+	/// copy.
+	pub fn save_recovery(&mut self) -> Result<PathBuf, io::Error> {
+		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		let id = AssetId::<World>::new();
+		let path = PathBuf::from(format!("worlds/recovered/{id}"));
+		let mut to = fs.create(&path, id)?;
+		self.edit.save(&mut to)?;
+		Ok(path)
+	}
+
+	/// Save the world, and export it with every asset it uses to a bundle at `path`.
+	pub fn export_bundle(&mut self, path: &Path) -> Result<(), io::Error> {
+		self.save()?;
+		bundle::export(self.id.unwrap(), &mut self.edit, path)
+	}
+
 	pub fn editor_mut(&mut self) -> EntityMut<'_> { self.edit.entity_mut(self.editor).into() }
 
 	pub fn edit_tick(&mut self) { self.edit_tick.tick(&mut self.edit); }
@@ -61,6 +108,17 @@ impl WorldContext {
 			.spawn_empty()
 			.insert((CameraComponent::default(), PrimaryViewComponent, DoNotSerialize))
 			.id();
+		// Worlds always get an environment, so it can be edited and is saved with them.
+		if self
+			.edit
+			.query::<&EnvironmentComponent>()
+			.iter(&self.edit)
+			.next()
+			.is_none()
+		{
+			self.edit
+				.spawn((EnvironmentComponent::default(), Name("environment".into())));
+		}
 		// TODO: move somewhere else.
 		register_all_gpu_scenes(&mut self.edit, &mut self.edit_tick);
 	}
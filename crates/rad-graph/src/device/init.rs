@@ -1,8 +1,8 @@
 use std::{
 	cell::UnsafeCell,
-	ffi::CStr,
+	ffi::{c_void, CStr},
 	mem::ManuallyDrop,
-	sync::{Arc, Mutex},
+	sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
 use ash::{ext, khr, vk, vk::TaggedStructure};
@@ -12,7 +12,7 @@ use gpu_allocator::{
 	AllocatorDebugSettings,
 };
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
-use tracing::{info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
 	device::{
@@ -28,12 +28,25 @@ use crate::{
 	Result,
 };
 
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// Enables the Khronos validation layer, with its messages logged through `tracing` under the `vulkan` target.
+#[derive(Copy, Clone, Default)]
+pub struct ValidationInfo<'a> {
+	/// Instrument shaders to validate their memory accesses on the GPU, which is much slower.
+	pub gpu_assisted: bool,
+	/// The IDs of messages that are never logged, such as `VUID-vkCmdDraw-None-08600`, to silence known-benign
+	/// warnings.
+	pub ignored: &'a [&'a str],
+}
+
 pub struct DeviceBuilder<'a> {
 	pub layers: &'a [&'static CStr],
 	pub instance_extensions: &'a [&'static CStr],
 	pub device_extensions: &'a [&'static CStr],
 	pub window: Option<(&'a dyn HasWindowHandle, &'a dyn HasDisplayHandle)>,
 	pub features: vk::PhysicalDeviceFeatures2<'a>,
+	pub validation: Option<ValidationInfo<'a>>,
 }
 
 impl Default for DeviceBuilder<'_> {
@@ -44,6 +57,7 @@ impl Default for DeviceBuilder<'_> {
 			device_extensions: &[],
 			window: None,
 			features: vk::PhysicalDeviceFeatures2::default(),
+			validation: None,
 		}
 	}
 }
@@ -77,6 +91,12 @@ impl<'a> DeviceBuilder<'a> {
 		self
 	}
 
+	/// Enable validation if the layer is installed, warning if it is not.
+	pub fn validation(mut self, validation: ValidationInfo<'a>) -> Self {
+		self.validation = Some(validation);
+		self
+	}
+
 	pub fn build(self) -> Result<(Device, vk::SurfaceKHR)> {
 		let entry = Self::load_entry()?;
 
@@ -89,8 +109,16 @@ impl<'a> DeviceBuilder<'a> {
 			window.map(|x| x.0.as_raw()),
 			self.layers,
 			self.instance_extensions,
+			self.validation.as_ref(),
 		)?;
-		let instance = Self::create_instance(&entry, &layers, &extensions)?;
+		let gpu_assisted = extensions.contains(&ext::validation_features::NAME);
+		let instance = Self::create_instance(&entry, &layers, &extensions, gpu_assisted)?;
+		let debug_messenger = match self.validation {
+			Some(v) if extensions.contains(&ext::debug_utils::NAME) => {
+				Some(DebugMessenger::new(&entry, &instance, v.ignored)?)
+			},
+			_ => None,
+		};
 
 		let surface_ext = khr::surface::Instance::new(&entry, &instance);
 		let surface = window
@@ -101,7 +129,7 @@ impl<'a> DeviceBuilder<'a> {
 
 		let s = surface.unwrap_or(vk::SurfaceKHR::null());
 
-		let (device, physical_device, queues, debug_utils_ext) = Self::create_device(
+		let (device, physical_device, queues, debug_utils_ext, mesh_shaders) = Self::create_device(
 			&instance,
 			surface.map(|s| (&surface_ext, s)),
 			self.device_extensions,
@@ -128,6 +156,7 @@ impl<'a> DeviceBuilder<'a> {
 				instance,
 				as_ext,
 				debug_utils_ext,
+				debug_messenger,
 				surface_ext,
 				physical_device,
 				queues,
@@ -136,6 +165,9 @@ impl<'a> DeviceBuilder<'a> {
 				rt_ext,
 				descriptors,
 				samplers: Mutex::new(Samplers::new()),
+				mesh_shaders,
+				lost: AtomicBool::new(false),
+				lost_callbacks: Mutex::new(Vec::new()),
 				device,
 			}),
 		};
@@ -154,19 +186,41 @@ impl<'a> DeviceBuilder<'a> {
 
 	fn get_instance_layers_and_extensions(
 		entry: &ash::Entry, window: Option<RawWindowHandle>, layers: &[&'static CStr], extensions: &[&'static CStr],
+		validation: Option<&ValidationInfo>,
 	) -> Result<(Vec<&'static CStr>, Vec<&'static CStr>)> {
 		unsafe {
+			let mut layers = layers.to_vec();
 			let mut exts: Vec<&CStr> = Self::get_surface_extensions(window)?.to_vec();
-			if entry
-				.enumerate_instance_extension_properties(None)?
-				.into_iter()
-				.any(|props| CStr::from_ptr(props.extension_name.as_ptr()) == ext::debug_utils::NAME)
-			{
+			let available = entry.enumerate_instance_extension_properties(None)?;
+			let has = |name: &CStr| {
+				available
+					.iter()
+					.any(|props| CStr::from_ptr(props.extension_name.as_ptr()) == name)
+			};
+			if has(ext::debug_utils::NAME) {
 				exts.push(ext::debug_utils::NAME);
 			}
+			// Required for the HDR10 and scRGB color spaces of swapchains.
+			if window.is_some() && has(ext::swapchain_colorspace::NAME) {
+				exts.push(ext::swapchain_colorspace::NAME);
+			}
+			if let Some(validation) = validation {
+				if entry
+					.enumerate_instance_layer_properties()?
+					.into_iter()
+					.any(|props| CStr::from_ptr(props.layer_name.as_ptr()) == VALIDATION_LAYER)
+				{
+					layers.push(VALIDATION_LAYER);
+					if validation.gpu_assisted {
+						exts.push(ext::validation_features::NAME);
+					}
+				} else {
+					warn!("validation was requested, but `{VALIDATION_LAYER:?}` is not installed");
+				}
+			}
 			exts.extend_from_slice(extensions);
 
-			Ok((layers.to_vec(), exts))
+			Ok((layers, exts))
 		}
 	}
 
@@ -196,22 +250,27 @@ impl<'a> DeviceBuilder<'a> {
 	}
 
 	fn create_instance(
-		entry: &ash::Entry, layers: &[&'static CStr], extensions: &[&'static CStr],
+		entry: &ash::Entry, layers: &[&'static CStr], extensions: &[&'static CStr], gpu_assisted: bool,
 	) -> Result<ash::Instance> {
-		let instance = unsafe {
-			entry.create_instance(
-				&vk::InstanceCreateInfo::default()
-					.application_info(
-						&vk::ApplicationInfo::default()
-							.application_name(CStr::from_bytes_with_nul(b"radiance\0").unwrap())
-							.engine_name(CStr::from_bytes_with_nul(b"radiance\0").unwrap())
-							.api_version(vk::make_api_version(0, 1, 3, 0)),
-					)
-					.enabled_layer_names(&layers.into_iter().map(|x| x.as_ptr()).collect::<Vec<_>>())
-					.enabled_extension_names(&extensions.into_iter().map(|x| x.as_ptr()).collect::<Vec<_>>()),
-				None,
-			)?
-		};
+		let app = vk::ApplicationInfo::default()
+			.application_name(CStr::from_bytes_with_nul(b"radiance\0").unwrap())
+			.engine_name(CStr::from_bytes_with_nul(b"radiance\0").unwrap())
+			.api_version(vk::make_api_version(0, 1, 3, 0));
+		let layers = layers.into_iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+		let extensions = extensions.into_iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+		let enables = [
+			vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+			vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+		];
+		let mut features = vk::ValidationFeaturesEXT::default().enabled_validation_features(&enables);
+		let mut info = vk::InstanceCreateInfo::default()
+			.application_info(&app)
+			.enabled_layer_names(&layers)
+			.enabled_extension_names(&extensions);
+		if gpu_assisted {
+			info = info.push_next(&mut features);
+		}
+		let instance = unsafe { entry.create_instance(&info, None)? };
 
 		Ok(instance)
 	}
@@ -276,6 +335,7 @@ impl<'a> DeviceBuilder<'a> {
 		vk::PhysicalDevice,
 		Queues<QueueData>,
 		Option<ext::debug_utils::Device>,
+		bool,
 	)> {
 		let extensions = Self::get_device_extensions(extensions);
 		trace!("using device extensions: {:?}", extensions);
@@ -289,6 +349,13 @@ impl<'a> DeviceBuilder<'a> {
 
 			trace!("trying device: {}", name);
 
+			// Mesh shaders are optional: without them, the renderer falls back to vertex shaders.
+			let mesh_shaders = Self::supports_mesh_shaders(instance, physical_device);
+			let mut extensions = extensions.clone();
+			if mesh_shaders {
+				extensions.push(ext::mesh_shader::NAME.as_ptr());
+			}
+
 			#[repr(C)]
 			struct VkStructHeader {
 				ty: vk::StructureType,
@@ -304,6 +371,7 @@ impl<'a> DeviceBuilder<'a> {
 			let mut rt_features = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
 			let mut rq_features = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
 			let mut maint5_features = vk::PhysicalDeviceMaintenance5FeaturesKHR::default();
+			let mut mesh_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
 			{
 				let mut next = features.p_next as *mut VkStructHeader;
 				let mut found_12 = false;
@@ -312,6 +380,7 @@ impl<'a> DeviceBuilder<'a> {
 				let mut found_rt = false;
 				let mut found_rq = false;
 				let mut found_maint5 = false;
+				let mut found_mesh = false;
 				while !next.is_null() {
 					unsafe {
 						match (*next).ty {
@@ -321,6 +390,7 @@ impl<'a> DeviceBuilder<'a> {
 							vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::STRUCTURE_TYPE => found_rt = true,
 							vk::PhysicalDeviceRayQueryFeaturesKHR::STRUCTURE_TYPE => found_rq = true,
 							vk::PhysicalDeviceMaintenance5FeaturesKHR::STRUCTURE_TYPE => found_maint5 = true,
+							vk::PhysicalDeviceMeshShaderFeaturesEXT::STRUCTURE_TYPE => found_mesh = true,
 							_ => {},
 						}
 						next = (*next).next;
@@ -357,6 +427,11 @@ impl<'a> DeviceBuilder<'a> {
 				} else {
 					features
 				};
+				features = if mesh_shaders && !found_mesh {
+					features.push_next(&mut mesh_features)
+				} else {
+					features
+				};
 			}
 
 			let mut next = features.p_next as *mut VkStructHeader;
@@ -402,6 +477,10 @@ impl<'a> DeviceBuilder<'a> {
 							let maint5_features = &mut *(next as *mut vk::PhysicalDeviceMaintenance5FeaturesKHR);
 							maint5_features.maintenance5 = true as _;
 						},
+						vk::PhysicalDeviceMeshShaderFeaturesEXT::STRUCTURE_TYPE => {
+							let mesh_features = &mut *(next as *mut vk::PhysicalDeviceMeshShaderFeaturesEXT);
+							mesh_features.mesh_shader = true as _;
+						},
 						_ => {},
 					}
 					next = (*next).next;
@@ -444,10 +523,16 @@ impl<'a> DeviceBuilder<'a> {
 			} {
 				Ok(device) => {
 					info!("created device: {}", name);
+					if matches!(queues, Queues::Single(_)) {
+						warn!("device has no dedicated compute and transfer queues, submitting all work to one queue");
+					}
+					if !mesh_shaders {
+						warn!("device does not support mesh shaders, falling back to vertex shaders");
+					}
 
 					let queues = queues.try_map(|family| QueueData::new(&device, family))?;
 					let debug = ext::debug_utils::Device::new(instance, &device);
-					return Ok((device, physical_device, queues, Some(debug)));
+					return Ok((device, physical_device, queues, Some(debug), mesh_shaders));
 				},
 				Err(err) => {
 					warn!("failed to create device: {}", err);
@@ -456,11 +541,9 @@ impl<'a> DeviceBuilder<'a> {
 			};
 		}
 
-		Err(
-			"failed to find suitable device: radiance needs mesh shaders, raytracing, and ReBAR"
-				.to_string()
-				.into(),
-		)
+		Err("failed to find suitable device: radiance needs raytracing and ReBAR"
+			.to_string()
+			.into())
 	}
 
 	fn get_device_extensions(extensions: &[&'static CStr]) -> Vec<&'static CStr> {
@@ -477,6 +560,21 @@ impl<'a> DeviceBuilder<'a> {
 		extensions
 	}
 
+	fn supports_mesh_shaders(instance: &ash::Instance, device: vk::PhysicalDevice) -> bool {
+		let has_ext = unsafe { instance.enumerate_device_extension_properties(device) }.is_ok_and(|exts| {
+			exts.iter()
+				.any(|props| unsafe { CStr::from_ptr(props.extension_name.as_ptr()) } == ext::mesh_shader::NAME)
+		});
+		if !has_ext {
+			return false;
+		}
+
+		let mut mesh_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
+		let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut mesh_features);
+		unsafe { instance.get_physical_device_features2(device, &mut features) };
+		mesh_features.mesh_shader == vk::TRUE
+	}
+
 	fn get_physical_devices<'i>(
 		instance: &'i ash::Instance, surface: Option<(&'i khr::surface::Instance, vk::SurfaceKHR)>,
 	) -> Result<impl IntoIterator<Item = (vk::PhysicalDevice, Queues<u32>, String)> + 'i> {
@@ -521,13 +619,17 @@ impl<'a> DeviceBuilder<'a> {
 
 		for (i, family) in queue_families.iter().enumerate() {
 			if family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
+				if graphics.is_some() {
+					continue;
+				}
+				// Skip graphics families that can't present, as long as another one can.
 				if let Some((surface_ext, surface)) = surface {
 					if !unsafe {
 						surface_ext
 							.get_physical_device_surface_support(device, i as u32, surface)
-							.ok()?
+							.unwrap_or(false)
 					} {
-						return None;
+						continue;
 					}
 				}
 				graphics = Some(i as u32);
@@ -544,6 +646,8 @@ impl<'a> DeviceBuilder<'a> {
 				compute: c,
 				transfer: t,
 			}),
+			// Without dedicated compute and transfer families, all work goes to the graphics queue, which supports
+			// both.
 			(Some(g), ..) => Some(Queues::Single(g)),
 			_ => None,
 		}
@@ -567,3 +671,82 @@ impl Device {
 		)
 	}
 }
+
+/// Routes validation messages to `tracing`.
+pub(super) struct DebugMessenger {
+	ext: ext::debug_utils::Instance,
+	messenger: vk::DebugUtilsMessengerEXT,
+	// Read by the callback until the messenger is destroyed.
+	_filter: Box<MessageFilter>,
+}
+
+struct MessageFilter {
+	ignored: Vec<String>,
+}
+
+impl DebugMessenger {
+	fn new(entry: &ash::Entry, instance: &ash::Instance, ignored: &[&str]) -> Result<Self> {
+		let ext = ext::debug_utils::Instance::new(entry, instance);
+		let filter = Box::new(MessageFilter {
+			ignored: ignored.iter().map(|x| x.to_string()).collect(),
+		});
+		let messenger = unsafe {
+			ext.create_debug_utils_messenger(
+				&vk::DebugUtilsMessengerCreateInfoEXT::default()
+					.message_severity(
+						vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+							| vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+							| vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+							| vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+					)
+					.message_type(
+						vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+							| vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+							| vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+					)
+					.pfn_user_callback(Some(debug_callback))
+					.user_data(&*filter as *const MessageFilter as *mut c_void),
+				None,
+			)?
+		};
+		Ok(Self {
+			ext,
+			messenger,
+			_filter: filter,
+		})
+	}
+
+	pub(super) unsafe fn destroy(&self) { self.ext.destroy_debug_utils_messenger(self.messenger, None); }
+}
+
+unsafe extern "system" fn debug_callback(
+	severity: vk::DebugUtilsMessageSeverityFlagsEXT, _: vk::DebugUtilsMessageTypeFlagsEXT,
+	data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>, filter: *mut c_void,
+) -> vk::Bool32 {
+	let data = &*data;
+	let filter = &*(filter as *const MessageFilter);
+	let id = if data.p_message_id_name.is_null() {
+		"".into()
+	} else {
+		CStr::from_ptr(data.p_message_id_name).to_string_lossy()
+	};
+	if filter.ignored.iter().any(|x| *x == id) {
+		return vk::FALSE;
+	}
+	let msg = if data.p_message.is_null() {
+		"".into()
+	} else {
+		CStr::from_ptr(data.p_message).to_string_lossy()
+	};
+
+	if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+		error!(target: "vulkan", "{id}: {msg}");
+	} else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+		warn!(target: "vulkan", "{id}: {msg}");
+	} else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+		debug!(target: "vulkan", "{id}: {msg}");
+	} else {
+		trace!(target: "vulkan", "{id}: {msg}");
+	}
+	vk::FALSE
+}
@@ -3,14 +3,21 @@
 use std::{
 	cell::UnsafeCell,
 	mem::ManuallyDrop,
-	sync::{Arc, Mutex, MutexGuard},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+		Mutex,
+		MutexGuard,
+	},
 };
 
 use ash::{ext, khr, vk};
 pub use gpu_allocator::vulkan as alloc;
-use gpu_allocator::vulkan::Allocator;
+use gpu_allocator::vulkan::{Allocator, MemoryTypeReport};
+use tracing::error;
 
 pub use crate::device::{
+	init::ValidationInfo,
 	queue::{
 		Compute,
 		Graphics,
@@ -38,10 +45,12 @@ pub use crate::device::{
 use crate::{
 	device::{
 		descriptor::{Descriptors, SamplerId},
+		init::DebugMessenger,
 		queue::QueueData,
 		sampler::Samplers,
 		shader::ShaderRuntime,
 	},
+	Error,
 	Result,
 };
 
@@ -58,17 +67,33 @@ struct DeviceInner {
 	rt_ext: khr::ray_tracing_pipeline::Device,
 	surface_ext: khr::surface::Instance,
 	debug_utils_ext: Option<ext::debug_utils::Device>,
+	debug_messenger: Option<DebugMessenger>,
 	queues: Queues<QueueData>,
 	allocator: ManuallyDrop<Mutex<Allocator>>,
 	shaders: UnsafeCell<Option<ShaderRuntime>>,
 	descriptors: Descriptors,
 	samplers: Mutex<Samplers>,
+	mesh_shaders: bool,
+	lost: AtomicBool,
+	lost_callbacks: Mutex<Vec<Box<dyn Fn() + Send + Sync>>>,
 	instance: ash::Instance,
 	entry: ash::Entry,
 }
 unsafe impl Send for DeviceInner {}
 unsafe impl Sync for DeviceInner {}
 
+/// A summary of the GPU memory allocated through a [`Device`].
+#[derive(Clone, Debug)]
+pub struct MemoryReport {
+	/// Sum of the memory used by all allocations, in bytes.
+	pub allocated: u64,
+	/// Sum of the memory reserved from the driver, in bytes.
+	pub capacity: u64,
+	/// The number of live allocations.
+	pub allocations: usize,
+	pub memory_types: Vec<MemoryTypeReport>,
+}
+
 /// Has everything you need to do Vulkan stuff.
 #[derive(Clone)]
 pub struct Device {
@@ -107,6 +132,20 @@ impl Device {
 		unsafe { (*self.inner.shaders.get()).as_ref().unwrap().status() }
 	}
 
+	/// Recompile all pipelines from the shaders on disk in the background. Pipelines are swapped as they finish, and
+	/// the replaced ones are destroyed by the render graph.
+	pub fn reload_shaders(&self) { unsafe { (*self.inner.shaders.get()).as_ref().unwrap().reload() } }
+
+	/// Take the pipelines replaced by shader reloads since the last call.
+	pub fn take_retired_pipelines(&self) -> Vec<crate::graph::Resource> {
+		unsafe { (*self.inner.shaders.get()).as_ref().unwrap().take_retired() }
+	}
+
+	/// Write the pipeline cache to disk, so the next run can skip compiling pipelines that haven't changed.
+	pub fn save_pipeline_cache(&self) -> Result<()> {
+		unsafe { (*self.inner.shaders.get()).as_ref().unwrap().save_pipeline_cache() }
+	}
+
 	pub fn entry(&self) -> &ash::Entry { &self.inner.entry }
 
 	pub fn instance(&self) -> &ash::Instance { &self.inner.instance }
@@ -121,10 +160,81 @@ impl Device {
 
 	pub fn surface_ext(&self) -> &khr::surface::Instance { &self.inner.surface_ext }
 
+	/// Whether `VK_EXT_mesh_shader` was enabled on this device.
+	pub fn supports_mesh_shaders(&self) -> bool { self.inner.mesh_shaders }
+
+	/// The sample counts a 2D image of `format` can have when used as `usage`, limited by both the format and the
+	/// device limits of every usage. Empty if `format` does not support `usage` at all.
+	pub fn supported_sample_counts(&self, format: vk::Format, usage: vk::ImageUsageFlags) -> vk::SampleCountFlags {
+		let instance = self.instance();
+		let Ok(props) = (unsafe {
+			instance.get_physical_device_image_format_properties(
+				self.physical_device(),
+				format,
+				vk::ImageType::TYPE_2D,
+				vk::ImageTiling::OPTIMAL,
+				usage,
+				vk::ImageCreateFlags::empty(),
+			)
+		}) else {
+			return vk::SampleCountFlags::empty();
+		};
+		let limits = unsafe { instance.get_physical_device_properties(self.physical_device()) }.limits;
+
+		let (depth, stencil) = match format {
+			vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => (true, false),
+			vk::Format::S8_UINT => (false, true),
+			vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => {
+				(true, true)
+			},
+			_ => (false, false),
+		};
+		let mut counts = props.sample_counts;
+		if usage.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) {
+			counts &= limits.framebuffer_color_sample_counts;
+		}
+		if usage.contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT) {
+			if depth {
+				counts &= limits.framebuffer_depth_sample_counts;
+			}
+			if stencil {
+				counts &= limits.framebuffer_stencil_sample_counts;
+			}
+		}
+		if usage.contains(vk::ImageUsageFlags::SAMPLED) {
+			if depth {
+				counts &= limits.sampled_image_depth_sample_counts;
+			}
+			if stencil {
+				counts &= limits.sampled_image_stencil_sample_counts;
+			}
+			if !depth && !stencil {
+				// Integer formats have a separate limit, which is the same as the color one on every driver in
+				// practice.
+				counts &= limits.sampled_image_color_sample_counts & limits.sampled_image_integer_sample_counts;
+			}
+		}
+		if usage.contains(vk::ImageUsageFlags::STORAGE) {
+			counts &= limits.storage_image_sample_counts;
+		}
+		counts
+	}
+
 	pub fn debug_utils_ext(&self) -> Option<&ext::debug_utils::Device> { self.inner.debug_utils_ext.as_ref() }
 
 	pub fn allocator(&self) -> MutexGuard<'_, Allocator> { self.inner.allocator.lock().unwrap() }
 
+	pub fn memory_report(&self) -> MemoryReport {
+		// Only hold the lock while copying out the per-type totals.
+		let memory_types = self.allocator().memory_type_reports();
+		MemoryReport {
+			allocated: memory_types.iter().map(|x| x.allocated_bytes).sum(),
+			capacity: memory_types.iter().map(|x| x.reserved_bytes).sum(),
+			allocations: memory_types.iter().map(|x| x.allocation_count).sum(),
+			memory_types,
+		}
+	}
+
 	pub fn descriptor_set(&self) -> vk::DescriptorSet { self.inner.descriptors.set() }
 
 	pub fn image_id(&self, image: vk::ImageView) -> descriptor::ImageId {
@@ -158,10 +268,36 @@ impl Device {
 	pub fn submit<TY: QueueType>(
 		&self, wait: QueueWait, bufs: &[vk::CommandBuffer], signal: &[SyncStage<vk::Semaphore>], fence: vk::Fence,
 	) -> Result<SyncPoint<TY>> {
-		self.inner
+		if self.is_lost() {
+			return Err(Error::DeviceLost);
+		}
+		let res = self
+			.inner
 			.queues
 			.get::<TY>()
-			.submit(&self.inner.queues, self, wait, bufs, signal, fence)
+			.submit(&self.inner.queues, self, wait, bufs, signal, fence);
+		self.check_lost(res)
+	}
+
+	/// Whether the device was lost. Submissions to a lost device fail immediately with `Error::DeviceLost`.
+	pub fn is_lost(&self) -> bool { self.inner.lost.load(Ordering::Acquire) }
+
+	/// Call `f` once the device is lost, so that the application can shut down or recreate its renderer.
+	pub fn on_lost(&self, f: impl Fn() + Send + Sync + 'static) {
+		self.inner.lost_callbacks.lock().unwrap().push(Box::new(f));
+	}
+
+	/// Mark the device as lost if `res` failed because the device was lost.
+	pub fn check_lost<T>(&self, res: Result<T>) -> Result<T> {
+		if let Err(Error::DeviceLost) = res
+			&& !self.inner.lost.swap(true, Ordering::AcqRel)
+		{
+			error!("GPU device lost");
+			for f in self.inner.lost_callbacks.lock().unwrap().iter() {
+				f();
+			}
+		}
+		res
 	}
 }
 
@@ -176,6 +312,9 @@ impl Drop for DeviceInner {
 			self.queues.map_ref(|x| x.destroy(&self.device));
 
 			self.device.destroy_device(None);
+			if let Some(m) = &self.debug_messenger {
+				m.destroy();
+			}
 			self.instance.destroy_instance(None);
 		}
 	}
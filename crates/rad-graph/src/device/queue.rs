@@ -108,7 +108,7 @@ pub struct SyncPoint<T: QueueType>(u64, PhantomData<fn() -> T>);
 
 impl<TY: QueueType> SyncPoint<TY> {
 	pub fn wait(self, device: &Device) -> Result<()> {
-		unsafe {
+		let res = unsafe {
 			device
 				.device()
 				.wait_semaphores(
@@ -118,7 +118,8 @@ impl<TY: QueueType> SyncPoint<TY> {
 					u64::MAX,
 				)
 				.map_err(Into::into)
-		}
+		};
+		device.check_lost(res)
 	}
 
 	pub fn is_complete(self, device: &Device) -> Result<bool> {
@@ -134,6 +135,8 @@ impl<TY: QueueType> SyncPoint<TY> {
 	pub fn later(self, other: Self) -> Self { Self(self.0.max(other.0), PhantomData) }
 }
 
+/// Data for each type of queue. Devices without dedicated compute and transfer queue families use one queue for
+/// everything, in which case all queue types share the same `T`.
 pub enum Queues<T> {
 	Multiple {
 		graphics: T, // Also supports presentation.
@@ -412,32 +415,43 @@ impl QueueData {
 		let s = span!(Level::TRACE, "gpu submit");
 		let _e = s.enter();
 
-		let wait: Vec<_> = wait
+		let binary = wait.binary_semaphores;
+		let mut wait: Vec<_> = wait
 			.graphics
 			.into_iter()
 			.map(|x| x.info(qs))
 			.chain(wait.compute.into_iter().map(|x| x.info(qs)))
 			.chain(wait.transfer.into_iter().map(|x| x.info(qs)))
-			.chain(wait.binary_semaphores.into_iter().map(|x| x.info()))
 			.collect();
+		if let Queues::Single(_) = qs {
+			// Every queue type shares one timeline, so only wait for the latest point on it.
+			wait = wait
+				.into_iter()
+				.reduce(|a, b| a.value(a.value.max(b.value)).stage_mask(a.stage_mask | b.stage_mask))
+				.into_iter()
+				.collect();
+		}
+		wait.extend(binary.iter().map(|x| x.info()));
 		let infos: Vec<_> = bufs
 			.iter()
 			.map(|&b| vk::CommandBufferSubmitInfo::default().command_buffer(b))
 			.collect();
-		let v = self.value.fetch_add(1, Ordering::Release);
-		let signal: Vec<_> = iter::once(
-			vk::SemaphoreSubmitInfo::default()
-				.semaphore(self.semaphore)
-				.value(v + 1)
-				.stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS),
-		)
-		.chain(signal.into_iter().map(|x| x.info()))
-		.collect();
 
 		unsafe {
 			let s = span!(Level::TRACE, "driver submit");
 			let _e = s.enter();
+			// Take the next timeline value under the lock, so that submissions from different threads signal in
+			// order. This matters most with a single queue, which all threads submit to.
 			let q = self.queue.lock().unwrap();
+			let v = self.value.fetch_add(1, Ordering::Release);
+			let signal: Vec<_> = iter::once(
+				vk::SemaphoreSubmitInfo::default()
+					.semaphore(self.semaphore)
+					.value(v + 1)
+					.stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS),
+			)
+			.chain(signal.iter().map(|x| x.info()))
+			.collect();
 			device.device().queue_submit2(
 				*q,
 				&[vk::SubmitInfo2::default()
@@ -1,5 +1,6 @@
 use std::{
 	marker::PhantomData,
+	path::{Path, PathBuf},
 	sync::{
 		atomic::{AtomicBool, AtomicU64, Ordering},
 		Arc,
@@ -24,6 +25,7 @@ use rspirv::{
 
 use crate::{
 	device::{shader::compile::ShaderBuilder, Device},
+	graph,
 	resource::{Buffer, BufferDesc, BufferType, Resource},
 	Error,
 };
@@ -38,6 +40,7 @@ pub struct ShaderInfo {
 
 pub struct GraphicsPipelineDesc<'a> {
 	pub shaders: &'a [ShaderInfo],
+	pub topology: vk::PrimitiveTopology,
 	pub raster: vk::PipelineRasterizationStateCreateInfo<'static>,
 	pub depth: vk::PipelineDepthStencilStateCreateInfo<'static>,
 	pub multisample: vk::PipelineMultisampleStateCreateInfo<'static>,
@@ -58,6 +61,7 @@ pub struct PipelineColorBlendStateCreateInfo {
 
 struct GraphicsPipelineDescOwned {
 	shaders: Vec<ShaderInfo>,
+	topology: vk::PrimitiveTopology,
 	raster: vk::PipelineRasterizationStateCreateInfo<'static>,
 	depth: vk::PipelineDepthStencilStateCreateInfo<'static>,
 	multisample: vk::PipelineMultisampleStateCreateInfo<'static>,
@@ -72,6 +76,7 @@ impl GraphicsPipelineDesc<'_> {
 	fn owned(&self) -> GraphicsPipelineDescOwned {
 		GraphicsPipelineDescOwned {
 			shaders: self.shaders.to_vec(),
+			topology: self.topology,
 			raster: self.raster,
 			depth: self.depth,
 			multisample: self.multisample,
@@ -165,6 +170,7 @@ impl Default for GraphicsPipelineDesc<'_> {
 
 		Self {
 			shaders: &[],
+			topology: vk::PrimitiveTopology::TRIANGLE_LIST,
 			color_attachments: &[],
 			blend: BLEND,
 			// Values that can be defaulted below.
@@ -309,9 +315,58 @@ enum PipelineData {
 struct PipelineCompiler {
 	device: Device,
 	builder: ShaderBuilder,
+	cache: vk::PipelineCache,
+	cache_path: PathBuf,
+}
+
+impl Drop for PipelineCompiler {
+	fn drop(&mut self) {
+		unsafe {
+			self.device.device().destroy_pipeline_cache(self.cache, None);
+		}
+	}
 }
 
 impl PipelineCompiler {
+	/// Load the pipeline cache saved by a previous run, discarding it if it was created by a different device or
+	/// driver.
+	fn load_cache(device: &Device, path: &Path) -> vk::PipelineCache {
+		let data = std::fs::read(path)
+			.ok()
+			.filter(|data| Self::cache_matches(device, data))
+			.unwrap_or_default();
+		unsafe {
+			device
+				.device()
+				.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default().initial_data(&data), None)
+				.unwrap()
+		}
+	}
+
+	fn cache_matches(device: &Device, data: &[u8]) -> bool {
+		// `VkPipelineCacheHeaderVersionOne`, which is always little-endian.
+		if data.len() < 32 {
+			return false;
+		}
+		let word = |i: usize| u32::from_le_bytes(data[i * 4..][..4].try_into().unwrap());
+		let props = unsafe {
+			device
+				.instance()
+				.get_physical_device_properties(device.physical_device())
+		};
+		word(0) >= 32
+			&& word(1) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+			&& word(2) == props.vendor_id
+			&& word(3) == props.device_id
+			&& data[16..32] == props.pipeline_cache_uuid
+	}
+
+	fn save_cache(&self) -> crate::Result<()> {
+		let data = unsafe { self.device.device().get_pipeline_cache_data(self.cache)? };
+		std::fs::write(&self.cache_path, data).map_err(|e| format!("failed to save pipeline cache: {e}"))?;
+		Ok(())
+	}
+
 	fn get_shader(&mut self, info: ShaderInfo) -> Result<(Vec<u32>, vk::ShaderStageFlags), String> {
 		let (module, entry) = info.shader.rsplit_once('.').unwrap();
 		let spirv = self.builder.load_module(module, entry, info.spec)?;
@@ -379,13 +434,12 @@ impl PipelineCompiler {
 			self.device
 				.device()
 				.create_graphics_pipelines(
-					vk::PipelineCache::null(),
+					self.cache,
 					&[vk::GraphicsPipelineCreateInfo::default()
 						.stages(&shaders)
 						.vertex_input_state(&vk::PipelineVertexInputStateCreateInfo::default())
 						.input_assembly_state(
-							&vk::PipelineInputAssemblyStateCreateInfo::default()
-								.topology(vk::PrimitiveTopology::TRIANGLE_LIST),
+							&vk::PipelineInputAssemblyStateCreateInfo::default().topology(desc.topology),
 						)
 						.viewport_state(
 							&vk::PipelineViewportStateCreateInfo::default()
@@ -425,7 +479,7 @@ impl PipelineCompiler {
 			self.device
 				.device()
 				.create_compute_pipelines(
-					vk::PipelineCache::null(),
+					self.cache,
 					&[vk::ComputePipelineCreateInfo::default()
 						.layout(self.device.layout())
 						.stage(
@@ -495,7 +549,7 @@ impl PipelineCompiler {
 				.rt_ext()
 				.create_ray_tracing_pipelines(
 					vk::DeferredOperationKHR::null(),
-					vk::PipelineCache::null(),
+					self.cache,
 					&[vk::RayTracingPipelineCreateInfoKHR::default()
 						.flags(vk::PipelineCreateFlags::empty())
 						.stages(&shaders)
@@ -628,6 +682,18 @@ fn align_up(size: u64, align: u64) -> u64 { (size + align - 1) & !(align - 1) }
 struct RuntimeShared {
 	pipelines: Vec<PipelineData>,
 	compiler: PipelineCompiler,
+	/// Pipelines replaced by a recompile, which frames in flight may still be using. Locked on its own, so that frames
+	/// taking them don't wait for a recompile to finish.
+	retired: Arc<Mutex<Vec<graph::Resource>>>,
+}
+
+impl Drop for RuntimeShared {
+	fn drop(&mut self) {
+		// No frames can be in flight once the runtime is torn down, so nothing uses these anymore.
+		for r in self.retired.lock().unwrap().drain(..) {
+			unsafe { r.destroy(&self.compiler.device) };
+		}
+	}
 }
 
 impl RuntimeShared {
@@ -672,23 +738,39 @@ impl RuntimeShared {
 		Ok(RtPipeline(inner, self.compiler.device.clone()))
 	}
 
-	fn recompile_pipelines(&mut self) {
-		let Self { pipelines, compiler } = self;
+	/// Recompile every live pipeline from the shaders on disk, keeping the old pipeline of any that fail. Returns
+	/// whether all of them compiled.
+	fn recompile_pipelines(&mut self) -> bool {
+		let Self {
+			pipelines,
+			compiler,
+			retired,
+		} = self;
+		let mut old = Vec::new();
+		let mut ok = true;
 		if let Err(e) = compiler.builder.reload() {
 			println!("failed to recompile pipeline: {e:?}");
+			ok = false;
 		}
+		// Pipelines whose owner was destroyed are only referenced from here.
+		pipelines.retain(|data| match data {
+			PipelineData::Graphics(_, out) | PipelineData::Compute(_, out) => Arc::strong_count(out) > 1,
+			PipelineData::Rt(_, out) => Arc::strong_count(out) > 1,
+		});
 		for data in pipelines.iter() {
-			// TODO: the GPU is still using the old pipeline, so we can't destroy it
-			// yet
 			let err = match data {
 				PipelineData::Graphics(desc, out) => compiler.compile_graphics(desc).map(|x| {
-					out.swap(x.as_raw(), Ordering::Relaxed);
+					let p = out.swap(x.as_raw(), Ordering::Relaxed);
+					old.push(graph::Resource::Pipeline(vk::Pipeline::from_raw(p)));
 				}),
 				PipelineData::Compute(shader, out) => compiler.compile_compute(*shader).map(|x| {
-					out.swap(x.as_raw(), Ordering::Relaxed);
+					let p = out.swap(x.as_raw(), Ordering::Relaxed);
+					old.push(graph::Resource::Pipeline(vk::Pipeline::from_raw(p)));
 				}),
 				PipelineData::Rt(desc, out) => compiler.compile_rt(desc).map(|data| {
-					*out.lock().unwrap() = data;
+					let data = std::mem::replace(&mut *out.lock().unwrap(), data);
+					old.push(graph::Resource::Pipeline(data.pipeline));
+					old.push(graph::Resource::Buffer(data.sbt));
 				}),
 			};
 			match err {
@@ -696,7 +778,10 @@ impl RuntimeShared {
 				Err(Ok(e)) => println!("failed to recompile pipeline: {e:?}"),
 				Err(Err(e)) => println!("{e}"),
 			}
+			ok &= err.is_ok();
 		}
+		retired.lock().unwrap().extend(old);
+		ok
 	}
 }
 
@@ -706,10 +791,27 @@ pub enum HotreloadStatus {
 	Errored,
 }
 
+#[derive(Default)]
+struct ReloadStatus {
+	recompiling: AtomicBool,
+	errored: AtomicBool,
+}
+
+impl ReloadStatus {
+	fn recompile(&self, shared: &Mutex<RuntimeShared>) {
+		self.recompiling.store(true, Ordering::Relaxed);
+		let ok = shared.lock().unwrap().recompile_pipelines();
+		self.errored.store(!ok, Ordering::Relaxed);
+		self.recompiling.store(false, Ordering::Relaxed);
+		println!();
+	}
+}
+
 pub struct ShaderRuntime {
 	_watcher: Debouncer<RecommendedWatcher, RecommendedCache>,
-	status: Arc<AtomicBool>,
+	status: Arc<ReloadStatus>,
 	shared: Arc<Mutex<RuntimeShared>>,
+	retired: Arc<Mutex<Vec<graph::Resource>>>,
 }
 
 impl ShaderRuntime {
@@ -729,12 +831,18 @@ impl ShaderRuntime {
 			curr = p;
 		}
 		let source = source.unwrap();
-		let status = Arc::new(AtomicBool::new(false));
+		let cache = cache.unwrap();
+		let cache_path = cache.join("pipelines.bin");
+		let status = Arc::new(ReloadStatus::default());
+		let retired = Arc::new(Mutex::new(Vec::new()));
 		let shared = Arc::new(Mutex::new(RuntimeShared {
 			pipelines: Vec::new(),
+			retired: retired.clone(),
 			compiler: PipelineCompiler {
+				cache: PipelineCompiler::load_cache(&device, &cache_path),
+				cache_path,
 				device,
-				builder: ShaderBuilder::new(source.clone(), cache.unwrap()).unwrap(),
+				builder: ShaderBuilder::new(source.clone(), cache).unwrap(),
 			},
 		}));
 		let s = shared.clone();
@@ -745,10 +853,7 @@ impl ShaderRuntime {
 					.into_iter()
 					.any(|ev| matches!(ev.kind, EventKind::Create(_) | EventKind::Modify(_)))
 			{
-				st.store(true, Ordering::Relaxed);
-				s.lock().unwrap().recompile_pipelines();
-				st.store(false, Ordering::Relaxed);
-				println!();
+				st.recompile(&s);
 			}
 		})
 		.unwrap();
@@ -758,6 +863,7 @@ impl ShaderRuntime {
 			_watcher: watcher,
 			status,
 			shared,
+			retired,
 		}
 	}
 
@@ -776,10 +882,25 @@ impl ShaderRuntime {
 		self.shared.lock().unwrap().create_rt_pipeline(desc)
 	}
 
+	pub fn save_pipeline_cache(&self) -> crate::Result<()> { self.shared.lock().unwrap().compiler.save_cache() }
+
+	/// Recompile every pipeline from the shaders on disk in the background, as if a shader had changed.
+	pub fn reload(&self) {
+		let s = self.shared.clone();
+		let st = self.status.clone();
+		std::thread::spawn(move || st.recompile(&s));
+	}
+
+	/// Take the pipelines replaced by recompiles since the last call, to be destroyed once frames in flight finish.
+	pub fn take_retired(&self) -> Vec<graph::Resource> { std::mem::take(&mut *self.retired.lock().unwrap()) }
+
 	pub fn status(&self) -> HotreloadStatus {
-		match self.status.load(Ordering::Relaxed) {
-			true => HotreloadStatus::Recompiling,
-			false => HotreloadStatus::Waiting,
+		if self.status.recompiling.load(Ordering::Relaxed) {
+			HotreloadStatus::Recompiling
+		} else if self.status.errored.load(Ordering::Relaxed) {
+			HotreloadStatus::Errored
+		} else {
+			HotreloadStatus::Waiting
 		}
 	}
 }
@@ -1,7 +1,7 @@
 use std::{alloc::Allocator, hash::BuildHasherDefault, hint::unreachable_unchecked, ops::BitOr, ptr::NonNull};
 
 use ash::vk;
-use tracing::{span, Level};
+use tracing::{debug, span, Level};
 
 use crate::{
 	arena::{Arena, IteratorAlloc},
@@ -18,6 +18,7 @@ use crate::{
 			VirtualResourceData,
 			VirtualResourceType,
 		},
+		AliasingStats,
 		ArenaMap,
 		BufferLoc,
 		Frame,
@@ -221,10 +222,16 @@ where
 
 struct ResourceAliaser<'graph> {
 	buffers: Vec<u32, &'graph Arena>,
+	/// Keyed by [`Self::alias_key`].
 	images: ArenaMap<'graph, ImageDesc, Vec<u32, &'graph Arena>>,
 	resource_map: Vec<u32, &'graph Arena>,
 	resources: Vec<Resource<'graph>, &'graph Arena>,
 	lifetimes: Vec<ResourceLifetime, &'graph Arena>,
+	stats: AliasingStats,
+	/// The total size of every transient buffer requested.
+	virtual_buffer_bytes: u64,
+	/// The concrete image each aliased virtual image was merged into.
+	image_merges: Vec<u32, &'graph Arena>,
 }
 
 impl<'graph> ResourceAliaser<'graph> {
@@ -235,6 +242,9 @@ impl<'graph> ResourceAliaser<'graph> {
 			resource_map: Vec::new_in(arena),
 			resources: Vec::new_in(arena),
 			lifetimes: Vec::new_in(arena),
+			stats: AliasingStats::default(),
+			virtual_buffer_bytes: 0,
+			image_merges: Vec::new_in(arena),
 		}
 	}
 
@@ -252,6 +262,8 @@ impl<'graph> ResourceAliaser<'graph> {
 
 	fn try_merge_buffer(&mut self, data: &BufferData<'graph>, lifetime: ResourceLifetime) {
 		if Self::is_buffer_merge_candidate(&data) {
+			self.stats.virtual_resources += 1;
+			self.virtual_buffer_bytes += data.desc.size;
 			for &i in self.buffers.iter() {
 				let res = &mut self.resources[i as usize];
 				let res = unsafe { res.buffer_mut() };
@@ -274,28 +286,48 @@ impl<'graph> ResourceAliaser<'graph> {
 		data.handle.0 == vk::Image::null() && data.desc.persist.is_none()
 	}
 
+	/// Images with the same key can share one image: everything but the format must match, and the formats must be
+	/// compatible, so that one can be viewed as the other.
+	fn alias_key(desc: &ImageDesc) -> ImageDesc {
+		ImageDesc {
+			format: vk::Format::UNDEFINED,
+			..*desc
+		}
+	}
+
 	fn try_merge_image(&mut self, data: &ImageData<'graph>, lifetime: ResourceLifetime) {
 		if Self::is_image_merge_candidate(&data) {
-			for &i in self.images.get(&data.desc).into_iter().flatten() {
+			self.stats.virtual_resources += 1;
+			for &i in self.images.get(&Self::alias_key(&data.desc)).into_iter().flatten() {
 				let res = &mut self.resources[i as usize];
 				let res = unsafe { res.image_mut() };
 				let res_lifetime = &mut self.lifetimes[i as usize];
 				// If the formats aren't compatible, don't merge.
 				if res_lifetime.independent(lifetime)
+					&& compatible_formats(res.desc.format, data.desc.format)
 					&& compatible_formats(
 						res.usages.first_key_value().unwrap().1.format,
 						data.usages.first_key_value().unwrap().1.format,
 					) && Self::is_image_merge_candidate(&res)
 				{
-					res.usages.extend(data.usages.iter().map(|(k, v)| (*k, v.clone())));
+					// Usages without a format view the image as the format it was created with, which is now the
+					// format of the image it was merged into.
+					res.usages.extend(data.usages.iter().map(|(k, v)| {
+						let mut v = v.clone();
+						if v.format == vk::Format::UNDEFINED {
+							v.format = data.desc.format;
+						}
+						(*k, v)
+					}));
 					*res_lifetime = res_lifetime.union(lifetime);
 					self.resource_map.push(i);
+					self.image_merges.push(i);
 					return;
 				}
 			}
 		}
 		self.images
-			.entry(data.desc)
+			.entry(Self::alias_key(&data.desc))
 			.or_insert_with(|| Vec::new_in(self.resources.allocator()))
 			.push(self.resources.len() as _);
 		self.push(Resource::Image(data.clone()), lifetime);
@@ -316,12 +348,17 @@ impl<'graph> ResourceAliaser<'graph> {
 		let alloc = *self.resources.allocator();
 		let mut buffers = Vec::new_in(alloc);
 		let mut images = Vec::new_in(alloc);
+		let mut buffer_bytes = 0;
 
 		for (i, res) in self.resources.iter_mut().enumerate() {
 			match res {
 				Resource::Data(..) => {},
 				Resource::Buffer(data) => {
 					buffers.push(i as _);
+					if Self::is_buffer_merge_candidate(data) {
+						self.stats.resources += 1;
+						buffer_bytes += data.desc.size;
+					}
 					if data.handle.buffer == vk::Buffer::null() {
 						let desc = crate::resource::BufferDescUnnamed {
 							size: data.desc.size,
@@ -371,12 +408,16 @@ impl<'graph> ResourceAliaser<'graph> {
 				},
 				Resource::Image(data) => {
 					images.push(i as _);
+					if Self::is_image_merge_candidate(data) {
+						self.stats.resources += 1;
+					}
 					if data.handle.0 == vk::Image::null() {
+						// Views of other formats may need usages the format the image is created with doesn't support.
 						let flags = data
 							.usages
 							.values()
 							.any(|u| u.format != vk::Format::UNDEFINED && u.format != data.desc.format)
-							.then_some(vk::ImageCreateFlags::MUTABLE_FORMAT)
+							.then_some(vk::ImageCreateFlags::MUTABLE_FORMAT | vk::ImageCreateFlags::EXTENDED_USAGE)
 							.unwrap_or_default();
 						let desc = crate::resource::ImageDescUnnamed {
 							flags,
@@ -408,6 +449,19 @@ impl<'graph> ResourceAliaser<'graph> {
 			}
 		}
 
+		// Merged images have the same description up to compatible formats, which have the same texel size, so each
+		// saved a whole image of the size of the one it was merged into.
+		let image_bytes_saved: u64 = self
+			.image_merges
+			.iter()
+			.map(|&i| unsafe {
+				let image = self.resources[i as usize].image().handle.0;
+				device.device().get_image_memory_requirements(image).size
+			})
+			.sum();
+		self.stats.bytes_saved = self.virtual_buffer_bytes - buffer_bytes + image_bytes_saved;
+		graph.aliasing = self.stats;
+
 		ResourceMap {
 			virtual_res,
 			resource_map: self.resource_map,
@@ -552,6 +606,8 @@ impl<'graph> InProgressDependencyInfo<'graph> {
 struct SyncBuilder<'graph> {
 	sync: Vec<InProgressSync<'graph>, &'graph Arena>,
 	last_pass: usize,
+	/// Barriers dropped because they only ordered reads after reads.
+	skipped: usize,
 }
 
 impl<'graph> SyncBuilder<'graph> {
@@ -574,6 +630,7 @@ impl<'graph> SyncBuilder<'graph> {
 				.rev()
 				.find_map(|(i, x)| matches!(x, FrameEvent::Pass(_)).then_some(i))
 				.unwrap_or(0),
+			skipped: 0,
 		}
 	}
 
@@ -703,6 +760,22 @@ impl<'graph> SyncBuilder<'graph> {
 				.dst_access_mask(vk::AccessFlags2::HOST_READ),
 		);
 
+		let emitted: usize = all_sync
+			.iter()
+			.flat_map(|x| {
+				[
+					&x.queue.barriers,
+					&x.cross_queue.signal_barriers,
+					&x.cross_queue.wait_barriers,
+				]
+			})
+			.map(|x| x.barriers.len() + x.image_barriers.len())
+			.sum();
+		debug!(
+			"{} barriers, {emitted} after dropping read-after-read barriers",
+			emitted + self.skipped
+		);
+
 		Ok(all_sync)
 	}
 }
@@ -822,16 +895,25 @@ impl<'temp, 'pass, 'graph> Synchronizer<'temp, 'pass, 'graph> {
 					usages.next();
 				}
 
-				sync.barrier(
-					res.handle.to_image().0,
-					subresource,
-					prev_pass,
-					prev_access,
-					next_pass,
-					next_access,
-				);
+				if prev_access.access_mask == vk::AccessFlags2::NONE
+					&& prev_access.image_layout == next_prev_access.image_layout
+				{
+					// Reads after reads in the same layout need no barrier. The earlier reads are kept in the previous
+					// access, so the next write still waits for them.
+					sync.skipped += 1;
+					prev_access |= next_prev_access;
+				} else {
+					sync.barrier(
+						res.handle.to_image().0,
+						subresource,
+						prev_pass,
+						prev_access,
+						next_pass,
+						next_access,
+					);
+					prev_access = next_prev_access;
+				}
 				prev_pass = last_read_pass;
-				prev_access = next_prev_access;
 			}
 		}
 	}
@@ -19,6 +19,8 @@ pub enum Resource {
 	Image(Image),
 	ImageView(ImageView),
 	AS(AS),
+	/// A pipeline replaced by a hot reload.
+	Pipeline(vk::Pipeline),
 }
 
 impl Resource {
@@ -28,14 +30,90 @@ impl Resource {
 			Resource::Image(x) => x.destroy(device),
 			Resource::ImageView(x) => x.destroy(device),
 			Resource::AS(x) => x.destroy(device),
+			Resource::Pipeline(x) => device.device().destroy_pipeline(x, None),
 		}
 	}
 }
 
+/// The maximum number of timed regions in a frame, any more are not timed.
+const MAX_TIMED_REGIONS: u32 = 64;
+
+/// Timestamp queries for the timed regions of a frame.
+struct Timestamps {
+	pool: vk::QueryPool,
+	/// Nanoseconds per timestamp tick.
+	period: f64,
+	valid_mask: u64,
+	/// The regions written this frame, region `i` uses queries `2 * i` and `2 * i + 1`.
+	regions: Vec<String>,
+}
+
+impl Timestamps {
+	fn new(device: &Device) -> Result<Option<Self>> {
+		unsafe {
+			let instance = device.instance();
+			let family = device.queue_families().into::<Graphics>();
+			let valid_bits = instance.get_physical_device_queue_family_properties(device.physical_device())
+				[family as usize]
+				.timestamp_valid_bits;
+			if valid_bits == 0 {
+				return Ok(None);
+			}
+			let period = instance
+				.get_physical_device_properties(device.physical_device())
+				.limits
+				.timestamp_period;
+
+			let pool = device.device().create_query_pool(
+				&vk::QueryPoolCreateInfo::default()
+					.query_type(vk::QueryType::TIMESTAMP)
+					.query_count(MAX_TIMED_REGIONS * 2),
+				None,
+			)?;
+			Ok(Some(Self {
+				pool,
+				period: period as f64,
+				valid_mask: if valid_bits >= 64 {
+					u64::MAX
+				} else {
+					(1 << valid_bits) - 1
+				},
+				regions: Vec::new(),
+			}))
+		}
+	}
+
+	fn resolve(&mut self, device: &Device) -> Result<Vec<(String, f64)>> {
+		if self.regions.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let mut data = vec![0u64; self.regions.len() * 2];
+		unsafe {
+			device.device().get_query_pool_results(
+				self.pool,
+				0,
+				&mut data,
+				vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+			)?;
+		}
+		Ok(self
+			.regions
+			.drain(..)
+			.zip(data.chunks_exact(2))
+			.map(|(name, t)| {
+				let ticks = (t[1] & self.valid_mask).wrapping_sub(t[0] & self.valid_mask) & self.valid_mask;
+				(name, ticks as f64 * self.period / 1_000_000.0)
+			})
+			.collect())
+	}
+}
+
 pub struct FrameData {
 	sync: SyncPoint<Graphics>,
 	pool: CommandPool,
 	delete_queue: Vec<Resource>,
+	timestamps: Option<Timestamps>,
 }
 
 impl FrameData {
@@ -44,11 +122,22 @@ impl FrameData {
 			sync: SyncPoint::default(),
 			pool: CommandPool::new(device, device.queue_families().into::<Graphics>())?,
 			delete_queue: Vec::new(),
+			timestamps: Timestamps::new(device)?,
 		})
 	}
 
 	pub fn delete(&mut self, res: impl Deletable) { res.into_resources(&mut self.delete_queue); }
 
+	/// Read back the timings of the timed regions from the last time this frame data was used, in milliseconds.
+	///
+	/// Must be called after [`Self::reset`].
+	pub fn resolve_timings(&mut self, device: &Device) -> Result<Vec<(String, f64)>> {
+		match &mut self.timestamps {
+			Some(t) => t.resolve(device),
+			None => Ok(Vec::new()),
+		}
+	}
+
 	pub fn reset(&mut self, device: &Device) -> Result<()> {
 		unsafe {
 			let span = span!(Level::TRACE, "wait for gpu");
@@ -72,6 +161,9 @@ impl FrameData {
 			for r in self.delete_queue {
 				r.destroy(device);
 			}
+			if let Some(t) = self.timestamps {
+				device.device().destroy_query_pool(t.pool, None);
+			}
 		}
 	}
 }
@@ -195,6 +287,35 @@ impl<'a, I: Iterator<Item = Sync<'a>>> Submitter<'a, I> {
 		Ok(self.buf)
 	}
 
+	/// Write the starting timestamp of a timed region, returning the query to end it with. Returns `None` if timestamps
+	/// are unsupported or there are too many timed regions.
+	pub fn start_timestamp(&mut self, device: &Device, name: String) -> Option<u32> {
+		let t = self.data.timestamps.as_mut()?;
+		let region = t.regions.len() as u32;
+		if region == MAX_TIMED_REGIONS {
+			return None;
+		}
+
+		unsafe {
+			let dev = device.device();
+			if region == 0 {
+				dev.cmd_reset_query_pool(self.buf, t.pool, 0, MAX_TIMED_REGIONS * 2);
+			}
+			dev.cmd_write_timestamp2(self.buf, vk::PipelineStageFlags2::ALL_COMMANDS, t.pool, region * 2);
+		}
+		t.regions.push(name);
+		Some(region * 2 + 1)
+	}
+
+	pub fn end_timestamp(&mut self, device: &Device, query: u32) {
+		let t = self.data.timestamps.as_ref().unwrap();
+		unsafe {
+			device
+				.device()
+				.cmd_write_timestamp2(self.buf, vk::PipelineStageFlags2::ALL_COMMANDS, t.pool, query);
+		}
+	}
+
 	pub fn finish(mut self, device: &Device) -> Result<()> {
 		let mut sync = self.sync.next().unwrap();
 
@@ -57,6 +57,20 @@ pub struct RenderGraph {
 	caches: Caches,
 	curr_frame: usize,
 	resource_base_id: usize,
+	timings: Vec<(String, f64)>,
+	aliasing: AliasingStats,
+}
+
+/// How much memory the transient resources of a frame saved by sharing buffers and images between passes that don't
+/// overlap.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct AliasingStats {
+	/// The transient buffers and images passes asked for.
+	pub virtual_resources: u32,
+	/// The buffers and images allocated for them.
+	pub resources: u32,
+	/// The bytes that would have been allocated without aliasing.
+	pub bytes_saved: u64,
 }
 
 pub struct Caches {
@@ -88,13 +102,33 @@ impl RenderGraph {
 			caches,
 			curr_frame: 0,
 			resource_base_id: 0,
+			timings: Vec::new(),
+			aliasing: AliasingStats::default(),
 		})
 	}
 
+	/// The GPU time taken by every timed region of the last completed frame, in milliseconds.
+	///
+	/// Regions are named by their path in the region hierarchy, such as `visbuffer/early pass/cull`.
+	pub fn timings(&self) -> &[(String, f64)] { &self.timings }
+
+	/// The transient resources aliased in the last frame.
+	pub fn aliasing(&self) -> AliasingStats { self.aliasing }
+
 	pub fn frame<'pass, 'graph>(
 		&'graph mut self, device: &'graph Device, arena: &'graph Arena,
 	) -> Result<Frame<'pass, 'graph>> {
-		self.frame_data[self.curr_frame].reset(device)?;
+		let data = &mut self.frame_data[self.curr_frame];
+		data.reset(device)?;
+		// Frames in flight may still use pipelines replaced by a shader reload, so destroy them when this frame comes
+		// around again.
+		for r in device.take_retired_pipelines() {
+			data.delete(r);
+		}
+		let timings = data.resolve_timings(device)?;
+		if !timings.is_empty() {
+			self.timings = timings;
+		}
 		Ok(Frame {
 			graph: self,
 			device,
@@ -131,7 +165,7 @@ impl RenderGraph {
 
 enum FrameEvent<'pass, 'graph> {
 	Pass(PassData<'pass, 'graph>),
-	RegionStart(Vec<u8, &'graph Arena>),
+	RegionStart { name: Vec<u8, &'graph Arena>, timed: bool },
 	RegionEnd,
 }
 
@@ -150,10 +184,10 @@ impl<'pass, 'graph> Frame<'pass, 'graph> {
 
 	pub fn arena(&self) -> &'graph Arena { self.passes.allocator() }
 
-	pub fn start_region(&mut self, name: &str) {
-		let name = name.as_bytes().iter().copied().chain([0]);
-		self.passes.push(FrameEvent::RegionStart(name.collect_in(self.arena())));
-	}
+	pub fn start_region(&mut self, name: &str) { self.region(name, false); }
+
+	/// Start a region that is also timed on the GPU, see [`RenderGraph::timings`].
+	pub fn time_region(&mut self, name: &str) { self.region(name, true); }
 
 	pub fn end_region(&mut self) { self.passes.push(FrameEvent::RegionEnd); }
 
@@ -162,6 +196,20 @@ impl<'pass, 'graph> Frame<'pass, 'graph> {
 		self.start_region(name);
 		PassBuilder { frame: self }
 	}
+
+	/// Build a pass with a name, timing it on the GPU.
+	pub fn timed_pass(&mut self, name: &str) -> PassBuilder<'_, 'pass, 'graph> {
+		self.time_region(name);
+		PassBuilder { frame: self }
+	}
+
+	fn region(&mut self, name: &str, timed: bool) {
+		let name = name.as_bytes().iter().copied().chain([0]);
+		self.passes.push(FrameEvent::RegionStart {
+			name: name.collect_in(self.arena()),
+			timed,
+		});
+	}
 }
 
 impl Frame<'_, '_> {
@@ -199,30 +247,40 @@ impl Frame<'_, '_> {
 		let mut submitter = Submitter::new(arena, sync, &mut graph.frame_data, graph.curr_frame);
 
 		let mut region_stack = Vec::new_in(arena);
+		let mut region_path = Vec::new_in(arena);
 		for (i, pass) in passes.into_iter().enumerate() {
 			match pass {
-				FrameEvent::RegionStart(name) => {
-					let span = span!(
-						Level::TRACE,
-						"graph exec",
-						name = unsafe { std::str::from_utf8_unchecked(&name[..name.len() - 1]) }
-					);
-					region_stack.push(span.entered());
+				FrameEvent::RegionStart { name, timed } => {
+					// Leaked into the arena, so the name can stay in `region_path` until the region ends.
+					let name = &*name.leak();
+					let str_name = unsafe { std::str::from_utf8_unchecked(&name[..name.len() - 1]) };
+					let span = span!(Level::TRACE, "graph exec", name = str_name);
+					region_path.push(str_name);
 
+					let buf = submitter.pass(device)?;
 					unsafe {
 						if let Some(debug) = device.debug_utils_ext() {
 							debug.cmd_begin_debug_utils_label(
-								submitter.pass(device)?,
+								buf,
 								&vk::DebugUtilsLabelEXT::default()
-									.label_name(std::ffi::CStr::from_bytes_with_nul_unchecked(&name)),
+									.label_name(std::ffi::CStr::from_bytes_with_nul_unchecked(name)),
 							);
 						}
 					}
+					let query = timed
+						.then(|| submitter.start_timestamp(device, region_path.join("/")))
+						.flatten();
+					region_stack.push((span.entered(), query));
 				},
 				FrameEvent::RegionEnd => unsafe {
-					region_stack.pop();
+					let (_, query) = region_stack.pop().unwrap();
+					region_path.pop();
+					let buf = submitter.pass(device)?;
 					if let Some(debug) = device.debug_utils_ext() {
-						debug.cmd_end_debug_utils_label(submitter.pass(device)?);
+						debug.cmd_end_debug_utils_label(buf);
+					}
+					if let Some(query) = query {
+						submitter.end_timestamp(device, query);
 					}
 				},
 				FrameEvent::Pass(pass) => {
@@ -254,6 +254,25 @@ impl ImageUsage<'_> {
 		Self::d2(format, [ImageUsageType::ColorAttachmentWrite])
 	}
 
+	pub fn depth_attachment() -> ImageUsageArray<1> {
+		Self::depth(Self::d2(
+			vk::Format::UNDEFINED,
+			[ImageUsageType::DepthStencilAttachmentWrite],
+		))
+	}
+
+	pub fn sampled_depth_2d(shader: Shader) -> ImageUsageArray<1> { Self::depth(Self::sampled_2d(shader)) }
+
+	fn depth<const N: usize>(usage: ImageUsageArray<N>) -> ImageUsageArray<N> {
+		ImageUsageArray {
+			subresource: Subresource {
+				aspect: vk::ImageAspectFlags::DEPTH,
+				..usage.subresource
+			},
+			..usage
+		}
+	}
+
 	pub fn no_view<const N: usize>(usages: [ImageUsageType; N]) -> ImageUsageArray<N> {
 		ImageUsageArray {
 			format: vk::Format::UNDEFINED,
@@ -24,6 +24,8 @@ pub mod util;
 pub enum Error {
 	Message(String),
 	Vulkan(ash::vk::Result),
+	/// The GPU hung or was reset. The device can't be used anymore, and has to be recreated.
+	DeviceLost,
 }
 
 impl std::error::Error for Error {}
@@ -33,6 +35,7 @@ impl Display for Error {
 		match self {
 			Error::Message(msg) => write!(f, "{}", msg),
 			Error::Vulkan(res) => write!(f, "Vulkan error: {}", res),
+			Error::DeviceLost => write!(f, "GPU device lost"),
 		}
 	}
 }
@@ -46,7 +49,12 @@ impl From<String> for Error {
 }
 
 impl From<ash::vk::Result> for Error {
-	fn from(result: ash::vk::Result) -> Self { Error::Vulkan(result) }
+	fn from(result: ash::vk::Result) -> Self {
+		match result {
+			ash::vk::Result::ERROR_DEVICE_LOST => Error::DeviceLost,
+			x => Error::Vulkan(x),
+		}
+	}
 }
 
 impl From<Error> for io::Error {
@@ -302,6 +302,8 @@ pub struct Image {
 
 impl Image {
 	pub fn desc(&self) -> graph::ImageDesc { self.desc }
+
+	pub fn size(&self) -> u64 { self.alloc.size() }
 }
 
 impl Resource for Image {
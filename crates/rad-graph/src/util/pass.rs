@@ -172,6 +172,38 @@ impl<'frame, 'graph> PassContext<'frame, 'graph> {
 		}
 	}
 
+	pub fn copy_image_to_buffer(
+		&mut self, src: Res<ImageView>, dst: Res<BufferHandle>, dst_offset: usize, copy: ImageCopy,
+	) {
+		let src = self.get(src);
+		let dst = self.get(dst);
+		unsafe {
+			assert!(
+				copy.subresource.mip_count == 1 || copy.subresource.mip_count == vk::REMAINING_MIP_LEVELS,
+				"Only one mip can be copied in a single command"
+			);
+			self.device.device().cmd_copy_image_to_buffer2(
+				self.buf,
+				&vk::CopyImageToBufferInfo2::default()
+					.src_image(src.image)
+					.src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+					.dst_buffer(dst.buffer)
+					.regions(&[vk::BufferImageCopy2::default()
+						.buffer_offset(dst_offset as _)
+						.buffer_row_length(copy.row_stride)
+						.buffer_image_height(copy.plane_stride)
+						.image_subresource(vk::ImageSubresourceLayers {
+							aspect_mask: copy.subresource.aspect,
+							mip_level: copy.subresource.first_mip,
+							base_array_layer: copy.subresource.first_layer,
+							layer_count: copy.subresource.layer_count,
+						})
+						.image_offset(copy.offset)
+						.image_extent(copy.extent)]),
+			);
+		}
+	}
+
 	pub fn write(&mut self, res: Res<BufferHandle>, offset: usize, data: &[impl NoUninit]) {
 		debug_assert!(
 			matches!(self.desc(res).loc, BufferLoc::Upload | BufferLoc::Staging),
@@ -399,4 +431,14 @@ impl RenderPass<'_, '_, '_> {
 				.cmd_draw(self.pass.buf, vertices, instances, first_vertex, first_instance);
 		}
 	}
+
+	pub fn draw_indirect(&mut self, buf: Res<BufferHandle>, offset: usize) {
+		unsafe {
+			let buf = self.pass.get(buf).buffer;
+			self.pass
+				.device
+				.device()
+				.cmd_draw_indirect(self.pass.buf, buf, offset as _, 1, 0);
+		}
+	}
 }
@@ -1,12 +1,46 @@
 use ash::vk;
 
-pub fn reverse_depth() -> vk::PipelineDepthStencilStateCreateInfo<'static> {
-	vk::PipelineDepthStencilStateCreateInfo::default()
-		.depth_test_enable(true)
-		.depth_write_enable(true)
-		.depth_compare_op(vk::CompareOp::GREATER)
+/// How view depth maps to `[0, 1]` depth.
+///
+/// Reverse-Z is the default, and is recommended: floats are most precise close to 0, which reverse-Z puts at the far
+/// plane where the perspective divide leaves the least precision. Standard depth is for integrations and tools that
+/// expect it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DepthConvention {
+	/// The near plane is at 1, and infinity at 0.
+	#[default]
+	Reverse,
+	/// The near plane is at 0, and infinity at 1.
+	Standard,
 }
 
+impl DepthConvention {
+	/// The compare op that passes fragments closer than the stored depth.
+	pub fn compare_op(self) -> vk::CompareOp {
+		match self {
+			Self::Reverse => vk::CompareOp::GREATER,
+			Self::Standard => vk::CompareOp::LESS,
+		}
+	}
+
+	/// The depth of nothing, which depth attachments are cleared to.
+	pub fn clear_value(self) -> f32 {
+		match self {
+			Self::Reverse => 0.0,
+			Self::Standard => 1.0,
+		}
+	}
+
+	pub fn depth_state(self) -> vk::PipelineDepthStencilStateCreateInfo<'static> {
+		vk::PipelineDepthStencilStateCreateInfo::default()
+			.depth_test_enable(true)
+			.depth_write_enable(true)
+			.depth_compare_op(self.compare_op())
+	}
+}
+
+pub fn reverse_depth() -> vk::PipelineDepthStencilStateCreateInfo<'static> { DepthConvention::Reverse.depth_state() }
+
 pub fn no_cull() -> vk::PipelineRasterizationStateCreateInfo<'static> {
 	vk::PipelineRasterizationStateCreateInfo::default()
 		.polygon_mode(vk::PolygonMode::FILL)
@@ -5,13 +5,14 @@ use bincode::{Decode, Encode};
 // use nvtt_rs::{CompressionOptions, Container, Context, Format, InputFormat, OutputOptions, Surface,
 // CUDA_SUPPORTED};
 use rad_core::{
-	asset::{AssetView, BincodeAsset, CookedAsset, Uuid},
+	asset::{map_dec_err, AssetRead, AssetView, BincodeAsset, CookedAsset, Uuid},
 	uuid,
 	Engine,
 };
 use rad_graph::{
 	cmd::CommandPool,
 	device::{descriptor::ImageId, Device, QueueWait, Transfer},
+	graph::{self, Deletable},
 	resource::{
 		Buffer,
 		BufferDesc,
@@ -42,6 +43,21 @@ impl BincodeAsset for ImageAsset {
 	const UUID: Uuid = uuid!("e68fac6b-41d0-48c5-a5ff-3e6cfe9b53f0");
 }
 
+/// The fields an [`ImageAsset`] starts with, so they can be read without its data.
+#[derive(Encode, Decode)]
+pub struct ImageHeader {
+	#[bincode(with_serde)]
+	pub size: Vec3<u32>,
+	pub format: i32,
+}
+
+impl ImageAsset {
+	/// Read only the size and format of a saved image asset.
+	pub fn read_header(mut from: Box<dyn AssetRead>) -> Result<ImageHeader, io::Error> {
+		bincode::decode_from_std_read(&mut from, bincode::config::standard()).map_err(map_dec_err)
+	}
+}
+
 impl CookedAsset for ImageAsset {
 	type Base = ImageAsset;
 
@@ -259,4 +275,13 @@ impl AssetView for ImageAssetView {
 		// TODO: fix
 		Self::new("image asset", base)
 	}
+
+	fn size(&self) -> u64 { self.image.size() }
+}
+
+impl Deletable for ImageAssetView {
+	fn into_resources(self, out: &mut Vec<graph::Resource>) {
+		self.view.into_resources(out);
+		self.image.into_resources(out);
+	}
 }
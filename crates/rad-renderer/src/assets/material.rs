@@ -1,10 +1,14 @@
-use std::sync::RwLock;
+use std::sync::{
+	atomic::{AtomicU64, Ordering},
+	RwLock,
+};
 
+use ash::vk;
 use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
 use rad_core::{
 	asset::{
-		aref::{ARef, AssetId, LARef},
+		aref::{ARef, AssetId, LARef, UntypedAssetId},
 		AssetView,
 		BincodeAsset,
 	},
@@ -12,15 +16,113 @@ use rad_core::{
 	Engine,
 };
 use rad_graph::{
-	device::descriptor::ImageId,
+	device::{
+		descriptor::{ImageId, SamplerId},
+		Device,
+		SamplerDesc,
+	},
 	resource::{Buffer, BufferDesc, BufferType, GpuPtr, Resource},
 };
 use rad_world::Uuid;
 use tracing::trace_span;
-use vek::{Vec3, Vec4};
+use vek::{Vec2, Vec3, Vec4};
 
 use crate::assets::image::{ImageAsset, ImageAssetView};
 
+/// How the alpha channel of the base color is interpreted.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum AlphaMode {
+	/// Alpha is ignored.
+	#[default]
+	Opaque,
+	/// Fragments with alpha below `alpha_cutoff` are discarded.
+	Mask,
+	/// Alpha is used for blending.
+	Blend,
+}
+
+/// How texture coordinates outside of `0..1` are handled.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum Wrap {
+	#[default]
+	Repeat,
+	MirroredRepeat,
+	Clamp,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum Filter {
+	#[default]
+	Linear,
+	Nearest,
+}
+
+/// The sampler state of a material texture.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug, Encode, Decode)]
+pub struct TextureSampler {
+	pub wrap_u: Wrap,
+	pub wrap_v: Wrap,
+	pub mag: Filter,
+	pub min: Filter,
+}
+
+impl TextureSampler {
+	fn desc(self) -> SamplerDesc {
+		let wrap = |w| match w {
+			Wrap::Repeat => vk::SamplerAddressMode::REPEAT,
+			Wrap::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+			Wrap::Clamp => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+		};
+		let filter = |f| match f {
+			Filter::Linear => vk::Filter::LINEAR,
+			Filter::Nearest => vk::Filter::NEAREST,
+		};
+		SamplerDesc {
+			mag_filter: filter(self.mag),
+			min_filter: filter(self.min),
+			address_mode_u: wrap(self.wrap_u),
+			address_mode_v: wrap(self.wrap_v),
+			..Default::default()
+		}
+	}
+}
+
+/// Which UV set a material texture samples, and how the UVs are transformed before sampling.
+#[derive(Copy, Clone, PartialEq, Debug, Encode, Decode)]
+pub struct TexCoord {
+	/// The UV set, either `0` or `1`.
+	pub set: u32,
+	#[bincode(with_serde)]
+	pub offset: Vec2<f32>,
+	/// Counter-clockwise rotation in radians.
+	pub rotation: f32,
+	#[bincode(with_serde)]
+	pub scale: Vec2<f32>,
+}
+
+impl Default for TexCoord {
+	fn default() -> Self {
+		Self {
+			set: 0,
+			offset: Vec2::zero(),
+			rotation: 0.0,
+			scale: Vec2::one(),
+		}
+	}
+}
+
+impl TexCoord {
+	/// Combine the rotation and scale into a row-major 2x2 matrix, applied before the offset.
+	fn gpu(self) -> GpuTexCoord {
+		let (s, c) = self.rotation.sin_cos();
+		GpuTexCoord {
+			set: self.set,
+			offset: self.offset,
+			mat: Vec4::new(c * self.scale.x, s * self.scale.y, -s * self.scale.x, c * self.scale.y),
+		}
+	}
+}
+
 #[derive(Encode, Decode)]
 pub struct Material {
 	#[bincode(with_serde)]
@@ -33,10 +135,24 @@ pub struct Material {
 	pub roughness_factor: f32,
 	#[bincode(with_serde)]
 	pub normal: Option<AssetId<ImageAsset>>,
+	/// Scales the XY of the normal map, flattening it below 1.
+	pub normal_scale: f32,
 	#[bincode(with_serde)]
 	pub emissive: Option<AssetId<ImageAsset>>,
 	#[bincode(with_serde)]
 	pub emissive_factor: Vec3<f32>,
+	pub alpha_mode: AlphaMode,
+	pub alpha_cutoff: f32,
+	/// Render both faces of every triangle, instead of culling back faces.
+	pub double_sided: bool,
+	pub base_color_sampler: TextureSampler,
+	pub metallic_roughness_sampler: TextureSampler,
+	pub normal_sampler: TextureSampler,
+	pub emissive_sampler: TextureSampler,
+	pub base_color_uv: TexCoord,
+	pub metallic_roughness_uv: TexCoord,
+	pub normal_uv: TexCoord,
+	pub emissive_uv: TexCoord,
 }
 
 impl BincodeAsset for Material {
@@ -52,8 +168,28 @@ pub struct GpuMaterial {
 	metallic_factor: f32,
 	roughness_factor: f32,
 	normal: Option<ImageId>,
+	normal_scale: f32,
 	emissive: Option<ImageId>,
 	emissive_factor: Vec3<f32>,
+	alpha_mode: u32,
+	alpha_cutoff: f32,
+	double_sided: u32,
+	base_color_sampler: Option<SamplerId>,
+	metallic_roughness_sampler: Option<SamplerId>,
+	normal_sampler: Option<SamplerId>,
+	emissive_sampler: Option<SamplerId>,
+	base_color_uv: GpuTexCoord,
+	metallic_roughness_uv: GpuTexCoord,
+	normal_uv: GpuTexCoord,
+	emissive_uv: GpuTexCoord,
+}
+
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct GpuTexCoord {
+	set: u32,
+	offset: Vec2<f32>,
+	mat: Vec4<f32>,
 }
 
 pub struct MaterialView {
@@ -65,6 +201,7 @@ pub struct MaterialView {
 	pub normal: Option<LARef<ImageAssetView>>,
 	pub emissive: Option<LARef<ImageAssetView>>,
 	pub emissive_factor: Vec3<f32>,
+	pub alpha_mode: AlphaMode,
 }
 
 impl MaterialView {
@@ -76,20 +213,35 @@ impl AssetView for MaterialView {
 	type Ctx = MaterialBuffers;
 
 	fn load(ctx: &'static Self::Ctx, base: Self::Base) -> Result<Self, std::io::Error> { Ok(ctx.load(base)) }
+
+	fn dependencies(base: &Self::Base, out: &mut Vec<UntypedAssetId>) {
+		out.extend(
+			[base.base_color, base.metallic_roughness, base.normal, base.emissive]
+				.into_iter()
+				.flatten()
+				.map(|x| x.to_untyped()),
+		);
+	}
 }
 
 impl Drop for MaterialView {
 	fn drop(&mut self) { self.ctx.unload(self); }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 struct BufRef {
 	buf: u32,
 	id: u32,
 }
 
+/// Storage for GPU materials.
+///
+/// Materials are allocated from pages of [`Self::BUFFER_SIZE`] entries, and a new page is created whenever all
+/// existing pages are full. Pages are never reallocated, so material pointers stay valid for the lifetime of the view
+/// and there is no limit on the number of materials other than GPU memory.
 pub struct MaterialBuffers {
 	inner: RwLock<MaterialBuffersInner>,
+	generation: AtomicU64,
 }
 
 struct MaterialBuffersInner {
@@ -106,14 +258,19 @@ impl Default for MaterialBuffers {
 				free: Vec::new(),
 				bump: 0,
 			}),
+			generation: AtomicU64::new(0),
 		}
 	}
 }
 
 impl MaterialBuffers {
-	const BUFFER_SIZE: u64 = 1024;
+	/// The number of materials in each page.
+	pub const BUFFER_SIZE: u64 = 1024;
 	const MATERIAL_SIZE: u64 = std::mem::size_of::<GpuMaterial>() as u64;
 
+	/// Counts every material written, so that renderers that accumulate over frames can tell when one was edited.
+	pub fn generation(&self) -> u64 { self.generation.load(Ordering::Relaxed) }
+
 	fn id(i: &Option<LARef<ImageAssetView>>) -> Option<ImageId> { i.as_ref().map(|i| i.image_id()) }
 
 	fn load(&'static self, mat: Material) -> MaterialView {
@@ -157,6 +314,9 @@ impl MaterialBuffers {
 		let metallic_roughness = mat.metallic_roughness.map(|id| ARef::loaded(id)).transpose().unwrap();
 		let normal = mat.normal.map(|id| ARef::loaded(id)).transpose().unwrap();
 		let emissive = mat.emissive.map(|id| ARef::loaded(id)).transpose().unwrap();
+		// Samplers are cached by the device, so materials with the same sampler state share one.
+		let device: &Device = Engine::get().global();
+		let sampler = |s: TextureSampler| Some(device.sampler(s.desc()));
 
 		unsafe {
 			b.data()
@@ -170,10 +330,23 @@ impl MaterialBuffers {
 					metallic_factor: mat.metallic_factor,
 					roughness_factor: mat.roughness_factor,
 					normal: Self::id(&normal),
+					normal_scale: mat.normal_scale,
 					emissive: Self::id(&emissive),
 					emissive_factor: mat.emissive_factor,
+					alpha_mode: mat.alpha_mode as u32,
+					alpha_cutoff: mat.alpha_cutoff,
+					double_sided: mat.double_sided as u32,
+					base_color_sampler: sampler(mat.base_color_sampler),
+					metallic_roughness_sampler: sampler(mat.metallic_roughness_sampler),
+					normal_sampler: sampler(mat.normal_sampler),
+					emissive_sampler: sampler(mat.emissive_sampler),
+					base_color_uv: mat.base_color_uv.gpu(),
+					metallic_roughness_uv: mat.metallic_roughness_uv.gpu(),
+					normal_uv: mat.normal_uv.gpu(),
+					emissive_uv: mat.emissive_uv.gpu(),
 				});
 		}
+		self.generation.fetch_add(1, Ordering::Relaxed);
 
 		MaterialView {
 			ptr,
@@ -184,11 +357,13 @@ impl MaterialBuffers {
 			normal,
 			emissive,
 			emissive_factor: mat.emissive_factor,
+			alpha_mode: mat.alpha_mode,
 		}
 	}
 
 	fn unload(&self, view: &MaterialView) {
 		let mut inner = self.inner.write().unwrap();
+		debug_assert!(!inner.free.contains(&view.buf), "material slot freed twice");
 		inner.free.push(view.buf);
 	}
 }
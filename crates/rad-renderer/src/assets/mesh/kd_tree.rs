@@ -0,0 +1,90 @@
+use vek::Vec3;
+
+/// A k-d tree over a set of points.
+///
+/// The tree is stored implicitly: every subtree is a contiguous range of `order`, split at its median.
+pub struct KdTree<'a> {
+	points: &'a [Vec3<f32>],
+	order: Vec<u32>,
+	/// The split axis of the subtree whose median is at the same position in `order`.
+	axes: Vec<u8>,
+}
+
+impl<'a> KdTree<'a> {
+	pub fn new(points: &'a [Vec3<f32>]) -> Self {
+		let mut order: Vec<_> = (0..points.len() as u32).collect();
+		let mut axes = vec![0; points.len()];
+		Self::build(points, &mut order, &mut axes);
+		Self { points, order, axes }
+	}
+
+	/// Find the `k` nearest points to point `i`, excluding itself, sorted by squared distance.
+	pub fn nearest(&self, i: u32, k: usize, out: &mut Vec<(f32, u32)>) {
+		out.clear();
+		if k > 0 {
+			self.nearest_inner(0, self.order.len(), self.points[i as usize], i, k, out);
+		}
+	}
+
+	fn build(points: &[Vec3<f32>], order: &mut [u32], axes: &mut [u8]) {
+		if order.len() <= 1 {
+			return;
+		}
+
+		let mut min = Vec3::broadcast(f32::MAX);
+		let mut max = Vec3::broadcast(f32::MIN);
+		for &i in order.iter() {
+			min = Vec3::partial_min(min, points[i as usize]);
+			max = Vec3::partial_max(max, points[i as usize]);
+		}
+		let size = max - min;
+		let axis = if size.x >= size.y && size.x >= size.z {
+			0
+		} else if size.y >= size.z {
+			1
+		} else {
+			2
+		};
+
+		let mid = order.len() / 2;
+		order.select_nth_unstable_by(mid, |&a, &b| {
+			points[a as usize][axis].total_cmp(&points[b as usize][axis])
+		});
+		axes[mid] = axis as u8;
+
+		let (left, right) = order.split_at_mut(mid);
+		let (left_axes, right_axes) = axes.split_at_mut(mid);
+		Self::build(points, left, left_axes);
+		Self::build(points, &mut right[1..], &mut right_axes[1..]);
+	}
+
+	fn nearest_inner(&self, start: usize, end: usize, p: Vec3<f32>, exclude: u32, k: usize, out: &mut Vec<(f32, u32)>) {
+		if start >= end {
+			return;
+		}
+
+		let mid = start + (end - start) / 2;
+		let i = self.order[mid];
+		let q = self.points[i as usize];
+		if i != exclude {
+			let dist = (q - p).magnitude_squared();
+			if out.len() < k || dist < out.last().unwrap().0 {
+				let pos = out.partition_point(|&(x, _)| x <= dist);
+				out.insert(pos, (dist, i));
+				out.truncate(k);
+			}
+		}
+
+		let axis = self.axes[mid] as usize;
+		let diff = p[axis] - q[axis];
+		let (near, far) = if diff < 0.0 {
+			((start, mid), (mid + 1, end))
+		} else {
+			((mid + 1, end), (start, mid))
+		};
+		self.nearest_inner(near.0, near.1, p, exclude, k, out);
+		if out.len() < k || diff * diff < out.last().unwrap().0 {
+			self.nearest_inner(far.0, far.1, p, exclude, k, out);
+		}
+	}
+}
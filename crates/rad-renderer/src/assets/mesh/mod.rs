@@ -1,11 +1,11 @@
-use std::{io, usize};
+use std::{io, time::Instant, usize};
 
 use ash::vk;
 use bincode::{Decode, Encode};
 use bytemuck::{cast_slice, Pod, Zeroable};
 use rad_core::{
 	asset::{
-		aref::{ARef, AssetId, LARef},
+		aref::{ARef, AssetId, LARef, UntypedAssetId},
 		AssetView,
 		BincodeAsset,
 		Uuid,
@@ -16,18 +16,24 @@ use rad_core::{
 use rad_graph::{
 	cmd::CommandPool,
 	device::{Compute, Device, QueueWait},
+	graph::{self, Deletable},
 	resource::{ASDesc, Buffer, BufferDesc, BufferType, Resource, AS},
 	sync::{get_global_barrier, GlobalBarrier, UsageType},
 };
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use static_assertions::const_assert_eq;
-use tracing::trace_span;
-use vek::{Vec2, Vec3};
+use tracing::{debug, field, trace_span, warn};
+use vek::{Aabb, Vec2, Vec3, Vec4};
 
 use crate::{
-	assets::material::{Material, MaterialView},
+	assets::{
+		material::{AlphaMode, Material, MaterialView},
+		mesh::virtual_mesh::MeshletConfig,
+	},
 	util::SliceWriter,
 };
 
+mod kd_tree;
 pub mod virtual_mesh;
 
 #[derive(Pod, Zeroable, Copy, Clone, Default, Encode, Decode)]
@@ -39,17 +45,53 @@ pub struct Vertex {
 	pub normal: Vec3<f32>,
 	#[bincode(with_serde)]
 	pub uv: Vec2<f32>,
+	/// The second UV set, which is the same as `uv` if the source mesh has only one.
+	#[bincode(with_serde)]
+	pub uv1: Vec2<f32>,
+	/// Tangent direction in `xyz` and bitangent sign in `w`, as snorm16.
+	#[bincode(with_serde)]
+	pub tangent: Vec4<i16>,
+	/// Linear RGBA vertex color, as unorm8.
+	#[bincode(with_serde)]
+	pub color: Vec4<u8>,
 }
 pub type GpuVertex = Vertex;
 
-const_assert_eq!(std::mem::size_of::<Vertex>(), 32);
+const_assert_eq!(std::mem::size_of::<Vertex>(), 52);
 const_assert_eq!(std::mem::align_of::<Vertex>(), 4);
 
+impl Vertex {
+	/// Quantize a unit tangent (with the bitangent sign in `w`) to snorm16.
+	pub fn pack_tangent(tangent: Vec4<f32>) -> Vec4<i16> {
+		tangent.map(|x| (x.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+	}
+
+	pub fn unpack_tangent(tangent: Vec4<i16>) -> Vec4<f32> { tangent.map(|x| (x as f32 / i16::MAX as f32).max(-1.0)) }
+}
+
 #[derive(Encode, Decode)]
 pub struct Mesh {
 	pub vertices: Vec<Vertex>,
 	pub indices: Vec<u32>,
+	/// The material of every triangle. Primitives with different materials are imported as separate meshes, so
+	/// meshlets never span more than one material and don't need per-triangle material ranges.
 	pub material: AssetId<Material>,
+	pub meshlet_config: MeshletConfig,
+}
+
+impl Mesh {
+	/// A rough average size of a compacted triangle BLAS per triangle. Drivers don't report sizes without building one.
+	const BLAS_BYTES_PER_TRIANGLE: u64 = 64;
+
+	pub fn tri_count(&self) -> usize { self.indices.len() / 3 }
+
+	pub fn vertex_bytes(&self) -> u64 { (self.vertices.len() * std::mem::size_of::<Vertex>()) as u64 }
+
+	pub fn index_bytes(&self) -> u64 { (self.indices.len() * std::mem::size_of::<u32>()) as u64 }
+
+	/// An estimate of the size of the compacted BLAS of the mesh, excluding the vertex and index buffers it is built
+	/// from.
+	pub fn blas_size_estimate(&self) -> u64 { self.tri_count() as u64 * Self::BLAS_BYTES_PER_TRIANGLE }
 }
 
 impl BincodeAsset for Mesh {
@@ -61,86 +103,253 @@ pub struct RaytracingMeshView {
 	pub as_: AS,
 	pub vertex_count: u32,
 	pub tri_count: u32,
+	/// The object-space bounds of every vertex.
+	pub aabb: Aabb<f32>,
 	pub material: LARef<MaterialView>,
 }
 
-impl AssetView for RaytracingMeshView {
-	type Base = Mesh;
-	type Ctx = ();
+/// The normalized CDF over the object-space areas of every triangle, with `tri_count + 1` entries. Used to sample
+/// emissive meshes proportional to the power of every triangle.
+fn tri_area_cdf(vertices: &[Vertex], indices: &[u32]) -> Vec<f32> {
+	let mut cdf = Vec::with_capacity(indices.len() / 3 + 1);
+	let mut sum = 0.0;
+	cdf.push(0.0);
+	for tri in indices.chunks_exact(3) {
+		let [a, b, c] = [0, 1, 2].map(|i| vertices[tri[i] as usize].position);
+		sum += (b - a).cross(c - a).magnitude() * 0.5;
+		cdf.push(sum);
+	}
 
-	fn load(_: &'static Self::Ctx, m: Self::Base) -> Result<Self, io::Error> {
-		let device: &Device = Engine::get().global();
+	let n = cdf.len() - 1;
+	for (i, x) in cdf.iter_mut().enumerate() {
+		*x = if sum > 0.0 { *x / sum } else { i as f32 / n as f32 };
+	}
+	cdf
+}
+
+/// Meshes with up to this many vertices have 16-bit indices in their raw buffer, instead of 32-bit ones. Shaders pick
+/// the index size from the vertex count in the same way.
+const MAX_NARROW_VERTICES: usize = 1 << 16;
+
+/// A mesh with its buffer uploaded, waiting for its BLAS to be built.
+struct PendingRtMesh {
+	buffer: Buffer,
+	vertex_count: u32,
+	tri_count: u32,
+	index_offset: u64,
+	index_type: vk::IndexType,
+	aabb: Aabb<f32>,
+	material: LARef<MaterialView>,
+}
+
+impl PendingRtMesh {
+	fn new(device: &Device, m: Mesh) -> Result<Self, io::Error> {
 		// TODO: fips.
 		let name = "raytracing mesh";
-		let s = trace_span!("load raytracing mesh", name = name);
+		let s = trace_span!("load", name = name);
 		let _e = s.enter();
 
-		let buffer = {
-			let s = trace_span!("load");
-			let _e = s.enter();
-			let buffer = Buffer::create(
-				device,
-				BufferDesc {
-					name: &format!("{name} raw buffer"),
-					size: (cast_slice::<_, u8>(&m.vertices).len() + cast_slice::<_, u8>(&m.indices).len()) as u64,
-					ty: BufferType::Gpu,
-				},
-			)?;
-			let mut writer = SliceWriter::new(unsafe { buffer.data().as_mut() });
-			writer.write_slice(&m.vertices);
-			writer.write_slice(&m.indices);
-			buffer
+		let area_cdf = tri_area_cdf(&m.vertices, &m.indices);
+		let narrow = m.vertices.len() <= MAX_NARROW_VERTICES;
+		let narrow_indices: Vec<u16> = if narrow {
+			let mut x: Vec<_> = m.indices.iter().map(|&i| i as u16).collect();
+			// Keep the CDF after the indices 4-byte aligned.
+			if x.len() % 2 == 1 {
+				x.push(0);
+			}
+			x
+		} else {
+			Vec::new()
+		};
+		let index_bytes = if narrow {
+			cast_slice::<_, u8>(&narrow_indices)
+		} else {
+			cast_slice::<_, u8>(&m.indices)
 		};
+		let mut aabb = Aabb::new_empty(m.vertices.first().map_or(Vec3::zero(), |v| v.position));
+		for v in m.vertices.iter() {
+			aabb.expand_to_contain_point(v.position);
+		}
+		let buffer = Buffer::create(
+			device,
+			BufferDesc {
+				name: &format!("{name} raw buffer"),
+				size: (cast_slice::<_, u8>(&m.vertices).len()
+					+ index_bytes.len()
+					+ cast_slice::<_, u8>(&area_cdf).len()) as u64,
+				ty: BufferType::Gpu,
+			},
+		)?;
+		let mut writer = SliceWriter::new(unsafe { buffer.data().as_mut() });
+		writer.write_slice(&m.vertices);
+		writer.write_slice(index_bytes);
+		writer.write_slice(&area_cdf);
 
-		let tri_count = m.indices.len() as u32 / 3;
-		unsafe {
-			let mut pool = CommandPool::new(device, device.queue_families().into::<Compute>())?;
-			let qpool = device
-				.device()
-				.create_query_pool(
-					&vk::QueryPoolCreateInfo::default()
-						.query_type(vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR)
-						.query_count(1),
-					None,
-				)
-				.unwrap();
-			let cmd = pool.next(device)?;
-			let old = {
-				let s = trace_span!("build AS");
-				let _e = s.enter();
-				let geo = [vk::AccelerationStructureGeometryKHR::default()
-					.geometry_type(vk::GeometryTypeKHR::TRIANGLES)
-					.geometry(vk::AccelerationStructureGeometryDataKHR {
-						triangles: vk::AccelerationStructureGeometryTrianglesDataKHR::default()
-							.vertex_format(vk::Format::R32G32B32_SFLOAT)
-							.vertex_data(vk::DeviceOrHostAddressConstKHR {
-								device_address: buffer.ptr::<u8>().addr(),
-							})
-							.vertex_stride(std::mem::size_of::<GpuVertex>() as _)
-							.max_vertex(m.indices.len() as u32 - 1)
-							.index_type(vk::IndexType::UINT32)
-							.index_data(vk::DeviceOrHostAddressConstKHR {
-								device_address: buffer.ptr::<u8>().addr()
-									+ cast_slice::<_, u8>(&m.vertices).len() as u64,
-							}),
-					})];
-				let mut info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
-					.ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
-					.flags(
-						vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
-							| vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION,
-					)
-					.mode(vk::BuildAccelerationStructureModeKHR::BUILD)
-					.geometries(&geo);
+		let material = match ARef::loaded(m.material) {
+			Ok(x) => x,
+			Err(e) => {
+				unsafe { buffer.destroy(device) };
+				return Err(e);
+			},
+		};
+		Ok(Self {
+			buffer,
+			vertex_count: m.vertices.len() as _,
+			tri_count: m.indices.len() as u32 / 3,
+			index_offset: cast_slice::<_, u8>(&m.vertices).len() as u64,
+			index_type: if narrow {
+				vk::IndexType::UINT16
+			} else {
+				vk::IndexType::UINT32
+			},
+			aabb,
+			material,
+		})
+	}
+
+	fn geometry(&self) -> [vk::AccelerationStructureGeometryKHR<'static>; 1] {
+		// Masked geometry runs the any-hit shader to reject transparent texels.
+		let flags = if self.material.alpha_mode == AlphaMode::Mask {
+			vk::GeometryFlagsKHR::NO_DUPLICATE_ANY_HIT_INVOCATION
+		} else {
+			vk::GeometryFlagsKHR::OPAQUE
+		};
+		[vk::AccelerationStructureGeometryKHR::default()
+			.geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+			.flags(flags)
+			.geometry(vk::AccelerationStructureGeometryDataKHR {
+				triangles: vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+					.vertex_format(vk::Format::R32G32B32_SFLOAT)
+					.vertex_data(vk::DeviceOrHostAddressConstKHR {
+						device_address: self.buffer.ptr::<u8>().addr(),
+					})
+					.vertex_stride(std::mem::size_of::<GpuVertex>() as _)
+					.max_vertex(self.vertex_count.saturating_sub(1))
+					.index_type(self.index_type)
+					.index_data(vk::DeviceOrHostAddressConstKHR {
+						device_address: self.buffer.ptr::<u8>().addr() + self.index_offset,
+					}),
+			})]
+	}
+}
+
+/// Everything created while building a batch of BLASes, which is freed once the batch is done or has failed.
+#[derive(Default)]
+struct BlasBuild {
+	pool: Option<CommandPool>,
+	query_pool: vk::QueryPool,
+	scratch: Vec<Buffer>,
+	uncompacted: Vec<AS>,
+	compacted: Vec<AS>,
+}
+
+impl BlasBuild {
+	unsafe fn destroy(self, device: &Device) {
+		if let Some(pool) = self.pool {
+			pool.destroy(device);
+		}
+		device.device().destroy_query_pool(self.query_pool, None);
+		for x in self.scratch {
+			x.destroy(device);
+		}
+		for x in self.uncompacted.into_iter().chain(self.compacted) {
+			x.destroy(device);
+		}
+	}
+}
+
+impl RaytracingMeshView {
+	/// Build and compact the BLASes of `meshes` together. All builds are recorded into one command buffer and all
+	/// compactions into another, so a batch only waits for the GPU twice.
+	///
+	/// If the batch fails, everything created for it is freed and each mesh is built again on its own, so that a mesh
+	/// that can't be built only fails itself. The buffers of meshes that still fail are freed.
+	unsafe fn build(device: &Device, meshes: Vec<PendingRtMesh>) -> Vec<Result<Self, io::Error>> {
+		if meshes.is_empty() {
+			return Vec::new();
+		}
+
+		let mut build = BlasBuild::default();
+		if let Err(e) = Self::build_into(device, &meshes, &mut build) {
+			build.destroy(device);
+			if meshes.len() > 1 {
+				warn!(
+					count = meshes.len(),
+					"failed to build BLAS batch, retrying one mesh at a time: {e}"
+				);
+				return meshes.into_iter().flat_map(|m| Self::build(device, vec![m])).collect();
+			}
+			for m in meshes {
+				m.buffer.destroy(device);
+			}
+			return vec![Err(e)];
+		}
+		let as_ = std::mem::take(&mut build.compacted);
+		build.destroy(device);
+
+		meshes
+			.into_iter()
+			.zip(as_)
+			.map(|(m, as_)| {
+				Ok(Self {
+					buffer: m.buffer,
+					as_,
+					vertex_count: m.vertex_count,
+					tri_count: m.tri_count,
+					aabb: m.aabb,
+					material: m.material,
+				})
+			})
+			.collect()
+	}
+
+	/// Build the compacted BLASes of `meshes` into `build.compacted`, keeping everything else that is created in
+	/// `build` to be freed by the caller.
+	unsafe fn build_into(device: &Device, meshes: &[PendingRtMesh], build: &mut BlasBuild) -> Result<(), io::Error> {
+		let name = "raytracing mesh";
+		let count = meshes.len() as u32;
+
+		let pool = build
+			.pool
+			.insert(CommandPool::new(device, device.queue_families().into::<Compute>())?);
+		build.query_pool = device
+			.device()
+			.create_query_pool(
+				&vk::QueryPoolCreateInfo::default()
+					.query_type(vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR)
+					.query_count(count),
+				None,
+			)
+			.unwrap();
+		let qpool = build.query_pool;
+		let cmd = pool.next(device)?;
+		{
+			let s = trace_span!("build AS", count);
+			let _e = s.enter();
+			let geos: Vec<_> = meshes.iter().map(|m| m.geometry()).collect();
+			let mut infos: Vec<_> = geos
+				.iter()
+				.map(|geo| {
+					vk::AccelerationStructureBuildGeometryInfoKHR::default()
+						.ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+						.flags(
+							vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+								| vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION,
+						)
+						.mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+						.geometries(geo)
+				})
+				.collect();
+
+			for (info, m) in infos.iter_mut().zip(meshes.iter()) {
 				let mut sinfo = vk::AccelerationStructureBuildSizesInfoKHR::default();
 				device.as_ext().get_acceleration_structure_build_sizes(
 					vk::AccelerationStructureBuildTypeKHR::DEVICE,
-					&info,
-					&[tri_count],
+					info,
+					&[m.tri_count],
 					&mut sinfo,
 				);
-
-				let old = AS::create(
+				let as_ = AS::create(
 					device,
 					ASDesc {
 						name: &format!("{name} uncompacted AS"),
@@ -149,7 +358,9 @@ impl AssetView for RaytracingMeshView {
 						size: sinfo.acceleration_structure_size,
 					},
 				)?;
-				let scratch = Buffer::create(
+				info.dst_acceleration_structure = as_.handle();
+				build.uncompacted.push(as_);
+				let s = Buffer::create(
 					device,
 					BufferDesc {
 						name: &format!("{name} AS build scratch"),
@@ -157,101 +368,152 @@ impl AssetView for RaytracingMeshView {
 						ty: BufferType::Gpu,
 					},
 				)?;
-				info.dst_acceleration_structure = old.handle();
-				info.scratch_data.device_address = scratch.ptr::<u8>().addr();
-
-				device
-					.device()
-					.begin_command_buffer(
-						cmd,
-						&vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
-					)
-					.unwrap();
-				device.device().cmd_reset_query_pool(cmd, qpool, 0, 1);
-				device.as_ext().cmd_build_acceleration_structures(
-					cmd,
-					&[info],
-					&[&[vk::AccelerationStructureBuildRangeInfoKHR::default()
-						.primitive_count(tri_count)
+				info.scratch_data.device_address = s.ptr::<u8>().addr();
+				build.scratch.push(s);
+			}
+			let ranges: Vec<_> = meshes
+				.iter()
+				.map(|m| {
+					[vk::AccelerationStructureBuildRangeInfoKHR::default()
+						.primitive_count(m.tri_count)
 						.primitive_offset(0)
-						.first_vertex(0)]],
-				);
-				device.device().cmd_pipeline_barrier2(
-					cmd,
-					&vk::DependencyInfo::default().memory_barriers(&[get_global_barrier(&GlobalBarrier {
-						previous_usages: &[UsageType::AccelerationStructureBuildWrite],
-						next_usages: &[UsageType::AccelerationStructureBuildRead],
-					})]),
-				);
-				device.as_ext().cmd_write_acceleration_structures_properties(
-					cmd,
-					&[old.handle()],
-					vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
-					qpool,
-					0,
-				);
-				device.device().end_command_buffer(cmd).unwrap();
-				let sync = device.submit::<Compute>(QueueWait::default(), &[cmd], &[], vk::Fence::null())?;
-				sync.wait(device)?;
-				pool.reset(device)?;
-				scratch.destroy(device);
-
-				old
-			};
-
-			let as_ = {
-				let s = trace_span!("compact AS");
-				let _e = s.enter();
-				let mut size = [0u64];
-				device
-					.device()
-					.get_query_pool_results(
-						qpool,
-						0,
-						&mut size,
-						vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
-					)
-					.unwrap();
-				device.device().destroy_query_pool(qpool, None);
+						.first_vertex(0)]
+				})
+				.collect();
+			let ranges: Vec<_> = ranges.iter().map(|x| &x[..]).collect();
+			let handles: Vec<_> = build.uncompacted.iter().map(|x| x.handle()).collect();
 
-				let as_ = AS::create(
-					device,
-					ASDesc {
-						name: &format!("{name} AS"),
-						flags: vk::AccelerationStructureCreateFlagsKHR::empty(),
-						ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
-						size: size[0],
-					},
-				)?;
-				device
-					.device()
-					.begin_command_buffer(
-						cmd,
-						&vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
-					)
-					.unwrap();
-				device.as_ext().cmd_copy_acceleration_structure(
+			device
+				.device()
+				.begin_command_buffer(
 					cmd,
-					&vk::CopyAccelerationStructureInfoKHR::default()
-						.src(old.handle())
-						.dst(as_.handle())
-						.mode(vk::CopyAccelerationStructureModeKHR::COMPACT),
-				);
-				device.device().end_command_buffer(cmd).unwrap();
-				let sync = device.submit::<Compute>(QueueWait::default(), &[cmd], &[], vk::Fence::null())?;
-				sync.wait(device)?;
-				pool.destroy(device);
-				old.destroy(device);
-				as_
-			};
-
-			Ok(Self {
-				buffer,
-				as_,
-				vertex_count: m.vertices.len() as _,
-				tri_count,
-				material: ARef::loaded(m.material)?,
-			})
+					&vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+				)
+				.unwrap();
+			device.device().cmd_reset_query_pool(cmd, qpool, 0, count);
+			device.as_ext().cmd_build_acceleration_structures(cmd, &infos, &ranges);
+			device.device().cmd_pipeline_barrier2(
+				cmd,
+				&vk::DependencyInfo::default().memory_barriers(&[get_global_barrier(&GlobalBarrier {
+					previous_usages: &[UsageType::AccelerationStructureBuildWrite],
+					next_usages: &[UsageType::AccelerationStructureBuildRead],
+				})]),
+			);
+			device.as_ext().cmd_write_acceleration_structures_properties(
+				cmd,
+				&handles,
+				vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
+				qpool,
+				0,
+			);
+			device.device().end_command_buffer(cmd).unwrap();
+			let sync = device.submit::<Compute>(QueueWait::default(), &[cmd], &[], vk::Fence::null())?;
+			sync.wait(device)?;
+			pool.reset(device)?;
+			for s in build.scratch.drain(..) {
+				s.destroy(device);
+			}
 		}
+
+		let s = trace_span!("compact AS", count, saved_bytes = field::Empty);
+		let _e = s.enter();
+		let mut sizes = vec![0u64; meshes.len()];
+		device
+			.device()
+			.get_query_pool_results(
+				qpool,
+				0,
+				&mut sizes,
+				vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+			)
+			.unwrap();
+		let saved: u64 = build
+			.uncompacted
+			.iter()
+			.zip(sizes.iter())
+			.map(|(o, &s)| o.size().saturating_sub(s))
+			.sum();
+		s.record("saved_bytes", saved);
+
+		for &size in sizes.iter() {
+			build.compacted.push(AS::create(
+				device,
+				ASDesc {
+					name: &format!("{name} AS"),
+					flags: vk::AccelerationStructureCreateFlagsKHR::empty(),
+					ty: vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+					size,
+				},
+			)?);
+		}
+		device
+			.device()
+			.begin_command_buffer(
+				cmd,
+				&vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+			)
+			.unwrap();
+		for (old, new) in build.uncompacted.iter().zip(build.compacted.iter()) {
+			device.as_ext().cmd_copy_acceleration_structure(
+				cmd,
+				&vk::CopyAccelerationStructureInfoKHR::default()
+					.src(old.handle())
+					.dst(new.handle())
+					.mode(vk::CopyAccelerationStructureModeKHR::COMPACT),
+			);
+		}
+		device.device().end_command_buffer(cmd).unwrap();
+		let sync = device.submit::<Compute>(QueueWait::default(), &[cmd], &[], vk::Fence::null())?;
+		sync.wait(device)?;
+		Ok(())
+	}
+}
+
+impl AssetView for RaytracingMeshView {
+	type Base = Mesh;
+	type Ctx = ();
+
+	fn load(ctx: &'static Self::Ctx, m: Self::Base) -> Result<Self, io::Error> {
+		Self::load_many(ctx, vec![m]).pop().unwrap()
+	}
+
+	fn load_many(_: &'static Self::Ctx, bases: Vec<Self::Base>) -> Vec<Result<Self, io::Error>> {
+		let device: &Device = Engine::get().global();
+		let s = trace_span!("load raytracing meshes", count = bases.len());
+		let _e = s.enter();
+		let count = bases.len();
+		let start = Instant::now();
+
+		let prepared: Vec<_> = bases.into_par_iter().map(|m| PendingRtMesh::new(device, m)).collect();
+		// Meshes that failed to upload are left out of the build.
+		let mut out = Vec::with_capacity(prepared.len());
+		let mut indices = Vec::new();
+		let mut pending = Vec::new();
+		for (i, x) in prepared.into_iter().enumerate() {
+			match x {
+				Ok(x) => {
+					indices.push(i);
+					pending.push(x);
+					out.push(None);
+				},
+				Err(e) => out.push(Some(Err(e))),
+			}
+		}
+		for (i, view) in indices.into_iter().zip(unsafe { Self::build(device, pending) }) {
+			out[i] = Some(view);
+		}
+		debug!(count, time = ?start.elapsed(), "loaded raytracing meshes");
+		out.into_iter().map(Option::unwrap).collect()
+	}
+
+	fn size(&self) -> u64 { self.buffer.size() + self.as_.size() }
+
+	fn dependencies(base: &Self::Base, out: &mut Vec<UntypedAssetId>) { out.push(base.material.to_untyped()); }
+}
+
+impl Deletable for RaytracingMeshView {
+	fn into_resources(self, out: &mut Vec<graph::Resource>) {
+		self.as_.into_resources(out);
+		self.buffer.into_resources(out);
 	}
 }
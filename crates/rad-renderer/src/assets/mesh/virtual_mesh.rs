@@ -1,12 +1,22 @@
-use std::{array, collections::BTreeMap, io, ops::Range};
+use std::{
+	array,
+	cmp::Reverse,
+	collections::{BTreeMap, BTreeSet},
+	io,
+	ops::Range,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+};
 
 use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
 use meshopt::{VertexDataAdapter, VertexStream};
-use metis::Graph;
+use metis::{option::Seed, Graph};
 use rad_core::{
 	asset::{
-		aref::{ARef, AssetId, LARef},
+		aref::{ARef, AssetId, LARef, UntypedAssetId},
 		AssetView,
 		BincodeAsset,
 		CookedAsset,
@@ -16,23 +26,119 @@ use rad_core::{
 };
 use rad_graph::{
 	device::Device,
+	graph::{self, Deletable},
 	resource::{Buffer, BufferDesc, BufferType, GpuPtr, Resource},
 };
 use rad_world::Uuid;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use rustc_hash::FxHashMap;
 use static_assertions::const_assert_eq;
-use tracing::{debug_span, field, trace_span};
+use tracing::{debug, debug_span, field, trace_span};
 use vek::{Aabb, Sphere, Vec3, Vec4};
 
 use crate::{
 	assets::{
-		material::{Material, MaterialView},
-		mesh::{GpuVertex, Mesh, Vertex},
+		material::{GpuMaterial, Material, MaterialView},
+		mesh::{kd_tree::KdTree, GpuVertex, Mesh, Vertex},
 	},
 	util::SliceWriter,
 };
 
+/// The largest number of vertices a meshlet can have, limited by the output arrays of the mesh shader.
+pub const MAX_MESHLET_VERTICES: u32 = 128;
+/// The largest number of triangles a meshlet can have, limited by the output arrays of the mesh shader. Mirrored as
+/// `MAX_MESHLET_TRIANGLES` in `asset.slang`.
+pub const MAX_MESHLET_TRIANGLES: u32 = 124;
+/// The vertices drawn per meshlet by non-indexed meshlet draws, three for every possible triangle.
+pub const MESHLET_DRAW_VERTICES: u32 = MAX_MESHLET_TRIANGLES * 3;
+
+/// Parameters for meshlet generation.
+#[derive(Copy, Clone, Debug, PartialEq, Encode, Decode)]
+pub struct MeshletConfig {
+	max_vertices: u32,
+	max_triangles: u32,
+	cone_weight: f32,
+	group_size: u32,
+	locality_neighbors: u32,
+	locality_weight: u32,
+}
+
+impl Default for MeshletConfig {
+	fn default() -> Self {
+		Self {
+			max_vertices: MAX_MESHLET_VERTICES,
+			max_triangles: MAX_MESHLET_TRIANGLES,
+			cone_weight: 0.0,
+			group_size: 8,
+			locality_neighbors: 4,
+			locality_weight: 1,
+		}
+	}
+}
+
+impl MeshletConfig {
+	pub fn new(max_vertices: u32, max_triangles: u32, cone_weight: f32) -> Result<Self, io::Error> {
+		if max_vertices < 3 || max_vertices > MAX_MESHLET_VERTICES {
+			return Err(io::Error::other(format!(
+				"meshlet vertex limit must be in 3..={MAX_MESHLET_VERTICES}, got {max_vertices}"
+			)));
+		}
+		// meshoptimizer requires the triangle limit to be a multiple of 4.
+		if max_triangles < 4 || max_triangles > MAX_MESHLET_TRIANGLES || max_triangles % 4 != 0 {
+			return Err(io::Error::other(format!(
+				"meshlet triangle limit must be a multiple of 4 in 4..={MAX_MESHLET_TRIANGLES}, got {max_triangles}"
+			)));
+		}
+		if !(0.0..=1.0).contains(&cone_weight) {
+			return Err(io::Error::other(format!(
+				"meshlet cone weight must be in 0..=1, got {cone_weight}"
+			)));
+		}
+
+		Ok(Self {
+			max_vertices,
+			max_triangles,
+			cone_weight,
+			..Default::default()
+		})
+	}
+
+	/// Configure how meshlets are grouped for simplification.
+	///
+	/// `group_size` is the target number of meshlets per group. Each meshlet is additionally linked to its
+	/// `locality_neighbors` spatially nearest meshlets, with a weight of `locality_weight` shared edges, so that
+	/// disconnected shells can still be grouped together.
+	pub fn with_grouping(
+		self, group_size: u32, locality_neighbors: u32, locality_weight: u32,
+	) -> Result<Self, io::Error> {
+		// Groups can hold at most 12 meshlets, and METIS does not partition perfectly evenly.
+		if group_size < 2 || group_size > 8 {
+			return Err(io::Error::other(format!(
+				"meshlet group size must be in 2..=8, got {group_size}"
+			)));
+		}
+
+		Ok(Self {
+			group_size,
+			locality_neighbors,
+			locality_weight,
+			..self
+		})
+	}
+
+	pub fn max_vertices(&self) -> u32 { self.max_vertices }
+
+	pub fn max_triangles(&self) -> u32 { self.max_triangles }
+
+	pub fn cone_weight(&self) -> f32 { self.cone_weight }
+
+	pub fn group_size(&self) -> u32 { self.group_size }
+
+	pub fn locality_neighbors(&self) -> u32 { self.locality_neighbors }
+
+	pub fn locality_weight(&self) -> u32 { self.locality_weight }
+}
+
 #[derive(Copy, Clone, Encode, Decode, Default)]
 pub struct BvhNode {
 	#[bincode(with_serde)]
@@ -64,6 +170,17 @@ pub struct Meshlet {
 	pub error: f32,
 	/// The length of the longest edge in this meshlet.
 	pub max_edge_length: f32,
+	/// The axis of the normal cone, as snorm8.
+	#[bincode(with_serde)]
+	pub cone_axis: Vec3<i8>,
+	/// The cosine of the normal cone cutoff angle, as snorm8. A cutoff of 127 means that the cone is degenerate and
+	/// the meshlet can never be cone culled.
+	pub cone_cutoff: i8,
+	/// The LOD level the meshlet was generated at, where `0` is the source mesh.
+	pub lod: u8,
+	/// The LOD level of the group the meshlet is in, which is never finer than `lod`. The meshlet is only resident
+	/// while this level is streamed in.
+	pub group_lod: u8,
 }
 
 impl Meshlet {
@@ -95,6 +212,21 @@ pub struct VirtualMesh {
 	#[bincode(with_serde)]
 	/// Material of the mesh.
 	pub material: AssetId<Material>,
+	/// The LOD levels that can be streamed in, from finest to coarsest. The vertices and indices of meshlets are
+	/// stored from the coarsest group LOD to the finest, so every level only needs a prefix of them.
+	pub levels: Vec<LodLevel>,
+}
+
+/// A LOD level of a virtual mesh, which is streamed in along with every coarser level.
+#[derive(Copy, Clone, Default, Encode, Decode)]
+pub struct LodLevel {
+	/// The largest parent error of the groups in this level. Groups are only rendered once their parent error is
+	/// perceptible, so this bounds the distance the level is needed at.
+	pub max_error: f32,
+	/// The number of vertices of this and every coarser level.
+	pub vertices: u32,
+	/// The number of indices of this and every coarser level.
+	pub indices: u32,
 }
 
 #[derive(Copy, Clone, Default, Pod, Zeroable)]
@@ -135,12 +267,34 @@ pub struct GpuMeshlet {
 	pub index_byte_offset: u32,
 	pub vertex_count: u8,
 	pub triangle_count: u8,
-	pub _pad: u16,
+	pub lod: u8,
+	pub group_lod: u8,
 	pub max_edge_length: f32,
+	pub cone_axis: Vec3<i8>,
+	pub cone_cutoff: i8,
 }
-const_assert_eq!(std::mem::size_of::<GpuMeshlet>(), 60);
+const_assert_eq!(std::mem::size_of::<GpuMeshlet>(), 64);
 const_assert_eq!(std::mem::align_of::<GpuMeshlet>(), 4);
 
+/// The data shared by every instance of a mesh, stored before the vertices and indices of its resident LOD levels.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuMeshEntry {
+	pub aabb: GpuAabb,
+	/// The BVH and meshlets.
+	pub mesh: GpuPtr<u8>,
+	/// The vertices of the resident LOD levels, which meshlet vertex offsets are relative to.
+	pub vertices: GpuPtr<u8>,
+	/// The indices of the resident LOD levels, which meshlet index offsets are relative to.
+	pub indices: GpuPtr<u8>,
+	pub material: GpuPtr<GpuMaterial>,
+	/// The finest resident LOD level.
+	pub resident_lod: u32,
+	pub _pad: u32,
+}
+const_assert_eq!(std::mem::size_of::<GpuMeshEntry>(), 64);
+const_assert_eq!(std::mem::align_of::<GpuMeshEntry>(), 8);
+
 pub(super) fn map_sphere(sphere: Sphere<f32, f32>) -> Vec4<f32> { sphere.center.with_w(sphere.radius) }
 
 impl BincodeAsset for VirtualMesh {
@@ -152,7 +306,36 @@ impl BincodeAsset for VirtualMesh {
 impl CookedAsset for VirtualMesh {
 	type Base = Mesh;
 
-	fn cook(mesh: &Self::Base) -> Self {
+	fn cook(mesh: &Self::Base) -> Self { Self::cook_with_progress(mesh, |_| {}, &AtomicBool::new(false)) }
+}
+
+/// Progress of LOD generation, reported after every LOD level.
+#[derive(Copy, Clone, Debug)]
+pub struct LodProgress {
+	/// The LOD level that was just generated.
+	pub lod: u32,
+	/// The number of meshlets that will be simplified to generate the next level.
+	pub meshlets: u32,
+	/// The largest simplification error accumulated so far.
+	pub error: f32,
+}
+
+impl VirtualMesh {
+	/// The number of meshlets across all LOD levels.
+	pub fn meshlet_count(&self) -> usize { self.meshlets.len() }
+
+	/// The number of LOD levels, including the source mesh.
+	pub fn lod_count(&self) -> u32 { self.meshlets.iter().map(|m| m.lod as u32 + 1).max().unwrap_or(0) }
+
+	pub fn vertex_bytes(&self) -> u64 { (self.vertices.len() * std::mem::size_of::<GpuVertex>()) as u64 }
+
+	pub fn index_bytes(&self) -> u64 { self.indices.len() as u64 }
+
+	/// Cook a mesh, reporting progress after every LOD level.
+	///
+	/// `cancel` is checked between LOD levels: if set, LOD generation stops and the levels generated so far are
+	/// returned.
+	pub fn cook_with_progress(mesh: &Mesh, progress: impl Fn(LodProgress), cancel: &AtomicBool) -> Self {
 		let (_, remap) = meshopt::generate_vertex_remap_multi(
 			mesh.vertices.len(),
 			&[VertexStream::new_with_stride::<Vec3<f32>, _>(
@@ -163,13 +346,21 @@ impl CookedAsset for VirtualMesh {
 		);
 
 		let mut boundary = vec![false; mesh.vertices.len()];
-		let mut meshlets = generate_meshlets(&mesh.vertices, &mesh.indices, None);
+		let config = mesh.meshlet_config;
+		let mut meshlets = generate_meshlets(&mesh.vertices, &mesh.indices, None, &config);
 
 		let mut bvh = BvhBuilder::default();
 		let mut simplify: Vec<_> = (0..meshlets.meshlets.len() as u32).collect();
 		let mut stuck = Vec::new();
 		let mut lod = 0;
+		let mut error = 0.0f32;
 		while !simplify.is_empty() {
+			if cancel.load(Ordering::Relaxed) {
+				// The remaining meshlets become the coarsest level.
+				stuck.extend(generate_groups(&simplify, &meshlets, &remap, &config));
+				break;
+			}
+
 			let s = debug_span!(
 				"generating lod",
 				lod,
@@ -181,7 +372,7 @@ impl CookedAsset for VirtualMesh {
 			);
 			let _e = s.enter();
 
-			let groups = generate_groups(&simplify, &mut meshlets, &remap);
+			let groups = generate_groups(&simplify, &mut meshlets, &remap, &config);
 			simplify.clear();
 			compute_boundary(&mut boundary, &meshlets, &remap, &groups);
 			s.record("groups", groups.len());
@@ -199,8 +390,12 @@ impl CookedAsset for VirtualMesh {
 					};
 
 					group.parent_error = parent_error;
-					let n_meshlets =
-						generate_meshlets(&mesh.vertices, &indices, Some((group.lod_bounds, parent_error)));
+					let n_meshlets = generate_meshlets(
+						&mesh.vertices,
+						&indices,
+						Some((group.lod_bounds, parent_error)),
+						&config,
+					);
 					let size = group
 						.meshlets()
 						.map(|x| {
@@ -210,6 +405,7 @@ impl CookedAsset for VirtualMesh {
 						.sum();
 					Ok((group, n_meshlets, size))
 				})
+				// `collect` keeps the order of `groups`, so meshlets are added in the same order on every run.
 				.collect();
 
 			let count = par.len();
@@ -224,10 +420,15 @@ impl CookedAsset for VirtualMesh {
 				match x {
 					Ok((group, n_meshlets, size)) => {
 						tris += n_meshlets.tris.len() / 3;
+						error = error.max(group.parent_error);
 						min_size = min_size.min(size);
 						avg_size += size;
 						max_size = max_size.max(size);
-						simplify.extend(meshlets.add(n_meshlets));
+						let added = meshlets.add(n_meshlets);
+						for m in &mut meshlets.meshlets[added.start as usize..added.end as usize] {
+							m.lod = (lod + 1).min(u8::MAX as u32) as u8;
+						}
+						simplify.extend(added);
 						meshlets.groups.push(group);
 					},
 					Err(group) => {
@@ -250,6 +451,11 @@ impl CookedAsset for VirtualMesh {
 			}
 
 			bvh.add_lod(first_group as _, &meshlets.groups);
+			progress(LodProgress {
+				lod,
+				meshlets: simplify.len() as _,
+				error,
+			});
 			lod += 1;
 		}
 
@@ -259,8 +465,17 @@ impl CookedAsset for VirtualMesh {
 			bvh.add_lod(first_group as _, &meshlets.groups);
 		}
 
+		let max_errors = assign_group_lods(&bvh, &mut meshlets);
 		let (bvh, depth) = bvh.build(&mut meshlets);
-		convert_meshlets(mesh, meshlets, bvh, depth)
+		let out = convert_meshlets(mesh, meshlets, bvh, depth, &max_errors);
+		debug!(
+			meshlets = out.meshlet_count(),
+			lods = out.lod_count(),
+			vertex_bytes = out.vertex_bytes(),
+			index_bytes = out.index_bytes(),
+			"cooked virtual mesh"
+		);
+		out
 	}
 }
 
@@ -326,12 +541,20 @@ impl Meshlets {
 	}
 }
 
-fn generate_meshlets(vertices: &[Vertex], indices: &[u32], error: Option<(Sphere<f32, f32>, f32)>) -> Meshlets {
+fn generate_meshlets(
+	vertices: &[Vertex], indices: &[u32], error: Option<(Sphere<f32, f32>, f32)>, config: &MeshletConfig,
+) -> Meshlets {
 	let s = trace_span!("building meshlets");
 	let _e = s.enter();
 
 	let adapter = VertexDataAdapter::new(bytemuck::cast_slice(vertices), std::mem::size_of::<Vertex>(), 0).unwrap();
-	let ms = meshopt::build_meshlets(indices, &adapter, 128, 124, 0.0);
+	let ms = meshopt::build_meshlets(
+		indices,
+		&adapter,
+		config.max_vertices as _,
+		config.max_triangles as _,
+		config.cone_weight,
+	);
 	let meshlets = ms
 		.meshlets
 		.iter()
@@ -342,13 +565,20 @@ fn generate_meshlets(vertices: &[Vertex], indices: &[u32], error: Option<(Sphere
 				&ms.triangles[m.triangle_offset as usize..(m.triangle_offset + m.triangle_count * 3) as usize];
 			let aabb = calc_aabb(m_vertices.iter().map(|&x| &vertices[x as usize]));
 			let bounds = meshopt::compute_meshlet_bounds(ms.get(i), &adapter);
-			let (lod_bounds, error) = error.unwrap_or((
+			let sphere = Sphere {
+				center: Vec3::from(bounds.center),
+				radius: bounds.radius,
+			};
+			// Degenerate meshlets can produce a NaN sphere, which would break culling and LOD selection.
+			let sphere = if sphere.center.map(f32::is_finite).reduce_and() && sphere.radius.is_finite() {
+				sphere
+			} else {
 				Sphere {
-					center: bounds.center.into(),
-					radius: bounds.radius,
-				},
-				0.0,
-			));
+					center: aabb.center(),
+					radius: (aabb.max - aabb.min).magnitude() * 0.5,
+				}
+			};
+			let (lod_bounds, error) = error.unwrap_or((sphere, 0.0));
 			let mut max_edge_length = 0.0f32;
 			for t in m_indices.chunks(3) {
 				for (v1, v2) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
@@ -370,6 +600,10 @@ fn generate_meshlets(vertices: &[Vertex], indices: &[u32], error: Option<(Sphere
 				lod_bounds,
 				error,
 				max_edge_length,
+				cone_axis: bounds.cone_axis_s8.into(),
+				cone_cutoff: bounds.cone_cutoff_s8,
+				lod: 0,
+				group_lod: 0,
 			}
 		})
 		.collect();
@@ -382,12 +616,12 @@ fn generate_meshlets(vertices: &[Vertex], indices: &[u32], error: Option<(Sphere
 	}
 }
 
-fn generate_groups(range: &[u32], meshlets: &Meshlets, remap: &[u32]) -> Vec<MeshletGroup> {
+fn generate_groups(range: &[u32], meshlets: &Meshlets, remap: &[u32], config: &MeshletConfig) -> Vec<MeshletGroup> {
 	let s = trace_span!("grouping meshlets");
 	let _e = s.enter();
 
-	// TODO: locality links
-	let connections = find_connections(range, meshlets, remap);
+	let mut connections = find_connections(range, meshlets, remap);
+	add_locality_links(range, meshlets, &mut connections, config);
 
 	let mut xadj = Vec::with_capacity(range.len() + 1);
 	let mut adj = Vec::new();
@@ -403,10 +637,11 @@ fn generate_groups(range: &[u32], meshlets: &Meshlets, remap: &[u32]) -> Vec<Mes
 	xadj.push(adj.len() as i32);
 
 	let mut group_of = vec![0; range.len()];
-	let group_count = range.len().div_ceil(8);
+	let group_count = range.len().div_ceil(config.group_size as usize);
 	Graph::new(1, group_count as _, &xadj, &adj)
 		.unwrap()
 		.set_adjwgt(&weights)
+		.set_option(Seed(0))
 		.part_kway(&mut group_of)
 		.unwrap();
 
@@ -420,6 +655,10 @@ fn generate_groups(range: &[u32], meshlets: &Meshlets, remap: &[u32]) -> Vec<Mes
 		group.aabb = group.aabb.union(m.aabb);
 		group.lod_bounds = merge_spheres(group.lod_bounds, m.lod_bounds);
 	}
+
+	// Partition ids carry no meaning, so order the groups by their meshlets to keep the output stable.
+	out.retain(|x| x.meshlets().next().is_some());
+	out.sort_unstable_by_key(|x| x.meshlets().min());
 	out
 }
 
@@ -455,8 +694,17 @@ fn simplify_group(
 			std::mem::size_of::<Vertex>() as _,
 			data.as_ptr().add(3),
 			std::mem::size_of::<Vertex>() as _,
-			[norm_weight, norm_weight, norm_weight, uv_weight, uv_weight].as_ptr(),
-			5,
+			[
+				norm_weight,
+				norm_weight,
+				norm_weight,
+				uv_weight,
+				uv_weight,
+				uv_weight,
+				uv_weight,
+			]
+			.as_ptr(),
+			7,
 			locked.as_ptr() as *const _,
 			target,
 			f32::MAX,
@@ -523,31 +771,111 @@ fn find_connections(range: &[u32], meshlets: &Meshlets, remap: &[u32]) -> Vec<Ve
 	connections
 }
 
+// Link each meshlet in `range` to its spatially nearest meshlets that it does not share edges with, so that meshlets
+// on disconnected shells (hair cards, foliage) don't end up in tiny groups.
+// Like `find_connections`, the links are indices into `range`.
+fn add_locality_links(range: &[u32], meshlets: &Meshlets, connections: &mut [Vec<(u32, u32)>], config: &MeshletConfig) {
+	let s = trace_span!("generating locality links");
+	let _e = s.enter();
+
+	if config.locality_neighbors == 0 || config.locality_weight == 0 {
+		return;
+	}
+
+	let centers: Vec<_> = range
+		.iter()
+		.map(|&m| meshlets.meshlets[m as usize].aabb.center())
+		.collect();
+	let tree = KdTree::new(&centers);
+
+	// Links must be symmetric for METIS.
+	let mut links = BTreeSet::new();
+	let mut nearest = Vec::new();
+	for i in 0..range.len() as u32 {
+		tree.nearest(i, config.locality_neighbors as _, &mut nearest);
+		for &(_, j) in nearest.iter() {
+			if !connections[i as usize].iter().any(|&(x, _)| x == j) {
+				links.insert((i.min(j), i.max(j)));
+			}
+		}
+	}
+
+	for (m1, m2) in links {
+		connections[m1 as usize].push((m2, config.locality_weight));
+		connections[m2 as usize].push((m1, config.locality_weight));
+	}
+}
+
+/// Set the group LOD of every meshlet from the LOD level its group was added in. Returns the largest parent error of
+/// the groups in each level.
+fn assign_group_lods(bvh: &BvhBuilder, meshlets: &mut Meshlets) -> Vec<f32> {
+	bvh.lods
+		.iter()
+		.enumerate()
+		.map(|(level, nodes)| {
+			let mut max_error = 0.0f32;
+			// Nodes are added in the same order as groups, so they share indices.
+			for group in &meshlets.groups[nodes.start as usize..nodes.end as usize] {
+				max_error = max_error.max(group.parent_error);
+				for m in group.meshlets() {
+					meshlets.meshlets[m as usize].group_lod = level.min(u8::MAX as usize) as u8;
+				}
+			}
+			max_error
+		})
+		.collect()
+}
+
 fn convert_meshlets(
-	Mesh { vertices, material, .. }: &Mesh, meshlets: Meshlets, bvh: Vec<BvhNode>, bvh_depth: u32,
+	Mesh {
+		vertices,
+		material,
+		meshlet_config,
+		..
+	}: &Mesh, meshlets: Meshlets, bvh: Vec<BvhNode>, bvh_depth: u32,
+	max_errors: &[f32],
 ) -> VirtualMesh {
 	let mut outv = Vec::with_capacity(vertices.len());
-	let mut outi = Vec::with_capacity(meshlets.meshlets.len() * 124 * 3);
-	let meshlets = meshlets
-		.meshlets
-		.into_iter()
-		.map(|m| {
-			let indices: Vec<_> = meshlets.tris[m.tris()].iter().map(|&x| x as u32).collect();
-			let vertices = meshlets.vertex_remap[m.vertices()]
-				.iter()
-				.map(|&x| vertices[x as usize]);
-			let index_offset = outi.len() as u32;
-			let vert_offset = outv.len() as u32;
-			outv.extend(vertices);
-			outi.extend(indices.into_iter().map(|x| x as u8));
-			Meshlet {
-				vert_offset,
-				index_offset,
-				..m
-			}
+	let mut outi = Vec::with_capacity(meshlets.meshlets.len() * meshlet_config.max_triangles as usize * 3);
+	let mut levels: Vec<_> = max_errors
+		.iter()
+		.map(|&max_error| LodLevel {
+			max_error,
+			..Default::default()
 		})
 		.collect();
-	let aabb = calc_aabb(&outv);
+
+	// Meshlets stay in BVH order, but their data is stored from the coarsest group LOD to the finest, so streaming in
+	// a level only has to upload a prefix.
+	let mut order: Vec<_> = (0..meshlets.meshlets.len()).collect();
+	order.sort_by_key(|&i| Reverse(meshlets.meshlets[i].group_lod));
+	let mut out = meshlets.meshlets.clone();
+	for i in order {
+		let m = &mut out[i];
+		m.vert_offset = outv.len() as u32;
+		m.index_offset = outi.len() as u32;
+		outv.extend(
+			meshlets.vertex_remap[meshlets.meshlets[i].vertices()]
+				.iter()
+				.map(|&x| vertices[x as usize]),
+		);
+		outi.extend_from_slice(&meshlets.tris[meshlets.meshlets[i].tris()]);
+		let level = &mut levels[m.group_lod as usize];
+		level.vertices = outv.len() as u32;
+		level.indices = outi.len() as u32;
+	}
+	// Levels without meshlets of their own still need every coarser level.
+	for i in (0..levels.len().saturating_sub(1)).rev() {
+		levels[i].vertices = levels[i].vertices.max(levels[i + 1].vertices);
+		levels[i].indices = levels[i].indices.max(levels[i + 1].indices);
+	}
+	let meshlets = out;
+	// Instance culling tests against the mesh bounds before any meshlet, so they must contain every LOD.
+	let aabb = meshlets.iter().fold(aabb_default(), |a, m| a.union(m.aabb));
+	debug_assert!(
+		meshlets.iter().all(|m| aabb.contains_aabb(m.aabb)),
+		"mesh AABB does not contain all meshlets"
+	);
 
 	VirtualMesh {
 		vertices: outv,
@@ -557,6 +885,7 @@ fn convert_meshlets(
 		bvh_depth,
 		aabb,
 		material: *material,
+		levels,
 	}
 }
 
@@ -867,10 +1196,69 @@ impl BvhBuilder {
 }
 
 pub struct VirtualMeshView {
+	/// The BVH and meshlets of every LOD level, which are always resident.
 	buffer: Buffer,
+	streamed: Mutex<StreamedLods>,
+	lods: LodData,
+	/// The entry of the mesh, without the pointers to the resident LOD levels.
+	entry: GpuMeshEntry,
 	bvh_depth: u32,
 	aabb: Aabb<f32>,
 	material: LARef<MaterialView>,
+	meshlet_byte_offset: u32,
+	finest_byte_offset: u32,
+	finest_count: u32,
+}
+
+/// The LOD levels of a mesh that are streamed in.
+struct StreamedLods {
+	/// The entry of the mesh, followed by the vertices and indices of the resident levels.
+	buffer: Buffer,
+	/// The finest resident level.
+	lod: u32,
+}
+
+/// The vertices and indices of every LOD level, kept to stream levels in when they are needed.
+struct LodData {
+	vertices: Vec<Vertex>,
+	indices: Vec<u8>,
+	levels: Vec<LodLevel>,
+}
+
+impl LodData {
+	/// Upload `entry`, followed by the vertices and indices of `lod` and every coarser level.
+	fn upload(&self, device: &Device, mut entry: GpuMeshEntry, lod: u32) -> Result<Buffer, io::Error> {
+		let s = trace_span!("upload mesh lods", lod);
+		let _e = s.enter();
+
+		let level = self.levels.get(lod as usize).copied().unwrap_or_default();
+		let entry_byte_len = std::mem::size_of::<GpuMeshEntry>() as u64;
+		let vertex_byte_len = level.vertices as u64 * std::mem::size_of::<GpuVertex>() as u64;
+		let buffer = Buffer::create(
+			device,
+			BufferDesc {
+				name: "virtual mesh lods",
+				size: entry_byte_len + vertex_byte_len + level.indices as u64,
+				ty: BufferType::Gpu,
+			},
+		)
+		.map_err(|x| {
+			io::Error::new(
+				io::ErrorKind::Other,
+				format!("failed to create mesh LOD buffer: {:?}", x),
+			)
+		})?;
+
+		let ptr = buffer.ptr::<u8>();
+		entry.vertices = ptr.offset(entry_byte_len);
+		entry.indices = ptr.offset(entry_byte_len + vertex_byte_len);
+		entry.resident_lod = lod;
+		let mut writer = SliceWriter::new(unsafe { buffer.data().as_mut() });
+		writer.write(entry);
+		writer.write_slice(&self.vertices[..level.vertices as usize]);
+		writer.write_slice(&self.indices[..level.indices as usize]);
+		Ok(buffer)
+	}
 }
 
 impl VirtualMeshView {
@@ -880,9 +1268,49 @@ impl VirtualMeshView {
 
 	pub fn gpu_aabb(&self) -> GpuAabb { map_aabb(self.aabb) }
 
+	/// The BVH and meshlets of the mesh, which stay in place while LOD levels are streamed.
 	pub fn gpu_ptr(&self) -> GpuPtr<u8> { self.buffer.ptr() }
 
+	/// The entry shared by every instance of this mesh. It moves whenever LOD levels are streamed, so instances must
+	/// be updated after [`Self::stream`].
+	pub fn entry_ptr(&self) -> GpuPtr<GpuMeshEntry> { self.streamed.lock().unwrap().buffer.ptr() }
+
 	pub fn material(&self) -> &LARef<MaterialView> { &self.material }
+
+	/// The byte offset of the meshlets in the buffer.
+	pub fn meshlet_offset(&self) -> u32 { self.meshlet_byte_offset }
+
+	/// The byte offset in the buffer of the `u32` indices of the full detail meshlets, and their count.
+	pub fn finest_meshlets(&self) -> (u32, u32) { (self.finest_byte_offset, self.finest_count) }
+
+	/// The number of LOD levels that can be streamed.
+	pub fn lod_levels(&self) -> u32 { self.lods.levels.len() as u32 }
+
+	/// The finest resident LOD level. Meshlets of finer levels are never rendered.
+	pub fn resident_lod(&self) -> u32 { self.streamed.lock().unwrap().lod }
+
+	/// The finest LOD level that can be rendered when object space errors below `error` are imperceptible.
+	pub fn lod_for_error(&self, error: f32) -> u32 {
+		let levels = &self.lods.levels;
+		levels
+			.iter()
+			.position(|l| l.max_error >= error)
+			.unwrap_or(levels.len().saturating_sub(1)) as u32
+	}
+
+	/// Make `lod` the finest resident LOD level. If the resident levels changed, returns the buffer of the old ones,
+	/// which must be kept alive until the GPU is done with the old entry.
+	pub fn stream(&self, lod: u32) -> Result<Option<Buffer>, io::Error> {
+		let lod = lod.min(self.lod_levels().saturating_sub(1));
+		let mut streamed = self.streamed.lock().unwrap();
+		if streamed.lod == lod {
+			return Ok(None);
+		}
+
+		let buffer = self.lods.upload(Engine::get().global(), self.entry, lod)?;
+		streamed.lod = lod;
+		Ok(Some(std::mem::replace(&mut streamed.buffer, buffer)))
+	}
 }
 
 impl AssetView for VirtualMeshView {
@@ -901,11 +1329,12 @@ impl AssetView for VirtualMeshView {
 		let bvh_byte_len = (m.bvh.len() * std::mem::size_of::<GpuBvhNode>()) as u64;
 		let meshlet_byte_offset = bvh_byte_offset + bvh_byte_len;
 		let meshlet_byte_len = (m.meshlets.len() * std::mem::size_of::<GpuMeshlet>()) as u64;
-		let vertex_byte_offset = meshlet_byte_offset + meshlet_byte_len;
-		let vertex_byte_len = (m.vertices.len() * std::mem::size_of::<GpuVertex>()) as u64;
-		let index_byte_offset = vertex_byte_offset + vertex_byte_len;
-		let index_byte_len = (m.indices.len() * std::mem::size_of::<u8>()) as u64;
-		let size = index_byte_offset + index_byte_len;
+		// The indices of the full detail meshlets, so they can be drawn without going through the BVH.
+		let finest: Vec<u32> = (0..m.meshlets.len() as u32)
+			.filter(|&i| m.meshlets[i as usize].lod == 0)
+			.collect();
+		let finest_byte_offset = meshlet_byte_offset + meshlet_byte_len;
+		let size = finest_byte_offset + (finest.len() * std::mem::size_of::<u32>()) as u64;
 
 		let buffer = Buffer::create(
 			device,
@@ -934,29 +1363,134 @@ impl AssetView for VirtualMeshView {
 			});
 		}
 
+		// Vertex and index offsets are relative to the resident LOD levels, which are stored separately.
 		for me in m.meshlets.iter() {
 			writer.write(GpuMeshlet {
 				aabb: map_aabb(me.aabb),
 				lod_bounds: map_sphere(me.lod_bounds),
 				error: me.error,
-				vertex_byte_offset: vertex_byte_offset as u32
-					+ (me.vert_offset * std::mem::size_of::<GpuVertex>() as u32),
-				index_byte_offset: index_byte_offset as u32 + (me.index_offset * std::mem::size_of::<u8>() as u32),
+				vertex_byte_offset: me.vert_offset * std::mem::size_of::<GpuVertex>() as u32,
+				index_byte_offset: me.index_offset * std::mem::size_of::<u8>() as u32,
 				vertex_count: me.vert_count,
 				triangle_count: me.tri_count,
-				_pad: 0,
+				lod: me.lod,
+				group_lod: me.group_lod,
 				max_edge_length: me.max_edge_length,
+				cone_axis: me.cone_axis,
+				cone_cutoff: me.cone_cutoff,
 			});
 		}
+		for &i in finest.iter() {
+			writer.write(i);
+		}
 
-		writer.write_slice(&m.vertices);
-		writer.write_slice(&m.indices);
+		let material: LARef<MaterialView> = ARef::loaded(m.material)?;
+		let entry = GpuMeshEntry {
+			aabb: map_aabb(m.aabb),
+			mesh: buffer.ptr(),
+			vertices: GpuPtr::null(),
+			indices: GpuPtr::null(),
+			material: material.gpu_ptr(),
+			resident_lod: 0,
+			_pad: 0,
+		};
+		let lods = LodData {
+			vertices: m.vertices,
+			indices: m.indices,
+			levels: m.levels,
+		};
+		// Only the coarsest level is uploaded at first. Finer levels are streamed in once instances come close enough
+		// to need them.
+		let lod = (lods.levels.len() as u32).saturating_sub(1);
+		let streamed = match lods.upload(device, entry, lod) {
+			Ok(buffer) => StreamedLods { buffer, lod },
+			Err(e) => {
+				unsafe { buffer.destroy(device) };
+				return Err(e);
+			},
+		};
 
 		Ok(Self {
 			buffer,
+			streamed: Mutex::new(streamed),
+			lods,
+			entry,
 			bvh_depth: m.bvh_depth,
 			aabb: m.aabb,
-			material: ARef::loaded(m.material)?,
+			material,
+			meshlet_byte_offset: meshlet_byte_offset as u32,
+			finest_byte_offset: finest_byte_offset as u32,
+			finest_count: finest.len() as u32,
 		})
 	}
+
+	fn size(&self) -> u64 { self.buffer.size() + self.streamed.lock().unwrap().buffer.size() }
+
+	fn dependencies(base: &Self::Base, out: &mut Vec<UntypedAssetId>) { out.push(base.material.to_untyped()); }
+}
+
+impl Deletable for VirtualMeshView {
+	fn into_resources(self, out: &mut Vec<graph::Resource>) {
+		self.buffer.into_resources(out);
+		self.streamed.into_inner().unwrap().buffer.into_resources(out);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use vek::Vec2;
+
+	use super::*;
+
+	/// A bumpy grid of `n` by `n` quads, large enough to generate several LOD levels.
+	fn grid(n: u32) -> Mesh {
+		let vertices = (0..=n)
+			.flat_map(|y| (0..=n).map(move |x| (x, y)))
+			.map(|(x, y)| {
+				let uv = Vec2::new(x as f32, y as f32) / n as f32;
+				Vertex {
+					position: Vec3::new(uv.x, uv.y, (uv.x * 17.0).sin() * (uv.y * 13.0).cos() * 0.05),
+					normal: Vec3::unit_z(),
+					uv,
+					uv1: uv,
+					tangent: Vec4::zero(),
+					color: Vec4::broadcast(255),
+				}
+			})
+			.collect();
+		let indices = (0..n)
+			.flat_map(|y| (0..n).map(move |x| y * (n + 1) + x))
+			.flat_map(|i| [i, i + 1, i + n + 1, i + 1, i + n + 2, i + n + 1])
+			.collect();
+		Mesh {
+			vertices,
+			indices,
+			material: AssetId::new(),
+			meshlet_config: MeshletConfig::default(),
+		}
+	}
+
+	#[test]
+	fn deterministic_cook() {
+		let mesh = grid(64);
+		let c = bincode::config::standard();
+		let a = bincode::encode_to_vec(VirtualMesh::cook(&mesh), c).unwrap();
+		let b = bincode::encode_to_vec(VirtualMesh::cook(&mesh), c).unwrap();
+		assert!(a == b, "cooking the same mesh twice gave different bytes");
+	}
+
+	#[test]
+	fn lod_levels_are_prefixes() {
+		let mesh = VirtualMesh::cook(&grid(64));
+		assert!(mesh.levels.len() > 1, "the grid should have several LOD levels");
+		// The finest level needs everything.
+		assert_eq!(mesh.levels[0].vertices as usize, mesh.vertices.len());
+		assert_eq!(mesh.levels[0].indices as usize, mesh.indices.len());
+		for (lod, level) in mesh.levels.iter().enumerate() {
+			for m in mesh.meshlets.iter().filter(|m| m.group_lod as usize >= lod) {
+				assert!(m.vertices().end <= level.vertices as usize);
+				assert!(m.tris().end <= level.indices as usize);
+			}
+		}
+	}
 }
@@ -1,3 +1,24 @@
+use rad_core::Engine;
+use rad_graph::graph::Frame;
+
 pub mod image;
 pub mod material;
 pub mod mesh;
+
+/// Queue the GPU resources of evicted or invalidated asset views for deletion.
+pub fn delete_evicted(frame: &mut Frame) {
+	let engine = Engine::get();
+	for view in engine.take_evicted::<mesh::virtual_mesh::VirtualMeshView>() {
+		frame.delete(view);
+	}
+	for view in engine.take_evicted::<mesh::RaytracingMeshView>() {
+		frame.delete(view);
+	}
+	for view in engine.take_evicted::<image::ImageAssetView>() {
+		frame.delete(view);
+	}
+	// Materials live in shared pages, so dropping the view frees its slot.
+	for view in engine.take_evicted::<material::MaterialView>() {
+		drop(view);
+	}
+}
@@ -1,4 +1,4 @@
-use rad_world::RadComponent;
+use rad_world::{serde::Since, RadComponent};
 
 #[derive(Copy, Clone, PartialEq, RadComponent)]
 #[uuid("34262fdf-3f97-47ab-a42a-a89786d6b2ac")]
@@ -6,6 +6,10 @@ pub struct CameraComponent {
 	/// Vertical FOV in radians.
 	pub fov: f32,
 	pub near: f32,
+	/// The far plane, for effects that need depth to end somewhere. `None` projects to infinity, which is the most
+	/// precise and never clips anything.
+	#[reflect(default, @Since(1))]
+	pub far: Option<f32>,
 }
 
 impl Default for CameraComponent {
@@ -13,6 +17,7 @@ impl Default for CameraComponent {
 		Self {
 			fov: 70f32.to_radians(),
 			near: 0.01,
+			far: None,
 		}
 	}
 }
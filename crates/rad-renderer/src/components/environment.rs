@@ -0,0 +1,59 @@
+use rad_core::asset::aref::AssetId;
+use rad_graph::graph::Frame;
+use rad_world::{bevy_reflect::Reflect, RadComponent, World};
+use vek::Vec3;
+
+use crate::{
+	assets::image::ImageAsset,
+	env::{Environment, EnvironmentMaps},
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Reflect)]
+pub enum EnvironmentType {
+	/// The atmosphere.
+	Sky,
+	/// A constant color.
+	Constant,
+	/// An equirectangular `R32G32B32A32_SFLOAT` image, such as an imported `.hdr` or `.exr`.
+	Map(AssetId<ImageAsset>),
+}
+
+/// How a world is lit beyond its lights. Only the first one in a world is used, and worlds without one use the
+/// default.
+#[derive(Copy, Clone, PartialEq, RadComponent)]
+#[uuid("7d42b8b4-6945-44c5-9d75-cdf39e2b0137")]
+pub struct EnvironmentComponent {
+	/// What rays that escape the scene see.
+	pub ty: EnvironmentType,
+	/// The radiance of `EnvironmentType::Constant`, in linear Rec. 709.
+	pub ambient: Vec3<f32>,
+	/// The direction sunlight travels in, replacing the direction of the directional light.
+	pub sun_dir: Option<Vec3<f32>>,
+	/// In stops, applied on top of automatic exposure.
+	pub exposure_compensation: f32,
+}
+
+impl Default for EnvironmentComponent {
+	fn default() -> Self {
+		Self {
+			ty: EnvironmentType::Sky,
+			ambient: Vec3::broadcast(0.5),
+			sun_dir: None,
+			exposure_compensation: 0.0,
+		}
+	}
+}
+
+impl EnvironmentComponent {
+	/// The environment of `world`.
+	pub fn of(world: &mut World) -> Self { world.query::<&Self>().iter(world).next().copied().unwrap_or_default() }
+
+	/// The environment the path tracer shows, loading its map into `maps`. Maps that fail to load show the sky.
+	pub fn environment<'a>(&self, frame: &mut Frame, maps: &'a mut EnvironmentMaps) -> Environment<'a> {
+		match self.ty {
+			EnvironmentType::Sky => Environment::Sky,
+			EnvironmentType::Constant => Environment::Constant(self.ambient),
+			EnvironmentType::Map(id) => maps.get(frame, id).map_or(Environment::Sky, Environment::Map),
+		}
+	}
+}
@@ -1,13 +1,13 @@
 use rad_world::{bevy_reflect::Reflect, RadComponent};
 use vek::Vec3;
 
-#[derive(Copy, Clone, Reflect)]
+#[derive(Copy, Clone, PartialEq, Eq, Reflect)]
 pub enum LightType {
 	Point,
 	Directional,
 }
 
-#[derive(RadComponent)]
+#[derive(Copy, Clone, PartialEq, RadComponent)]
 #[uuid("69a570e9-032e-4ca0-aa96-92e9cc4a950c")]
 pub struct LightComponent {
 	pub ty: LightType,
@@ -1,18 +1,36 @@
 use rad_core::asset::aref::AssetId;
-use rad_world::RadComponent;
+use rad_world::{serde::Since, RadComponent};
 
-use crate::assets::mesh::Mesh;
+use crate::assets::{material::Material, mesh::Mesh};
 
 #[derive(RadComponent)]
 #[uuid("2a0f8a13-08ac-4bdc-ae62-467e40195445")]
 pub struct MeshComponent {
 	pub(crate) inner: Vec<AssetId<Mesh>>,
+	/// Materials drawn instead of the material of the mesh at the same index. Meshes without one, or past the end,
+	/// keep their own material.
+	#[reflect(default, @Since(1))]
+	pub(crate) materials: Vec<Option<AssetId<Material>>>,
 }
 
 impl MeshComponent {
 	pub fn new(inner: &[AssetId<Mesh>]) -> Self {
 		Self {
 			inner: inner.to_owned(),
+			materials: Vec::new(),
 		}
 	}
+
+	/// Draw the meshes with `materials` instead of their own, so one mesh can be reused with different materials.
+	pub fn with_materials(mut self, materials: &[Option<AssetId<Material>>]) -> Self {
+		self.materials = materials.to_owned();
+		self
+	}
+
+	pub fn meshes(&self) -> &[AssetId<Mesh>] { &self.inner }
+
+	pub fn material_overrides(&self) -> &[Option<AssetId<Material>>] { &self.materials }
+
+	/// The material overriding the material of mesh `i`, if any.
+	pub fn material_override(&self, i: usize) -> Option<AssetId<Material>> { self.materials.get(i).copied().flatten() }
 }
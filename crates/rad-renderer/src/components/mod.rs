@@ -1,3 +1,140 @@
+use rad_core::{asset::aref::AssetId, EngineBuilder};
+use rad_world::WorldBuilderExt;
+
+use crate::assets::{image::ImageAsset, material::Material, mesh::Mesh};
+
 pub mod camera;
+pub mod environment;
 pub mod light;
 pub mod mesh;
+
+/// Register every component, and the types inside them, so that worlds can serialize them.
+pub(crate) fn register(engine: &mut EngineBuilder) {
+	engine.component::<mesh::MeshComponent>();
+	engine.component_dep_type::<Vec<AssetId<Mesh>>>();
+	engine.component_dep_type::<Vec<Option<AssetId<Material>>>>();
+	engine.component_dep_type::<Option<AssetId<Material>>>();
+	engine.component::<light::LightComponent>();
+	engine.component::<camera::CameraComponent>();
+	engine.component_dep_type::<Option<f32>>();
+	engine.component::<camera::PrimaryViewComponent>();
+	engine.component::<environment::EnvironmentComponent>();
+	engine.component_dep_type::<Option<vek::Vec3<f32>>>();
+	engine.component_dep_type::<AssetId<ImageAsset>>();
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{self, Cursor, Read, Write};
+
+	use rad_core::{
+		asset::{Asset, AssetRead, AssetWrite},
+		Engine,
+		Module,
+	};
+	use rad_world::{serde::DoNotSerialize, transform::Transform, RadComponent, World, WorldModule};
+	use vek::{Quaternion, Vec3};
+
+	use super::*;
+	use crate::components::{
+		camera::CameraComponent,
+		light::{LightComponent, LightType},
+		mesh::MeshComponent,
+	};
+
+	struct Buf(Cursor<Vec<u8>>);
+
+	impl Read for Buf {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) }
+	}
+	impl Write for Buf {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.write(buf) }
+
+		fn flush(&mut self) -> io::Result<()> { Ok(()) }
+	}
+	impl AssetRead for Buf {}
+	impl AssetWrite for Buf {}
+
+	fn init() {
+		let mut engine = Engine::builder();
+		WorldModule::init(&mut engine);
+		register(&mut engine);
+		engine.build();
+	}
+
+	#[test]
+	fn world_round_trip() {
+		init();
+
+		let transform = Transform {
+			position: Vec3::new(1.0, 2.0, 3.0),
+			rotation: Quaternion::rotation_y(0.5),
+			scale: Vec3::broadcast(2.0),
+		};
+		let mesh = MeshComponent::new(&[AssetId::new(), AssetId::new()]).with_materials(&[None, Some(AssetId::new())]);
+		let light = LightComponent {
+			ty: LightType::Directional,
+			radiance: Vec3::new(4.0, 5.0, 6.0),
+		};
+		let camera = CameraComponent {
+			fov: 1.0,
+			near: 0.1,
+			far: Some(100.0),
+		};
+
+		let mut world = World::new();
+		let meshes = (mesh.meshes().to_vec(), mesh.material_overrides().to_vec());
+		let a = world.spawn_empty().insert((transform, mesh)).id();
+		let b = world.spawn_empty().insert((light, camera)).id();
+		let skipped = world.spawn_empty().insert(DoNotSerialize).id();
+
+		let mut buf = Buf(Cursor::new(Vec::new()));
+		world.save(&mut buf).unwrap();
+		buf.0.set_position(0);
+		let mut loaded = World::load(Box::new(buf)).unwrap();
+
+		assert_eq!(loaded.get::<Transform>(a), Some(&transform));
+		let m = loaded.get::<MeshComponent>(a).unwrap();
+		assert_eq!((m.meshes(), m.material_overrides()), (&meshes.0[..], &meshes.1[..]));
+		assert_eq!(loaded.get::<Transform>(b), Some(&Transform::identity()));
+		assert!(loaded.get::<LightComponent>(b) == Some(&light));
+		assert!(loaded.get::<CameraComponent>(b) == Some(&camera));
+		assert!(loaded.get_entity(skipped).is_err());
+		// Entities spawned after loading don't reuse the ids of saved ones.
+		let new = loaded.spawn_empty().id();
+		assert!(new != a && new != b);
+	}
+
+	#[test]
+	fn load_unversioned_world() {
+		init();
+
+		// A world saved before it had a format version, when meshes had no material overrides and cameras no far
+		// plane.
+		fn put(buf: &mut Buf, x: impl bincode::Encode) {
+			bincode::encode_into_std_write(x, buf, bincode::config::standard()).unwrap();
+		}
+		let mut buf = Buf(Cursor::new(Vec::new()));
+		// One entity, with index 0 and two components.
+		put(&mut buf, 1u32);
+		put(&mut buf, 0u32);
+		put(&mut buf, 2u32);
+		put(&mut buf, MeshComponent::uuid().as_bytes());
+		put(&mut buf, 0usize);
+		put(&mut buf, CameraComponent::uuid().as_bytes());
+		put(&mut buf, 1.0f32);
+		put(&mut buf, 0.1f32);
+		buf.0.set_position(0);
+		let loaded = World::load(Box::new(buf)).unwrap();
+
+		let en = loaded.iter_entities().next().unwrap().id();
+		let m = loaded.get::<MeshComponent>(en).unwrap();
+		assert!(m.meshes().is_empty() && m.material_overrides().is_empty());
+		let camera = CameraComponent {
+			fov: 1.0,
+			near: 0.1,
+			far: None,
+		};
+		assert!(loaded.get::<CameraComponent>(en) == Some(&camera));
+	}
+}
@@ -1,16 +1,25 @@
 use ash::vk;
 use bytemuck::NoUninit;
 use rad_graph::{
-	device::{Device, ShaderInfo},
-	graph::{BufferDesc, BufferUsage, Frame, ImageDesc, ImageUsage, PassContext, Res, Shader},
+	device::{
+		descriptor::{ImageId, SamplerId, StorageImageId},
+		Device,
+		ShaderInfo,
+	},
+	graph::{BufferDesc, BufferUsage, Frame, ImageDesc, ImageUsage, PassContext, Persist, Res, Shader},
 	resource::{BufferHandle, GpuPtr, ImageView},
-	util::render::FullscreenPass,
+	util::{compute::ComputePass, render::FullscreenPass},
 	Result,
 };
+use vek::{Lerp, Vec3};
 
 use crate::{
+	assets::material::GpuMaterial,
+	env::EnvironmentMap,
 	mesh::{GpuVisBufferReaderDebug, RenderOutput},
 	scene::{camera::GpuCamera, virtual_scene::GpuInstance},
+	shadow::{GpuShadows, ShadowOutput},
+	sky::{GpuSkySampler, SkySampler},
 	util::SliceWriter,
 };
 
@@ -18,15 +27,90 @@ use crate::{
 pub enum DebugVis {
 	Triangles,
 	Meshlets,
-	Overdraw(f32),
+	/// The number of times every sample was drawn to, from blue at one to red at the given count and above.
+	Overdraw(u32),
 	HwSw,
-	Normals,
+	/// Normals mapped from `-1..1` to black..white.
+	Normals {
+		space: NormalSpace,
+		/// Show the normals of the triangles themselves, instead of the normal mapped vertex normals.
+		geometric: bool,
+	},
 	Uvs,
 	Error,
 	BaseColor,
 	Roughness,
 	Metallic,
 	Emissive,
+	VertexColors,
+	Lod,
+	/// Linear view depth, mapped from `near..far` to black..white.
+	Depth {
+		near: f32,
+		far: f32,
+		/// Show the HZB mip chain on the right half of the screen.
+		hzb: bool,
+	},
+	/// Base color lit by the sun, with shadows from the cascaded shadow maps, plus emission. Emissive surfaces do not
+	/// light their surroundings, which needs the path tracer.
+	Lit,
+	/// Only the edges between meshlets, colored like `Meshlets`, to see how meshes were partitioned.
+	MeshletBoundaries,
+}
+
+/// What is shown where no geometry was drawn. `DebugVis::Depth` always shows infinite depth instead.
+#[derive(Copy, Clone)]
+pub enum Background {
+	/// In linear Rec. 709.
+	Color(Vec3<f32>),
+	/// From `bottom` looking straight down to `top` looking straight up, in linear Rec. 709.
+	Gradient { top: Vec3<f32>, bottom: Vec3<f32> },
+	/// The atmosphere, scaled so that the sun is white, as the output isn't tonemapped.
+	Sky(SkySampler),
+	/// An environment map, multiplied by `scale`. Created with [`Background::map`].
+	Map { image: ImageId, scale: f32 },
+}
+
+impl Default for Background {
+	fn default() -> Self { Self::Color(Vec3::zero()) }
+}
+
+impl Background {
+	/// `map`, scaled so that its average luminance is middle gray, as the output isn't tonemapped.
+	pub fn map(map: &EnvironmentMap) -> Self {
+		Self::Map {
+			image: map.image_id(),
+			scale: 0.18 / map.average_luminance().max(1e-6),
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct GpuBackground {
+	ty: u32,
+	top: Vec3<f32>,
+	bottom: Vec3<f32>,
+	map: Option<ImageId>,
+	map_scale: f32,
+}
+
+/// The space `DebugVis::Normals` shows normals in.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub enum NormalSpace {
+	#[default]
+	World,
+	/// +X right, +Y forward, and +Z up from the camera.
+	View,
+}
+
+/// Triangle edges drawn over any `DebugVis`.
+#[derive(Copy, Clone)]
+pub struct Wireframe {
+	/// In pixels.
+	pub thickness: f32,
+	/// Linear Rec. 709.
+	pub color: Vec3<f32>,
 }
 
 impl DebugVis {
@@ -38,19 +122,46 @@ impl DebugVis {
 			DebugVis::Meshlets => 1,
 			DebugVis::Overdraw(_) => 2,
 			DebugVis::HwSw => 3,
-			DebugVis::Normals => 4,
+			DebugVis::Normals { .. } => 4,
 			DebugVis::Uvs => 5,
 			DebugVis::Error => 6,
 			DebugVis::BaseColor => 7,
 			DebugVis::Roughness => 8,
 			DebugVis::Metallic => 9,
 			DebugVis::Emissive => 10,
+			DebugVis::VertexColors => 11,
+			DebugVis::Lod => 12,
+			DebugVis::Depth { .. } => 13,
+			DebugVis::Lit => 14,
+			DebugVis::MeshletBoundaries => 15,
 		}
 	}
 }
 
+/// The color of `DebugVis::Overdraw` for `overdraw` with a maximum of `max`, in linear Rec. 709.
+pub fn overdraw_heat(overdraw: u32, max: u32) -> Vec3<f32> {
+	if overdraw == 0 {
+		return Vec3::zero();
+	}
+	let x = ((overdraw - 1) as f32 / (max.max(2) - 1) as f32).clamp(0.0, 1.0) * 3.0;
+	let [blue, green, yellow, red] = [
+		Vec3::new(0.0, 0.0, 1.0),
+		Vec3::new(0.0, 1.0, 0.0),
+		Vec3::new(1.0, 1.0, 0.0),
+		Vec3::new(1.0, 0.0, 0.0),
+	];
+	if x < 1.0 {
+		Lerp::lerp(blue, green, x)
+	} else if x < 2.0 {
+		Lerp::lerp(green, yellow, x - 1.0)
+	} else {
+		Lerp::lerp(yellow, red, x - 2.0)
+	}
+}
+
 pub struct DebugMesh {
 	pass: FullscreenPass<PushConstants>,
+	hdr: FullscreenPass<PushConstants>,
 }
 
 #[repr(C)]
@@ -62,39 +173,135 @@ struct PushConstants {
 	highlighted: GpuPtr<u32>,
 	highlight_count: u32,
 	ty: u32,
-	overdraw_scale: f32,
-	pad: u32,
+	highlight_material: GpuPtr<GpuMaterial>,
+	overdraw_max: f32,
+	depth_near: f32,
+	depth_far: f32,
+	hzb: ImageId,
+	hzb_sampler: SamplerId,
+	show_hzb: u32,
+	wire_thickness: f32,
+	wire_color: u32,
+	shadows: GpuPtr<GpuShadows>,
+	view_normals: u32,
+	geometric_normals: u32,
+	background: GpuPtr<GpuBackground>,
 }
 
 impl DebugMesh {
+	/// The format of the images shaded by [`Self::run_lit_hdr`].
+	pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
 	pub fn new(device: &Device) -> Result<Self> {
-		Ok(Self {
-			pass: FullscreenPass::new(
+		let pipeline = |format| {
+			FullscreenPass::new(
 				device,
 				ShaderInfo {
 					shader: "passes.debug.main",
 					spec: &["passes.mesh.debug"],
 				},
-				&[vk::Format::R8G8B8A8_SRGB],
-			)?,
+				&[format],
+			)
+		};
+		Ok(Self {
+			pass: pipeline(vk::Format::R8G8B8A8_SRGB)?,
+			hdr: pipeline(Self::HDR_FORMAT)?,
 		})
 	}
 
-	/// `highlights` must be sorted.
+	/// Highlight the instances in `highlights`, which must be sorted. `shadows` are only used by `DebugVis::Lit`.
 	pub fn run<'pass>(
-		&'pass self, frame: &mut Frame<'pass, '_>, vis: DebugVis, output: RenderOutput,
-		highlights: impl ExactSizeIterator<Item = u32> + 'pass,
+		&'pass self, frame: &mut Frame<'pass, '_>, vis: DebugVis, wireframe: Option<Wireframe>, output: RenderOutput,
+		shadows: Option<ShadowOutput>, background: Background, highlights: impl ExactSizeIterator<Item = u32> + 'pass,
 	) -> Res<ImageView> {
-		let mut pass = frame.pass("debug mesh");
+		self.run_inner(
+			frame,
+			vis,
+			wireframe,
+			output,
+			shadows,
+			background,
+			highlights,
+			GpuPtr::null(),
+			vk::Format::R8G8B8A8_SRGB,
+		)
+	}
+
+	/// Shade `output` like [`DebugVis::Lit`], but into a linear [`Self::HDR_FORMAT`] image that is left to be exposed
+	/// and tonemapped.
+	pub fn run_lit_hdr<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, output: RenderOutput, shadows: Option<ShadowOutput>,
+		background: Background,
+	) -> Res<ImageView> {
+		self.run_inner(
+			frame,
+			DebugVis::Lit,
+			None,
+			output,
+			shadows,
+			background,
+			[].into_iter(),
+			GpuPtr::null(),
+			Self::HDR_FORMAT,
+		)
+	}
+
+	/// Highlight every instance using `material`, which is resolved on the GPU, so nothing has to be enumerated on
+	/// the CPU. Meshes have a single material, so this covers all of their meshlets.
+	pub fn run_highlight_material<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, vis: DebugVis, wireframe: Option<Wireframe>, output: RenderOutput,
+		shadows: Option<ShadowOutput>, background: Background, material: GpuPtr<GpuMaterial>,
+	) -> Res<ImageView> {
+		self.run_inner(
+			frame,
+			vis,
+			wireframe,
+			output,
+			shadows,
+			background,
+			[].into_iter(),
+			material,
+			vk::Format::R8G8B8A8_SRGB,
+		)
+	}
+
+	fn run_inner<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, vis: DebugVis, wireframe: Option<Wireframe>, output: RenderOutput,
+		shadows: Option<ShadowOutput>, background: Background, highlights: impl ExactSizeIterator<Item = u32> + 'pass,
+		material: GpuPtr<GpuMaterial>, format: vk::Format,
+	) -> Res<ImageView> {
+		let mut pass = frame.timed_pass("debug mesh");
 
 		pass.reference(output.instances, BufferUsage::read(Shader::Fragment));
 		pass.reference(output.camera, BufferUsage::read(Shader::Fragment));
 		output.reader.add(&mut pass, Shader::Fragment, true);
+		pass.reference(output.hzb, ImageUsage::sampled_2d(Shader::Fragment));
+		let shadows = shadows.filter(|_| matches!(vis, DebugVis::Lit)).map(|s| {
+			s.add(&mut pass, Shader::Fragment);
+			let buf = pass.resource(
+				BufferDesc::upload(std::mem::size_of::<GpuShadows>() as u64),
+				BufferUsage::read(Shader::Fragment),
+			);
+			(s, buf)
+		});
+		if let Background::Sky(sky) = background {
+			sky.reference(&mut pass, Shader::Fragment);
+		}
+		let background_buf = pass.resource(
+			BufferDesc::upload((std::mem::size_of::<GpuBackground>() + std::mem::size_of::<GpuSkySampler>()) as _),
+			BufferUsage::read(Shader::Fragment),
+		);
 
 		let desc = pass.desc(output.reader.visbuffer);
+		let grid = output.reader.sample_grid;
 		let out = pass.resource(
 			ImageDesc {
-				format: vk::Format::R8G8B8A8_SRGB,
+				size: vk::Extent3D {
+					width: desc.size.width / grid,
+					height: desc.size.height / grid,
+					depth: 1,
+				},
+				format,
 				..desc
 			},
 			ImageUsage::color_attachment(),
@@ -107,13 +314,29 @@ impl DebugMesh {
 			)
 		});
 
-		pass.build(move |ctx| self.execute(ctx, vis, output, highlight_buf, highlights, out));
+		pass.build(move |ctx| {
+			self.execute(
+				ctx,
+				vis,
+				wireframe,
+				output,
+				shadows,
+				(background, background_buf),
+				highlight_buf,
+				highlights,
+				material,
+				format,
+				out,
+			)
+		});
 		out
 	}
 
 	fn execute<'pass>(
-		&'pass self, mut pass: PassContext, vis: DebugVis, output: RenderOutput,
-		highlight_buf: Option<Res<BufferHandle>>, highlights: impl Iterator<Item = u32> + 'pass, out: Res<ImageView>,
+		&'pass self, mut pass: PassContext, vis: DebugVis, wireframe: Option<Wireframe>, output: RenderOutput,
+		shadows: Option<(ShadowOutput, Res<BufferHandle>)>, background: (Background, Res<BufferHandle>),
+		highlight_buf: Option<Res<BufferHandle>>, highlights: impl Iterator<Item = u32> + 'pass,
+		material: GpuPtr<GpuMaterial>, format: vk::Format, out: Res<ImageView>,
 	) {
 		unsafe {
 			let highlight = highlight_buf.map(|x| pass.get(x));
@@ -126,14 +349,71 @@ impl DebugMesh {
 				}
 			}
 
-			let overdraw_scale = match vis {
-				DebugVis::Overdraw(s) => s,
+			let overdraw_max = match vis {
+				DebugVis::Overdraw(max) => max.max(1) as f32,
 				_ => 0.0,
 			};
+			let (view_normals, geometric_normals) = match vis {
+				DebugVis::Normals { space, geometric } => (space == NormalSpace::View, geometric),
+				_ => (false, false),
+			};
+			let (depth_near, depth_far, show_hzb) = match vis {
+				DebugVis::Depth { near, far, hzb } => (near, far, hzb),
+				_ => (0.0, 0.0, false),
+			};
+			let (wire_thickness, wire_color) = wireframe
+				.map(|w| {
+					let c = w.color.map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u32);
+					(w.thickness, c.x | (c.y << 8) | (c.z << 16))
+				})
+				.unwrap_or((0.0, 0));
 			let instances = pass.get(output.instances).ptr();
 			let camera = pass.get(output.camera).ptr();
 			let read = output.reader.get_debug(&mut pass);
-			self.pass.run_one(
+			let hzb = pass.get(output.hzb).id.unwrap();
+			let shadows = shadows
+				.map(|(s, buf)| {
+					let data = s.get(&mut pass);
+					pass.write(buf, 0, &[data]);
+					pass.get(buf).ptr()
+				})
+				.unwrap_or(GpuPtr::null());
+			let (background, background_buf) = background;
+			let mut gpu = GpuBackground {
+				ty: 0,
+				top: Vec3::zero(),
+				bottom: Vec3::zero(),
+				map: None,
+				map_scale: 0.0,
+			};
+			match background {
+				Background::Color(c) => {
+					gpu.top = c;
+					gpu.bottom = c;
+				},
+				Background::Gradient { top, bottom } => {
+					gpu.ty = 1;
+					gpu.top = top;
+					gpu.bottom = bottom;
+				},
+				Background::Sky(sky) => {
+					gpu.ty = 2;
+					let sky = sky.to_gpu(&mut pass);
+					pass.write(background_buf, std::mem::size_of::<GpuBackground>(), &[sky]);
+				},
+				Background::Map { image, scale } => {
+					gpu.ty = 3;
+					gpu.map = Some(image);
+					gpu.map_scale = scale;
+				},
+			}
+			pass.write(background_buf, 0, &[gpu]);
+			let pipeline = if format == Self::HDR_FORMAT {
+				&self.hdr
+			} else {
+				&self.pass
+			};
+			pipeline.run_one(
 				&mut pass,
 				&PushConstants {
 					instances,
@@ -142,13 +422,104 @@ impl DebugMesh {
 					highlighted: highlight.map(|x| x.ptr()).unwrap_or(GpuPtr::null()),
 					highlight_count: count,
 					ty: vis.to_u32(),
-					overdraw_scale,
-					pad: 0,
+					highlight_material: material,
+					overdraw_max,
+					depth_near,
+					depth_far,
+					hzb,
+					hzb_sampler: output.hzb_sampler,
+					show_hzb: show_hzb as _,
+					wire_thickness,
+					wire_color,
+					shadows,
+					view_normals: view_normals as _,
+					geometric_normals: geometric_normals as _,
+					background: pass.get(background_buf).ptr(),
 				},
 				out,
 			);
 		}
 	}
 
+	pub unsafe fn destroy(self) {
+		self.pass.destroy();
+		self.hdr.destroy();
+	}
+}
+
+/// Reads back the most times any sample of the visbuffer was drawn to, to scale `DebugVis::Overdraw` by. Results
+/// arrive `FRAMES_IN_FLIGHT` frames after they are rendered.
+pub struct OverdrawPeak {
+	pass: ComputePass<PeakConstants>,
+	readback: Persist<BufferHandle>,
+	peak: Option<u32>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PeakConstants {
+	peak: GpuPtr<u32>,
+	overdraw: StorageImageId,
+	_pad: u32,
+}
+
+impl OverdrawPeak {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.debug.overdraw_peak",
+					spec: &[],
+				},
+			)?,
+			readback: Persist::new(),
+			peak: None,
+		})
+	}
+
+	/// The last peak read back, if any.
+	pub fn peak(&self) -> Option<u32> { self.peak }
+
+	/// Find the peak overdraw of `output`. Does nothing unless it was rendered with `RenderInfo::debug_info`.
+	pub fn run<'pass>(&'pass mut self, frame: &mut Frame<'pass, '_>, output: RenderOutput) {
+		let Some(debug) = output.reader.debug else {
+			return;
+		};
+		let Self { pass, readback, peak } = self;
+
+		let mut p = frame.pass("read overdraw peak");
+		let size = std::mem::size_of::<u32>();
+		let buf = p.resource(
+			BufferDesc::readback(size as u64, *readback),
+			BufferUsage::transfer_write(),
+		);
+		p.build(move |mut p| {
+			if !p.is_uninit(buf) {
+				*peak = Some(p.readback(buf, 0));
+			}
+			p.fill_buffer(buf, 0, 0, size);
+		});
+
+		let mut p = frame.pass("overdraw peak");
+		p.reference(debug.overdraw, ImageUsage::read_2d(Shader::Compute));
+		p.reference(buf, BufferUsage::write(Shader::Compute));
+		let desc = p.desc(debug.overdraw);
+		p.build(move |mut p| {
+			let push = PeakConstants {
+				peak: p.get(buf).ptr(),
+				overdraw: p.get(debug.overdraw).storage_id.unwrap(),
+				_pad: 0,
+			};
+			pass.dispatch(
+				&mut p,
+				&push,
+				desc.size.width.div_ceil(8),
+				desc.size.height.div_ceil(8),
+				1,
+			);
+		});
+	}
+
 	pub unsafe fn destroy(self) { self.pass.destroy(); }
 }
@@ -0,0 +1,216 @@
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, GraphicsPipelineDesc, ShaderInfo},
+	graph::{BufferDesc, BufferUsage, BufferUsageType, Frame, ImageUsage, ImageUsageType, Res},
+	resource::{GpuPtr, ImageView},
+	sync::Shader,
+	util::{
+		compute::ComputePass,
+		pass::{Attachment, Load},
+		pipeline::{no_blend, no_cull, simple_blend},
+		render::RenderPass,
+	},
+	Result,
+};
+use vek::{Vec2, Vec3, Vec4};
+
+use crate::{
+	mesh::{GpuVisBufferReader, RenderOutput},
+	scene::{camera::GpuCamera, virtual_scene::GpuInstance},
+};
+
+/// The sphere of a meshlet to draw.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MeshletSphere {
+	/// The sphere around the AABB of the meshlet, which frustum and occlusion culling test.
+	Bounding,
+	/// The LOD bounds and error of the group the meshlet was simplified from, which decide whether it is fine enough
+	/// to draw.
+	GroupError,
+	/// The LOD bounds and error of the group the meshlet is in, which decide whether its simplified parents are too
+	/// coarse to draw instead. The coarsest meshlets have no parent, and are skipped.
+	ParentError,
+}
+
+impl MeshletSphere {
+	fn to_u32(self) -> u32 {
+		match self {
+			Self::Bounding => 0,
+			Self::GroupError => 1,
+			Self::ParentError => 2,
+		}
+	}
+}
+
+/// Draw `sphere` for every visible meshlet within `radius` pixels of `center`.
+#[derive(Copy, Clone)]
+pub struct MeshletSpheresInfo {
+	pub sphere: MeshletSphere,
+	/// In pixels.
+	pub center: Vec2<u32>,
+	/// In pixels.
+	pub radius: u32,
+	/// Linear Rec. 709.
+	pub color: Vec3<f32>,
+}
+
+/// The most meshlets selected in a frame. The rest are not drawn.
+const MAX_SPHERES: u32 = 1024;
+/// The line segments of each of the three great circles drawn per sphere.
+const SEGMENTS: u32 = 32;
+
+/// Draws wireframe spheres of meshlets around a point on screen, to debug culling and LOD selection.
+pub struct MeshletSpheres {
+	select: ComputePass<SelectConstants>,
+	draw: RenderPass<DrawConstants>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct SelectConstants {
+	instances: GpuPtr<GpuInstance>,
+	read: GpuVisBufferReader,
+	spheres: GpuPtr<u8>,
+	seen: GpuPtr<u32>,
+	center: Vec2<u32>,
+	radius: u32,
+	ty: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct DrawConstants {
+	instances: GpuPtr<GpuInstance>,
+	camera: GpuPtr<GpuCamera>,
+	spheres: GpuPtr<u8>,
+	color: Vec3<f32>,
+	_pad: u32,
+}
+
+impl MeshletSpheres {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			select: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.meshlet_spheres.select",
+					spec: &[],
+				},
+			)?,
+			draw: RenderPass::new(
+				device,
+				GraphicsPipelineDesc {
+					shaders: &[
+						ShaderInfo {
+							shader: "passes.meshlet_spheres.vertex",
+							..Default::default()
+						},
+						ShaderInfo {
+							shader: "passes.meshlet_spheres.pixel",
+							..Default::default()
+						},
+					],
+					topology: vk::PrimitiveTopology::LINE_LIST,
+					color_attachments: &[vk::Format::R8G8B8A8_SRGB],
+					blend: simple_blend(&[no_blend()]),
+					raster: no_cull(),
+					..Default::default()
+				},
+				true,
+			)?,
+		})
+	}
+
+	/// Draw spheres over `target`, which must be at the resolution of the visbuffer in pixels.
+	pub fn run<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, output: RenderOutput, info: MeshletSpheresInfo,
+		target: Res<ImageView>,
+	) -> Res<ImageView> {
+		let mut pass = frame.pass("clear meshlet spheres");
+		// The draw arguments, followed by the object space sphere and instance of every selected meshlet.
+		let sphere_size = std::mem::size_of::<Vec4<f32>>() + std::mem::size_of::<u32>();
+		let spheres = pass.resource(
+			BufferDesc::gpu((std::mem::size_of::<u32>() * 4 + sphere_size * MAX_SPHERES as usize) as u64),
+			BufferUsage::transfer_write(),
+		);
+		// A bit for every pointer of the render queue, so meshlets covering many pixels are only selected once.
+		let seen_size = (pass.desc(output.reader.queue).size / 256 + 1) * 4;
+		let seen = pass.resource(BufferDesc::gpu(seen_size), BufferUsage::transfer_write());
+		pass.build(move |mut pass| {
+			pass.update_buffer(spheres, 0, &[SEGMENTS * 6, 0, 0, 0]);
+			pass.fill_buffer(seen, 0, 0, seen_size as usize);
+		});
+
+		let mut pass = frame.pass("select meshlet spheres");
+		pass.reference(output.instances, BufferUsage::read(Shader::Compute));
+		output.reader.add(&mut pass, Shader::Compute, false);
+		pass.reference(spheres, BufferUsage::read_write(Shader::Compute));
+		pass.reference(seen, BufferUsage::read_write(Shader::Compute));
+		pass.build(move |mut pass| {
+			let push = SelectConstants {
+				instances: pass.get(output.instances).ptr(),
+				read: output.reader.get(&mut pass),
+				spheres: pass.get(spheres).ptr(),
+				seen: pass.get(seen).ptr(),
+				center: info.center,
+				radius: info.radius,
+				ty: info.sphere.to_u32(),
+			};
+			let size = (info.radius * 2 + 1).div_ceil(8);
+			self.select.dispatch(&mut pass, &push, size, size, 1);
+		});
+
+		let mut pass = frame.pass("draw meshlet spheres");
+		let read = BufferUsage {
+			usages: &[BufferUsageType::ShaderStorageRead(Shader::Vertex)],
+		};
+		pass.reference(output.instances, read);
+		pass.reference(output.camera, read);
+		pass.reference(
+			spheres,
+			BufferUsage {
+				usages: &[
+					BufferUsageType::IndirectBuffer,
+					BufferUsageType::ShaderStorageRead(Shader::Vertex),
+				],
+			},
+		);
+		pass.reference(
+			target,
+			ImageUsage::d2(
+				vk::Format::UNDEFINED,
+				[
+					ImageUsageType::ColorAttachmentRead,
+					ImageUsageType::ColorAttachmentWrite,
+				],
+			),
+		);
+		pass.build(move |mut pass| {
+			let push = DrawConstants {
+				instances: pass.get(output.instances).ptr(),
+				camera: pass.get(output.camera).ptr(),
+				spheres: pass.get(spheres).ptr(),
+				color: info.color,
+				_pad: 0,
+			};
+			let mut rpass = self.draw.start(
+				&mut pass,
+				&push,
+				&[Attachment {
+					image: target,
+					load: Load::Load,
+					store: true,
+				}],
+				None,
+			);
+			rpass.draw_indirect(spheres, 0);
+		});
+		target
+	}
+
+	pub unsafe fn destroy(self) {
+		self.select.destroy();
+		self.draw.destroy();
+	}
+}
@@ -0,0 +1,250 @@
+use std::f32::consts::PI;
+
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_core::{asset::aref::AssetId, Engine};
+use rad_graph::{
+	device::{descriptor::ImageId, Device},
+	graph::{self, Deletable, Frame},
+	resource::{Buffer, BufferDesc, BufferType, GpuPtr},
+	Result,
+};
+use tracing::warn;
+use vek::{Vec2, Vec3, Vec4};
+
+use crate::assets::image::{ImageAsset, ImageAssetView};
+
+/// An equirectangular environment map, along with a 2D CDF over its texels for importance sampling.
+///
+/// The map is Z-up, with `u` going around the horizon and `v` going from the zenith to the nadir.
+pub struct EnvironmentMap {
+	image: ImageAssetView,
+	cdf: Buffer,
+	size: Vec2<u32>,
+	average: f32,
+}
+
+/// Loads the environment map of a world from its image asset, keeping it until a different one is used.
+pub struct EnvironmentMaps {
+	/// The last map asked for, and the map if it loaded. Maps that failed to load aren't retried until a different
+	/// one is asked for.
+	curr: Option<(AssetId<ImageAsset>, Option<EnvironmentMap>)>,
+}
+
+/// What rays that escape the scene see.
+#[derive(Copy, Clone)]
+pub enum Environment<'a> {
+	/// The atmosphere.
+	Sky,
+	Map(&'a EnvironmentMap),
+	/// A constant color, in linear Rec. 709.
+	Constant(Vec3<f32>),
+}
+
+#[derive(Copy, Clone, NoUninit)]
+#[repr(u32)]
+enum GpuEnvironmentType {
+	Sky,
+	Map,
+	Constant,
+}
+
+#[derive(Copy, Clone, NoUninit)]
+#[repr(C)]
+pub struct GpuEnvironment {
+	ty: GpuEnvironmentType,
+	map: Option<ImageId>,
+	cdf: GpuPtr<f32>,
+	size: Vec2<u32>,
+	ambient: Vec3<f32>,
+	_pad: u32,
+}
+
+impl EnvironmentMap {
+	/// Create an environment map from linear Rec. 709 texels.
+	pub fn new(device: &Device, name: &str, size: Vec2<u32>, data: &[Vec4<f32>]) -> Result<Self> {
+		assert_eq!(data.len(), (size.x * size.y) as usize, "environment map size mismatch");
+
+		let cdf = Self::build_cdf(size, data);
+		let average = Self::average_luminance_of(size, data);
+		let cdf_buf = Buffer::create(
+			device,
+			BufferDesc {
+				name: &format!("{name} cdf"),
+				size: (cdf.len() * std::mem::size_of::<f32>()) as _,
+				ty: BufferType::Gpu,
+			},
+		)?;
+		unsafe {
+			cdf_buf
+				.data()
+				.cast::<f32>()
+				.as_ptr()
+				.copy_from_nonoverlapping(cdf.as_ptr(), cdf.len());
+		}
+
+		let image = ImageAssetView::new(
+			name,
+			ImageAsset {
+				size: Vec3::new(size.x, size.y, 1),
+				format: vk::Format::R32G32B32A32_SFLOAT.as_raw(),
+				data: bytemuck::cast_slice(data).to_vec(),
+			},
+		)
+		.map_err(|e| e.to_string())?;
+
+		Ok(Self {
+			image,
+			cdf: cdf_buf,
+			size,
+			average,
+		})
+	}
+
+	/// The marginal CDF over rows (`h + 1` entries), followed by the conditional CDF of every row (`w + 1` entries
+	/// each). Texels are weighted by their luminance and solid angle.
+	fn build_cdf(size: Vec2<u32>, data: &[Vec4<f32>]) -> Vec<f32> {
+		let (w, h) = (size.x as usize, size.y as usize);
+		let mut cdf = vec![0.0; (h + 1) + h * (w + 1)];
+		let (marginal, conditional) = cdf.split_at_mut(h + 1);
+
+		for (y, (row, texels)) in conditional
+			.chunks_exact_mut(w + 1)
+			.zip(data.chunks_exact(w))
+			.enumerate()
+		{
+			let sin = ((y as f32 + 0.5) / h as f32 * PI).sin();
+			for (x, t) in texels.iter().enumerate() {
+				let lum = 0.2126 * t.x + 0.7152 * t.y + 0.0722 * t.z;
+				row[x + 1] = row[x] + lum.max(0.0) * sin;
+			}
+			marginal[y + 1] = marginal[y] + row[w];
+			Self::normalize(row);
+		}
+		Self::normalize(marginal);
+
+		cdf
+	}
+
+	fn average_luminance_of(size: Vec2<u32>, data: &[Vec4<f32>]) -> f32 {
+		let h = size.y as usize;
+		let (mut sum, mut weight) = (0.0, 0.0);
+		for (y, texels) in data.chunks_exact(size.x as usize).enumerate() {
+			let sin = ((y as f32 + 0.5) / h as f32 * PI).sin();
+			for t in texels {
+				sum += (0.2126 * t.x + 0.7152 * t.y + 0.0722 * t.z).max(0.0) * sin;
+				weight += sin;
+			}
+		}
+		if weight > 0.0 {
+			sum / weight
+		} else {
+			0.0
+		}
+	}
+
+	fn normalize(cdf: &mut [f32]) {
+		let n = cdf.len() - 1;
+		let sum = cdf[n];
+		for (i, x) in cdf.iter_mut().enumerate() {
+			*x = if sum > 0.0 { *x / sum } else { i as f32 / n as f32 };
+		}
+	}
+
+	/// Whether an image asset of `size` and `format` can be an environment map.
+	pub fn supports(size: Vec3<u32>, format: i32) -> bool {
+		format == vk::Format::R32G32B32A32_SFLOAT.as_raw() && size.z == 1
+	}
+
+	/// Create an environment map from an `R32G32B32A32_SFLOAT` image asset, such as an imported `.hdr` or `.exr`.
+	pub fn from_asset(device: &Device, name: &str, asset: &ImageAsset) -> Result<Self> {
+		if !Self::supports(asset.size, asset.format) {
+			return Err("environment maps must be 2D `R32G32B32A32_SFLOAT` images"
+				.to_string()
+				.into());
+		}
+
+		let data: Vec<Vec4<f32>> = asset.data.chunks_exact(16).map(bytemuck::pod_read_unaligned).collect();
+		Self::new(device, name, asset.size.xy(), &data)
+	}
+
+	pub fn size(&self) -> Vec2<u32> { self.size }
+
+	pub fn image_id(&self) -> ImageId { self.image.image_id() }
+
+	/// The average luminance over the sphere, in linear Rec. 709.
+	pub fn average_luminance(&self) -> f32 { self.average }
+}
+
+impl EnvironmentMaps {
+	pub fn new() -> Self { Self { curr: None } }
+
+	/// The environment map of `id`, loading it if it isn't the last one asked for.
+	pub fn get(&mut self, frame: &mut Frame, id: AssetId<ImageAsset>) -> Option<&EnvironmentMap> {
+		if self.curr.as_ref().is_none_or(|(curr, _)| *curr != id) {
+			if let Some((_, Some(old))) = self.curr.take() {
+				frame.delete(old);
+			}
+
+			let name = format!("environment map {id}");
+			let map = Engine::get()
+				.load_asset::<ImageAsset>(id)
+				.map_err(|e| rad_graph::Error::Message(e.to_string()))
+				.and_then(|asset| EnvironmentMap::from_asset(frame.device(), &name, &asset));
+			let map = match map {
+				Ok(map) => Some(map),
+				Err(e) => {
+					warn!("failed to load {name}: {e}");
+					None
+				},
+			};
+			self.curr = Some((id, map));
+		}
+
+		self.curr.as_ref().and_then(|(_, map)| map.as_ref())
+	}
+
+	pub unsafe fn destroy(self, device: &Device) {
+		let mut resources = Vec::new();
+		if let Some((_, Some(map))) = self.curr {
+			map.into_resources(&mut resources);
+		}
+		for res in resources {
+			res.destroy(device);
+		}
+	}
+}
+
+impl Deletable for EnvironmentMap {
+	fn into_resources(self, out: &mut Vec<graph::Resource>) {
+		self.image.into_resources(out);
+		self.cdf.into_resources(out);
+	}
+}
+
+impl Environment<'_> {
+	pub fn to_gpu(&self) -> GpuEnvironment {
+		let mut ret = GpuEnvironment {
+			ty: GpuEnvironmentType::Sky,
+			map: None,
+			cdf: GpuPtr::null(),
+			size: Vec2::zero(),
+			ambient: Vec3::zero(),
+			_pad: 0,
+		};
+		match *self {
+			Self::Sky => {},
+			Self::Map(map) => {
+				ret.ty = GpuEnvironmentType::Map;
+				ret.map = Some(map.image.image_id());
+				ret.cdf = map.cdf.ptr();
+				ret.size = map.size;
+			},
+			Self::Constant(color) => {
+				ret.ty = GpuEnvironmentType::Constant;
+				ret.ambient = color;
+			},
+		}
+		ret
+	}
+}
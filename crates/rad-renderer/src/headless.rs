@@ -0,0 +1,358 @@
+//! Rendering worlds to images without a window, for image tests and thumbnails.
+
+use std::time::Duration;
+
+use ash::vk;
+use image::RgbaImage;
+use rad_core::Engine;
+use rad_graph::{
+	arena::Arena,
+	device::Device,
+	graph::{BufferDesc, BufferUsage, Frame, ImageUsage, Persist, RenderGraph, Res, FRAMES_IN_FLIGHT},
+	resource::{BufferHandle, ImageView, Subresource},
+	util::{pass::ImageCopy, pipeline::DepthConvention},
+	Result,
+};
+use rad_world::{
+	bevy_ecs::{entity::Entity, query::With},
+	serde::DoNotSerialize,
+	tick::Tick,
+	World,
+};
+use vek::Vec2;
+
+use crate::{
+	assets::mesh::virtual_mesh::VirtualMeshView,
+	components::{camera::PrimaryViewComponent, environment::EnvironmentComponent},
+	debug::mesh::{Background, DebugMesh},
+	env::EnvironmentMaps,
+	mesh::{self, TransparentPass, VisBuffer},
+	pt::{self, PathTracer},
+	scene::{
+		camera::{Camera, CameraSceneInfo},
+		register_all_gpu_scenes,
+		PendingMeshes,
+		WorldRenderer,
+	},
+	shadow::{CascadedShadows, ShadowInfo},
+	sky::SkyLuts,
+	tonemap::{agx::AgXLook, agx_hdr::AgxHdrTonemap, exposure::ExposureCalc, DisplayTarget, TonemapOutput},
+};
+
+/// Frames rendered before a raster image is captured. Scenes sync a frame after they are first used, and occlusion
+/// culling settles once it has the HZB of the same view.
+const RASTER_WARMUP_FRAMES: u32 = 4;
+
+pub struct HeadlessInfo {
+	/// The view to render from, replacing any primary view in the world.
+	pub camera: Camera,
+	pub size: Vec2<u32>,
+	/// The number of path traced samples per pixel.
+	pub samples: u32,
+	pub max_bounces: u32,
+}
+
+impl Default for HeadlessInfo {
+	fn default() -> Self {
+		Self {
+			camera: Camera::default(),
+			size: Vec2::new(512, 512),
+			samples: 64,
+			max_bounces: pt::RenderInfo::DEFAULT_MAX_BOUNCES,
+		}
+	}
+}
+
+/// Renders worlds to images, with a render graph of its own instead of one driven by a window.
+pub struct HeadlessRenderer {
+	device: Device,
+	graph: RenderGraph,
+	arena: Arena,
+	sky: SkyLuts,
+	pt: PathTracer,
+	env_maps: EnvironmentMaps,
+	visbuffer: VisBuffer,
+	shadows: CascadedShadows,
+	debug: DebugMesh,
+	transparent: TransparentPass,
+	exposure: ExposureCalc,
+	tonemap: AgxHdrTonemap,
+	readback: Persist<BufferHandle>,
+}
+
+impl HeadlessRenderer {
+	pub fn new() -> Result<Self> {
+		let device: &Device = Engine::get().global();
+		Ok(Self {
+			device: device.clone(),
+			graph: RenderGraph::new(device)?,
+			arena: Arena::new(),
+			sky: SkyLuts::new(device)?,
+			pt: PathTracer::new(device)?,
+			env_maps: EnvironmentMaps::new(),
+			visbuffer: VisBuffer::new(device)?,
+			shadows: CascadedShadows::new(device)?,
+			debug: DebugMesh::new(device)?,
+			transparent: TransparentPass::new(device)?,
+			exposure: ExposureCalc::new(device)?,
+			tonemap: AgxHdrTonemap::new(device)?,
+			readback: Persist::new(),
+		})
+	}
+
+	/// Rasterize and shade `world` from `camera`, then expose and tonemap it to an 8-bit sRGB image.
+	///
+	/// Exposure adapts fully within every frame instead of over time. Unlike [`Self::render`], nothing is accumulated
+	/// or randomized, so a fully loaded world always gives the same image for the same camera, which makes it suitable
+	/// for comparing against reference images.
+	pub fn render_to_image(&mut self, mut world: World, camera: Camera, size: Vec2<u32>) -> Result<RgbaImage> {
+		let mut tick = Tick::new();
+		prepare_world(&mut world, &mut tick, camera);
+		let env = EnvironmentComponent::of(&mut world);
+
+		let mut frames = 0;
+		let format = loop {
+			Engine::get().run_staged_loads(Duration::MAX);
+			tick.tick(&mut world);
+			let loading = world
+				.query_filtered::<(), With<PendingMeshes<VirtualMeshView>>>()
+				.iter(&world)
+				.next()
+				.is_some();
+			let done = frames >= RASTER_WARMUP_FRAMES && !loading;
+			frames += 1;
+
+			self.arena.reset();
+			let mut frame = self.graph.frame(&self.device, &self.arena)?;
+			let mut rend = WorldRenderer::new(&mut world, frame.arena());
+			let aspect = size.x as f32 / size.y as f32;
+			rend.set_input(CameraSceneInfo {
+				aspect,
+				jitter: Vec2::zero(),
+				depth: DepthConvention::default(),
+			});
+
+			let visbuffer = self.visbuffer.run(
+				&mut frame,
+				&mut rend,
+				mesh::RenderInfo {
+					size,
+					debug_info: false,
+					samples: vk::SampleCountFlags::TYPE_1,
+					view: None,
+					hzb: mesh::HzbInfo::default(),
+					motion_vectors: false,
+					sw_edge_pixels: mesh::RenderInfo::DEFAULT_SW_EDGE_PIXELS,
+				},
+			);
+			let shadows = self.shadows.run(
+				&mut frame,
+				&mut rend,
+				&ShadowInfo {
+					aspect,
+					..Default::default()
+				},
+			);
+			let hdr = self
+				.debug
+				.run_lit_hdr(&mut frame, visbuffer, shadows, Background::default());
+			let hdr = self.transparent.run(&mut frame, &mut rend, visbuffer, hdr);
+			let (exp, _) = self
+				.exposure
+				.run(&mut frame, hdr, env.exposure_compensation, f32::INFINITY);
+			let img = self.tonemap.run(
+				&mut frame,
+				hdr,
+				exp,
+				AgXLook::default(),
+				DisplayTarget {
+					output: TonemapOutput::Srgb8,
+					white: DisplayTarget::DEFAULT_WHITE,
+				},
+			);
+			let format = done.then(|| copy_to_readback(&mut frame, img, self.readback));
+			drop(rend);
+			frame.run()?;
+			if let Some(format) = format {
+				break format;
+			}
+		};
+
+		let data = self.read_back(size.x as u64 * size.y as u64 * texel_size(format))?;
+		let rgba =
+			to_rgba8(format, &data).ok_or_else(|| format!("cannot read back images with format `{format:?}`"))?;
+		RgbaImage::from_raw(size.x, size.y, rgba).ok_or_else(|| {
+			format!(
+				"read back image of format `{format:?}` does not match its size {}x{}",
+				size.x, size.y
+			)
+			.into()
+		})
+	}
+
+	/// Render `world`, returning the linear HDR image as tightly packed RGBA rows.
+	///
+	/// Frames are rendered until the path tracer has accumulated `info.samples` samples since the scene was last
+	/// updated, so meshes still loading in the background delay the image instead of being missing from it.
+	pub fn render(&mut self, mut world: World, info: &HeadlessInfo) -> Result<Vec<f32>> {
+		let mut tick = Tick::new();
+		prepare_world(&mut world, &mut tick, info.camera);
+		let env = EnvironmentComponent::of(&mut world);
+
+		loop {
+			Engine::get().run_staged_loads(Duration::MAX);
+			tick.tick(&mut world);
+			self.arena.reset();
+			let mut frame = self.graph.frame(&self.device, &self.arena)?;
+			let mut rend = WorldRenderer::new(&mut world, frame.arena());
+			rend.set_input(CameraSceneInfo {
+				aspect: info.size.x as f32 / info.size.y as f32,
+				jitter: Vec2::zero(),
+				depth: DepthConvention::default(),
+			});
+
+			let sky = self.sky.run(&mut frame, &mut rend);
+			let environment = env.environment(&mut frame, &mut self.env_maps);
+			let (raw, samples) = self.pt.run(
+				&mut frame,
+				&mut rend,
+				pt::RenderInfo {
+					sky,
+					env: environment,
+					size: info.size,
+					max_bounces: info.max_bounces,
+					russian_roulette_start_depth: pt::RenderInfo::DEFAULT_RUSSIAN_ROULETTE_START_DEPTH,
+					firefly_clamp: None,
+					aperture: 0.0,
+					focus_distance: 1.0,
+					view: None,
+				},
+			);
+			let done = samples + 1 >= info.samples;
+			if done {
+				copy_to_readback(&mut frame, raw, self.readback);
+			}
+			drop(rend);
+			frame.run()?;
+			if done {
+				break;
+			}
+		}
+
+		let texel = texel_size(vk::Format::R32G32B32A32_SFLOAT);
+		let data = self.read_back(info.size.x as u64 * info.size.y as u64 * texel)?;
+		Ok(bytemuck::pod_collect_to_vec(&data))
+	}
+
+	/// Read `len` bytes of the image copied by [`copy_to_readback`] in the last frame.
+	fn read_back(&mut self, len: u64) -> Result<Vec<u8>> {
+		// Readback buffers are per frame in flight, so the copy is read back once its frame comes around again.
+		for _ in 0..FRAMES_IN_FLIGHT - 1 {
+			self.arena.reset();
+			self.graph.frame(&self.device, &self.arena)?.run()?;
+		}
+		self.arena.reset();
+		let mut out = None;
+		let mut frame = self.graph.frame(&self.device, &self.arena)?;
+		let mut pass = frame.pass("read headless image");
+		let buf = pass.resource(BufferDesc::readback(len, self.readback), BufferUsage::none());
+		pass.build(|mut pass| {
+			if !pass.is_uninit(buf) {
+				let data = unsafe { &pass.get(buf).data.as_ref()[..len as usize] };
+				out = Some(data.to_vec());
+			}
+		});
+		frame.run()?;
+
+		out.ok_or_else(|| "headless image readback buffer was lost".to_string().into())
+	}
+
+	pub unsafe fn destroy(self) {
+		self.pt.destroy();
+		self.env_maps.destroy(&self.device);
+		self.sky.destroy();
+		self.visbuffer.destroy();
+		self.shadows.destroy();
+		self.debug.destroy();
+		self.transparent.destroy();
+		self.exposure.destroy();
+		self.tonemap.destroy();
+		self.graph.destroy(&self.device);
+	}
+}
+
+/// Register the GPU scenes of `world`, and make `camera` its only primary view.
+fn prepare_world(world: &mut World, tick: &mut Tick, camera: Camera) {
+	register_all_gpu_scenes(world, tick);
+	let views: Vec<Entity> = world
+		.query_filtered::<Entity, With<PrimaryViewComponent>>()
+		.iter(world)
+		.collect();
+	for e in views {
+		world.entity_mut(e).remove::<PrimaryViewComponent>();
+	}
+	world
+		.spawn_empty()
+		.insert((camera.transform, camera.camera, PrimaryViewComponent, DoNotSerialize));
+}
+
+/// Copy `img` into the readback buffer, with tightly packed rows. Returns the format of `img`.
+fn copy_to_readback(frame: &mut Frame, img: Res<ImageView>, readback: Persist<BufferHandle>) -> vk::Format {
+	let mut pass = frame.pass("copy headless image");
+	pass.reference(img, ImageUsage::transfer_read());
+	let desc = pass.desc(img);
+	let len = desc.size.width as u64 * desc.size.height as u64 * texel_size(desc.format);
+	let buf = pass.resource(BufferDesc::readback(len, readback), BufferUsage::transfer_write());
+	pass.build(move |mut pass| {
+		pass.copy_image_to_buffer(
+			img,
+			buf,
+			0,
+			ImageCopy {
+				row_stride: 0,
+				plane_stride: 0,
+				subresource: Subresource::default(),
+				offset: vk::Offset3D::default(),
+				extent: desc.size,
+			},
+		);
+	});
+	desc.format
+}
+
+fn texel_size(format: vk::Format) -> u64 {
+	match format {
+		vk::Format::R32G32B32A32_SFLOAT => 16,
+		vk::Format::R16G16B16A16_SFLOAT | vk::Format::R16G16B16A16_UNORM => 8,
+		_ => 4,
+	}
+}
+
+/// Convert tightly packed texels of `format` to 8-bit RGBA.
+fn to_rgba8(format: vk::Format, data: &[u8]) -> Option<Vec<u8>> {
+	match format {
+		vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Some(data.to_vec()),
+		vk::Format::A2B10G10R10_UNORM_PACK32 => Some(
+			bytemuck::pod_collect_to_vec::<u8, u32>(data)
+				.into_iter()
+				.flat_map(|x| {
+					let c = |shift: u32| (((x >> shift) & 0x3ff) * 255 + 511) / 1023;
+					[c(0), c(10), c(20), (x >> 30) * 85].map(|x| x as u8)
+				})
+				.collect(),
+		),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn a2b10g10r10_to_rgba8() {
+		let texel: u32 = 1023 | (512 << 10) | (3 << 30);
+		let rgba = to_rgba8(vk::Format::A2B10G10R10_UNORM_PACK32, bytemuck::bytes_of(&texel)).unwrap();
+		assert_eq!(rgba, [255, 128, 0, 255]);
+	}
+}
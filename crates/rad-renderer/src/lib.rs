@@ -1,17 +1,20 @@
 #![feature(allocator_api)]
 #![feature(let_chains)]
 
-use rad_core::{asset::aref::AssetId, EngineBuilder, Module};
-use rad_world::WorldBuilderExt;
+use rad_core::{EngineBuilder, Module};
 pub use vek;
 
 pub mod assets;
 pub mod components;
 pub mod debug;
+pub mod env;
+pub mod headless;
 pub mod mesh;
 pub mod pt;
 pub mod scene;
+pub mod shadow;
 pub mod sky;
+pub mod taa;
 pub mod tonemap;
 mod util;
 
@@ -29,10 +32,6 @@ impl Module for RendererModule {
 		engine.asset_view::<assets::image::ImageAssetView>();
 		engine.asset_view::<assets::material::MaterialView>();
 
-		engine.component::<components::mesh::MeshComponent>();
-		engine.component_dep_type::<Vec<AssetId<assets::mesh::Mesh>>>();
-		engine.component::<components::light::LightComponent>();
-		engine.component::<components::camera::CameraComponent>();
-		engine.component::<components::camera::PrimaryViewComponent>();
+		components::register(engine);
 	}
 }
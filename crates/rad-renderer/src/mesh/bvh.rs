@@ -1,10 +1,6 @@
 use bytemuck::NoUninit;
 use rad_graph::{
-	device::{
-		descriptor::{ImageId, SamplerId},
-		Device,
-		ShaderInfo,
-	},
+	device::{descriptor::ImageId, Device, ShaderInfo},
 	graph::Frame,
 	resource::GpuPtr,
 	util::compute::ComputePass,
@@ -27,16 +23,14 @@ pub struct BvhCull {
 struct PushConstants {
 	instances: GpuPtr<GpuInstance>,
 	camera: GpuPtr<GpuCamera>,
-	hzb: ImageId,
-	hzb_sampler: SamplerId,
 	queue: GpuPtr<u8>,
 	late: GpuPtr<u8>,
 	meshlet: GpuPtr<u8>,
 	stats: GpuPtr<CullStats>,
 	frame: u64,
+	hzb: ImageId,
 	res: Vec2<u32>,
 	ping: u32,
-	_pad: u32,
 }
 
 impl BvhCull {
@@ -75,16 +69,13 @@ impl BvhCull {
 			let meshlet = resources.output(&mut pass, resources.meshlet_queue);
 			let stats = resources.stats(&mut pass);
 
-			let hzb_sampler = resources.hzb_sampler;
-			// TODO: fix
-			let frame = 0;
+			let frame = resources.scene.frame;
 			let res = resources.res;
 			pass.build(move |mut pass| {
 				let push = PushConstants {
 					instances: pass.get(instances).ptr(),
 					camera: pass.get(camera).ptr(),
 					hzb: pass.get(hzb).id.unwrap(),
-					hzb_sampler,
 					queue: pass.get(queue).ptr(),
 					meshlet: pass.get(meshlet).ptr(),
 					stats: pass.get(stats).ptr(),
@@ -92,7 +83,6 @@ impl BvhCull {
 					frame,
 					res,
 					ping: ping as _,
-					_pad: 0,
 				};
 				self.pass.dispatch_indirect(
 					&mut pass,
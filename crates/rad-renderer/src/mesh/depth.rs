@@ -0,0 +1,205 @@
+use ash::{ext, vk};
+use rad_graph::{
+	device::{Device, GraphicsPipelineDesc, ShaderInfo},
+	graph::{BufferUsage, BufferUsageType, Frame, ImageDesc, ImageUsage, Res},
+	resource::{BufferHandle, ImageView},
+	sync::Shader,
+	util::{
+		pass::{Attachment, Load},
+		pipeline::{no_cull, DepthConvention},
+		render::RenderPass,
+	},
+	Result,
+};
+use vek::Vec2;
+
+use crate::{
+	mesh::{
+		bvh::BvhCull,
+		draw::DrawArgs,
+		hzb::HzbGen,
+		instance::InstanceCull,
+		meshlet::MeshletCull,
+		setup::Setup,
+		HzbInfo,
+		PushConstants,
+		RenderInfo,
+		View,
+		VisBuffer,
+	},
+	scene::{camera::Camera, WorldRenderer},
+};
+
+/// Renders only the depth of opaque and alpha tested meshes, for shadow maps.
+///
+/// Culling is the same as for the visbuffer, but in a single pass without occlusion, and every meshlet is rasterized
+/// in hardware into a depth attachment.
+pub struct DepthPass {
+	setup: Setup,
+	instance_cull: InstanceCull,
+	bvh_cull: BvhCull,
+	meshlet_cull: MeshletCull,
+	/// Only for the sampler of the HZB, which is never generated.
+	hzb_gen: HzbGen,
+	pass: RenderPass<PushConstants>,
+	mesh: Option<ext::mesh_shader::Device>,
+	draw_args: Option<DrawArgs>,
+}
+
+#[derive(Copy, Clone)]
+pub struct DepthOutput {
+	pub camera: Res<BufferHandle>,
+	/// Reverse-Z depth as [`DepthPass::FORMAT`], which is 0 where nothing was drawn.
+	pub depth: Res<ImageView>,
+}
+
+impl DepthPass {
+	pub const FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+	pub fn new(device: &Device) -> Result<Self> {
+		let mesh_shaders = device.supports_mesh_shaders();
+		Ok(Self {
+			setup: Setup::new(if mesh_shaders { Shader::Mesh } else { Shader::Vertex }, true),
+			instance_cull: InstanceCull::new(device, true)?,
+			bvh_cull: BvhCull::new(device, true)?,
+			meshlet_cull: MeshletCull::new(device, true)?,
+			hzb_gen: HzbGen::new(device)?,
+			pass: RenderPass::new(
+				device,
+				GraphicsPipelineDesc {
+					shaders: &[
+						ShaderInfo {
+							shader: if mesh_shaders {
+								"passes.mesh.mesh.hw"
+							} else {
+								"passes.mesh.mesh.vertex"
+							},
+							spec: &["passes.mesh.early"],
+						},
+						ShaderInfo {
+							shader: "passes.mesh.pixel.depth",
+							spec: &[],
+						},
+					],
+					// Back faces are culled in the shaders, except for double-sided materials.
+					raster: no_cull(),
+					depth: DepthConvention::Reverse.depth_state(),
+					depth_attachment: Self::FORMAT,
+					..Default::default()
+				},
+				true,
+			)?,
+			mesh: mesh_shaders.then(|| ext::mesh_shader::Device::new(device.instance(), device.device())),
+			draw_args: (!mesh_shaders).then(|| DrawArgs::new(device)).transpose()?,
+		})
+	}
+
+	/// Render the depth seen by `camera` at `size`.
+	pub fn run<'pass>(
+		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, size: Vec2<u32>,
+		camera: Camera, aspect: f32,
+	) -> DepthOutput {
+		frame.time_region("depth");
+
+		let info = RenderInfo {
+			size,
+			debug_info: false,
+			samples: vk::SampleCountFlags::TYPE_1,
+			view: Some(View {
+				curr: camera,
+				prev: camera,
+				aspect,
+				depth: DepthConvention::Reverse,
+			}),
+			hzb: HzbInfo::default(),
+			motion_vectors: false,
+			sw_edge_pixels: 0.0,
+		};
+		let res = self.setup.run(frame, rend, &info, &self.hzb_gen);
+
+		frame.time_region("cull");
+		self.instance_cull.run(frame, &res);
+		self.bvh_cull.run(frame, &res);
+		self.meshlet_cull.run(frame, &res);
+		frame.end_region();
+
+		let draw_args = self.draw_args.as_ref().map(|d| d.run(frame, &res));
+		let mut pass = frame.timed_pass("rasterize");
+		VisBuffer::draw_args_indirect(&mut pass, draw_args);
+		let instances = res.instances_mesh(&mut pass);
+		let camera = res.camera_mesh(&mut pass);
+		let queue = res.mesh(&mut pass);
+		let stats = res.stats_mesh(&mut pass);
+		// The visbuffer is a dummy, only bound because the rasterizer shares its push constants with the visbuffer.
+		pass.reference(res.visbuffer, ImageUsage::read_2d(res.raster));
+		let visbuffer = res.visbuffer;
+		let depth = pass.resource(
+			ImageDesc {
+				size: vk::Extent3D {
+					width: size.x,
+					height: size.y,
+					depth: 1,
+				},
+				format: Self::FORMAT,
+				..Default::default()
+			},
+			ImageUsage::depth_attachment(),
+		);
+
+		let mesh = self.mesh.as_ref();
+		pass.build(move |mut pass| {
+			let push = PushConstants {
+				instances: pass.get(instances).ptr(),
+				camera: pass.get(camera).ptr(),
+				queue: pass.get(queue).ptr(),
+				stats: pass.get(stats).ptr(),
+				output: pass.get(visbuffer).storage_id.unwrap(),
+				debug: None,
+				size,
+				_pad: 0,
+			};
+			let queue = pass.get(queue).buffer;
+			let mut rpass = self.pass.start(
+				&mut pass,
+				&push,
+				&[],
+				Some(&Attachment {
+					image: depth,
+					load: Load::Clear(vk::ClearValue {
+						depth_stencil: vk::ClearDepthStencilValue {
+							depth: DepthConvention::Reverse.clear_value(),
+							stencil: 0,
+						},
+					}),
+					store: true,
+				}),
+			);
+			match mesh {
+				Some(mesh) => unsafe {
+					mesh.cmd_draw_mesh_tasks_indirect(
+						rpass.pass.buf,
+						queue,
+						std::mem::size_of::<u32>() as u64 * 2,
+						1,
+						std::mem::size_of::<u32>() as u32 * 3,
+					);
+				},
+				None => rpass.draw_indirect(draw_args.unwrap(), 0),
+			}
+		});
+		frame.end_region();
+
+		DepthOutput { camera, depth }
+	}
+
+	pub unsafe fn destroy(self) {
+		self.instance_cull.destroy();
+		self.bvh_cull.destroy();
+		self.meshlet_cull.destroy();
+		self.hzb_gen.destroy();
+		self.pass.destroy();
+		if let Some(d) = self.draw_args {
+			d.destroy();
+		}
+	}
+}
@@ -0,0 +1,61 @@
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, ShaderInfo},
+	graph::{BufferDesc, BufferUsage, Frame, Res},
+	resource::{BufferHandle, GpuPtr},
+	sync::Shader,
+	util::compute::ComputePass,
+	Result,
+};
+
+use crate::mesh::setup::Resources;
+
+/// Converts the hardware meshlet count of the render queue into a `VkDrawIndirectCommand`, for rasterizing with
+/// vertex shaders when mesh shaders are unsupported.
+pub struct DrawArgs {
+	pass: ComputePass<PushConstants>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	queue: GpuPtr<u8>,
+	args: GpuPtr<u8>,
+}
+
+impl DrawArgs {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.mesh.draw.main",
+					spec: &["passes.mesh.early"],
+				},
+			)?,
+		})
+	}
+
+	pub fn run<'pass>(&'pass self, frame: &mut Frame<'pass, '_>, resources: &Resources) -> Res<BufferHandle> {
+		let mut pass = frame.pass("draw args");
+
+		let queue = resources.meshlet_render;
+		pass.reference(queue, BufferUsage::read(Shader::Compute));
+		let args = pass.resource(
+			BufferDesc::gpu(std::mem::size_of::<u32>() as u64 * 4),
+			BufferUsage::write(Shader::Compute),
+		);
+
+		pass.build(move |mut pass| {
+			let push = PushConstants {
+				queue: pass.get(queue).ptr(),
+				args: pass.get(args).ptr(),
+			};
+			self.pass.dispatch(&mut pass, &push, 1, 1, 1);
+		});
+
+		args
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
@@ -0,0 +1,127 @@
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{descriptor::ImageId, Device, ShaderInfo},
+	graph::{BufferDesc, BufferUsage, Frame, Persist},
+	resource::{BufferHandle, GpuPtr},
+	sync::Shader,
+	util::compute::ComputePass,
+	Result,
+};
+
+use crate::{
+	mesh::{GpuVisBufferReader, RenderOutput},
+	scene::{camera::GpuCamera, virtual_scene::GpuInstance},
+};
+
+/// Reads back the resolution every material texture is seen at in the visbuffer, so textures can be streamed at the
+/// resolution they need. Results arrive `FRAMES_IN_FLIGHT` frames after they are rendered.
+///
+/// Image assets are still uploaded whole with a single mip level, so nothing loads or evicts mips from these results
+/// yet. They are only shown in the editor's debug window until images are streamed.
+pub struct TextureFeedback {
+	pass: ComputePass<PushConstants>,
+	readback: Persist<BufferHandle>,
+	/// Indexed by the bindless index of every image.
+	required: Vec<u32>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	instances: GpuPtr<GpuInstance>,
+	camera: GpuPtr<GpuCamera>,
+	read: GpuVisBufferReader,
+	required: GpuPtr<u32>,
+	max_images: u32,
+	stride: u32,
+}
+
+impl TextureFeedback {
+	/// Images with bindless indices from this up aren't tracked.
+	pub const MAX_IMAGES: u32 = 1 << 16;
+	/// Only one pixel of every `STRIDE` x `STRIDE` block is checked, which is plenty for picking mip levels.
+	pub const STRIDE: u32 = 4;
+
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: ComputePass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.texture_feedback.main",
+					spec: &[],
+				},
+			)?,
+			readback: Persist::new(),
+			required: vec![0; Self::MAX_IMAGES as usize],
+		})
+	}
+
+	/// The size along its longest axis that `image` was last seen to need, which is never larger than the image.
+	/// `None` if no visible pixel sampled it.
+	pub fn required_size(&self, image: ImageId) -> Option<u32> {
+		self.required.get(image.get() as usize).copied().filter(|&x| x > 0)
+	}
+
+	/// The sizes along their longest axis that every image seen was last seen to need.
+	pub fn seen(&self) -> impl Iterator<Item = u32> + '_ { self.required.iter().copied().filter(|&x| x > 0) }
+
+	/// The coarsest mip level of `image` that is still sharp where it is seen, given the size of its longest axis.
+	/// `None` if it wasn't seen, or is empty.
+	pub fn required_mip(&self, image: ImageId, size: u32) -> Option<u32> {
+		if size == 0 {
+			return None;
+		}
+		let required = self.required_size(image)?;
+		Some((size / required.clamp(1, size)).ilog2())
+	}
+
+	/// Find the texture sizes needed to draw `output`, and read back the ones found `FRAMES_IN_FLIGHT` frames ago.
+	pub fn run<'pass>(&'pass mut self, frame: &mut Frame<'pass, '_>, output: RenderOutput) {
+		let Self {
+			pass,
+			readback,
+			required,
+		} = self;
+
+		let mut p = frame.pass("read texture feedback");
+		let size = std::mem::size_of::<u32>() * Self::MAX_IMAGES as usize;
+		let buf = p.resource(
+			BufferDesc::readback(size as u64, *readback),
+			BufferUsage::transfer_write(),
+		);
+		p.build(move |mut p| {
+			if !p.is_uninit(buf) {
+				let data = unsafe { &p.get(buf).data.as_ref()[..size] };
+				required.copy_from_slice(bytemuck::cast_slice(data));
+			}
+			p.fill_buffer(buf, 0, 0, size);
+		});
+
+		let mut p = frame.pass("texture feedback");
+		p.reference(output.instances, BufferUsage::read(Shader::Compute));
+		p.reference(output.camera, BufferUsage::read(Shader::Compute));
+		output.reader.add(&mut p, Shader::Compute, false);
+		p.reference(buf, BufferUsage::write(Shader::Compute));
+		let desc = p.desc(output.reader.visbuffer);
+		let pixels = Self::STRIDE * output.reader.sample_grid;
+		p.build(move |mut p| {
+			let push = PushConstants {
+				instances: p.get(output.instances).ptr(),
+				camera: p.get(output.camera).ptr(),
+				read: output.reader.get(&mut p),
+				required: p.get(buf).ptr(),
+				max_images: Self::MAX_IMAGES,
+				stride: Self::STRIDE,
+			};
+			pass.dispatch(
+				&mut p,
+				&push,
+				desc.size.width.div_ceil(pixels * 8),
+				desc.size.height.div_ceil(pixels * 8),
+				1,
+			);
+		});
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
@@ -9,14 +9,51 @@ use rad_graph::{
 	},
 	graph::{BufferDesc, BufferUsage, Frame, ImageUsage, PassContext, Res, Shader},
 	resource::{BufferHandle, GpuPtr, ImageView, ImageViewDescUnnamed, ImageViewUsage, Subresource},
-	util::compute::ComputePass,
+	util::{compute::ComputePass, pipeline::DepthConvention},
 	Result,
 };
 use vek::Vec2;
 
+/// How the HZB used for occlusion culling is generated.
+#[derive(Copy, Clone, PartialEq)]
+pub struct HzbInfo {
+	/// The most mips to generate, up to `HzbInfo::MAX_MIPS`.
+	pub mips: u32,
+	/// Stop the mip chain before the longest side of a mip is smaller than this, in texels. Nodes and meshlets too
+	/// large on screen for the last mip are never occluded.
+	pub min_tile: u32,
+	/// Reduce to the farthest depth of every texel footprint, which never culls visible geometry. Reducing to the
+	/// closest depth culls more, including some visible geometry.
+	pub conservative: bool,
+}
+
+impl Default for HzbInfo {
+	fn default() -> Self {
+		Self {
+			mips: Self::MAX_MIPS,
+			min_tile: 1,
+			conservative: true,
+		}
+	}
+}
+
+impl HzbInfo {
+	pub const MAX_MIPS: u32 = 12;
+
+	/// The number of mips of an HZB whose first mip is `size`.
+	pub fn levels(&self, size: Vec2<u32>) -> u32 {
+		let max = size.x.max(size.y);
+		let full = max.ilog2();
+		let tiles = (max / self.min_tile.max(1)).max(1).ilog2() + 1;
+		full.min(tiles).min(self.mips).clamp(1, Self::MAX_MIPS)
+	}
+}
+
 pub struct HzbGen {
 	pass: ComputePass<PushConstants>,
-	hzb_sample: SamplerId,
+	/// Samplers reducing to the smallest and largest depth.
+	min_sample: SamplerId,
+	max_sample: SamplerId,
 }
 
 #[repr(C)]
@@ -24,10 +61,12 @@ pub struct HzbGen {
 struct PushConstants {
 	atomic: GpuPtr<u32>,
 	visbuffer: StorageImageId,
-	outs: [Option<StorageImageId>; 12],
+	outs: [Option<StorageImageId>; HzbInfo::MAX_MIPS as usize],
 	mips: u32,
 	target: u32,
-	_pad: u32,
+	sample_grid: u32,
+	conservative: u32,
+	standard_depth: u32,
 }
 
 struct PassIO {
@@ -36,10 +75,25 @@ struct PassIO {
 	out: Res<ImageView>,
 	size: Vec2<u32>,
 	levels: u32,
+	sample_grid: u32,
+	conservative: bool,
+	depth: DepthConvention,
 }
 
 impl HzbGen {
 	pub fn new(device: &Device) -> Result<Self> {
+		let sampler = |reduction_mode| {
+			device.sampler(SamplerDesc {
+				mag_filter: vk::Filter::LINEAR,
+				min_filter: vk::Filter::LINEAR,
+				mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+				address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				reduction_mode,
+				..Default::default()
+			})
+		};
 		Ok(Self {
 			pass: ComputePass::new(
 				device,
@@ -48,23 +102,26 @@ impl HzbGen {
 					spec: &[],
 				},
 			)?,
-			hzb_sample: device.sampler(SamplerDesc {
-				mag_filter: vk::Filter::LINEAR,
-				min_filter: vk::Filter::LINEAR,
-				mipmap_mode: vk::SamplerMipmapMode::NEAREST,
-				address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
-				address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
-				address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
-				reduction_mode: vk::SamplerReductionMode::MIN,
-				..Default::default()
-			}),
+			min_sample: sampler(vk::SamplerReductionMode::MIN),
+			max_sample: sampler(vk::SamplerReductionMode::MAX),
 		})
 	}
 
-	pub fn sampler(&self) -> SamplerId { self.hzb_sample }
+	/// The sampler reducing like an HZB generated with `conservative` in `depth`.
+	pub fn sampler(&self, conservative: bool, depth: DepthConvention) -> SamplerId {
+		// With reverse-Z, the farthest depth is the smallest.
+		if conservative == (depth == DepthConvention::Reverse) {
+			self.min_sample
+		} else {
+			self.max_sample
+		}
+	}
 
-	pub fn run<'pass>(&'pass self, frame: &mut Frame<'pass, '_>, visbuffer: Res<ImageView>, out: Res<ImageView>) {
-		frame.start_region("generate hzb");
+	pub fn run<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, visbuffer: Res<ImageView>, out: Res<ImageView>, sample_grid: u32,
+		conservative: bool, depth: DepthConvention,
+	) {
+		frame.time_region("generate hzb");
 
 		let atomic = frame.stage_buffer_new(
 			"zero atomic",
@@ -89,6 +146,9 @@ impl HzbGen {
 					out,
 					size,
 					levels: desc.levels,
+					sample_grid,
+					conservative,
+					depth,
 				},
 			)
 		});
@@ -101,7 +161,7 @@ impl HzbGen {
 		let visbuffer = pass.get(io.visbuffer);
 		let out = pass.get(io.out);
 
-		let mut outs = [None; 12];
+		let mut outs = [None; HzbInfo::MAX_MIPS as usize];
 		let mut s = io.size;
 		for i in 0..io.levels {
 			if s.x > 1 {
@@ -144,7 +204,9 @@ impl HzbGen {
 			outs,
 			mips: io.levels,
 			target: x * y - 1,
-			_pad: 0,
+			sample_grid: io.sample_grid,
+			conservative: io.conservative as u32,
+			standard_depth: (io.depth == DepthConvention::Standard) as u32,
 		};
 		self.pass.dispatch(&mut pass, &push, x, y, 1);
 	}
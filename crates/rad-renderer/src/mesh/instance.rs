@@ -1,10 +1,6 @@
 use bytemuck::NoUninit;
 use rad_graph::{
-	device::{
-		descriptor::{ImageId, SamplerId},
-		Device,
-		ShaderInfo,
-	},
+	device::{descriptor::ImageId, Device, ShaderInfo},
 	graph::Frame,
 	resource::GpuPtr,
 	util::compute::ComputePass,
@@ -27,15 +23,13 @@ pub struct InstanceCull {
 struct PushConstants {
 	instances: GpuPtr<GpuInstance>,
 	camera: GpuPtr<GpuCamera>,
-	hzb: ImageId,
-	hzb_sampler: SamplerId,
 	next: GpuPtr<u8>,
 	late_instances: GpuPtr<u32>,
 	stats: GpuPtr<CullStats>,
 	frame: u64,
+	hzb: ImageId,
 	instance_count: u32,
 	res: Vec2<u32>,
-	_pad: u32,
 }
 
 impl InstanceCull {
@@ -71,23 +65,19 @@ impl InstanceCull {
 		let stats = resources.stats(&mut pass);
 
 		let instance_count = resources.scene.instance_count;
-		let hzb_sampler = resources.hzb_sampler;
-		// TODO: fix
-		let frame = 0;
+		let frame = resources.scene.frame;
 		let res = resources.res;
 		pass.build(move |mut pass| {
 			let push = PushConstants {
 				instances: pass.get(instances).ptr(),
 				camera: pass.get(camera).ptr(),
 				hzb: pass.get(hzb).id.unwrap(),
-				hzb_sampler,
 				next: pass.get(next).ptr(),
 				late_instances: pass.get(late_instances).ptr(),
 				stats: pass.get(stats).ptr(),
 				frame,
 				instance_count,
 				res,
-				_pad: 0,
 			};
 			if self.early {
 				self.pass.dispatch(&mut pass, &push, instance_count.div_ceil(64), 1, 1);
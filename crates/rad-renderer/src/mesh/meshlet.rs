@@ -1,10 +1,6 @@
 use bytemuck::NoUninit;
 use rad_graph::{
-	device::{
-		descriptor::{ImageId, SamplerId},
-		Device,
-		ShaderInfo,
-	},
+	device::{descriptor::ImageId, Device, ShaderInfo},
 	graph::Frame,
 	resource::GpuPtr,
 	util::compute::ComputePass,
@@ -27,13 +23,13 @@ pub struct MeshletCull {
 struct PushConstants {
 	instances: GpuPtr<GpuInstance>,
 	camera: GpuPtr<GpuCamera>,
-	hzb: ImageId,
-	hzb_sampler: SamplerId,
 	queue: GpuPtr<u8>,
 	render: GpuPtr<u8>,
 	stats: GpuPtr<CullStats>,
 	frame: u64,
+	hzb: ImageId,
 	res: Vec2<u32>,
+	sw_edge_pixels: f32,
 }
 
 impl MeshletCull {
@@ -68,21 +64,20 @@ impl MeshletCull {
 		let render = resources.output(&mut pass, resources.meshlet_render);
 		let stats = resources.stats(&mut pass);
 
-		let hzb_sampler = resources.hzb_sampler;
-		// TODO: fix
-		let frame = 0;
+		let frame = resources.scene.frame;
 		let res = resources.res;
+		let sw_edge_pixels = resources.sw_edge_pixels;
 		pass.build(move |mut pass| {
 			let push = PushConstants {
 				instances: pass.get(instances).ptr(),
 				camera: pass.get(camera).ptr(),
 				hzb: pass.get(hzb).id.unwrap(),
-				hzb_sampler,
 				queue: pass.get(queue).ptr(),
 				render: pass.get(render).ptr(),
 				stats: pass.get(stats).ptr(),
 				frame,
 				res,
+				sw_edge_pixels,
 			};
 			self.pass.dispatch_indirect(
 				&mut pass,
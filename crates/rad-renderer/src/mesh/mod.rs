@@ -1,31 +1,101 @@
 use ash::{ext, vk};
 use bytemuck::{NoUninit, Pod, Zeroable};
 use rad_graph::{
-	device::{descriptor::StorageImageId, Device, GraphicsPipelineDesc, ShaderInfo},
-	graph::{BufferUsage, Frame, ImageUsage, PassBuilder, PassContext, Res},
+	device::{
+		descriptor::{SamplerId, StorageImageId},
+		Device,
+		GraphicsPipelineDesc,
+		ShaderInfo,
+	},
+	graph::{BufferUsage, BufferUsageType, Frame, ImageUsage, PassBuilder, PassContext, Res},
 	resource::{BufferHandle, GpuPtr, ImageView},
 	sync::Shader,
-	util::{compute::ComputePass, render::RenderPass},
+	util::{
+		compute::ComputePass,
+		pipeline::{no_cull, DepthConvention},
+		render::RenderPass,
+	},
 	Result,
 };
 use vek::Vec2;
 
-pub use crate::mesh::setup::{DebugRes, DebugResId};
+pub use crate::mesh::{
+	depth::{DepthOutput, DepthPass},
+	feedback::TextureFeedback,
+	hzb::HzbInfo,
+	setup::{DebugRes, DebugResId},
+	transparent::TransparentPass,
+};
 use crate::{
-	mesh::{bvh::BvhCull, hzb::HzbGen, instance::InstanceCull, meshlet::MeshletCull, setup::Setup},
-	scene::{camera::GpuCamera, virtual_scene::GpuInstance, WorldRenderer},
+	mesh::{
+		bvh::BvhCull,
+		draw::DrawArgs,
+		hzb::HzbGen,
+		instance::InstanceCull,
+		meshlet::MeshletCull,
+		motion::MotionVectors,
+		setup::Setup,
+	},
+	scene::{
+		camera::{Camera, GpuCamera},
+		virtual_scene::GpuInstance,
+		WorldRenderer,
+	},
 };
 
 mod bvh;
+mod depth;
+mod draw;
+mod feedback;
 mod hzb;
 mod instance;
 mod meshlet;
+mod motion;
 mod setup;
+mod transparent;
 
 #[derive(Clone)]
 pub struct RenderInfo {
 	pub size: Vec2<u32>,
 	pub debug_info: bool,
+	/// The MSAA sample count of the hardware rasterizer, with every sample shaded so it keeps its own visibility.
+	/// 64-bit atomics can't write multisampled storage images, so the samples of a pixel are stored as a grid in the
+	/// visbuffer, which is `sqrt(samples)` times the size along each axis. Only 1 and 4 samples are supported, and
+	/// other counts are rounded down to them, or to 1 on devices without 4x MSAA. Meshlets rasterized in software
+	/// sample the centers of the grid instead.
+	pub samples: vk::SampleCountFlags,
+	/// Render from this view instead of the primary view.
+	pub view: Option<View>,
+	pub hzb: HzbInfo,
+	/// Write `RenderOutput::motion`.
+	pub motion_vectors: bool,
+	/// Meshlets whose longest edge covers fewer pixels than this are rasterized in software, as a compute shader
+	/// beats the hardware rasterizer on tiny triangles. The edge is measured at the nearest point of the bounding
+	/// sphere of the meshlet, so it is an upper bound. `0` rasterizes everything in hardware, which is the default.
+	pub sw_edge_pixels: f32,
+}
+
+/// A view to render, other than the primary view.
+#[derive(Copy, Clone)]
+pub struct View {
+	pub curr: Camera,
+	/// The view last frame, for occlusion culling against the previous HZB.
+	pub prev: Camera,
+	pub aspect: f32,
+	pub depth: DepthConvention,
+}
+
+impl RenderInfo {
+	pub const DEFAULT_SW_EDGE_PIXELS: f32 = 0.0;
+
+	/// The number of samples along each axis of a pixel.
+	pub fn sample_grid(&self) -> u32 {
+		if self.samples.as_raw() >= vk::SampleCountFlags::TYPE_4.as_raw() {
+			2
+		} else {
+			1
+		}
+	}
 }
 
 #[derive(Copy, Clone)]
@@ -33,6 +103,7 @@ pub struct VisBufferReader {
 	pub visbuffer: Res<ImageView>,
 	pub queue: Res<BufferHandle>,
 	pub debug: Option<DebugRes>,
+	pub sample_grid: u32,
 }
 
 impl VisBufferReader {
@@ -53,7 +124,7 @@ impl VisBufferReader {
 		GpuVisBufferReader {
 			queue: pass.get(self.queue).ptr(),
 			visbuffer: pass.get(self.visbuffer).storage_id.unwrap(),
-			_pad: 0,
+			sample_grid: self.sample_grid,
 		}
 	}
 
@@ -62,7 +133,7 @@ impl VisBufferReader {
 			queue: pass.get(self.queue).ptr(),
 			visbuffer: pass.get(self.visbuffer).storage_id.unwrap(),
 			debug: self.debug.map(|x| x.get(pass)),
-			_pad: 0,
+			sample_grid: self.sample_grid,
 		}
 	}
 }
@@ -73,7 +144,7 @@ pub struct GpuVisBufferReaderDebug {
 	queue: GpuPtr<u8>,
 	visbuffer: StorageImageId,
 	debug: Option<DebugResId>,
-	_pad: u32,
+	sample_grid: u32,
 }
 
 #[derive(Copy, Clone, NoUninit)]
@@ -81,7 +152,7 @@ pub struct GpuVisBufferReaderDebug {
 pub struct GpuVisBufferReader {
 	queue: GpuPtr<u8>,
 	visbuffer: StorageImageId,
-	_pad: u32,
+	sample_grid: u32,
 }
 
 #[derive(Copy, Clone)]
@@ -90,10 +161,18 @@ pub struct RenderOutput {
 	pub instances: Res<BufferHandle>,
 	pub camera: Res<BufferHandle>,
 	pub reader: VisBufferReader,
+	pub hzb: Res<ImageView>,
+	pub hzb_sampler: SamplerId,
+	/// How far the surface in every pixel moved on screen since the last frame, in UV, as `R16G16_SFLOAT`. Only
+	/// written if `RenderInfo::motion_vectors` is set.
+	pub motion: Option<Res<ImageView>>,
 }
 
 pub struct VisBuffer {
 	setup: Setup,
+	// TODO: run the early culls on the async compute queue, overlapping the previous frame's raster. The graph records
+	// every pass into one graphics command buffer, so it first needs to schedule passes on other queues, with queue
+	// ownership transfers for their outputs and a fallback for devices with a single queue.
 	early_instance_cull: InstanceCull,
 	late_instance_cull: InstanceCull,
 	early_bvh_cull: BvhCull,
@@ -101,18 +180,47 @@ pub struct VisBuffer {
 	early_meshlet_cull: MeshletCull,
 	late_meshlet_cull: MeshletCull,
 	hzb_gen: HzbGen,
+	motion: MotionVectors,
 	no_debug: Passes,
 	debug: Passes,
-	mesh: ext::mesh_shader::Device,
+	/// `None` if mesh shaders are unsupported, in which case meshlets are drawn with vertex shaders instead.
+	mesh: Option<ext::mesh_shader::Device>,
+	/// Whether the device can rasterize with 4x MSAA and no attachments.
+	msaa: bool,
+	draw_args: Option<DrawArgs>,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Pod, Zeroable)]
+/// Counters for a cull pass. `*_culled` counts rejections by the frustum, LOD, or backface tests, and `*_occluded`
+/// rejections by the HZB. In the early pass, occluded nodes are retested in the late pass.
 pub struct PassStats {
 	pub instances: u32,
+	pub instances_culled: u32,
+	pub instances_occluded: u32,
+	pub bvh_nodes: u32,
+	pub bvh_nodes_culled: u32,
+	pub bvh_nodes_occluded: u32,
 	pub candidate_meshlets: u32,
+	pub meshlets_culled: u32,
+	pub meshlets_occluded: u32,
+	/// Meshlets too large on screen for the mips of the HZB, which are never occluded.
+	pub meshlets_hzb_skipped: u32,
 	pub hw_meshlets: u32,
 	pub sw_meshlets: u32,
+	/// The triangles in `sw_meshlets`.
+	pub sw_triangles: u32,
+}
+
+impl PassStats {
+	/// The average triangles per software rasterized meshlet.
+	pub fn sw_triangles_per_meshlet(&self) -> f32 {
+		if self.sw_meshlets == 0 {
+			0.0
+		} else {
+			self.sw_triangles as f32 / self.sw_meshlets as f32
+		}
+	}
 }
 
 #[repr(C)]
@@ -132,6 +240,8 @@ struct PushConstants {
 	stats: GpuPtr<CullStats>,
 	output: StorageImageId,
 	debug: Option<DebugResId>,
+	/// The size of `output`, or of the depth attachment of a [`DepthPass`], which gets a dummy `output`.
+	size: Vec2<u32>,
 	_pad: u32,
 }
 
@@ -144,17 +254,20 @@ struct PassIO {
 	stats: Res<BufferHandle>,
 	visbuffer: Res<ImageView>,
 	debug: Option<DebugRes>,
+	draw_args: Option<Res<BufferHandle>>,
+	sample_grid: u32,
 }
 
 struct Passes {
-	early_hw: RenderPass<PushConstants>,
+	/// Indexed by whether the pass rasterizes with 4x MSAA.
+	early_hw: [RenderPass<PushConstants>; 2],
 	early_sw: ComputePass<PushConstants>,
-	late_hw: RenderPass<PushConstants>,
+	late_hw: [RenderPass<PushConstants>; 2],
 	late_sw: ComputePass<PushConstants>,
 }
 
 impl Passes {
-	fn execute(&self, mesh: &ext::mesh_shader::Device, mut pass: PassContext, io: PassIO) {
+	fn execute(&self, mesh: Option<&ext::mesh_shader::Device>, mut pass: PassContext, io: PassIO) {
 		let visbuffer = pass.get(io.visbuffer);
 		let queue = pass.get(io.queue);
 
@@ -165,25 +278,32 @@ impl Passes {
 			stats: pass.get(io.stats).ptr(),
 			output: visbuffer.storage_id.unwrap(),
 			debug: io.debug.map(|d| d.get(&mut pass)),
+			size: Vec2::new(visbuffer.size.width, visbuffer.size.height),
 			_pad: 0,
 		};
 
-		unsafe {
-			let pass = if io.early { &self.early_hw } else { &self.late_hw }.start_empty(
+		{
+			let hw = if io.early { &self.early_hw } else { &self.late_hw };
+			let mut rpass = hw[(io.sample_grid > 1) as usize].start_empty(
 				&mut pass,
 				&push,
 				vk::Extent2D {
-					width: visbuffer.size.width,
-					height: visbuffer.size.height,
+					width: visbuffer.size.width / io.sample_grid,
+					height: visbuffer.size.height / io.sample_grid,
 				},
 			);
-			mesh.cmd_draw_mesh_tasks_indirect(
-				pass.pass.buf,
-				queue.buffer,
-				std::mem::size_of::<u32>() as u64 * 2,
-				1,
-				std::mem::size_of::<u32>() as u32 * 3,
-			);
+			match mesh {
+				Some(mesh) => unsafe {
+					mesh.cmd_draw_mesh_tasks_indirect(
+						rpass.pass.buf,
+						queue.buffer,
+						std::mem::size_of::<u32>() as u64 * 2,
+						1,
+						std::mem::size_of::<u32>() as u32 * 3,
+					);
+				},
+				None => rpass.draw_indirect(io.draw_args.unwrap(), 0),
+			}
 		}
 
 		if io.early { &self.early_sw } else { &self.late_sw }.dispatch_indirect(
@@ -195,17 +315,25 @@ impl Passes {
 	}
 
 	unsafe fn destroy(self) {
-		self.early_hw.destroy();
+		for p in self.early_hw.into_iter().chain(self.late_hw) {
+			p.destroy();
+		}
 		self.early_sw.destroy();
-		self.late_hw.destroy();
 		self.late_sw.destroy();
 	}
 }
 
 impl VisBuffer {
 	pub fn new(device: &Device) -> Result<Self> {
+		let mesh_shaders = device.supports_mesh_shaders();
+		let limits = unsafe {
+			device
+				.instance()
+				.get_physical_device_properties(device.physical_device())
+		}
+		.limits;
 		Ok(Self {
-			setup: Setup::new(),
+			setup: Setup::new(if mesh_shaders { Shader::Mesh } else { Shader::Vertex }, false),
 			early_instance_cull: InstanceCull::new(device, true)?,
 			late_instance_cull: InstanceCull::new(device, false)?,
 			early_bvh_cull: BvhCull::new(device, true)?,
@@ -213,52 +341,79 @@ impl VisBuffer {
 			early_meshlet_cull: MeshletCull::new(device, true)?,
 			late_meshlet_cull: MeshletCull::new(device, false)?,
 			hzb_gen: HzbGen::new(device)?,
-			no_debug: Passes {
-				early_hw: Self::hw(device, true, false)?,
-				early_sw: Self::sw(device, true, false)?,
-				late_hw: Self::hw(device, false, false)?,
-				late_sw: Self::sw(device, false, false)?,
-			},
-			debug: Passes {
-				early_hw: Self::hw(device, true, true)?,
-				early_sw: Self::sw(device, true, true)?,
-				late_hw: Self::hw(device, false, true)?,
-				late_sw: Self::sw(device, false, true)?,
-			},
-			mesh: ext::mesh_shader::Device::new(device.instance(), device.device()),
+			motion: MotionVectors::new(device)?,
+			no_debug: Self::passes(device, false)?,
+			debug: Self::passes(device, true)?,
+			mesh: mesh_shaders.then(|| ext::mesh_shader::Device::new(device.instance(), device.device())),
+			msaa: limits
+				.framebuffer_no_attachments_sample_counts
+				.contains(vk::SampleCountFlags::TYPE_4),
+			draw_args: (!mesh_shaders).then(|| DrawArgs::new(device)).transpose()?,
 		})
 	}
 
-	fn spec(debug: bool, early: bool) -> &'static [&'static str] {
-		if debug {
-			if early {
-				&["passes.mesh.debug", "passes.mesh.early"]
-			} else {
-				&["passes.mesh.debug", "passes.mesh.late"]
-			}
-		} else {
-			if early {
-				&["passes.mesh.early"]
-			} else {
-				&["passes.mesh.late"]
-			}
+	fn passes(device: &Device, debug: bool) -> Result<Passes> {
+		Ok(Passes {
+			early_hw: [
+				Self::hw(device, true, debug, false)?,
+				Self::hw(device, true, debug, true)?,
+			],
+			early_sw: Self::sw(device, true, debug)?,
+			late_hw: [
+				Self::hw(device, false, debug, false)?,
+				Self::hw(device, false, debug, true)?,
+			],
+			late_sw: Self::sw(device, false, debug)?,
+		})
+	}
+
+	fn spec(debug: bool, early: bool, msaa: bool) -> &'static [&'static str] {
+		match (debug, early, msaa) {
+			(false, true, false) => &["passes.mesh.early"],
+			(false, false, false) => &["passes.mesh.late"],
+			(false, true, true) => &["passes.mesh.early", "passes.mesh.msaa"],
+			(false, false, true) => &["passes.mesh.late", "passes.mesh.msaa"],
+			(true, true, false) => &["passes.mesh.debug", "passes.mesh.early"],
+			(true, false, false) => &["passes.mesh.debug", "passes.mesh.late"],
+			(true, true, true) => &["passes.mesh.debug", "passes.mesh.early", "passes.mesh.msaa"],
+			(true, false, true) => &["passes.mesh.debug", "passes.mesh.late", "passes.mesh.msaa"],
 		}
 	}
 
-	fn hw(device: &Device, early: bool, debug: bool) -> Result<RenderPass<PushConstants>> {
+	fn hw(device: &Device, early: bool, debug: bool, msaa: bool) -> Result<RenderPass<PushConstants>> {
 		RenderPass::new(
 			device,
 			GraphicsPipelineDesc {
 				shaders: &[
 					ShaderInfo {
-						shader: "passes.mesh.mesh.hw",
-						spec: Self::spec(debug, early),
+						shader: if device.supports_mesh_shaders() {
+							"passes.mesh.mesh.hw"
+						} else {
+							"passes.mesh.mesh.vertex"
+						},
+						spec: Self::spec(debug, early, msaa),
 					},
 					ShaderInfo {
 						shader: "passes.mesh.pixel.main",
-						spec: if debug { &["passes.mesh.debug"] } else { &[] },
+						spec: match (debug, msaa) {
+							(false, false) => &[],
+							(false, true) => &["passes.mesh.msaa"],
+							(true, false) => &["passes.mesh.debug"],
+							(true, true) => &["passes.mesh.debug", "passes.mesh.msaa"],
+						},
 					},
 				],
+				// Back faces are culled in the shaders, except for double-sided materials.
+				raster: no_cull(),
+				// Shade every sample, so that each writes its own visibility.
+				multisample: vk::PipelineMultisampleStateCreateInfo::default()
+					.rasterization_samples(if msaa {
+						vk::SampleCountFlags::TYPE_4
+					} else {
+						vk::SampleCountFlags::TYPE_1
+					})
+					.sample_shading_enable(msaa)
+					.min_sample_shading(1.0),
 				..Default::default()
 			},
 			true,
@@ -270,27 +425,34 @@ impl VisBuffer {
 			device,
 			ShaderInfo {
 				shader: "passes.mesh.mesh.sw",
-				spec: Self::spec(debug, early),
+				spec: Self::spec(debug, early, false),
 			},
 		)
 	}
 
 	pub fn run<'pass>(
-		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, info: RenderInfo,
+		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, mut info: RenderInfo,
 	) -> RenderOutput {
-		frame.start_region("visbuffer");
+		frame.time_region("visbuffer");
 
+		if !self.msaa {
+			info.samples = vk::SampleCountFlags::TYPE_1;
+		}
 		let rstats = self.setup.stats;
-		let res = self.setup.run(frame, rend, &info, self.hzb_gen.sampler());
+		let sample_grid = info.sample_grid();
+		let conservative = info.hzb.conservative;
+		let res = self.setup.run(frame, rend, &info, &self.hzb_gen);
 
 		frame.start_region("early pass");
-		frame.start_region("cull");
+		frame.time_region("cull");
 		self.early_instance_cull.run(frame, &res);
 		self.early_bvh_cull.run(frame, &res);
 		self.early_meshlet_cull.run(frame, &res);
 		frame.end_region();
 
-		let mut pass = frame.pass("rasterize");
+		let draw_args = self.draw_args.as_ref().map(|d| d.run(frame, &res));
+		let mut pass = frame.timed_pass("rasterize");
+		Self::draw_args_indirect(&mut pass, draw_args);
 		let instances = res.instances_mesh(&mut pass);
 		let camera = res.camera_mesh(&mut pass);
 		let queue = res.mesh(&mut pass);
@@ -305,13 +467,15 @@ impl VisBuffer {
 			stats,
 			visbuffer,
 			debug,
+			draw_args,
+			sample_grid,
 		};
 		let p = if io.debug.is_some() {
 			&self.debug
 		} else {
 			&self.no_debug
 		};
-		let mesh = &self.mesh;
+		let mesh = self.mesh.as_ref();
 		pass.build(move |pass| p.execute(mesh, pass, io));
 
 		let mut pass = frame.pass("zero render queue");
@@ -321,36 +485,61 @@ impl VisBuffer {
 		});
 		frame.end_region();
 
-		self.hzb_gen.run(frame, visbuffer, res.hzb);
+		self.hzb_gen
+			.run(frame, visbuffer, res.hzb, sample_grid, conservative, res.depth);
 		frame.start_region("late pass");
-		frame.start_region("cull");
+		frame.time_region("cull");
 		self.late_instance_cull.run(frame, &res);
 		self.late_bvh_cull.run(frame, &res);
 		self.late_meshlet_cull.run(frame, &res);
 		frame.end_region();
 
-		let mut pass = frame.pass("rasterize");
+		let draw_args = self.draw_args.as_ref().map(|d| d.run(frame, &res));
+		let mut pass = frame.timed_pass("rasterize");
+		Self::draw_args_indirect(&mut pass, draw_args);
 		res.camera_mesh(&mut pass);
 		res.mesh(&mut pass);
 		res.stats_mesh(&mut pass);
 		res.visbuffer(&mut pass);
 		res.debug(&mut pass);
 		io.early = false;
+		io.draw_args = draw_args;
 		pass.build(move |pass| p.execute(mesh, pass, io));
 		frame.end_region();
 
-		self.hzb_gen.run(frame, visbuffer, res.hzb);
+		self.hzb_gen
+			.run(frame, visbuffer, res.hzb, sample_grid, conservative, res.depth);
+
+		let reader = VisBufferReader {
+			visbuffer,
+			queue,
+			debug,
+			sample_grid,
+		};
+		let motion = info
+			.motion_vectors
+			.then(|| self.motion.run(frame, &res.scene, camera, reader));
 
 		frame.end_region();
 		RenderOutput {
 			stats: rstats,
 			instances: res.scene.instances,
 			camera,
-			reader: VisBufferReader {
-				visbuffer,
-				queue,
-				debug,
-			},
+			reader,
+			hzb: res.hzb,
+			hzb_sampler: res.hzb_sampler,
+			motion,
+		}
+	}
+
+	fn draw_args_indirect(pass: &mut PassBuilder, draw_args: Option<Res<BufferHandle>>) {
+		if let Some(args) = draw_args {
+			pass.reference(
+				args,
+				BufferUsage {
+					usages: &[BufferUsageType::IndirectBuffer],
+				},
+			);
 		}
 	}
 
@@ -362,7 +551,11 @@ impl VisBuffer {
 		self.early_meshlet_cull.destroy();
 		self.late_meshlet_cull.destroy();
 		self.hzb_gen.destroy();
+		self.motion.destroy();
 		self.no_debug.destroy();
 		self.debug.destroy();
+		if let Some(d) = self.draw_args {
+			d.destroy();
+		}
 	}
 }
@@ -0,0 +1,89 @@
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, ShaderInfo},
+	graph::{BufferUsage, Frame, ImageDesc, ImageUsage, Res, Shader},
+	resource::{BufferHandle, GpuPtr, ImageView},
+	util::render::FullscreenPass,
+	Result,
+};
+
+use crate::{
+	mesh::{GpuVisBufferReader, VisBufferReader},
+	scene::{
+		camera::GpuCamera,
+		virtual_scene::{GpuInstance, VirtualScene},
+	},
+};
+
+/// Writes how far the surface in every pixel moved on screen since the last frame, in UV, from the movement of both
+/// the camera and the instance.
+pub struct MotionVectors {
+	pass: FullscreenPass<PushConstants>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	instances: GpuPtr<GpuInstance>,
+	camera: GpuPtr<GpuCamera>,
+	frame: u64,
+	read: GpuVisBufferReader,
+}
+
+impl MotionVectors {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.mesh.motion.main",
+					spec: &[],
+				},
+				&[vk::Format::R16G16_SFLOAT],
+			)?,
+		})
+	}
+
+	pub fn run<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, scene: &VirtualScene, camera: Res<BufferHandle>,
+		reader: VisBufferReader,
+	) -> Res<ImageView> {
+		let instances = scene.instances;
+		let scene_frame = scene.frame;
+		let mut pass = frame.timed_pass("motion vectors");
+
+		pass.reference(instances, BufferUsage::read(Shader::Fragment));
+		pass.reference(camera, BufferUsage::read(Shader::Fragment));
+		reader.add(&mut pass, Shader::Fragment, false);
+
+		let desc = pass.desc(reader.visbuffer);
+		let grid = reader.sample_grid;
+		let out = pass.resource(
+			ImageDesc {
+				size: vk::Extent3D {
+					width: desc.size.width / grid,
+					height: desc.size.height / grid,
+					depth: 1,
+				},
+				format: vk::Format::R16G16_SFLOAT,
+				..desc
+			},
+			ImageUsage::color_attachment(),
+		);
+
+		pass.build(move |mut pass| {
+			let push = PushConstants {
+				instances: pass.get(instances).ptr(),
+				camera: pass.get(camera).ptr(),
+				frame: scene_frame,
+				read: reader.get(&mut pass),
+			};
+			self.pass.run_one(&mut pass, &push, out);
+		});
+
+		out
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
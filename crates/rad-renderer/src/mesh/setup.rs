@@ -1,5 +1,5 @@
 use ash::vk;
-use bytemuck::{offset_of, NoUninit, PodInOption, ZeroableInOption};
+use bytemuck::{NoUninit, PodInOption, ZeroableInOption};
 use rad_graph::{
 	device::descriptor::{SamplerId, StorageImageId},
 	graph::{
@@ -17,13 +17,18 @@ use rad_graph::{
 	},
 	resource::{BufferHandle, ImageView, Subresource},
 	sync::Shader,
+	util::pipeline::DepthConvention,
 };
 use tracing::error;
 use vek::Vec2;
 
 use crate::{
-	mesh::{CullStats, RenderInfo},
-	scene::{camera::CameraScene, virtual_scene::VirtualScene, WorldRenderer},
+	mesh::{hzb::HzbGen, CullStats, RenderInfo},
+	scene::{
+		camera::{CameraScene, GpuCamera},
+		virtual_scene::VirtualScene,
+		WorldRenderer,
+	},
 };
 
 #[derive(Copy, Clone)]
@@ -56,6 +61,8 @@ pub struct Resources {
 	pub camera: Res<BufferHandle>,
 	pub hzb: Res<ImageView>,
 	pub hzb_sampler: SamplerId,
+	/// The depth convention of the camera, which the HZB is in too.
+	pub depth: DepthConvention,
 	pub late_instances: Res<BufferHandle>,
 	pub bvh_queues: [Res<BufferHandle>; 2],
 	pub meshlet_queue: Res<BufferHandle>,
@@ -64,6 +71,10 @@ pub struct Resources {
 	pub visbuffer: Res<ImageView>,
 	pub debug: Option<DebugRes>,
 	pub res: Vec2<u32>,
+	/// See `RenderInfo::sw_edge_pixels`.
+	pub sw_edge_pixels: f32,
+	/// The stage the rasterizer runs meshlets in: `Mesh`, or `Vertex` without mesh shader support.
+	pub raster: Shader,
 }
 
 impl Resources {
@@ -73,7 +84,7 @@ impl Resources {
 	}
 
 	pub fn instances_mesh(&self, pass: &mut PassBuilder) -> Res<BufferHandle> {
-		pass.reference(self.scene.instances, BufferUsage::read(Shader::Mesh));
+		pass.reference(self.scene.instances, BufferUsage::read(self.raster));
 		self.scene.instances
 	}
 
@@ -83,7 +94,7 @@ impl Resources {
 	}
 
 	pub fn camera_mesh(&self, pass: &mut PassBuilder) -> Res<BufferHandle> {
-		pass.reference(self.camera, BufferUsage::read(Shader::Mesh));
+		pass.reference(self.camera, BufferUsage::read(self.raster));
 		self.camera
 	}
 
@@ -130,7 +141,7 @@ impl Resources {
 			BufferUsage {
 				usages: &[
 					BufferUsageType::IndirectBuffer,
-					BufferUsageType::ShaderStorageRead(Shader::Mesh),
+					BufferUsageType::ShaderStorageRead(self.raster),
 					BufferUsageType::ShaderStorageRead(Shader::Compute),
 				],
 			},
@@ -153,7 +164,7 @@ impl Resources {
 			self.stats,
 			BufferUsage {
 				usages: &[
-					BufferUsageType::ShaderStorageWrite(Shader::Mesh),
+					BufferUsageType::ShaderStorageWrite(self.raster),
 					BufferUsageType::ShaderStorageWrite(Shader::Compute),
 				],
 			},
@@ -179,28 +190,56 @@ pub struct Setup {
 	pub stats: CullStats,
 	hzb: Persist<ImageView>,
 	stats_readback: Persist<BufferHandle>,
+	raster: Shader,
+	/// The depth convention the HZB was last generated in.
+	depth: DepthConvention,
+	/// Cull without occlusion for `DepthPass`, which never writes the visbuffer, so it is only a single texel.
+	depth_only: bool,
 }
 
 impl Setup {
-	pub fn new() -> Self {
+	pub fn new(raster: Shader, depth_only: bool) -> Self {
 		Self {
 			stats: CullStats::default(),
 			hzb: Persist::new(),
 			stats_readback: Persist::new(),
+			raster,
+			depth: DepthConvention::default(),
+			depth_only,
 		}
 	}
 
 	pub fn run<'pass>(
 		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, info: &RenderInfo,
-		hzb_sampler: SamplerId,
+		hzb_gen: &HzbGen,
 	) -> Resources {
 		let scene = rend.get::<VirtualScene>(frame);
-		let camera = rend.get::<CameraScene>(frame);
+		let (camera, camera_changed, depth) = match info.view {
+			Some(v) => (None, v.prev.camera != v.curr.camera, v.depth),
+			None => {
+				let c = rend.get::<CameraScene>(frame);
+				(Some(c.buf), c.prev.camera != c.curr.camera, c.depth)
+			},
+		};
+		let hzb_sampler = hzb_gen.sampler(info.hzb.conservative, depth);
+		let depth_changed = std::mem::replace(&mut self.depth, depth) != depth;
 
 		let mut pass = frame.pass("setup cull buffers");
+		let view = info.view;
+		let camera = camera.unwrap_or_else(|| {
+			pass.resource(
+				BufferDesc::upload(std::mem::size_of::<[GpuCamera; 2]>() as u64),
+				BufferUsage::none(),
+			)
+		});
 
 		let res = info.size;
-		let size = info.size.map(|x| 1 << x.ilog2());
+		// Without occlusion, a single texel of nothing is enough.
+		let size = if self.depth_only {
+			Vec2::one()
+		} else {
+			info.size.map(|x| 1 << x.ilog2())
+		};
 		// TODO: handle world change.
 		let hzb_desc = ImageDesc {
 			size: vk::Extent3D {
@@ -209,11 +248,11 @@ impl Setup {
 				depth: 1,
 			},
 			format: vk::Format::R32_SFLOAT,
-			levels: size.x.max(size.y).ilog2(),
+			levels: info.hzb.levels(size),
 			persist: Some(self.hzb),
 			..Default::default()
 		};
-		let needs_clear = camera.prev.camera != camera.curr.camera || pass.persistent_desc(self.hzb) != Some(hzb_desc);
+		let needs_clear = camera_changed || depth_changed || pass.persistent_desc(self.hzb) != Some(hzb_desc);
 		let hzb = pass.resource(
 			hzb_desc,
 			ImageUsage {
@@ -248,10 +287,15 @@ impl Setup {
 			BufferUsage::transfer_write(),
 		);
 
+		let raster = self.raster;
+		let depth_only = self.depth_only;
+		let grid = info.sample_grid();
+		// Depth-only culling never writes the visbuffer, but still needs one to bind.
+		let size = if depth_only { Vec2::one() } else { res * grid };
 		let desc = ImageDesc {
 			size: vk::Extent3D {
-				width: res.x,
-				height: res.y,
+				width: size.x,
+				height: size.y,
 				depth: 1,
 			},
 			format: vk::Format::R64_UINT,
@@ -281,15 +325,35 @@ impl Setup {
 		});
 
 		pass.build(move |mut pass| {
+			if let Some(v) = view {
+				pass.write(
+					camera,
+					0,
+					&[
+						GpuCamera::new(v.aspect, v.curr, Vec2::zero(), v.depth),
+						GpuCamera::new(v.aspect, v.prev, Vec2::zero(), v.depth),
+					],
+				);
+			}
+			// An HZB of nothing, which never occludes anything.
 			if needs_clear | pass.is_uninit(hzb) {
-				pass.zero(hzb);
+				pass.clear_image(
+					hzb,
+					vk::ClearColorValue {
+						float32: [depth.clear_value(), 0.0, 0.0, 0.0],
+					},
+				);
+			}
+			// The visbuffer stores reverse-Z depth whatever the convention of the camera, so that atomic max keeps the
+			// closest sample and the cleared depth of 0 is the farthest.
+			if !depth_only {
+				pass.clear_image(
+					visbuffer,
+					vk::ClearColorValue {
+						uint32: [u32::MAX, 0, 0, 0],
+					},
+				);
 			}
-			pass.clear_image(
-				visbuffer,
-				vk::ClearColorValue {
-					uint32: [u32::MAX, 0, 0, 0],
-				},
-			);
 			if let Some(d) = debug {
 				pass.zero(d.overdraw);
 				pass.zero(d.hwsw);
@@ -308,14 +372,15 @@ impl Setup {
 			if self.stats.overflow != 0 {
 				error!("Cull queues overflowed");
 			}
-			pass.fill_buffer(stats, 0, offset_of!(self.stats, CullStats, overflow) as _, 4);
+			pass.fill_buffer(stats, 0, 0, std::mem::size_of::<CullStats>());
 		});
 
 		Resources {
 			scene,
-			camera: camera.buf,
+			camera,
 			hzb,
 			hzb_sampler,
+			depth,
 			late_instances,
 			bvh_queues,
 			meshlet_queue,
@@ -324,6 +389,8 @@ impl Setup {
 			visbuffer,
 			debug,
 			res: info.size,
+			sw_edge_pixels: info.sw_edge_pixels,
+			raster,
 		}
 	}
 }
@@ -0,0 +1,160 @@
+//! Forward rendering of `AlphaMode::Blend` materials over an existing image.
+//!
+//! Blended instances are sorted back-to-front by their centers only, so overlapping or self-intersecting transparent
+//! meshes may blend in the wrong order.
+
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, GraphicsPipelineDesc, ShaderInfo},
+	graph::{BufferUsage, BufferUsageType, Frame, ImageUsage, ImageUsageType, Res, Shader},
+	resource::{GpuPtr, ImageView},
+	util::{
+		pass::{Attachment, Load},
+		pipeline::{default_blend, no_cull, simple_blend},
+		render::RenderPass,
+	},
+	Result,
+};
+
+use crate::{
+	debug::mesh::DebugMesh,
+	mesh::{GpuVisBufferReader, RenderOutput},
+	scene::{
+		camera::GpuCamera,
+		virtual_scene::{GpuInstance, GpuTransparentDraw, VirtualScene},
+		WorldRenderer,
+	},
+};
+
+pub struct TransparentPass {
+	pass: RenderPass<PushConstants>,
+	hdr: RenderPass<PushConstants>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	instances: GpuPtr<GpuInstance>,
+	camera: GpuPtr<GpuCamera>,
+	draws: GpuPtr<GpuTransparentDraw>,
+	read: GpuVisBufferReader,
+	draw: u32,
+	_pad: u32,
+}
+
+impl TransparentPass {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: Self::pipeline(device, vk::Format::R8G8B8A8_SRGB)?,
+			hdr: Self::pipeline(device, DebugMesh::HDR_FORMAT)?,
+		})
+	}
+
+	fn pipeline(device: &Device, format: vk::Format) -> Result<RenderPass<PushConstants>> {
+		RenderPass::new(
+			device,
+			GraphicsPipelineDesc {
+				shaders: &[
+					ShaderInfo {
+						shader: "passes.mesh.transparent.vertex",
+						..Default::default()
+					},
+					ShaderInfo {
+						shader: "passes.mesh.transparent.pixel",
+						..Default::default()
+					},
+				],
+				color_attachments: &[format],
+				blend: simple_blend(&[default_blend()]),
+				raster: no_cull(),
+				..Default::default()
+			},
+			true,
+		)
+	}
+
+	/// Blend transparent instances over `target`, which must be at the resolution of the visbuffer in pixels, and
+	/// either 8-bit sRGB or [`DebugMesh::HDR_FORMAT`].
+	pub fn run<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, output: RenderOutput,
+		target: Res<ImageView>,
+	) -> Res<ImageView> {
+		let scene = rend.get::<VirtualScene>(frame);
+		if scene.transparent_count == 0 {
+			return target;
+		}
+
+		let mut pass = frame.timed_pass("transparent");
+		pass.reference(
+			scene.transparent,
+			BufferUsage {
+				usages: &[
+					BufferUsageType::IndirectBuffer,
+					BufferUsageType::ShaderStorageRead(Shader::Vertex),
+					BufferUsageType::ShaderStorageRead(Shader::Fragment),
+				],
+			},
+		);
+		let read = BufferUsage {
+			usages: &[
+				BufferUsageType::ShaderStorageRead(Shader::Vertex),
+				BufferUsageType::ShaderStorageRead(Shader::Fragment),
+			],
+		};
+		pass.reference(output.instances, read);
+		pass.reference(output.camera, read);
+		output.reader.add(&mut pass, Shader::Fragment, false);
+		pass.reference(
+			target,
+			ImageUsage::d2(
+				vk::Format::UNDEFINED,
+				[
+					ImageUsageType::ColorAttachmentRead,
+					ImageUsageType::ColorAttachmentWrite,
+				],
+			),
+		);
+
+		let format = pass.desc(target).format;
+
+		pass.build(move |mut pass| {
+			let push = PushConstants {
+				instances: pass.get(output.instances).ptr(),
+				camera: pass.get(output.camera).ptr(),
+				draws: pass.get(scene.transparent).ptr(),
+				read: output.reader.get(&mut pass),
+				draw: 0,
+				_pad: 0,
+			};
+			let pipeline = if format == DebugMesh::HDR_FORMAT {
+				&self.hdr
+			} else {
+				&self.pass
+			};
+			let mut rpass = pipeline.start(
+				&mut pass,
+				&push,
+				&[Attachment {
+					image: target,
+					load: Load::Load,
+					store: true,
+				}],
+				None,
+			);
+			for draw in 0..scene.transparent_count {
+				rpass.push(0, &PushConstants { draw, ..push });
+				rpass.draw_indirect(
+					scene.transparent,
+					draw as usize * std::mem::size_of::<GpuTransparentDraw>(),
+				);
+			}
+		});
+		target
+	}
+
+	pub unsafe fn destroy(self) {
+		self.pass.destroy();
+		self.hdr.destroy();
+	}
+}
@@ -1,5 +1,6 @@
 use ash::vk;
 use bytemuck::NoUninit;
+use rad_core::Engine;
 use rad_graph::{
 	device::{
 		descriptor::{ImageId, SamplerId, StorageImageId},
@@ -9,19 +10,23 @@ use rad_graph::{
 		SamplerDesc,
 		ShaderInfo,
 	},
-	graph::{BufferUsage, Frame, ImageDesc, ImageUsage, Persist, Res},
+	graph::{BufferDesc, BufferUsage, Frame, ImageDesc, ImageUsage, Persist, Res},
 	resource::{GpuPtr, ImageView},
 	sync::Shader,
-	util::compute::RtPass,
+	util::{compute::RtPass, pipeline::DepthConvention},
 	Result,
 };
 use rand::{thread_rng, RngCore};
 use vek::{Vec2, Vec3};
 
 use crate::{
-	assets::image::{ImageAsset, ImageAssetView},
+	assets::{
+		image::{ImageAsset, ImageAssetView},
+		material::MaterialView,
+	},
+	env::{Environment, GpuEnvironment},
 	scene::{
-		camera::{CameraScene, GpuCamera},
+		camera::{Camera, CameraScene, GpuCamera},
 		light::{GpuLight, LightScene},
 		rt_scene::{GpuRtInstance, RtScene},
 		WorldRenderer,
@@ -29,7 +34,6 @@ use crate::{
 	sky::{GpuSkySampler, SkySampler},
 };
 
-// TODO: reset on world change and edit.
 pub struct PathTracer {
 	pass: RtPass<PushConstants>,
 	sampler: SamplerId,
@@ -37,11 +41,59 @@ pub struct PathTracer {
 	cached: Option<Vec2<u32>>,
 	samples: u32,
 	ggx_e_lut: ImageAssetView,
+	cached_env: GpuEnvironment,
+	cached_settings: Option<Settings>,
+	cached_materials: u64,
+}
+
+/// Everything in [`RenderInfo`] that should restart accumulation when changed.
+#[derive(Copy, Clone)]
+struct Settings {
+	max_bounces: u32,
+	rr_start_depth: u32,
+	firefly_clamp: Option<f32>,
+	aperture: f32,
+	focus_distance: f32,
+	view: Option<Camera>,
+}
+
+impl PartialEq for Settings {
+	fn eq(&self, other: &Self) -> bool {
+		let view = match (self.view, other.view) {
+			(Some(a), Some(b)) => a.approx_eq(&b),
+			(a, b) => a.is_none() && b.is_none(),
+		};
+		self.max_bounces == other.max_bounces
+			&& self.rr_start_depth == other.rr_start_depth
+			&& self.firefly_clamp == other.firefly_clamp
+			&& self.aperture == other.aperture
+			&& self.focus_distance == other.focus_distance
+			&& view
+	}
 }
 
-pub struct RenderInfo {
+pub struct RenderInfo<'a> {
 	pub sky: SkySampler,
+	pub env: Environment<'a>,
 	pub size: Vec2<u32>,
+	/// The maximum number of rays traced per path, so `1` only gives direct lighting.
+	pub max_bounces: u32,
+	/// The bounce after which paths are randomly terminated based on their throughput.
+	pub russian_roulette_start_depth: u32,
+	/// Clamp the luminance of every sample to this value, to get rid of fireflies. This loses energy in bright
+	/// highlights, so should be `None` for reference renders.
+	pub firefly_clamp: Option<f32>,
+	/// The f-stop of a thin lens camera with a full-frame sensor, or `0` for a pinhole camera.
+	pub aperture: f32,
+	/// The distance to the plane in focus, along the view direction.
+	pub focus_distance: f32,
+	/// Render from this view instead of the primary view.
+	pub view: Option<Camera>,
+}
+
+impl RenderInfo<'_> {
+	pub const DEFAULT_MAX_BOUNCES: u32 = 8;
+	pub const DEFAULT_RUSSIAN_ROULETTE_START_DEPTH: u32 = 2;
 }
 
 #[repr(C)]
@@ -51,6 +103,7 @@ struct PushConstants {
 	lights: GpuPtr<GpuLight>,
 	camera: GpuPtr<GpuCamera>,
 	as_: GpuPtr<u8>,
+	primary_as: GpuPtr<u8>,
 	sampler: SamplerId,
 	out: StorageImageId,
 	ggx_e_lut: ImageId,
@@ -59,6 +112,13 @@ struct PushConstants {
 	light_count: u32,
 	sky: GpuSkySampler,
 	_pad: u32,
+	env: GpuPtr<GpuEnvironment>,
+	max_bounces: u32,
+	rr_start_depth: u32,
+	firefly_clamp: f32,
+	aperture: f32,
+	focus_distance: f32,
+	_pad2: u32,
 }
 
 impl PathTracer {
@@ -86,6 +146,10 @@ impl PathTracer {
 							shader: "passes.pt.hit.main",
 							spec: &[],
 						},
+						ShaderInfo {
+							shader: "passes.pt.hit.any_hit",
+							spec: &[],
+						},
 					],
 					groups: &[
 						RtShaderGroup::General(0),
@@ -93,7 +157,7 @@ impl PathTracer {
 						RtShaderGroup::General(2),
 						RtShaderGroup::Triangles {
 							closest_hit: Some(3),
-							any_hit: None,
+							any_hit: Some(4),
 						},
 					],
 					recursion_depth: 1,
@@ -112,24 +176,46 @@ impl PathTracer {
 				},
 			)
 			.unwrap(),
+			cached_env: Environment::Sky.to_gpu(),
+			cached_settings: None,
+			cached_materials: 0,
 		})
 	}
 
 	pub fn run<'pass>(
-		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, info: RenderInfo,
+		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, info: RenderInfo<'pass>,
 	) -> (Res<ImageView>, u32) {
 		let rt = rend.get::<RtScene>(frame);
-		let camera = rend.get::<CameraScene>(frame);
+		let (camera, camera_changed) = match info.view {
+			Some(_) => (None, false),
+			None => {
+				let c = rend.get::<CameraScene>(frame);
+				(Some(c.buf), !c.prev.approx_eq(&c.curr))
+			},
+		};
 		let lights = rend.get::<LightScene>(frame);
 
-		let mut pass = frame.pass("path trace");
+		let mut pass = frame.timed_pass("path trace");
 
 		let read = BufferUsage::read(Shader::RayTracing);
+		let camera = match camera {
+			Some(camera) => {
+				pass.reference(camera, read);
+				camera
+			},
+			None => pass.resource(BufferDesc::upload(std::mem::size_of::<GpuCamera>() as u64), read),
+		};
 		pass.reference(rt.instances, read);
 		pass.reference(rt.as_, read);
-		pass.reference(camera.buf, read);
+		// Thin lens rays don't start at the camera, so they can leave the pinhole frustum. The primary TLAS is culled
+		// against the primary view, so other views can't use it either.
+		let primary = rt.primary.filter(|_| info.aperture == 0.0 && info.view.is_none());
+		if let Some((primary, _)) = primary {
+			pass.reference(primary, read);
+		}
 		pass.reference(lights.buf, read);
 		info.sky.reference(&mut pass, Shader::RayTracing);
+		let env = pass.resource(BufferDesc::upload(std::mem::size_of::<GpuEnvironment>() as u64), read);
 
 		let out = pass.resource(
 			ImageDesc {
@@ -153,20 +239,59 @@ impl PathTracer {
 			}
 		}
 		self.cached = Some(info.size);
+		let gpu_env = info.env.to_gpu();
+		if bytemuck::bytes_of(&gpu_env) != bytemuck::bytes_of(&self.cached_env) {
+			self.samples = 0;
+		}
+		self.cached_env = gpu_env;
+		let settings = Some(Settings {
+			max_bounces: info.max_bounces,
+			rr_start_depth: info.russian_roulette_start_depth,
+			firefly_clamp: info.firefly_clamp,
+			aperture: info.aperture,
+			focus_distance: info.focus_distance,
+			view: info.view,
+		});
+		if settings != self.cached_settings {
+			self.samples = 0;
+		}
+		self.cached_settings = settings;
+
+		// An edited material can be written to the slot its old version freed, leaving every instance unchanged.
+		let materials = Engine::get().asset_context::<MaterialView>().generation();
+		if materials != self.cached_materials {
+			self.samples = 0;
+		}
+		self.cached_materials = materials;
+
+		if rt.updated || lights.updated || camera_changed {
+			self.samples = 0;
+		}
 
 		let s = self.samples;
 		pass.build(move |mut pass| {
-			if pass.is_uninit(out) || camera.prev != camera.curr {
+			if pass.is_uninit(out) {
 				self.samples = 0;
 			}
 
 			let out = pass.get(out);
 			let as_ = pass.get(rt.as_).ptr().offset(rt.as_offset);
+			let primary_as = primary.map_or(as_, |(primary, offset)| pass.get(primary).ptr().offset(offset));
 			let instances = pass.get(rt.instances).ptr();
 			let light_count = lights.count;
 			let lights = pass.get(lights.buf).ptr();
-			let camera = pass.get(camera.buf).ptr();
+			if let Some(view) = info.view {
+				let aspect = out.size.width as f32 / out.size.height as f32;
+				pass.write(
+					camera,
+					0,
+					&[GpuCamera::new(aspect, view, Vec2::zero(), DepthConvention::default())],
+				);
+			}
+			let camera = pass.get(camera).ptr();
 			let sky = info.sky.to_gpu(&mut pass);
+			pass.write(env, 0, &[gpu_env]);
+			let env = pass.get(env).ptr();
 
 			self.pass.trace(
 				&mut pass,
@@ -175,6 +300,7 @@ impl PathTracer {
 					lights,
 					camera,
 					as_,
+					primary_as,
 					sampler: self.sampler,
 					out: out.storage_id.unwrap(),
 					ggx_e_lut: self.ggx_e_lut.image_id(),
@@ -183,6 +309,13 @@ impl PathTracer {
 					light_count,
 					sky,
 					_pad: 0,
+					env,
+					max_bounces: info.max_bounces,
+					rr_start_depth: info.russian_roulette_start_depth,
+					firefly_clamp: info.firefly_clamp.unwrap_or(0.0),
+					aperture: info.aperture,
+					focus_distance: info.focus_distance,
+					_pad2: 0,
 				},
 				out.size.width,
 				out.size.height,
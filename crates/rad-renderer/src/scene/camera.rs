@@ -2,6 +2,7 @@ use bytemuck::NoUninit;
 use rad_graph::{
 	graph::{BufferDesc, BufferUsage, Frame, Res},
 	resource::BufferHandle,
+	util::pipeline::DepthConvention,
 };
 use rad_world::{
 	bevy_ecs::{
@@ -15,6 +16,7 @@ use rad_world::{
 	World,
 };
 use tracing::warn;
+use vek::{Mat4, Quaternion, Vec2, Vec3};
 
 use crate::{
 	components::camera::{CameraComponent, PrimaryViewComponent},
@@ -28,10 +30,15 @@ pub struct GpuCamera {
 	w: f32,
 	h: f32,
 	near: f32,
+	/// Infinity without a far plane.
+	far: f32,
+	jitter: Vec2<f32>,
+	standard_depth: u32,
 }
 
 impl GpuCamera {
-	pub fn new(aspect: f32, camera: Camera) -> Self {
+	/// `jitter` offsets the projection by a fraction of a pixel, in NDC.
+	pub fn new(aspect: f32, camera: Camera, jitter: Vec2<f32>, depth: DepthConvention) -> Self {
 		let h = (camera.camera.fov / 2.0).tan().recip();
 		let w = h / aspect;
 		Self {
@@ -39,6 +46,9 @@ impl GpuCamera {
 			w,
 			h,
 			near: camera.camera.near,
+			far: camera.camera.far.unwrap_or(f32::INFINITY),
+			jitter,
+			standard_depth: (depth == DepthConvention::Standard) as u32,
 		}
 	}
 }
@@ -48,10 +58,15 @@ pub struct CameraScene {
 	pub buf: Res<BufferHandle>,
 	pub prev: Camera,
 	pub curr: Camera,
+	pub depth: DepthConvention,
 }
 
 pub struct CameraSceneInfo {
 	pub aspect: f32,
+	/// The subpixel offset of the projection this frame, in NDC, for temporal antialiasing.
+	pub jitter: Vec2<f32>,
+	/// The depth the projection maps to.
+	pub depth: DepthConvention,
 }
 
 impl GpuScene for CameraScene {
@@ -72,23 +87,156 @@ impl GpuScene for CameraScene {
 		let prev = data.prev;
 		let curr = data.curr;
 		let aspect = input.aspect;
+		let jitter = input.jitter;
+		let depth = input.depth;
+		let prev_jitter = std::mem::replace(&mut data.jitter, jitter);
 		pass.build(move |mut pass| {
-			pass.write(buf, 0, &[GpuCamera::new(aspect, curr), GpuCamera::new(aspect, prev)]);
+			pass.write(
+				buf,
+				0,
+				&[
+					GpuCamera::new(aspect, curr, jitter, depth),
+					GpuCamera::new(aspect, prev, prev_jitter, depth),
+				],
+			);
 		});
-		Self { buf, prev, curr }
+		Self { buf, prev, curr, depth }
 	}
 }
 
+/// A view to render from. The renderer projects with a perspective that is infinite unless the camera has a far plane,
+/// in the view space of `transform`: +X is right, +Y forward, and +Z up.
 #[derive(Copy, Clone, Default, PartialEq)]
 pub struct Camera {
 	pub transform: Transform,
 	pub camera: CameraComponent,
 }
 
+impl Camera {
+	/// A camera at `transform`, with a vertical `fov` in radians.
+	pub fn perspective(transform: Transform, fov: f32, near: f32) -> Self {
+		Self {
+			transform,
+			camera: CameraComponent { fov, near, far: None },
+		}
+	}
+
+	/// The camera with a far plane at `far`, or projecting to infinity if `None`.
+	pub fn with_far(mut self, far: Option<f32>) -> Self {
+		self.camera.far = far;
+		self
+	}
+
+	/// The camera with the world to view matrix `view` and the projection `proj`, both in the view space of the
+	/// renderer, and the aspect ratio `proj` was made for.
+	///
+	/// `proj` must be a symmetric perspective projection like [`Self::projection`], with reverse or standard depth and
+	/// with or without a far plane. The camera can't represent anything else, such as off-axis or jittered projections,
+	/// so those give `None`. The renderer takes the aspect ratio from the size of its output, so it should be
+	/// rendered at the returned aspect ratio to match `proj`.
+	pub fn from_matrices(view: Mat4<f32>, proj: Mat4<f32>) -> Option<(Self, f32)> {
+		let [[w, 0.0, 0.0, 0.0], [0.0, 0.0, h, 0.0], [0.0, a, 0.0, b], [0.0, 1.0, 0.0, 0.0]] = proj.into_row_arrays()
+		else {
+			return None;
+		};
+		if w <= 0.0 || h <= 0.0 {
+			return None;
+		}
+		// Depth is `a + b / y` for view depth `y`, which is 1 at the near plane and 0 at the far plane with reverse-Z,
+		// and the other way around otherwise. Without a far plane, the far depth is never reached.
+		let (near, far) = if a > 0.0 {
+			(-b / a, b / (1.0 - a))
+		} else {
+			(b / (1.0 - a), -b / a)
+		};
+		if !near.is_finite() || near <= 0.0 {
+			return None;
+		}
+		let far = (far.is_finite() && far > near).then_some(far);
+
+		let m = view.inverted();
+		let [x, y, z, p] = [0, 1, 2, 3].map(|i| Vec3::from(m.cols[i]));
+		let scale = Vec3::new(x.magnitude(), y.magnitude(), z.magnitude());
+		let camera = Self::perspective(
+			Transform {
+				position: p,
+				rotation: rotation_of_basis(x / scale.x, y / scale.y, z / scale.z),
+				scale,
+			},
+			2.0 * h.recip().atan(),
+			near,
+		)
+		.with_far(far);
+		Some((camera, h / w))
+	}
+
+	/// Whether `other` is the same view, ignoring float error from recomputing the transform.
+	pub fn approx_eq(&self, other: &Self) -> bool {
+		const EPS: f32 = 1e-5;
+		let (a, b) = (self.transform, other.transform);
+		let far = match (self.camera.far, other.camera.far) {
+			(Some(x), Some(y)) => (x - y).abs() <= EPS * x.abs().max(1.0),
+			(x, y) => x == y,
+		};
+		a.position.distance(b.position) <= EPS * a.position.magnitude().max(1.0)
+			&& a.rotation.dot(b.rotation).abs() >= 1.0 - EPS
+			&& a.scale.distance(b.scale) <= EPS
+			&& (self.camera.fov - other.camera.fov).abs() <= EPS
+			&& (self.camera.near - other.camera.near).abs() <= EPS * self.camera.near.max(1.0)
+			&& far
+	}
+
+	/// The world to view matrix.
+	pub fn view(&self) -> Mat4<f32> { self.transform.into_matrix().inverted() }
+
+	/// The projection the renderer uses for an output with `aspect`, without jitter.
+	pub fn projection(&self, aspect: f32, depth: DepthConvention) -> Mat4<f32> {
+		let h = (self.camera.fov / 2.0).tan().recip();
+		let w = h / aspect;
+		let n = self.camera.near;
+		// `near / far`, which is 0 when projecting to infinity.
+		let r = self.camera.far.map_or(0.0, |f| n / f);
+		let (a, b) = match depth {
+			DepthConvention::Reverse => (-r / (1.0 - r), n / (1.0 - r)),
+			DepthConvention::Standard => (1.0 / (1.0 - r), -n / (1.0 - r)),
+		};
+		#[rustfmt::skip]
+		let m = Mat4::new(
+			w,   0.0, 0.0, 0.0,
+			0.0, 0.0, h,   0.0,
+			0.0, a,   0.0, b,
+			0.0, 1.0, 0.0, 0.0,
+		);
+		m
+	}
+}
+
+/// The rotation taking the axes to the orthonormal basis `x`, `y`, `z`.
+fn rotation_of_basis(x: Vec3<f32>, y: Vec3<f32>, z: Vec3<f32>) -> Quaternion<f32> {
+	// https://www.euclideanspace.com/maths/geometry/rotations/conversions/matrixToQuaternion/
+	let trace = x.x + y.y + z.z;
+	let q = if trace > 0.0 {
+		let s = (trace + 1.0).sqrt() * 2.0;
+		Quaternion::from_xyzw((y.z - z.y) / s, (z.x - x.z) / s, (x.y - y.x) / s, 0.25 * s)
+	} else if x.x > y.y && x.x > z.z {
+		let s = (1.0 + x.x - y.y - z.z).sqrt() * 2.0;
+		Quaternion::from_xyzw(0.25 * s, (y.x + x.y) / s, (z.x + x.z) / s, (y.z - z.y) / s)
+	} else if y.y > z.z {
+		let s = (1.0 + y.y - x.x - z.z).sqrt() * 2.0;
+		Quaternion::from_xyzw((y.x + x.y) / s, 0.25 * s, (z.y + y.z) / s, (z.x - x.z) / s)
+	} else {
+		let s = (1.0 + z.z - x.x - y.y).sqrt() * 2.0;
+		Quaternion::from_xyzw((z.x + x.z) / s, (z.y + y.z) / s, 0.25 * s, (x.y - y.x) / s)
+	};
+	q.normalized()
+}
+
 #[derive(Default)]
 pub struct CameraSceneData {
 	curr: Camera,
 	prev: Camera,
+	/// The jitter of the last frame, for `prev`.
+	jitter: Vec2<f32>,
 }
 impl Resource for CameraSceneData {}
 
@@ -110,3 +258,86 @@ fn find_primary_view(
 		warn!("multiple primary views found, using the first one");
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn camera(far: Option<f32>) -> Camera {
+		Camera::perspective(
+			Transform {
+				position: Vec3::new(3.0, -2.0, 1.5),
+				rotation: Quaternion::rotation_z(0.7) * Quaternion::rotation_x(0.2),
+				scale: Vec3::one(),
+			},
+			1.1,
+			0.05,
+		)
+		.with_far(far)
+	}
+
+	#[test]
+	fn from_matrices_round_trip() {
+		for depth in [DepthConvention::Reverse, DepthConvention::Standard] {
+			for far in [None, Some(250.0)] {
+				let c = camera(far);
+				let (back, aspect) = Camera::from_matrices(c.view(), c.projection(16.0 / 9.0, depth)).unwrap();
+				// Standard depth only keeps the far plane in how far `a` is from 1, so it loses most of its precision.
+				let far_eq = match (back.camera.far, far) {
+					(Some(x), Some(y)) => (x - y).abs() <= 1e-3 * y,
+					(x, y) => x == y,
+				};
+				assert!(far_eq, "{depth:?}, far {far:?}: got {:?}", back.camera.far);
+				let back = Camera {
+					camera: CameraComponent { far, ..back.camera },
+					..back
+				};
+				assert!(back.approx_eq(&c), "{depth:?}, far {far:?}");
+				assert!(
+					(aspect - 16.0 / 9.0).abs() < 1e-5,
+					"{depth:?}, far {far:?}: aspect {aspect}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn projects_near_and_far() {
+		use vek::Vec4;
+
+		// NDC depth of a point `y` in front of the camera.
+		let depth_at = |proj: Mat4<f32>, y: f32| {
+			let p = proj * Vec4::new(0.0, y, 0.0, 1.0);
+			p.z / p.w
+		};
+		for (depth, near_depth, far_depth) in [
+			(DepthConvention::Reverse, 1.0, 0.0),
+			(DepthConvention::Standard, 0.0, 1.0),
+		] {
+			for far in [None, Some(250.0)] {
+				let proj = camera(far).projection(16.0 / 9.0, depth);
+				let n = depth_at(proj, 0.05);
+				// Without a far plane, far away points only approach the far depth.
+				let f = depth_at(proj, far.unwrap_or(1e6));
+				assert!((n - near_depth).abs() < 1e-5, "{depth:?}, far {far:?}: near depth {n}");
+				assert!((f - far_depth).abs() < 1e-4, "{depth:?}, far {far:?}: far depth {f}");
+				// Past the far plane is outside the depth range, so it's clipped.
+				let past = depth_at(proj, 500.0);
+				assert_eq!(
+					far.is_some(),
+					!(0.0..=1.0).contains(&past),
+					"{depth:?}, far {far:?}: depth {past} past the far plane"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn from_matrices_rejects_off_axis() {
+		let c = camera(None);
+		let mut proj = c.projection(1.0, DepthConvention::Reverse);
+		// Shift the image horizontally, like jitter or an off-axis frustum.
+		proj.cols.z.x = 0.1;
+		assert!(Camera::from_matrices(c.view(), proj).is_none());
+	}
+}
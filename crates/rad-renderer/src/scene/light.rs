@@ -23,7 +23,10 @@ use rad_world::{
 use vek::Vec3;
 
 use crate::{
-	components::light::{LightComponent, LightType},
+	components::{
+		environment::EnvironmentComponent,
+		light::{LightComponent, LightType},
+	},
 	scene::{rt_scene::KnownRtInstances, should_scene_sync, GpuScene},
 	util::ResizableBuffer,
 };
@@ -34,6 +37,8 @@ pub struct LightScene {
 	pub count: u32,
 	pub sun_radiance: Vec3<f32>,
 	pub sun_dir: Vec3<f32>,
+	/// Whether any lights were added or changed this frame.
+	pub updated: bool,
 }
 
 impl GpuScene for LightScene {
@@ -73,6 +78,7 @@ impl GpuScene for LightScene {
 		let count = data.light_count;
 		let sun_radiance = data.sun_radiance;
 		let sun_dir = data.sun_dir;
+		let updated = !data.updates.is_empty();
 		pass.build(move |mut pass| {
 			let count = data.updates.len() as u32;
 			pass.write_iter(updates, 0, data.updates.drain(..));
@@ -96,6 +102,7 @@ impl GpuScene for LightScene {
 			count,
 			sun_radiance,
 			sun_dir,
+			updated,
 		}
 	}
 }
@@ -131,6 +138,10 @@ pub struct LightSceneData {
 	light_count: u32,
 	sun_radiance: Vec3<f32>,
 	sun_dir: Vec3<f32>,
+	/// The index and own direction of the directional light.
+	sun: Option<(u32, Vec3<f32>)>,
+	/// The sun direction of the environment, replacing the direction of the directional light.
+	sun_dir_override: Option<Vec3<f32>>,
 }
 impl Resource for LightSceneData {}
 
@@ -160,29 +171,43 @@ impl LightSceneData {
 			light_count: 0,
 			sun_radiance: Vec3::zero(),
 			sun_dir: -Vec3::unit_z(),
+			sun: None,
+			sun_dir_override: None,
 		}
 	}
 
 	fn push_light(&mut self, index: u32, t: &Transform, l: &LightComponent) {
+		match l.ty {
+			LightType::Point => self.updates.push(GpuLightUpdate {
+				index,
+				light: GpuLight {
+					ty: GpuLightType::Point,
+					radiance: l.radiance,
+					pos_or_dir: t.position,
+				},
+			}),
+			LightType::Directional => {
+				self.sun = Some((index, t.rotation * -Vec3::unit_z()));
+				self.sun_radiance = l.radiance;
+				self.push_sun();
+			},
+		}
+	}
+
+	/// Push the directional light, pointing in the overridden direction if there is one.
+	fn push_sun(&mut self) {
+		let Some((index, dir)) = self.sun else {
+			return;
+		};
+		self.sun_dir = self.sun_dir_override.unwrap_or(dir);
 		self.updates.push(GpuLightUpdate {
 			index,
 			light: GpuLight {
-				ty: match l.ty {
-					LightType::Point => GpuLightType::Point,
-					LightType::Directional => GpuLightType::Directional,
-				},
-				radiance: l.radiance,
-				pos_or_dir: match l.ty {
-					LightType::Point => t.position,
-					LightType::Directional => t.rotation * -Vec3::unit_z(),
-				},
+				ty: GpuLightType::Directional,
+				radiance: self.sun_radiance,
+				pos_or_dir: self.sun_dir,
 			},
 		});
-
-		if matches!(l.ty, LightType::Directional) {
-			self.sun_radiance = l.radiance;
-			self.sun_dir = t.rotation * -Vec3::unit_z();
-		}
 	}
 
 	fn push_emissive(&mut self, index: u32, mesh_index: u32) {
@@ -207,8 +232,25 @@ fn sync_lights(
 	mut r: ResMut<LightSceneData>, mut cmd: Commands,
 	unknown_punctual: Query<(Entity, &Transform, &LightComponent), Without<KnownLight>>,
 	unknown_emissive: Query<(Entity, &KnownRtInstances), Without<KnownLight>>,
-	_: Query<(&Transform, &LightComponent, &KnownLight), Or<(Changed<Transform>, Changed<LightComponent>)>>,
+	changed: Query<(&Transform, &LightComponent, &KnownLight), Or<(Changed<Transform>, Changed<LightComponent>)>>,
+	env: Query<&EnvironmentComponent>,
 ) {
+	let sun_dir = env
+		.iter()
+		.next()
+		.and_then(|e| e.sun_dir)
+		.and_then(|d| d.try_normalized());
+	if sun_dir != r.sun_dir_override {
+		r.sun_dir_override = sun_dir;
+		r.push_sun();
+	}
+
+	// Edited punctual lights keep their index.
+	for (t, l, k) in changed.iter() {
+		if let Some(&index) = k.0.first() {
+			r.push_light(index, t, l);
+		}
+	}
 	for (e, t, l) in unknown_punctual.iter() {
 		let index = r.light_count;
 		r.light_count += 1;
@@ -216,9 +258,8 @@ fn sync_lights(
 		cmd.entity(e).insert(KnownLight(vec![index]));
 	}
 	for (e, m) in unknown_emissive.iter() {
-		let m = m.0.iter().map(|(i, v)| (i, &v.material));
 		let mut inner = Vec::new();
-		for (&i, m) in m {
+		for (i, m) in m.materials() {
 			if m.emissive_factor == Vec3::zero() {
 				continue;
 			}
@@ -5,13 +5,19 @@ use std::{
 
 use bytemuck::NoUninit;
 use hashbrown::hash_map::Entry;
+use rad_core::asset::{
+	aref::{ARef, LARef},
+	Asset,
+	AssetView,
+};
 use rad_graph::{
 	arena::Arena,
 	graph::{ArenaMap, ArenaSet, Frame},
 };
 use rad_world::{
 	bevy_ecs::{
-		component::ComponentId,
+		component::{Component, ComponentId, StorageType},
+		query::With,
 		system::{Res, Resource},
 		world::unsafe_world_cell::UnsafeWorldCell,
 	},
@@ -19,7 +25,13 @@ use rad_world::{
 	transform::Transform,
 	World,
 };
-use vek::{Quaternion, Vec3};
+use tracing::warn;
+use vek::{Aabb, Quaternion, Vec3};
+
+use crate::{
+	assets::{material::MaterialView, mesh::Mesh},
+	components::mesh::MeshComponent,
+};
 
 pub mod camera;
 pub mod light;
@@ -53,6 +65,21 @@ impl From<Transform> for GpuTransform {
 	}
 }
 
+/// The world-space bounds of `aabb` transformed by `t`.
+fn transform_aabb(t: &Transform, aabb: Aabb<f32>) -> Aabb<f32> {
+	let mat = t.into_matrix();
+	let mut out = Aabb::new_empty(mat.mul_point(aabb.min));
+	for i in 1..8 {
+		let c = Vec3::new(
+			if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+			if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+			if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+		);
+		out.expand_to_contain_point(mat.mul_point(c));
+	}
+	out
+}
+
 #[derive(Default)]
 #[repr(transparent)]
 struct SceneRunCondition<T: GpuScene> {
@@ -65,6 +92,158 @@ fn should_scene_sync<T: GpuScene>(cond: Option<Res<SceneRunCondition<T>>>) -> bo
 	cond.map(|c| c.run).unwrap_or(false)
 }
 
+/// The meshes of an entity that are loading in the background, with the materials overriding theirs. The entity is
+/// added to the scene once all of them are done. Until then, the scene has nothing to draw for it, so views should
+/// draw a placeholder at each of the [`placeholders`](Self::placeholders) instead.
+pub struct PendingMeshes<T: AssetView>(Vec<(ARef<T>, Option<ARef<MaterialView>>)>);
+impl<T: AssetView> Component for PendingMeshes<T> {
+	const STORAGE_TYPE: StorageType = StorageType::Table;
+}
+
+impl<T: AssetView> PendingMeshes<T>
+where
+	T::Base: Asset<Root = Mesh> + Send,
+{
+	fn start(m: &MeshComponent) -> Self {
+		let this = Self::unloaded(m);
+		for (r, _) in this.0.iter() {
+			r.load_in_background();
+		}
+		this
+	}
+
+	/// Like [`Self::start`] for every component in `ms`, but loads all of their meshes in one batch with
+	/// [`AssetView::load_many`].
+	fn start_all<'a>(ms: impl IntoIterator<Item = &'a MeshComponent>) -> Vec<Self> {
+		let out: Vec<_> = ms.into_iter().map(Self::unloaded).collect();
+		ARef::load_many_in_background(out.iter().flat_map(|x| x.0.iter().map(|(r, _)| r)));
+		out
+	}
+
+	/// References to the meshes of `m`, with their material overrides loading in the background.
+	fn unloaded(m: &MeshComponent) -> Self {
+		Self(
+			m.inner
+				.iter()
+				.enumerate()
+				.map(|(i, &id)| {
+					let r = ARef::unloaded(id);
+					let material = m.material_override(i).map(|id| {
+						let r = ARef::unloaded(id);
+						r.load_in_background();
+						r
+					});
+					(r, material)
+				})
+				.collect(),
+		)
+	}
+
+	/// The loaded meshes and their material overrides once none are loading anymore, leaving out the meshes that
+	/// failed to load. Meshes whose override failed to load keep their own material.
+	fn ready(&self) -> Option<Vec<(LARef<T>, Option<LARef<MaterialView>>)>> {
+		let mut out = Vec::with_capacity(self.0.len());
+		for (r, material) in self.0.iter() {
+			let material = match material {
+				Some(m) => match m.try_loaded() {
+					Some(x) => Some(x),
+					None if m.failed() => None,
+					None => return None,
+				},
+				None => None,
+			};
+			match r.try_loaded() {
+				Some(x) => out.push((x, material)),
+				None if r.failed() => {},
+				None => return None,
+			}
+		}
+		Some(out)
+	}
+}
+
+impl<T: AssetView> PendingMeshes<T> {
+	/// The transforms of the entities in `world` whose meshes are still loading.
+	pub fn placeholders(world: &mut World) -> Vec<Transform> {
+		world
+			.query_filtered::<&Transform, With<Self>>()
+			.iter(world)
+			.copied()
+			.collect()
+	}
+}
+
+/// Load the material overriding mesh `i` of `m`, if it has one. The mesh keeps its own material if it fails to load.
+fn load_material_override(m: &MeshComponent, i: usize) -> Option<LARef<MaterialView>> {
+	let id = m.material_override(i)?;
+	ARef::loaded(id)
+		.map_err(|e| warn!("failed to load material {:?}: {:?}", id, e))
+		.ok()
+}
+
+/// Swap in a reloaded view, keeping the old one if it fails to load. Returns whether it changed.
+fn reload<T: AssetView>(view: &mut LARef<T>) -> bool {
+	match view.reload() {
+		Some(Ok(new)) => {
+			*view = new;
+			true
+		},
+		Some(Err(e)) => {
+			warn!("failed to reload asset {:?}: {:?}", view.id(), e);
+			false
+		},
+		None => false,
+	}
+}
+
+/// Swap in a reloaded material override, keeping the old one if it fails to load. Returns whether it changed.
+fn reload_material_override(material: &mut Option<LARef<MaterialView>>) -> bool {
+	material.as_mut().is_some_and(reload)
+}
+
+/// How many meshes of a world have loaded, for showing progress while it loads in the background. Meshes load the
+/// materials and images they use, so those are counted as part of their mesh.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct LoadProgress {
+	/// Meshes that are loaded, or failed to load.
+	pub loaded: u32,
+	pub total: u32,
+}
+
+impl LoadProgress {
+	/// The progress of loading the `T` views of every mesh in `world`.
+	pub fn of<T: AssetView>(world: &mut World) -> Self
+	where
+		T::Base: Asset<Root = Mesh>,
+	{
+		let total = world
+			.query::<&MeshComponent>()
+			.iter(world)
+			.map(|m| m.inner.len() as u32)
+			.sum();
+		let pending = world
+			.query::<&PendingMeshes<T>>()
+			.iter(world)
+			.flat_map(|p| p.0.iter())
+			.filter(|(r, _)| r.try_loaded().is_none() && !r.failed())
+			.count() as u32;
+		Self {
+			loaded: total - pending.min(total),
+			total,
+		}
+	}
+
+	pub fn ratio(&self) -> f32 {
+		if self.total == 0 {
+			1.0
+		} else {
+			self.loaded as f32 / self.total as f32
+		}
+	}
+
+	pub fn done(&self) -> bool { self.loaded == self.total }
+}
+
 // TODO: i think run conditions have one frame of latency.
 pub struct WorldRenderer<'pass, 'graph> {
 	world: UnsafeWorldCell<'pass>,
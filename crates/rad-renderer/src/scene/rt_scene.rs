@@ -1,5 +1,3 @@
-use std::sync::Mutex;
-
 use ash::vk;
 use bytemuck::NoUninit;
 use rad_core::{
@@ -15,10 +13,10 @@ use rad_graph::{
 };
 use rad_world::{
 	bevy_ecs::{
-		batching::BatchingStrategy,
+		change_detection::Ref,
 		component::{Component, StorageType},
 		entity::Entity,
-		query::{Changed, Or, Without},
+		query::{Changed, With, Without},
 		schedule::IntoSystemConfigs,
 		system::{Commands, Query, ResMut, Resource},
 	},
@@ -28,14 +26,28 @@ use rad_world::{
 	World,
 };
 use tracing::warn;
+use vek::{Aabb, Vec3, Vec4};
 
 use crate::{
 	assets::{
-		material::GpuMaterial,
+		material::{GpuMaterial, MaterialView},
 		mesh::{GpuVertex, RaytracingMeshView},
 	},
-	components::mesh::MeshComponent,
-	scene::{should_scene_sync, GpuScene, GpuTransform},
+	components::{
+		camera::{CameraComponent, PrimaryViewComponent},
+		mesh::MeshComponent,
+	},
+	scene::{
+		camera::{Camera, CameraSceneInfo},
+		load_material_override,
+		reload,
+		reload_material_override,
+		should_scene_sync,
+		transform_aabb,
+		GpuScene,
+		GpuTransform,
+		PendingMeshes,
+	},
 	util::ResizableBuffer,
 };
 
@@ -44,6 +56,12 @@ pub struct RtScene {
 	pub instances: Res<BufferHandle>,
 	pub as_: Res<BufferHandle>,
 	pub as_offset: u64,
+	/// The TLAS and its offset for primary rays, with only the instances in the view frustum. This is only culled
+	/// while the primary view stays still, and is `None` otherwise. Every other ray must use the full TLAS, as
+	/// off-screen instances still cast shadows and show up in reflections.
+	pub primary: Option<(Res<BufferHandle>, u64)>,
+	/// Whether any instances were added or changed this frame.
+	pub updated: bool,
 }
 
 #[repr(C)]
@@ -66,8 +84,18 @@ struct PushConstants {
 	_pad: u32,
 }
 
+#[derive(Copy, Clone, NoUninit)]
+#[repr(C)]
+struct CullPushConstants {
+	src: GpuPtr<()>,
+	dst: GpuPtr<()>,
+	visible: GpuPtr<u32>,
+	count: u32,
+	_pad: u32,
+}
+
 impl GpuScene for RtScene {
-	type In = ();
+	type In = CameraSceneInfo;
 	type Res = RtSceneData;
 
 	fn add_to_world(world: &mut World, tick: &mut Tick) {
@@ -75,16 +103,34 @@ impl GpuScene for RtScene {
 		tick.add_systems(TickStage::Render, sync_rt_scene.run_if(should_scene_sync::<Self>));
 	}
 
-	fn update<'pass>(frame: &mut Frame<'pass, '_>, data: &'pass mut RtSceneData, _: &Self::In) -> Self {
+	fn update<'pass>(frame: &mut Frame<'pass, '_>, data: &'pass mut RtSceneData, input: &Self::In) -> Self {
 		let RtSceneData {
 			update,
+			cull,
 			instances,
 			as_,
 			as_instances,
 			instance_count,
 			updates,
+			built_count,
+			rebuild,
+			bounds,
+			view,
+			view_static,
+			primary,
+			edited,
+			..
 		} = data;
 		let count = *instance_count;
+		// Moving instances only needs a refit, but new instances or BLASes need a full build.
+		let mode = if *rebuild || *built_count != count {
+			vk::BuildAccelerationStructureModeKHR::BUILD
+		} else {
+			vk::BuildAccelerationStructureModeKHR::UPDATE
+		};
+		let build = !updates.is_empty() || mode == vk::BuildAccelerationStructureModeKHR::BUILD;
+		*rebuild = false;
+		*built_count = count;
 
 		let tinstances = instances
 			.reserve(
@@ -101,6 +147,8 @@ impl GpuScene for RtScene {
 			)
 			.unwrap();
 
+		let updated = !updates.is_empty();
+		let edited = std::mem::take(edited);
 		let mut pass = frame.pass("update rt scene");
 		let update_buf = pass.resource(
 			BufferDesc::upload(std::mem::size_of::<GpuRtInstanceUpdate>() as u64 * updates.len() as u64),
@@ -147,97 +195,263 @@ impl GpuScene for RtScene {
 			);
 		});
 
-		let geo = [vk::AccelerationStructureGeometryKHR::default()
-			.geometry_type(vk::GeometryTypeKHR::INSTANCES)
-			.geometry(vk::AccelerationStructureGeometryDataKHR {
-				instances: vk::AccelerationStructureGeometryInstancesDataKHR::default()
-					.array_of_pointers(false)
-					.data(vk::DeviceOrHostAddressConstKHR {
-						device_address: as_instances.inner.ptr::<()>().addr(),
-					}),
-			})];
-		let info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
-			.ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
-			.flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
-			.mode(vk::BuildAccelerationStructureModeKHR::BUILD)
-			.geometries(&geo);
-		let mut sinfo = vk::AccelerationStructureBuildSizesInfoKHR::default();
-		unsafe {
-			frame.device().as_ext().get_acceleration_structure_build_sizes(
-				vk::AccelerationStructureBuildTypeKHR::DEVICE,
-				&info,
-				&[count],
-				&mut sinfo,
-			);
-		}
-
-		let mut curr_size = as_.size();
-		if sinfo.acceleration_structure_size > curr_size {
-			if curr_size == 0 {
-				curr_size = 1024;
-			}
-			while sinfo.acceleration_structure_size > curr_size {
-				curr_size *= 2;
-			}
-			let old = std::mem::replace(
-				as_,
-				AS::create(
-					frame.device(),
-					ASDesc {
-						name: "tlas",
-						flags: vk::AccelerationStructureCreateFlagsKHR::empty(),
-						ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
-						size: curr_size,
-					},
-				)
-				.unwrap(),
-			);
-			frame.delete(old);
-		}
-
-		let mut pass = frame.pass("build rt scene tlas");
-		pass.reference(
+		let as_buf = build_tlas(
+			frame,
+			"build rt scene tlas",
+			as_,
 			as_instances_h,
-			BufferUsage {
-				usages: &[BufferUsageType::AccelerationStructureBuildRead],
-			},
+			as_instances.inner.ptr::<()>().addr(),
+			count,
+			build.then_some(mode),
 		);
-		let scratch = pass.resource(
-			BufferDesc::gpu(sinfo.build_scratch_size),
-			BufferUsage {
-				usages: &[BufferUsageType::AccelerationStructureBuildScratch],
+
+		// A moving camera would change the culled instances every frame, so it traces the full TLAS instead.
+		let primary = match view {
+			Some(view) if *view_static => {
+				let planes = frustum_planes(view, input.aspect);
+				Some(primary.update(
+					frame,
+					cull,
+					as_instances_h,
+					bounds.iter().map(|aabb| planes.iter().all(|p| !outside(p, aabb))),
+					mode == vk::BuildAccelerationStructureModeKHR::BUILD,
+					updated,
+				))
 			},
-		);
-		let as_buf = pass.resource(
-			ExternalBuffer::new(as_.inner()),
-			BufferUsage {
-				usages: &[BufferUsageType::AccelerationStructureBuildWrite],
+			_ => {
+				primary.visible = None;
+				None
 			},
-		);
-		let dst = as_.handle();
-		pass.build(move |mut pass| unsafe {
-			pass.device.as_ext().cmd_build_acceleration_structures(
-				pass.buf,
-				&[vk::AccelerationStructureBuildGeometryInfoKHR::default()
-					.ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
-					.flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
-					.mode(vk::BuildAccelerationStructureModeKHR::BUILD)
-					.geometries(&geo)
-					.dst_acceleration_structure(dst)
-					.scratch_data(vk::DeviceOrHostAddressKHR {
-						device_address: pass.get(scratch).ptr::<u8>().addr(),
-					})],
-				&[&[vk::AccelerationStructureBuildRangeInfoKHR::default()
-					.primitive_count(count)
-					.primitive_offset(0)]],
-			);
-		});
+		};
 
 		Self {
 			instances,
 			as_: as_buf,
 			as_offset: as_.addr() - as_.buf_handle().addr,
+			primary,
+			updated: updated || edited,
+		}
+	}
+}
+
+/// Build or refit the TLAS `as_` over the first `count` instances of `instances`, growing it if it's too small. The
+/// build is skipped if `mode` is `None`.
+fn build_tlas(
+	frame: &mut Frame, name: &'static str, as_: &mut AS, instances: Res<BufferHandle>, instances_addr: u64, count: u32,
+	mode: Option<vk::BuildAccelerationStructureModeKHR>,
+) -> Res<BufferHandle> {
+	let geo = [vk::AccelerationStructureGeometryKHR::default()
+		.geometry_type(vk::GeometryTypeKHR::INSTANCES)
+		.geometry(vk::AccelerationStructureGeometryDataKHR {
+			instances: vk::AccelerationStructureGeometryInstancesDataKHR::default()
+				.array_of_pointers(false)
+				.data(vk::DeviceOrHostAddressConstKHR {
+					device_address: instances_addr,
+				}),
+		})];
+	let flags = vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD
+		| vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE;
+	let info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+		.ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+		.flags(flags)
+		.mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+		.geometries(&geo);
+	let mut sinfo = vk::AccelerationStructureBuildSizesInfoKHR::default();
+	unsafe {
+		frame.device().as_ext().get_acceleration_structure_build_sizes(
+			vk::AccelerationStructureBuildTypeKHR::DEVICE,
+			&info,
+			&[count],
+			&mut sinfo,
+		);
+	}
+
+	let mut curr_size = as_.size();
+	if sinfo.acceleration_structure_size > curr_size {
+		if curr_size == 0 {
+			curr_size = 1024;
 		}
+		while sinfo.acceleration_structure_size > curr_size {
+			curr_size *= 2;
+		}
+		let old = std::mem::replace(
+			as_,
+			AS::create(
+				frame.device(),
+				ASDesc {
+					name: "tlas",
+					flags: vk::AccelerationStructureCreateFlagsKHR::empty(),
+					ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+					size: curr_size,
+				},
+			)
+			.unwrap(),
+		);
+		frame.delete(old);
+	}
+
+	let mut pass = frame.pass(name);
+	pass.reference(
+		instances,
+		BufferUsage {
+			usages: &[BufferUsageType::AccelerationStructureBuildRead],
+		},
+	);
+	let scratch_size = if mode == Some(vk::BuildAccelerationStructureModeKHR::UPDATE) {
+		sinfo.update_scratch_size
+	} else {
+		sinfo.build_scratch_size
+	};
+	let scratch = pass.resource(
+		BufferDesc::gpu(scratch_size),
+		BufferUsage {
+			usages: &[BufferUsageType::AccelerationStructureBuildScratch],
+		},
+	);
+	let as_buf = pass.resource(
+		ExternalBuffer::new(as_.inner()),
+		BufferUsage {
+			usages: &[BufferUsageType::AccelerationStructureBuildWrite],
+		},
+	);
+	let dst = as_.handle();
+	pass.build(move |mut pass| unsafe {
+		let Some(mode) = mode else {
+			return;
+		};
+		let src = if mode == vk::BuildAccelerationStructureModeKHR::UPDATE {
+			dst
+		} else {
+			vk::AccelerationStructureKHR::null()
+		};
+		pass.device.as_ext().cmd_build_acceleration_structures(
+			pass.buf,
+			&[vk::AccelerationStructureBuildGeometryInfoKHR::default()
+				.ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+				.flags(flags)
+				.mode(mode)
+				.geometries(&geo)
+				.src_acceleration_structure(src)
+				.dst_acceleration_structure(dst)
+				.scratch_data(vk::DeviceOrHostAddressKHR {
+					device_address: pass.get(scratch).ptr::<u8>().addr(),
+				})],
+			&[&[vk::AccelerationStructureBuildRangeInfoKHR::default()
+				.primitive_count(count)
+				.primitive_offset(0)]],
+		);
+	});
+	as_buf
+}
+
+/// The world-space side planes of the pinhole frustum of `view`, as `(normal, distance)` with normals pointing
+/// outwards. Primary rays all start at the camera, so neither a near nor a far plane is needed.
+fn frustum_planes(view: &Camera, aspect: f32) -> [Vec4<f32>; 4] {
+	let h = (view.camera.fov / 2.0).tan().recip();
+	let w = h / aspect;
+	// The view direction is +Y, with +Z up.
+	let normals = [
+		Vec3::new(w, -1.0, 0.0),
+		Vec3::new(-w, -1.0, 0.0),
+		Vec3::new(0.0, -1.0, h),
+		Vec3::new(0.0, -1.0, -h),
+	];
+	normals.map(|n| {
+		let n = (view.transform.rotation * n).normalized();
+		Vec4::from_direction(n).with_w(n.dot(view.transform.position))
+	})
+}
+
+/// Whether `aabb` is entirely outside of `plane`.
+fn outside(plane: &Vec4<f32>, aabb: &Aabb<f32>) -> bool {
+	let n = Vec3::from(*plane);
+	let center = aabb.center();
+	let extent = aabb.half_size();
+	n.dot(center) - plane.w - n.map(f32::abs).dot(Vec3::from(extent)) > 0.0
+}
+
+/// A TLAS over only the instances that intersect the view frustum, for primary rays.
+struct CulledTlas {
+	as_: AS,
+	as_instances: ResizableBuffer,
+	/// The instances in the last build, or `None` if it must be rebuilt.
+	visible: Option<Vec<u32>>,
+}
+
+impl CulledTlas {
+	/// Cull the instances of the full TLAS in `src`, keeping the ones `visible` yields `true` for, and build the TLAS
+	/// over them if anything changed.
+	fn update<'pass>(
+		&'pass mut self, frame: &mut Frame<'pass, '_>, cull: &'pass ComputePass<CullPushConstants>,
+		src: Res<BufferHandle>, visible: impl Iterator<Item = bool>, rebuild: bool, updated: bool,
+	) -> (Res<BufferHandle>, u64) {
+		let visible: Vec<_> = visible.enumerate().filter_map(|(i, v)| v.then_some(i as u32)).collect();
+		let mode = if rebuild || Some(&visible) != self.visible.as_ref() {
+			vk::BuildAccelerationStructureModeKHR::BUILD
+		} else {
+			vk::BuildAccelerationStructureModeKHR::UPDATE
+		};
+		let build = updated || mode == vk::BuildAccelerationStructureModeKHR::BUILD;
+		let count = visible.len() as u32;
+		let visible = &*self.visible.insert(visible);
+
+		let resized = self
+			.as_instances
+			.reserve(
+				frame,
+				"resize culled rt scene as",
+				std::mem::size_of::<vk::AccelerationStructureInstanceKHR>() as u64 * count as u64,
+			)
+			.unwrap();
+		let mut pass = frame.pass("cull rt scene");
+		pass.reference(src, BufferUsage::read(Shader::Compute));
+		let indices = pass.resource(
+			BufferDesc::upload(std::mem::size_of::<u32>() as u64 * count as u64),
+			BufferUsage::read(Shader::Compute),
+		);
+		let dst = match resized {
+			Some(dst) => {
+				pass.reference(dst, BufferUsage::write(Shader::Compute));
+				dst
+			},
+			None => pass.resource(
+				ExternalBuffer::new(&self.as_instances.inner),
+				BufferUsage::write(Shader::Compute),
+			),
+		};
+		pass.build(move |mut pass| {
+			if !build {
+				return;
+			}
+			pass.write(indices, 0, visible.as_slice());
+			let src = pass.get(src).ptr();
+			let dst = pass.get(dst).ptr();
+			let visible = pass.get(indices).ptr();
+			cull.dispatch(
+				&mut pass,
+				&CullPushConstants {
+					src,
+					dst,
+					visible,
+					count,
+					_pad: 0,
+				},
+				count.div_ceil(64),
+				1,
+				1,
+			);
+		});
+
+		let as_buf = build_tlas(
+			frame,
+			"build culled rt scene tlas",
+			&mut self.as_,
+			dst,
+			self.as_instances.inner.ptr::<()>().addr(),
+			count,
+			build.then_some(mode),
+		);
+		(as_buf, self.as_.addr() - self.as_.buf_handle().addr)
 	}
 }
 
@@ -252,11 +466,27 @@ struct GpuRtInstanceUpdate {
 
 pub struct RtSceneData {
 	update: ComputePass<PushConstants>,
+	cull: ComputePass<CullPushConstants>,
 	instances: ResizableBuffer,
 	as_: AS,
 	as_instances: ResizableBuffer,
 	instance_count: u32,
 	updates: Vec<GpuRtInstanceUpdate>,
+	/// The instance count of the last TLAS build.
+	built_count: u32,
+	/// Whether the TLAS must be fully rebuilt, instead of updated with new transforms.
+	rebuild: bool,
+	/// The entity of every instance.
+	entities: Vec<Entity>,
+	/// The world-space bounds of every instance.
+	bounds: Vec<Aabb<f32>>,
+	/// The primary view, and whether it's the same as last frame.
+	view: Option<Camera>,
+	view_static: bool,
+	primary: CulledTlas,
+	/// Whether the mesh component of a known instance was edited since the last update. Edits aren't applied yet, but
+	/// still count as an update so that accumulated results restart.
+	edited: bool,
 }
 impl Resource for RtSceneData {}
 
@@ -272,6 +502,14 @@ impl RtSceneData {
 				},
 			)
 			.unwrap(),
+			cull: ComputePass::new(
+				dev,
+				ShaderInfo {
+					shader: "asset.scene.cull_rt",
+					spec: &[],
+				},
+			)
+			.unwrap(),
 			instances: ResizableBuffer::new(dev, "rt scene", std::mem::size_of::<GpuRtInstance>() as u64 * 1000)
 				.unwrap(),
 			as_: AS::default(),
@@ -283,24 +521,56 @@ impl RtSceneData {
 			.unwrap(),
 			instance_count: 0,
 			updates: Vec::new(),
+			built_count: 0,
+			rebuild: true,
+			entities: Vec::new(),
+			bounds: Vec::new(),
+			view: None,
+			view_static: false,
+			edited: false,
+			primary: CulledTlas {
+				as_: AS::default(),
+				as_instances: ResizableBuffer::new(
+					dev,
+					"culled rt as instances",
+					std::mem::size_of::<vk::AccelerationStructureInstanceKHR>() as u64 * 1000,
+				)
+				.unwrap(),
+				visible: None,
+			},
 		}
 	}
+
+	/// The entity an instance belongs to.
+	pub fn entity(&self, instance: u32) -> Option<Entity> { self.entities.get(instance as usize).copied() }
 }
 
-fn map_instance(t: &Transform, m: &LARef<RaytracingMeshView>) -> (GpuRtInstance, u64) {
+fn map_instance(
+	t: &Transform, m: &LARef<RaytracingMeshView>, material: Option<&LARef<MaterialView>>,
+) -> (GpuRtInstance, u64) {
 	(
 		GpuRtInstance {
 			transform: (*t).into(),
 			raw_mesh: m.buffer.ptr(),
 			raw_vertex_count: m.vertex_count,
 			raw_tri_count: m.tri_count,
-			material: m.material.gpu_ptr(),
+			material: material.unwrap_or(&m.material).gpu_ptr(),
 		},
 		m.as_.addr(),
 	)
 }
 
-pub struct KnownRtInstances(pub Vec<(u32, LARef<RaytracingMeshView>)>);
+/// The index, mesh, and material override of every instance of an entity.
+pub struct KnownRtInstances(pub Vec<(u32, LARef<RaytracingMeshView>, Option<LARef<MaterialView>>)>);
+
+impl KnownRtInstances {
+	/// The index and material of every instance, with material overrides applied.
+	pub fn materials(&self) -> impl Iterator<Item = (u32, &LARef<MaterialView>)> {
+		self.0
+			.iter()
+			.map(|(index, view, material)| (*index, material.as_ref().unwrap_or(&view.material)))
+	}
+}
 impl Component for KnownRtInstances {
 	const STORAGE_TYPE: StorageType = StorageType::Table;
 }
@@ -308,40 +578,117 @@ impl Component for KnownRtInstances {
 // TODO: edits and deletion.
 fn sync_rt_scene(
 	mut r: ResMut<RtSceneData>, mut cmd: Commands,
-	unknown: Query<(Entity, &Transform, &MeshComponent), Without<KnownRtInstances>>,
-	_: Query<(&Transform, &MeshComponent, &KnownRtInstances), Or<(Changed<Transform>, Changed<MeshComponent>)>>,
+	unknown: Query<
+		(Entity, &Transform, &MeshComponent),
+		(Without<KnownRtInstances>, Without<PendingMeshes<RaytracingMeshView>>),
+	>,
+	pending: Query<(Entity, &Transform, &PendingMeshes<RaytracingMeshView>)>,
+	mut known: Query<(Ref<Transform>, &mut KnownRtInstances)>,
+	views: Query<(&Transform, &CameraComponent), With<PrimaryViewComponent>>,
+	edited: Query<(), (With<KnownRtInstances>, Changed<MeshComponent>)>,
 ) {
-	let cache = Mutex::new(Vec::new());
-	unknown
-		.par_iter()
-		.batching_strategy(BatchingStrategy::fixed(1))
-		.for_each(|(e, t, m)| {
-			let x: Vec<_> = m
-				.inner
-				.iter()
-				.filter_map(|&m| {
-					ARef::loaded(m)
-						.map_err(|e| warn!("failed to load mesh {:?}: {:?}", m, e))
-						.ok()
-				})
-				.collect();
-			cache.lock().unwrap().push((e, t, x));
-		});
+	r.edited |= !edited.is_empty();
+
+	let camera = views
+		.iter()
+		.next()
+		.map(|(&transform, &camera)| Camera { transform, camera });
+	r.view_static = camera.is_some() && camera == r.view;
+	r.view = camera;
+
+	for (t, mut k) in known.iter_mut() {
+		// Moved instances, which only need a TLAS update.
+		if t.is_changed() && !t.is_added() {
+			for (index, view, material) in k.0.iter() {
+				r.bounds[*index as usize] = transform_aabb(&t, view.aabb);
+				let (instance, as_) = map_instance(&t, view, material.as_ref());
+				r.updates.push(GpuRtInstanceUpdate {
+					index: *index,
+					_pad: 0,
+					as_,
+					instance,
+				});
+			}
+		}
 
-	for (e, t, inner) in cache.into_inner().unwrap() {
+		// Swap in reloaded meshes and materials, keeping the old ones until the new ones are ready.
+		if !k
+			.0
+			.iter()
+			.any(|(_, view, material)| view.is_stale() || material.as_ref().is_some_and(|m| m.is_stale()))
+		{
+			continue;
+		}
+		r.rebuild = true;
+		for (index, view, material) in k.0.iter_mut() {
+			let changed = reload_material_override(material) | reload(view);
+			if changed {
+				r.bounds[*index as usize] = transform_aabb(&t, view.aabb);
+				let (instance, as_) = map_instance(&t, view, material.as_ref());
+				r.updates.push(GpuRtInstanceUpdate {
+					index: *index,
+					_pad: 0,
+					as_,
+					instance,
+				});
+			}
+		}
+	}
+
+	let cache: Vec<_> = if Engine::get().loads_assets_in_background() {
+		// Start every new mesh in one batch, so that their BLASes are built together.
+		let unknown: Vec<_> = unknown.iter().collect();
+		let started = PendingMeshes::<RaytracingMeshView>::start_all(unknown.iter().map(|&(_, _, m)| m));
+		for (&(e, ..), p) in unknown.iter().zip(started) {
+			cmd.entity(e).insert(p);
+		}
+		pending
+			.iter()
+			.filter_map(|(e, t, p)| {
+				let x = p.ready()?;
+				cmd.entity(e).remove::<PendingMeshes<RaytracingMeshView>>();
+				Some((e, t, x))
+			})
+			.collect()
+	} else {
+		// Load every new mesh in one batch, so that their BLASes are built together.
+		let unknown: Vec<_> = unknown.iter().collect();
+		let ids: Vec<_> = unknown.iter().flat_map(|(_, _, m)| m.inner.iter().copied()).collect();
+		let mut views = ARef::<RaytracingMeshView>::loaded_many(&ids).into_iter();
+		unknown
+			.into_iter()
+			.map(|(e, t, m)| {
+				let x: Vec<_> = m
+					.inner
+					.iter()
+					.zip(views.by_ref())
+					.enumerate()
+					.filter_map(|(i, (&id, view))| {
+						let view = view.map_err(|e| warn!("failed to load mesh {:?}: {:?}", id, e)).ok()?;
+						Some((view, load_material_override(m, i)))
+					})
+					.collect();
+				(e, t, x)
+			})
+			.collect()
+	};
+	r.rebuild |= !cache.is_empty();
+	for (e, t, inner) in cache {
 		let inner = inner
 			.into_iter()
-			.map(|view| {
+			.map(|(view, material)| {
 				let index = r.instance_count;
 				r.instance_count += 1;
-				let (instance, as_) = map_instance(t, &view);
+				r.entities.push(e);
+				r.bounds.push(transform_aabb(t, view.aabb));
+				let (instance, as_) = map_instance(t, &view, material.as_ref());
 				r.updates.push(GpuRtInstanceUpdate {
 					index,
 					_pad: 0,
 					as_,
 					instance,
 				});
-				(index, view)
+				(index, view, material)
 			})
 			.collect();
 		cmd.entity(e).insert(KnownRtInstances(inner));
@@ -8,16 +8,17 @@ use rad_core::{
 use rad_graph::{
 	device::ShaderInfo,
 	graph::{BufferDesc, BufferUsage, ExternalBuffer, Frame, Res},
-	resource::{BufferHandle, GpuPtr},
+	resource::{Buffer, BufferHandle, GpuPtr},
 	sync::Shader,
 	util::compute::ComputePass,
 };
 use rad_world::{
 	bevy_ecs::{
 		batching::BatchingStrategy,
+		change_detection::Ref,
 		component::{Component, StorageType},
 		entity::Entity,
-		query::{Changed, Or, Without},
+		query::{With, Without},
 		schedule::IntoSystemConfigs,
 		system::{Commands, Query, ResMut, Resource},
 	},
@@ -26,15 +27,29 @@ use rad_world::{
 	TickStage,
 	World,
 };
+use rustc_hash::{FxHashMap, FxHashSet};
 use tracing::error;
+use vek::{Aabb, Vec3};
 
 use crate::{
 	assets::{
-		material::GpuMaterial,
-		mesh::virtual_mesh::{GpuAabb, VirtualMeshView},
+		material::{AlphaMode, GpuMaterial, MaterialView},
+		mesh::virtual_mesh::{GpuMeshEntry, VirtualMeshView, MESHLET_DRAW_VERTICES},
+	},
+	components::{
+		camera::{CameraComponent, PrimaryViewComponent},
+		mesh::MeshComponent,
+	},
+	scene::{
+		load_material_override,
+		reload,
+		reload_material_override,
+		should_scene_sync,
+		transform_aabb,
+		GpuScene,
+		GpuTransform,
+		PendingMeshes,
 	},
-	components::mesh::MeshComponent,
-	scene::{should_scene_sync, GpuScene, GpuTransform},
 	util::ResizableBuffer,
 };
 
@@ -43,6 +58,13 @@ pub struct VirtualScene {
 	pub instances: Res<BufferHandle>,
 	pub instance_count: u32,
 	pub bvh_depth: u32,
+	/// `GpuTransparentDraw`s for instances with `AlphaMode::Blend` materials, sorted back-to-front from the primary
+	/// view. These instances are skipped by the visbuffer.
+	pub transparent: Res<BufferHandle>,
+	pub transparent_count: u32,
+	/// Counts the updates of the scene. Instances whose transform changed this frame have their last transform in
+	/// `last_updated_transform`, with `last_updated_frame` set to this.
+	pub frame: u64,
 }
 
 #[repr(C)]
@@ -50,12 +72,27 @@ pub struct VirtualScene {
 pub struct GpuInstance {
 	transform: GpuTransform,
 	last_updated_transform: GpuTransform,
-	aabb: GpuAabb,
 	last_updated_frame: u64,
-	mesh: GpuPtr<u8>,
+	/// Shared by every instance of the same mesh.
+	entry: GpuPtr<GpuMeshEntry>,
+	/// Drawn instead of the material of the mesh, or null.
 	material: GpuPtr<GpuMaterial>,
 }
 
+/// A `vk::DrawIndirectCommand` drawing the full detail meshlets of a transparent instance, followed by the instance.
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+pub struct GpuTransparentDraw {
+	vertex_count: u32,
+	instance_count: u32,
+	first_vertex: u32,
+	first_instance: u32,
+	instance: u32,
+	meshlet_offset: u32,
+	finest_offset: u32,
+	_pad: u32,
+}
+
 #[derive(Copy, Clone, NoUninit)]
 #[repr(C)]
 struct PushConstants {
@@ -81,9 +118,19 @@ impl GpuScene for VirtualScene {
 			instance_count,
 			bvh_depth,
 			updates,
+			transparent,
+			frame: scene_frame,
+			retired,
+			..
 		} = data;
+		*scene_frame += 1;
+		for buf in retired.drain(..) {
+			frame.delete(buf);
+		}
+		let scene_frame = *scene_frame;
 		let instance_count = *instance_count;
 		let bvh_depth = *bvh_depth;
+		let transparent_count = transparent.len() as u32;
 
 		let tinstances = instances
 			.reserve(
@@ -98,6 +145,10 @@ impl GpuScene for VirtualScene {
 			BufferDesc::upload(std::mem::size_of::<GpuInstanceUpdate>() as u64 * updates.len() as u64),
 			BufferUsage::read(Shader::Compute),
 		);
+		let transparent_buf = pass.resource(
+			BufferDesc::upload(std::mem::size_of::<GpuTransparentDraw>() as u64 * transparent_count.max(1) as u64),
+			BufferUsage::none(),
+		);
 		let instances = match tinstances {
 			Some(instances) => {
 				pass.reference(instances, BufferUsage::write(Shader::Compute));
@@ -109,8 +160,30 @@ impl GpuScene for VirtualScene {
 			),
 		};
 		pass.build(move |mut pass| {
+			pass.write_iter(
+				transparent_buf,
+				0,
+				transparent.iter().map(|t| GpuTransparentDraw {
+					vertex_count: MESHLET_DRAW_VERTICES,
+					instance_count: t.finest_count,
+					first_vertex: 0,
+					first_instance: 0,
+					instance: t.index,
+					meshlet_offset: t.meshlet_offset,
+					finest_offset: t.finest_offset,
+					_pad: 0,
+				}),
+			);
+
 			let count = updates.len() as u32;
-			pass.write_iter(update_buf, 0, updates.drain(..));
+			pass.write_iter(
+				update_buf,
+				0,
+				updates.drain(..).map(|mut u| {
+					u.instance.last_updated_frame = scene_frame;
+					u
+				}),
+			);
 			let instances = pass.get(instances).ptr();
 			let updates = pass.get(update_buf).ptr();
 			update.dispatch(
@@ -131,6 +204,9 @@ impl GpuScene for VirtualScene {
 			instances,
 			instance_count,
 			bvh_depth,
+			transparent: transparent_buf,
+			transparent_count,
+			frame: scene_frame,
 		}
 	}
 }
@@ -149,9 +225,38 @@ pub struct VirtualSceneData {
 	instance_count: u32,
 	bvh_depth: u32,
 	updates: Vec<GpuInstanceUpdate>,
+	transparent: Vec<TransparentInstance>,
+	/// The CPU side of every instance, indexed like the GPU instances.
+	cpu_instances: Vec<SceneInstance>,
+	/// The bounds of every instance pushed so far. Never shrinks.
+	bounds: Option<Aabb<f32>>,
+	frame: u64,
+	/// LOD levels streamed out since the last update, deleted once the frames using them are done.
+	retired: Vec<Buffer>,
 }
 impl Resource for VirtualSceneData {}
 
+/// An instance of a mesh in the virtual scene, as last synced from the world.
+pub struct SceneInstance {
+	pub entity: Entity,
+	pub transform: Transform,
+	/// The object space bounds of the mesh.
+	pub aabb: Aabb<f32>,
+}
+
+impl SceneInstance {
+	/// The world space bounds of the instance.
+	pub fn world_aabb(&self) -> Aabb<f32> { transform_aabb(&self.transform, self.aabb) }
+}
+
+struct TransparentInstance {
+	index: u32,
+	center: Vec3<f32>,
+	meshlet_offset: u32,
+	finest_offset: u32,
+	finest_count: u32,
+}
+
 impl VirtualSceneData {
 	fn new() -> Self {
 		let dev = Engine::get().global();
@@ -169,27 +274,65 @@ impl VirtualSceneData {
 			instance_count: 0,
 			bvh_depth: 0,
 			updates: Vec::new(),
+			transparent: Vec::new(),
+			cpu_instances: Vec::new(),
+			bounds: None,
+			frame: 0,
+			retired: Vec::new(),
 		}
 	}
 
-	fn push_instance(&mut self, index: u32, t: &Transform, m: &LARef<VirtualMeshView>) {
+	/// The world space bounds of the scene, if it has any meshes.
+	pub fn bounds(&self) -> Option<Aabb<f32>> { self.bounds }
+
+	/// The entity an instance belongs to.
+	pub fn entity(&self, instance: u32) -> Option<Entity> {
+		self.cpu_instances.get(instance as usize).map(|x| x.entity)
+	}
+
+	/// Every instance in the scene, indexed like the instances on the GPU.
+	pub fn instances(&self) -> &[SceneInstance] { &self.cpu_instances }
+
+	/// `cpu_instances[index]` must already exist, holding the transform of the instance last frame.
+	fn push_instance(
+		&mut self, index: u32, t: &Transform, m: &LARef<VirtualMeshView>, material: Option<&LARef<MaterialView>>,
+	) {
 		self.updates.push(GpuInstanceUpdate {
 			index,
 			_pad: 0,
 			instance: GpuInstance {
 				transform: (*t).into(),
-				last_updated_transform: (*t).into(),
-				aabb: m.gpu_aabb(),
+				last_updated_transform: self.cpu_instances[index as usize].transform.into(),
+				// Set once the update is uploaded.
 				last_updated_frame: 0,
-				mesh: m.gpu_ptr(),
-				material: m.material().gpu_ptr(),
+				entry: m.entry_ptr(),
+				material: material.map_or(GpuPtr::null(), |x| x.gpu_ptr()),
 			},
 		});
 		self.bvh_depth = self.bvh_depth.max(m.bvh_depth());
+
+		let instance = &mut self.cpu_instances[index as usize];
+		instance.transform = *t;
+		instance.aabb = m.aabb();
+		let aabb = instance.world_aabb();
+		self.bounds = Some(self.bounds.map_or(aabb, |b| b.union(aabb)));
+
+		self.transparent.retain(|x| x.index != index);
+		if material.unwrap_or(m.material()).alpha_mode == AlphaMode::Blend {
+			let (finest_offset, finest_count) = m.finest_meshlets();
+			self.transparent.push(TransparentInstance {
+				index,
+				center: t.position + t.rotation * (t.scale * m.aabb().center()),
+				meshlet_offset: m.meshlet_offset(),
+				finest_offset,
+				finest_count,
+			});
+		}
 	}
 }
 
-pub struct KnownVirtualInstances(pub Vec<(u32, LARef<VirtualMeshView>)>);
+/// The index, mesh, and material override of every instance of an entity.
+pub struct KnownVirtualInstances(pub Vec<(u32, LARef<VirtualMeshView>, Option<LARef<MaterialView>>)>);
 impl Component for KnownVirtualInstances {
 	const STORAGE_TYPE: StorageType = StorageType::Table;
 }
@@ -197,36 +340,160 @@ impl Component for KnownVirtualInstances {
 // TODO: edits and deletion.
 fn sync_virtual_scene(
 	mut r: ResMut<VirtualSceneData>, mut cmd: Commands,
-	unknown: Query<(Entity, &Transform, &MeshComponent), Without<KnownVirtualInstances>>,
-	_: Query<(&Transform, &MeshComponent, &KnownVirtualInstances), Or<(Changed<Transform>, Changed<MeshComponent>)>>,
+	unknown: Query<
+		(Entity, &Transform, &MeshComponent),
+		(Without<KnownVirtualInstances>, Without<PendingMeshes<VirtualMeshView>>),
+	>,
+	pending: Query<(Entity, &Transform, &PendingMeshes<VirtualMeshView>)>,
+	mut known: Query<(Ref<Transform>, &mut KnownVirtualInstances)>,
+	view: Query<(&Transform, &CameraComponent), With<PrimaryViewComponent>>,
 ) {
-	let cache = Mutex::new(Vec::new());
-	unknown
-		.par_iter()
-		.batching_strategy(BatchingStrategy::fixed(1))
-		.for_each(|(e, t, m)| {
-			let x: Vec<_> = m
-				.inner
-				.iter()
-				.filter_map(|&m| {
-					ARef::loaded(m)
-						.map_err(|e| error!("failed to load mesh {:?}: {:?}", m, e))
-						.ok()
-				})
-				.collect();
-			cache.lock().unwrap().push((e, t, x));
-		});
+	let streamed = match view.iter().next() {
+		Some(view) => stream_lods(&mut r, &known, view),
+		None => FxHashSet::default(),
+	};
 
-	for (e, t, inner) in cache.into_inner().unwrap() {
+	for (t, mut k) in known.iter_mut() {
+		let moved = t.is_changed() && !t.is_added();
+		for (index, view, material) in k.0.iter() {
+			if moved || streamed.contains(&view.gpu_ptr().addr()) {
+				r.push_instance(*index, &t, view, material.as_ref());
+			}
+		}
+
+		// Swap in reloaded meshes and materials, keeping the old ones until the new ones are ready.
+		if !k
+			.0
+			.iter()
+			.any(|(_, view, material)| view.is_stale() || material.as_ref().is_some_and(|m| m.is_stale()))
+		{
+			continue;
+		}
+		for (index, view, material) in k.0.iter_mut() {
+			let changed = reload_material_override(material) | reload(view);
+			if changed {
+				r.push_instance(*index, &t, view, material.as_ref());
+			}
+		}
+	}
+
+	let mut cache: Vec<_> = if Engine::get().loads_assets_in_background() {
+		for (e, _, m) in unknown.iter() {
+			cmd.entity(e).insert(PendingMeshes::<VirtualMeshView>::start(m));
+		}
+		pending
+			.iter()
+			.filter_map(|(e, t, p)| {
+				let x = p.ready()?;
+				cmd.entity(e).remove::<PendingMeshes<VirtualMeshView>>();
+				Some((e, t, x))
+			})
+			.collect()
+	} else {
+		let cache = Mutex::new(Vec::new());
+		unknown
+			.par_iter()
+			.batching_strategy(BatchingStrategy::fixed(1))
+			.for_each(|(e, t, m)| {
+				let x: Vec<_> = m
+					.inner
+					.iter()
+					.enumerate()
+					.filter_map(|(i, &id)| {
+						let view = ARef::loaded(id)
+							.map_err(|e| error!("failed to load mesh {:?}: {:?}", id, e))
+							.ok()?;
+						Some((view, load_material_override(m, i)))
+					})
+					.collect();
+				cache.lock().unwrap().push((e, t, x));
+			});
+		cache.into_inner().unwrap()
+	};
+
+	// Keep instances of the same mesh next to each other, so that instance culling sees runs of them.
+	cache.sort_unstable_by_key(|(_, _, x)| x.first().map(|(x, _)| x.gpu_ptr().addr()));
+	for (e, t, inner) in cache {
 		let inner = inner
 			.into_iter()
-			.map(|view| {
+			.map(|(view, material)| {
 				let index = r.instance_count;
 				r.instance_count += 1;
-				r.push_instance(index, t, &view);
-				(index, view)
+				r.cpu_instances.push(SceneInstance {
+					entity: e,
+					transform: *t,
+					aabb: view.aabb(),
+				});
+				r.push_instance(index, t, &view, material.as_ref());
+				(index, view, material)
 			})
 			.collect();
 		cmd.entity(e).insert(KnownVirtualInstances(inner));
 	}
+
+	if let Some((view, _)) = view.iter().next() {
+		let dist = |t: &TransparentInstance| (t.center - view.position).magnitude_squared();
+		r.transparent.sort_by(|a, b| dist(b).total_cmp(&dist(a)));
+	}
+}
+
+/// The screen height LOD streaming plans for. Smaller views never need finer LOD levels than are streamed in for it.
+const STREAMING_SCREEN_HEIGHT: f32 = 2160.0;
+
+/// Stream the LOD levels of every known mesh in or out, depending on how close its instances are to `view`. Returns
+/// the meshes whose entry moved, by the address of their buffer.
+fn stream_lods(
+	r: &mut VirtualSceneData, known: &Query<(Ref<Transform>, &mut KnownVirtualInstances)>,
+	(view, camera): (&Transform, &CameraComponent),
+) -> FxHashSet<u64> {
+	// The same threshold as a pixel of error in the cull shader.
+	let h = (camera.fov / 2.0).tan().recip();
+	let thresh = (h / STREAMING_SCREEN_HEIGHT).atan().sin();
+
+	// The finest level each mesh needs, and the finest it needs at half the distance.
+	let mut needed: FxHashMap<u64, (&LARef<VirtualMeshView>, u32, u32)> = FxHashMap::default();
+	for (t, k) in known.iter() {
+		let scale = t.scale.reduce_partial_max();
+		for (_, mesh, material) in k.0.iter() {
+			let aabb = transform_aabb(&t, mesh.aabb());
+			let dist = (view.position.clamped(aabb.min, aabb.max) - view.position)
+				.magnitude()
+				.max(camera.near);
+			let error = thresh * dist / scale;
+			let (lod, keep) = if material.as_ref().unwrap_or(mesh.material()).alpha_mode == AlphaMode::Blend {
+				// The transparent pass always draws the finest level.
+				(0, 0)
+			} else {
+				(mesh.lod_for_error(error), mesh.lod_for_error(error * 0.5))
+			};
+			let e = needed
+				.entry(mesh.gpu_ptr().addr())
+				.or_insert((mesh, u32::MAX, u32::MAX));
+			e.1 = e.1.min(lod);
+			e.2 = e.2.min(keep);
+		}
+	}
+
+	let mut streamed = FxHashSet::default();
+	for (addr, (mesh, lod, keep)) in needed {
+		// Finer levels are only freed once they wouldn't be needed at half the distance either, so meshes near the
+		// boundary between two levels don't stream them in and out every frame.
+		let resident = mesh.resident_lod();
+		let lod = if lod < resident {
+			lod
+		} else if keep > resident {
+			keep
+		} else {
+			continue;
+		};
+		match mesh.stream(lod) {
+			Ok(Some(old)) => {
+				r.retired.push(old);
+				streamed.insert(addr);
+			},
+			Ok(None) => {},
+			Err(e) => error!("failed to stream LODs of mesh {:?}: {:?}", mesh.id(), e),
+		}
+	}
+	streamed
 }
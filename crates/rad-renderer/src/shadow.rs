@@ -0,0 +1,223 @@
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{descriptor::ImageId, Device},
+	graph::{BufferUsage, Frame, ImageUsage, PassBuilder, PassContext},
+	resource::GpuPtr,
+	sync::Shader,
+	Result,
+};
+use rad_world::transform::Transform;
+use vek::{Quaternion, Vec2, Vec3};
+
+use crate::{
+	components::camera::CameraComponent,
+	mesh::{DepthOutput, DepthPass},
+	scene::{
+		camera::{Camera, CameraScene, GpuCamera},
+		light::LightScene,
+		WorldRenderer,
+	},
+};
+
+pub const MAX_CASCADES: usize = 4;
+
+/// How far the light camera is from the cascade it renders, in cascade radii. Casters up to this far in front of a
+/// cascade towards the sun still cast shadows into it.
+const LIGHT_DISTANCE: f32 = 16.0;
+/// The blend between uniform (0) and logarithmic (1) cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+
+#[derive(Copy, Clone)]
+pub struct ShadowInfo {
+	/// The number of cascades, up to `MAX_CASCADES`.
+	pub cascades: u32,
+	/// The size of the shadow map of each cascade.
+	pub resolution: u32,
+	/// How far from the camera shadows are drawn.
+	pub distance: f32,
+	/// The aspect ratio of the primary view.
+	pub aspect: f32,
+}
+
+impl Default for ShadowInfo {
+	fn default() -> Self {
+		Self {
+			cascades: 3,
+			resolution: 2048,
+			distance: 100.0,
+			aspect: 1.0,
+		}
+	}
+}
+
+/// Cascaded shadow maps for the sun, split along the view frustum of the primary view.
+///
+/// Every cascade is a depth map, rendered from a perspective camera far enough from the cascade that it is close to
+/// orthographic. Shadows are looked up by projecting with the same camera, so the small remaining perspective only
+/// changes the texel density over the cascade.
+pub struct CascadedShadows {
+	cascades: Vec<DepthPass>,
+}
+
+#[derive(Copy, Clone)]
+struct CascadeOutput {
+	depth: DepthOutput,
+	split: f32,
+}
+
+#[derive(Copy, Clone)]
+pub struct ShadowOutput {
+	sun_dir: Vec3<f32>,
+	sun_radiance: Vec3<f32>,
+	count: usize,
+	cascades: [Option<CascadeOutput>; MAX_CASCADES],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct GpuCascade {
+	camera: GpuPtr<GpuCamera>,
+	depth: ImageId,
+	/// The view depth up to which this cascade is used.
+	split: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+pub struct GpuShadows {
+	sun_dir: Vec3<f32>,
+	count: u32,
+	sun_radiance: Vec3<f32>,
+	_pad: u32,
+	cascades: [GpuCascade; MAX_CASCADES],
+}
+
+impl ShadowOutput {
+	pub fn add(&self, pass: &mut PassBuilder, shader: Shader) {
+		for c in self.cascades.iter().flatten() {
+			pass.reference(c.depth.depth, ImageUsage::sampled_depth_2d(shader));
+			pass.reference(c.depth.camera, BufferUsage::read(shader));
+		}
+	}
+
+	pub fn get(&self, pass: &mut PassContext) -> GpuShadows {
+		let cascades = self.cascades.map(|c| {
+			c.map(|c| GpuCascade {
+				camera: pass.get(c.depth.camera).ptr(),
+				depth: pass.get(c.depth.depth).id.unwrap(),
+				split: c.split,
+			})
+		});
+		// Unused cascades are never read, but must still be valid values.
+		let first = cascades[0].unwrap();
+		GpuShadows {
+			sun_dir: self.sun_dir,
+			count: self.count as u32,
+			sun_radiance: self.sun_radiance,
+			_pad: 0,
+			cascades: cascades.map(|c| c.unwrap_or(first)),
+		}
+	}
+}
+
+impl CascadedShadows {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			cascades: (0..MAX_CASCADES)
+				.map(|_| DepthPass::new(device))
+				.collect::<Result<_>>()?,
+		})
+	}
+
+	/// Render the shadow cascades, or `None` if there is no sun.
+	pub fn run<'pass>(
+		&'pass mut self, frame: &mut Frame<'pass, '_>, rend: &mut WorldRenderer<'pass, '_>, info: &ShadowInfo,
+	) -> Option<ShadowOutput> {
+		let lights = rend.get::<LightScene>(frame);
+		let view = rend.get::<CameraScene>(frame).curr;
+		let count = (info.cascades as usize).clamp(1, MAX_CASCADES);
+		if lights.sun_radiance == Vec3::zero() || lights.sun_dir == Vec3::zero() {
+			return None;
+		}
+
+		frame.start_region("shadows");
+		let sun_dir = lights.sun_dir.normalized();
+		// Nothing past the far plane is drawn, so shadows never need to reach further.
+		let distance = view.camera.far.map_or(info.distance, |f| info.distance.min(f));
+		let splits = splits(view.camera.near, distance.max(view.camera.near * 2.0), count);
+		let mut out = ShadowOutput {
+			sun_dir,
+			sun_radiance: lights.sun_radiance,
+			count,
+			cascades: [None; MAX_CASCADES],
+		};
+		for (i, c) in self.cascades.iter_mut().take(count).enumerate() {
+			let camera = light_camera(&view, info, sun_dir, splits[i], splits[i + 1]);
+			let depth = c.run(frame, rend, Vec2::broadcast(info.resolution), camera, 1.0);
+			out.cascades[i] = Some(CascadeOutput {
+				depth,
+				split: splits[i + 1],
+			});
+		}
+		frame.end_region();
+
+		Some(out)
+	}
+
+	pub unsafe fn destroy(self) {
+		for c in self.cascades {
+			c.destroy();
+		}
+	}
+}
+
+/// The view depths the cascades are split at, mixing uniform and logarithmic splits.
+fn splits(near: f32, far: f32, count: usize) -> [f32; MAX_CASCADES + 1] {
+	let mut out = [far; MAX_CASCADES + 1];
+	for (i, s) in out.iter_mut().enumerate().take(count + 1) {
+		let t = i as f32 / count as f32;
+		let log = near * (far / near).powf(t);
+		let uniform = near + (far - near) * t;
+		*s = uniform + (log - uniform) * SPLIT_LAMBDA;
+	}
+	out
+}
+
+/// The light camera for the slice of the view frustum between the view depths `near` and `far`.
+fn light_camera(view: &Camera, info: &ShadowInfo, sun_dir: Vec3<f32>, near: f32, far: f32) -> Camera {
+	// The bounding sphere of the slice, which doesn't change as the view rotates, keeping the shadows stable.
+	let h = (view.camera.fov / 2.0).tan();
+	let w = h * info.aspect;
+	let k2 = w * w + h * h;
+	let center_depth = ((near + far) * (1.0 + k2) / 2.0).min(far);
+	let radius = ((far - center_depth).powi(2) + far * far * k2).sqrt();
+	let forward = view.transform.rotation * Vec3::unit_y();
+	let mut center = view.transform.position + forward * center_depth;
+
+	let rotation = if sun_dir.y < -0.9999 {
+		Quaternion::rotation_z(std::f32::consts::PI)
+	} else {
+		Quaternion::rotation_from_to_3d(Vec3::unit_y(), sun_dir)
+	};
+	// Snap the center to whole texels across the light, so that the shadow edges don't shimmer as the view moves.
+	let texel = 2.0 * radius / info.resolution as f32;
+	for axis in [Vec3::unit_x(), Vec3::unit_z()] {
+		let axis = rotation * axis;
+		let d = center.dot(axis);
+		center += axis * ((d / texel).round() * texel - d);
+	}
+
+	let distance = radius * LIGHT_DISTANCE;
+	Camera {
+		transform: Transform {
+			position: center - sun_dir * distance,
+			rotation,
+			scale: Vec3::broadcast(1.0),
+		},
+		camera: CameraComponent {
+			fov: 2.0 * (radius / (distance - radius)).atan(),
+			near: radius,
+			far: None,
+		},
+	}
+}
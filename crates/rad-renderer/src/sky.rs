@@ -121,7 +121,7 @@ impl SkyLuts {
 		let camera = rend.get::<CameraScene>(frame);
 		let lights = rend.get::<LightScene>(frame);
 
-		frame.start_region("sky");
+		frame.time_region("sky");
 		let format = Self::FORMAT;
 
 		let mut pass = frame.pass("transmittance");
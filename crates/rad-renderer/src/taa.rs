@@ -0,0 +1,173 @@
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{
+		descriptor::{ImageId, SamplerId},
+		Device,
+		SamplerDesc,
+		ShaderInfo,
+	},
+	graph::{Frame, ImageDesc, ImageUsage, Persist, Res, Shader},
+	resource::ImageView,
+	util::{
+		pass::{Attachment, Load},
+		render::FullscreenPass,
+	},
+	Result,
+};
+use vek::Vec2;
+
+#[derive(Copy, Clone)]
+pub struct TaaInfo {
+	/// How much of the current frame is blended into the history every frame. Lower is smoother, but ghosts more.
+	pub blend: f32,
+}
+
+impl Default for TaaInfo {
+	fn default() -> Self { Self { blend: 0.1 } }
+}
+
+/// Temporal antialiasing, accumulating frames rendered with a jittered projection into a history reprojected with
+/// the motion vectors of the visbuffer.
+pub struct Taa {
+	pass: FullscreenPass<PushConstants>,
+	sampler: SamplerId,
+	/// Read and written on alternate frames.
+	history: [Persist<ImageView>; 2],
+	frame: u32,
+	valid: bool,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	input: ImageId,
+	motion: ImageId,
+	history: ImageId,
+	sampler: SamplerId,
+	blend: f32,
+	has_history: u32,
+}
+
+impl Taa {
+	/// The length of the jitter sequence.
+	pub const JITTER_PHASES: u32 = 8;
+
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.taa.main",
+					spec: &[],
+				},
+				&[vk::Format::R8G8B8A8_SRGB, vk::Format::R16G16B16A16_SFLOAT],
+			)?,
+			sampler: device.sampler(SamplerDesc {
+				address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				..Default::default()
+			}),
+			history: [Persist::new(), Persist::new()],
+			frame: 0,
+			valid: false,
+		})
+	}
+
+	/// The jitter to render this frame with, in NDC, given the render size.
+	pub fn jitter(&self, size: Vec2<u32>) -> Vec2<f32> {
+		let i = self.frame % Self::JITTER_PHASES + 1;
+		let offset = Vec2::new(halton(i, 2), halton(i, 3)) - 0.5;
+		offset * 2.0 / size.map(|x| x as f32)
+	}
+
+	/// Forget the history, for when frames were rendered without TAA.
+	pub fn reset(&mut self) { self.valid = false; }
+
+	/// Resolve `input`, rendered with the jitter from [`Self::jitter`], with the history of earlier frames.
+	pub fn run<'pass>(
+		&'pass mut self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, motion: Res<ImageView>, info: &TaaInfo,
+	) -> Res<ImageView> {
+		let mut pass = frame.timed_pass("taa");
+
+		pass.reference(input, ImageUsage::sampled_2d(Shader::Fragment));
+		pass.reference(motion, ImageUsage::sampled_2d(Shader::Fragment));
+		let desc = pass.desc(input);
+		let history = ImageDesc {
+			format: vk::Format::R16G16B16A16_SFLOAT,
+			..desc
+		};
+		let curr = self.frame as usize & 1;
+		let prev = pass.resource(
+			ImageDesc {
+				persist: Some(self.history[curr ^ 1]),
+				..history
+			},
+			ImageUsage::sampled_2d(Shader::Fragment),
+		);
+		let next = pass.resource(
+			ImageDesc {
+				persist: Some(self.history[curr]),
+				..history
+			},
+			ImageUsage::color_attachment(),
+		);
+		let out = pass.resource(
+			ImageDesc {
+				format: vk::Format::R8G8B8A8_SRGB,
+				..desc
+			},
+			ImageUsage::color_attachment(),
+		);
+
+		let valid = self.valid;
+		self.valid = true;
+		self.frame = self.frame.wrapping_add(1);
+		let blend = info.blend.clamp(0.0, 1.0);
+		let this = &*self;
+		pass.build(move |mut pass| {
+			let has_history = valid && !pass.is_uninit(prev);
+			let push = PushConstants {
+				input: pass.get(input).id.unwrap(),
+				motion: pass.get(motion).id.unwrap(),
+				history: pass.get(prev).id.unwrap(),
+				sampler: this.sampler,
+				blend,
+				has_history: has_history as _,
+			};
+			this.pass.run(
+				&mut pass,
+				&push,
+				&[
+					Attachment {
+						image: out,
+						load: Load::DontCare,
+						store: true,
+					},
+					Attachment {
+						image: next,
+						load: Load::DontCare,
+						store: true,
+					},
+				],
+			);
+		});
+
+		out
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
+
+/// The `i`th element of the Halton sequence with `base`, in `[0, 1)`.
+fn halton(mut i: u32, base: u32) -> f32 {
+	let mut f = 1.0;
+	let mut r = 0.0;
+	while i > 0 {
+		f /= base as f32;
+		r += f * (i % base) as f32;
+		i /= base;
+	}
+	r
+}
@@ -42,6 +42,17 @@ impl AgXLook {
 			sat: 1.1,
 		}
 	}
+
+	/// Blend between the default look (`0.0`) and the punchy look (`1.0`).
+	pub fn punchy_by(strength: f32) -> Self {
+		let (a, b) = (Self::default(), Self::punchy());
+		Self {
+			offset: Vec3::lerp_unclamped(a.offset, b.offset, strength),
+			slope: Vec3::lerp_unclamped(a.slope, b.slope, strength),
+			power: Vec3::lerp_unclamped(a.power, b.power, strength),
+			sat: a.sat + (b.sat - a.sat) * strength,
+		}
+	}
 }
 
 #[repr(C)]
@@ -70,7 +81,7 @@ impl AgXTonemap {
 	pub fn run<'pass>(
 		&'pass self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, exp: Res<BufferHandle>, look: AgXLook,
 	) -> Res<ImageView> {
-		let mut pass = frame.pass("agx tonemap");
+		let mut pass = frame.timed_pass("agx tonemap");
 
 		pass.reference(input, ImageUsage::sampled_2d(Shader::Fragment));
 		pass.reference(exp, BufferUsage::read(Shader::Fragment));
@@ -41,9 +41,12 @@ pub struct ExposureCalc {
 	target_exposure: f32,
 	scene_exposure: f32,
 	read_histogram: [u32; 256],
+	ready: bool,
 }
 
 pub struct ExposureStats {
+	/// Whether the exposure has been read back yet. If not, every other field is zero.
+	pub ready: bool,
 	pub exposure: f32,
 	pub target_exposure: f32,
 	pub scene_exposure: f32,
@@ -96,13 +99,22 @@ impl ExposureCalc {
 			target_exposure: 0.0,
 			scene_exposure: 0.0,
 			read_histogram: [0; 256],
+			ready: false,
 		})
 	}
 
+	/// The exposure (in EV) that was last read back from the GPU, which lags behind by a frame.
+	/// Returns `None` until the first readback has completed.
+	pub fn exposure(&self) -> Option<f32> { self.ready.then_some(self.curr_exposure) }
+
+	/// Calculate the exposure of `input`, adapting to it over time. `ec` is a compensation in stops, with positive
+	/// values brightening the image.
+	///
+	/// The returned buffer starts with the compensated exposure in EV, which the tonemappers read.
 	pub fn run<'pass>(
 		&'pass mut self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, ec: f32, dt: f32,
 	) -> (Res<BufferHandle>, ExposureStats) {
-		frame.start_region("exposure");
+		frame.time_region("exposure");
 
 		let Self {
 			histogram: hist,
@@ -114,6 +126,7 @@ impl ExposureCalc {
 			target_exposure,
 			scene_exposure,
 			read_histogram,
+			ready,
 		} = self;
 
 		let histogram_size = std::mem::size_of::<u32>() as u64 * 256;
@@ -121,7 +134,7 @@ impl ExposureCalc {
 		let mut pass = frame.pass("zero data");
 		let histogram = pass.resource(BufferDesc::gpu(histogram_size), BufferUsage::transfer_write());
 		let exposure = pass.resource(
-			BufferDesc::gpu(std::mem::size_of::<f32>() as u64 * 3).persist(*exposure_value),
+			BufferDesc::gpu(std::mem::size_of::<f32>() as u64 * 4).persist(*exposure_value),
 			BufferUsage::transfer_write(),
 		);
 		pass.build(move |mut pass| {
@@ -185,7 +198,7 @@ impl ExposureCalc {
 			BufferUsage::transfer_write(),
 		);
 		let exposure_read = pass.resource(
-			BufferDesc::readback(std::mem::size_of::<f32>() as u64 * 3, *exposure_readback),
+			BufferDesc::readback(std::mem::size_of::<f32>() as u64 * 4, *exposure_readback),
 			BufferUsage::transfer_write(),
 		);
 
@@ -193,11 +206,13 @@ impl ExposureCalc {
 		let target_exp = *target_exposure;
 		let scene_exp = *scene_exposure;
 		let hist = *read_histogram;
+		let was_ready = *ready;
 		pass.build(move |mut pass| {
+			*ready = !pass.is_uninit(exposure_read);
 			pass.copy_full_buffer(histogram, histogram_read, 0);
 			*read_histogram = pass.readback(histogram_read, 0);
 			pass.copy_full_buffer(exposure, exposure_read, 0);
-			[*curr_exposure, *target_exposure, *scene_exposure] = pass.readback(exposure_read, 0);
+			[*curr_exposure, _, *target_exposure, *scene_exposure] = pass.readback(exposure_read, 0);
 		});
 
 		frame.end_region();
@@ -205,6 +220,7 @@ impl ExposureCalc {
 		(
 			exposure,
 			ExposureStats {
+				ready: was_ready,
 				exposure: ret_exp,
 				target_exposure: target_exp,
 				scene_exposure: scene_exp,
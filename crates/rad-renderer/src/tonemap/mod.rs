@@ -8,4 +8,105 @@ pub mod agx_hdr;
 pub mod exposure;
 pub mod frostbite;
 pub mod null;
+pub mod reinhard;
 pub mod tony_mc_mapface;
+pub mod uncharted2;
+
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{Device, ShaderInfo},
+	util::render::FullscreenPass,
+	Result,
+};
+
+/// The display encoding the HDR tonemappers write.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TonemapOutput {
+	/// PQ encoded Rec. 2020 in `A2B10G10R10_UNORM_PACK32`, for HDR10 displays.
+	Hdr10,
+	/// Linear Rec. 709 in `R16G16B16A16_SFLOAT`, with 1 at 80 nits, for scRGB displays.
+	ScRgb,
+	/// Rec. 709 in `R8G8B8A8_SRGB`, for SDR displays. The sRGB OETF is applied by the attachment.
+	Srgb8,
+}
+
+impl TonemapOutput {
+	pub fn format(self) -> vk::Format {
+		match self {
+			Self::Hdr10 => vk::Format::A2B10G10R10_UNORM_PACK32,
+			Self::ScRgb => vk::Format::R16G16B16A16_SFLOAT,
+			Self::Srgb8 => vk::Format::R8G8B8A8_SRGB,
+		}
+	}
+
+	/// The output to display on a swapchain of `format`.
+	pub fn for_swapchain(format: vk::Format) -> Self {
+		match format {
+			vk::Format::A2B10G10R10_UNORM_PACK32 => Self::Hdr10,
+			vk::Format::R16G16B16A16_SFLOAT => Self::ScRgb,
+			_ => Self::Srgb8,
+		}
+	}
+}
+
+/// Where the HDR tonemappers display their output.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DisplayTarget {
+	pub output: TonemapOutput,
+	/// The luminance of SDR white in nits, which HDR outputs are scaled to.
+	pub white: f32,
+}
+
+impl DisplayTarget {
+	pub const DEFAULT_WHITE: f32 = 300.0;
+}
+
+/// A tonemap pass with a pipeline for every [`TonemapOutput`].
+struct OutputPasses<T> {
+	hdr10: FullscreenPass<T>,
+	scrgb: FullscreenPass<T>,
+	srgb8: FullscreenPass<T>,
+}
+
+impl<T: NoUninit> OutputPasses<T> {
+	fn new(device: &Device, shader: &'static str) -> Result<Self> {
+		Ok(Self {
+			hdr10: FullscreenPass::new(
+				device,
+				ShaderInfo { shader, spec: &[] },
+				&[TonemapOutput::Hdr10.format()],
+			)?,
+			scrgb: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader,
+					spec: &["passes.tonemap.scrgb"],
+				},
+				&[TonemapOutput::ScRgb.format()],
+			)?,
+			srgb8: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader,
+					spec: &["passes.tonemap.sdr"],
+				},
+				&[TonemapOutput::Srgb8.format()],
+			)?,
+		})
+	}
+
+	fn get(&self, output: TonemapOutput) -> &FullscreenPass<T> {
+		match output {
+			TonemapOutput::Hdr10 => &self.hdr10,
+			TonemapOutput::ScRgb => &self.scrgb,
+			TonemapOutput::Srgb8 => &self.srgb8,
+		}
+	}
+
+	unsafe fn destroy(self) {
+		self.hdr10.destroy();
+		self.scrgb.destroy();
+		self.srgb8.destroy();
+	}
+}
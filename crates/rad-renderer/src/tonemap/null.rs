@@ -1,15 +1,15 @@
-use ash::vk;
 use bytemuck::NoUninit;
 use rad_graph::{
-	device::{descriptor::ImageId, Device, ShaderInfo},
+	device::{descriptor::ImageId, Device},
 	graph::{BufferUsage, Frame, ImageDesc, ImageUsage, Res, Shader},
 	resource::{BufferHandle, GpuPtr, ImageView},
-	util::render::FullscreenPass,
 	Result,
 };
 
+use crate::tonemap::{DisplayTarget, OutputPasses};
+
 pub struct NullTonemap {
-	pass: FullscreenPass<PushConstants>,
+	pass: OutputPasses<PushConstants>,
 }
 
 #[repr(C)]
@@ -17,34 +17,27 @@ pub struct NullTonemap {
 struct PushConstants {
 	exp: GpuPtr<f32>,
 	input: ImageId,
-	_pad: u32,
+	white: f32,
 }
 
 impl NullTonemap {
 	pub fn new(device: &Device) -> Result<Self> {
 		Ok(Self {
-			pass: FullscreenPass::new(
-				device,
-				ShaderInfo {
-					shader: "passes.tonemap.null.main",
-					spec: &[],
-				},
-				&[vk::Format::A2B10G10R10_UNORM_PACK32],
-			)?,
+			pass: OutputPasses::new(device, "passes.tonemap.null.main")?,
 		})
 	}
 
 	pub fn run<'pass>(
-		&'pass self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, exp: Res<BufferHandle>,
+		&'pass self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, exp: Res<BufferHandle>, target: DisplayTarget,
 	) -> Res<ImageView> {
-		let mut pass = frame.pass("null tonemap");
+		let mut pass = frame.timed_pass("null tonemap");
 
 		pass.reference(input, ImageUsage::sampled_2d(Shader::Fragment));
 		pass.reference(exp, BufferUsage::read(Shader::Fragment));
 		let desc = pass.desc(input);
 		let out = pass.resource(
 			ImageDesc {
-				format: vk::Format::A2B10G10R10_UNORM_PACK32,
+				format: target.output.format(),
 				..desc
 			},
 			ImageUsage::color_attachment(),
@@ -53,8 +46,15 @@ impl NullTonemap {
 		pass.build(move |mut pass| {
 			let input = pass.get(input).id.unwrap();
 			let exp = pass.get(exp).ptr();
-			self.pass
-				.run_one(&mut pass, &PushConstants { exp, input, _pad: 0 }, out);
+			self.pass.get(target.output).run_one(
+				&mut pass,
+				&PushConstants {
+					exp,
+					input,
+					white: target.white,
+				},
+				out,
+			);
 		});
 
 		out
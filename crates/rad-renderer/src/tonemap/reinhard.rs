@@ -0,0 +1,64 @@
+use ash::vk;
+use bytemuck::NoUninit;
+use rad_graph::{
+	device::{descriptor::ImageId, Device, ShaderInfo},
+	graph::{BufferUsage, Frame, ImageDesc, ImageUsage, Res, Shader},
+	resource::{BufferHandle, GpuPtr, ImageView},
+	util::render::FullscreenPass,
+	Result,
+};
+
+pub struct ReinhardTonemap {
+	pass: FullscreenPass<PushConstants>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct PushConstants {
+	exp: GpuPtr<f32>,
+	input: ImageId,
+	_pad: u32,
+}
+
+impl ReinhardTonemap {
+	pub fn new(device: &Device) -> Result<Self> {
+		Ok(Self {
+			pass: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader: "passes.tonemap.reinhard.main",
+					spec: &[],
+				},
+				&[vk::Format::R8G8B8A8_SRGB],
+			)?,
+		})
+	}
+
+	pub fn run<'pass>(
+		&'pass self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, exp: Res<BufferHandle>,
+	) -> Res<ImageView> {
+		let mut pass = frame.timed_pass("reinhard tonemap");
+
+		pass.reference(input, ImageUsage::sampled_2d(Shader::Fragment));
+		pass.reference(exp, BufferUsage::read(Shader::Fragment));
+		let desc = pass.desc(input);
+		let out = pass.resource(
+			ImageDesc {
+				format: vk::Format::R8G8B8A8_SRGB,
+				..desc
+			},
+			ImageUsage::color_attachment(),
+		);
+
+		pass.build(move |mut pass| {
+			let input = pass.get(input).id.unwrap();
+			let exp = pass.get(exp).ptr();
+			self.pass
+				.run_one(&mut pass, &PushConstants { exp, input, _pad: 0 }, out);
+		});
+
+		out
+	}
+
+	pub unsafe fn destroy(self) { self.pass.destroy(); }
+}
@@ -69,7 +69,7 @@ impl TonyMcMapfaceTonemap {
 	pub fn run<'pass>(
 		&'pass self, frame: &mut Frame<'pass, '_>, input: Res<ImageView>, exp: Res<BufferHandle>,
 	) -> Res<ImageView> {
-		let mut pass = frame.pass("tony mcmapface tonemap");
+		let mut pass = frame.timed_pass("tony mcmapface tonemap");
 
 		pass.reference(input, ImageUsage::sampled_2d(Shader::Fragment));
 		pass.reference(exp, BufferUsage::read(Shader::Fragment));
@@ -0,0 +1,280 @@
+//! Image tests of the headless renderer. They need a GPU, so run them with `cargo test -- --ignored`.
+
+use std::{
+	env,
+	f32::consts::PI,
+	fs,
+	io::{self, Cursor, Read, Write},
+	path::Path,
+	sync::{Arc, Mutex, Once},
+	time::Instant,
+};
+
+use image::RgbaImage;
+use rad_core::{
+	asset::{
+		aref::{ARef, AssetId, UntypedAssetId},
+		Asset,
+		AssetRead,
+		AssetSource,
+		AssetWrite,
+		Uuid,
+	},
+	Engine,
+};
+use rad_renderer::{
+	assets::{
+		material::{AlphaMode, Material, TexCoord, TextureSampler},
+		mesh::{virtual_mesh::MeshletConfig, Mesh, RaytracingMeshView, Vertex},
+	},
+	components::mesh::MeshComponent,
+	headless::HeadlessRenderer,
+	scene::camera::Camera,
+	RendererModule,
+};
+use rad_rhi::RhiModule;
+use rad_world::{transform::Transform, World, WorldModule};
+use rustc_hash::FxHashMap;
+use vek::{Quaternion, Vec2, Vec3, Vec4};
+
+/// Assets saved by the tests, as the only asset source.
+#[derive(Default)]
+struct MemorySource(Mutex<FxHashMap<UntypedAssetId, (Uuid, Vec<u8>)>>);
+
+impl AssetSource for MemorySource {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error> {
+		match self.0.lock().unwrap().get(&id) {
+			Some((t, data)) if *t == ty => Ok(Box::new(Bytes(Cursor::new(data.clone())))),
+			_ => Err(io::Error::new(io::ErrorKind::NotFound, "asset not found")),
+		}
+	}
+}
+
+struct Bytes(Cursor<Vec<u8>>);
+
+impl Read for Bytes {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) }
+}
+impl Write for Bytes {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.write(buf) }
+
+	fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+impl AssetRead for Bytes {}
+impl AssetWrite for Bytes {}
+
+/// Only one test renders at a time, as they share the device.
+static GPU: Mutex<()> = Mutex::new(());
+
+fn init() {
+	static INIT: Once = Once::new();
+	INIT.call_once(|| {
+		let mut engine = Engine::builder()
+			.module::<RhiModule>()
+			.module::<WorldModule>()
+			.module::<RendererModule>();
+		engine.asset_source(Arc::new(MemorySource::default()));
+		engine.build();
+	});
+}
+
+fn save<T: Asset>(asset: &T) -> AssetId<T> {
+	let id = AssetId::new();
+	let mut out = Bytes(Cursor::new(Vec::new()));
+	asset.save(&mut out).unwrap();
+	let source: &Arc<MemorySource> = Engine::get().asset_source();
+	source
+		.0
+		.lock()
+		.unwrap()
+		.insert(id.to_untyped(), (T::UUID, out.0.into_inner()));
+	id
+}
+
+fn material(double_sided: bool) -> Material {
+	Material {
+		base_color: None,
+		base_color_factor: Vec4::new(0.8, 0.8, 0.8, 1.0),
+		metallic_roughness: None,
+		metallic_factor: 0.0,
+		roughness_factor: 1.0,
+		normal: None,
+		normal_scale: 1.0,
+		emissive: None,
+		// Visible without any lights.
+		emissive_factor: Vec3::one(),
+		alpha_mode: AlphaMode::Opaque,
+		alpha_cutoff: 0.5,
+		double_sided,
+		base_color_sampler: TextureSampler::default(),
+		metallic_roughness_sampler: TextureSampler::default(),
+		normal_sampler: TextureSampler::default(),
+		emissive_sampler: TextureSampler::default(),
+		base_color_uv: TexCoord::default(),
+		metallic_roughness_uv: TexCoord::default(),
+		normal_uv: TexCoord::default(),
+		emissive_uv: TexCoord::default(),
+	}
+}
+
+/// A unit quad mesh in the XZ plane at the origin, facing -Y.
+fn quad_mesh(material: &Material) -> AssetId<Mesh> {
+	let vertex = |x: f32, z: f32| Vertex {
+		position: Vec3::new(x, 0.0, z),
+		normal: -Vec3::unit_y(),
+		uv: Vec2::new(x, z) + 0.5,
+		uv1: Vec2::new(x, z) + 0.5,
+		tangent: Vec4::new(i16::MAX, 0, 0, i16::MAX),
+		color: Vec4::broadcast(255),
+	};
+	save(&Mesh {
+		vertices: vec![
+			vertex(-0.5, -0.5),
+			vertex(0.5, -0.5),
+			vertex(0.5, 0.5),
+			vertex(-0.5, 0.5),
+		],
+		indices: vec![0, 1, 2, 0, 2, 3],
+		material: save(material),
+		meshlet_config: MeshletConfig::default(),
+	})
+}
+
+/// A world with a single unit quad in the XZ plane at the origin, facing -Y.
+fn quad(material: &Material) -> World {
+	let mesh = quad_mesh(material);
+	let mut world = World::new();
+	world
+		.spawn_empty()
+		.insert((Transform::identity(), MeshComponent::new(&[mesh])));
+	world
+}
+
+/// A camera 2 units from the origin on the Y axis, looking at it from -Y if `front`, or +Y otherwise.
+fn facing(front: bool) -> Camera {
+	let (position, rotation) = if front {
+		(-2.0 * Vec3::unit_y(), Quaternion::identity())
+	} else {
+		(2.0 * Vec3::unit_y(), Quaternion::rotation_z(PI))
+	};
+	Camera::perspective(
+		Transform {
+			position,
+			rotation,
+			scale: Vec3::one(),
+		},
+		PI / 4.0,
+		0.1,
+	)
+}
+
+const SIZE: Vec2<u32> = Vec2::new(64, 64);
+
+fn render(renderer: &mut HeadlessRenderer, world: World, camera: Camera) -> RgbaImage {
+	renderer.render_to_image(world, camera, SIZE).unwrap()
+}
+
+fn center(img: &RgbaImage) -> [u8; 4] { img.get_pixel(SIZE.x / 2, SIZE.y / 2).0 }
+
+/// Compare `img` to the reference image `tests/golden/{name}.png`. Run with `RADBLESS=1` to write the reference
+/// instead, after checking that the image looks right.
+fn golden(name: &str, img: &RgbaImage) {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/golden")
+		.join(format!("{name}.png"));
+	if env::var_os("RADBLESS").is_some() {
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		img.save(&path).unwrap();
+		return;
+	}
+
+	let reference = match image::open(&path) {
+		Ok(x) => x.into_rgba8(),
+		Err(e) => panic!(
+			"failed to open reference image {}: {e}, run with `RADBLESS=1` to create it",
+			path.display()
+		),
+	};
+	assert_eq!(reference.dimensions(), img.dimensions(), "`{name}` changed size");
+	// GPUs and drivers round differently, so only count pixels that are clearly off.
+	let differing = reference
+		.pixels()
+		.zip(img.pixels())
+		.filter(|(a, b)| a.0.iter().zip(b.0).any(|(&a, b)| a.abs_diff(b) > 8))
+		.count();
+	let total = (img.width() * img.height()) as usize;
+	assert!(
+		differing * 100 <= total,
+		"`{name}` differs from its reference in {differing} of {total} pixels"
+	);
+}
+
+#[test]
+#[ignore = "needs a GPU"]
+fn double_sided_quad() {
+	init();
+	let _gpu = GPU.lock().unwrap();
+	let mut renderer = HeadlessRenderer::new().unwrap();
+
+	let background = center(&render(&mut renderer, World::new(), facing(true)));
+	let double = material(true);
+	for front in [true, false] {
+		let seen = center(&render(&mut renderer, quad(&double), facing(front)));
+		assert_ne!(
+			seen,
+			background,
+			"double-sided quad not visible from the {}",
+			if front { "front" } else { "back" }
+		);
+	}
+	// Without the flag, exactly one side is culled.
+	let single = material(false);
+	let visible = [true, false].map(|front| center(&render(&mut renderer, quad(&single), facing(front))) != background);
+	assert!(visible[0] != visible[1], "single-sided quad visible from {visible:?}");
+
+	unsafe { renderer.destroy() };
+}
+
+#[test]
+#[ignore = "needs a GPU"]
+fn golden_quad() {
+	init();
+	let _gpu = GPU.lock().unwrap();
+	let mut renderer = HeadlessRenderer::new().unwrap();
+
+	let img = render(&mut renderer, quad(&material(true)), facing(true));
+	golden("quad", &img);
+
+	unsafe { renderer.destroy() };
+}
+
+/// Compares loading the BLASes of a 200 mesh scene one at a time and in one batch. Run with `--nocapture` to see the
+/// times.
+#[test]
+#[ignore = "needs a GPU"]
+fn batched_blas_loads() {
+	const MESHES: usize = 200;
+
+	init();
+	let _gpu = GPU.lock().unwrap();
+	let m = material(false);
+	let single: Vec<_> = (0..MESHES).map(|_| quad_mesh(&m)).collect();
+	let batched: Vec<_> = (0..MESHES).map(|_| quad_mesh(&m)).collect();
+
+	let start = Instant::now();
+	let one_by_one: Vec<_> = single
+		.iter()
+		.map(|&id| ARef::<RaytracingMeshView>::loaded(id).unwrap())
+		.collect();
+	let one_by_one_time = start.elapsed();
+
+	let start = Instant::now();
+	let batch: Vec<_> = ARef::<RaytracingMeshView>::loaded_many(&batched)
+		.into_iter()
+		.map(Result::unwrap)
+		.collect();
+	let batch_time = start.elapsed();
+
+	println!("{MESHES} meshes: {one_by_one_time:?} one at a time, {batch_time:?} batched");
+	assert_eq!(batch.len(), one_by_one.len());
+}
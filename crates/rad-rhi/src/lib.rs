@@ -1,17 +1,29 @@
 use rad_core::{EngineBuilder, Module};
 use rad_graph::{
 	ash::{ext, vk},
-	device::Device,
+	device::{Device, ValidationInfo},
 };
 
 pub struct RhiModule;
 
 impl Module for RhiModule {
+	/// Validation is enabled by setting `RADVALIDATION` to `1`, or to `gpu` for GPU-assisted validation.
+	/// `RADVALIDATION_IGNORE` is a comma-separated list of message IDs to silence.
 	fn init(engine: &mut EngineBuilder) {
+		let validation = std::env::var("RADVALIDATION").ok();
+		let ignored = std::env::var("RADVALIDATION_IGNORE").unwrap_or_default();
+		let ignored: Vec<_> = ignored.split(',').map(str::trim).filter(|x| !x.is_empty()).collect();
+		let mut builder = Device::builder();
+		if let Some(v) = validation.as_deref().filter(|&v| v != "0") {
+			builder = builder.validation(ValidationInfo {
+				gpu_assisted: v == "gpu",
+				ignored: &ignored,
+			});
+		}
+
 		engine.global(
-			Device::builder()
+			builder
 				.device_extensions(&[
-					ext::mesh_shader::NAME,
 					ext::shader_image_atomic_int64::NAME,
 					c"VK_KHR_shader_relaxed_extended_instruction",
 				])
@@ -21,6 +33,8 @@ impl Module for RhiModule {
 							vk::PhysicalDeviceFeatures::default()
 								.shader_int16(true)
 								.shader_int64(true)
+								.sample_rate_shading(true)
+								.texture_compression_bc(true)
 								.fragment_stores_and_atomics(true),
 						)
 						.push_next(
@@ -43,7 +57,6 @@ impl Module for RhiModule {
 								.dynamic_rendering(true)
 								.shader_demote_to_helper_invocation(true),
 						)
-						.push_next(&mut vk::PhysicalDeviceMeshShaderFeaturesEXT::default().mesh_shader(true))
 						.push_next(
 							&mut vk::PhysicalDeviceShaderImageAtomicInt64FeaturesEXT::default()
 								.shader_image_int64_atomics(true),
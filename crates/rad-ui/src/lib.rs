@@ -46,7 +46,13 @@ pub struct UiApp<T> {
 pub trait App {
 	fn render<'pass>(&'pass mut self, window: &mut Window, frame: &mut Frame<'pass, '_>, ctx: &Context) -> Result<()>;
 
+	/// The luminance of SDR white in nits, which the UI is drawn at on HDR outputs.
+	fn sdr_white(&self) -> f32 { 300.0 }
+
 	fn window_event(&mut self, _window: &mut Window, _event: &WindowEvent) {}
+
+	/// Called when the GPU device is lost, right before the event loop exits.
+	fn device_lost(&mut self) {}
 }
 
 impl<T: App> UiApp<T> {
@@ -101,6 +107,7 @@ impl<T: App> rad_window::App for UiApp<T> {
 			ScreenDescriptor {
 				physical_size: Vec2::new(window.inner_size().width, window.inner_size().height),
 				scaling: window.scale_factor() as _,
+				sdr_white: self.inner.sdr_white(),
 			},
 			image,
 		);
@@ -115,6 +122,8 @@ impl<T: App> rad_window::App for UiApp<T> {
 		let _ = self.state.as_mut().unwrap().on_window_event(window, &event);
 		Ok(())
 	}
+
+	fn device_lost(&mut self) { self.inner.device_lost(); }
 }
 
 impl<T> Drop for UiApp<T> {
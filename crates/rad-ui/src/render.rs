@@ -55,13 +55,16 @@ const INDEX_BUFFER_START_CAPACITY: u64 = (std::mem::size_of::<u32>() * 1024 * 3)
 pub struct ScreenDescriptor {
 	pub physical_size: Vec2<u32>,
 	pub scaling: f32,
+	/// The luminance of SDR white in nits, for HDR outputs and images.
+	pub sdr_white: f32,
 }
 
 pub struct Renderer {
 	images: FxHashMap<u64, (Persist<ImageView>, Vec2<u32>, SamplerId)>,
 	sdr: RenderPass<PushConstantsStatic>,
 	hdr: RenderPass<PushConstantsStatic>,
-	blit: FullscreenPass<ImageId>,
+	blit: FullscreenPass<BlitConstants>,
+	blit_scrgb: FullscreenPass<BlitConstants>,
 	vertex_size: u64,
 	index_size: u64,
 	default_sampler: SamplerId,
@@ -82,12 +85,25 @@ struct PushConstantsStatic {
 	vertex_buffer: GpuPtr<egui::epaint::Vertex>,
 }
 
+/// The encodings of images drawn in the UI, as `hdr_image` in `egui.slang`.
+const IMAGE_SDR: u32 = 0;
+const IMAGE_PQ: u32 = 1;
+const IMAGE_SCRGB: u32 = 2;
+
 #[repr(C)]
 #[derive(Copy, Clone, NoUninit)]
 struct PushConstantsDynamic {
 	image: ImageId,
 	sampler: SamplerId,
 	hdr_image: u32,
+	sdr_white: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, NoUninit)]
+struct BlitConstants {
+	image: ImageId,
+	sdr_white: f32,
 }
 
 impl Renderer {
@@ -104,6 +120,14 @@ impl Renderer {
 				},
 				&[vk::Format::A2B10G10R10_UNORM_PACK32],
 			)?,
+			blit_scrgb: FullscreenPass::new(
+				device,
+				ShaderInfo {
+					shader: "egui.blit_scrgb",
+					..Default::default()
+				},
+				&[vk::Format::R16G16B16A16_SFLOAT],
+			)?,
 			vertex_size: VERTEX_BUFFER_START_CAPACITY,
 			index_size: INDEX_BUFFER_START_CAPACITY,
 			default_sampler: device.sampler(SamplerDesc::default()),
@@ -172,7 +196,10 @@ impl Renderer {
 		}
 		let index = pass.resource(BufferDesc::upload(self.index_size), BufferUsage::index());
 
-		let hdr = out.format == vk::Format::A2B10G10R10_UNORM_PACK32;
+		let hdr = matches!(
+			out.format,
+			vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::R16G16B16A16_SFLOAT
+		);
 		// We need real alpha, so we can't use the swapchain HDR image
 		let outr = if hdr {
 			pass.resource(
@@ -217,6 +244,7 @@ impl Renderer {
 		}
 
 		let this = &*self;
+		let sdr_white = screen.sdr_white;
 		pass.build(move |pass| {
 			this.execute(
 				pass,
@@ -235,10 +263,15 @@ impl Renderer {
 		if hdr {
 			let mut pass = frame.pass("blit ui");
 			pass.reference(outr, ImageUsage::sampled_2d(Shader::Fragment));
+			let blit = if out.format == vk::Format::R16G16B16A16_SFLOAT {
+				&this.blit_scrgb
+			} else {
+				&this.blit
+			};
 			let out = pass.resource(out, ImageUsage::color_attachment());
 			pass.build(move |mut pass| {
-				let id = pass.get(outr).id.unwrap();
-				this.blit.run_one(&mut pass, &id, out);
+				let image = pass.get(outr).id.unwrap();
+				blit.run_one(&mut pass, &BlitConstants { image, sdr_white }, out);
 			});
 		}
 	}
@@ -248,6 +281,8 @@ impl Renderer {
 	pub unsafe fn destroy(self) {
 		self.sdr.destroy();
 		self.hdr.destroy();
+		self.blit.destroy();
+		self.blit_scrgb.destroy();
 	}
 
 	fn execute(&self, mut pass: PassContext, io: PassIO<'_>, tris: &[ClippedPrimitive], screen: &ScreenDescriptor) {
@@ -289,19 +324,21 @@ impl Renderer {
 						TextureId::Managed(x) => {
 							let &img = io.imgs.get(&x).unwrap();
 							let &(_, _, sampler) = self.images.get(&x).unwrap();
-							(pass.pass.get(img).id.unwrap(), sampler, false)
+							(pass.pass.get(img).id.unwrap(), sampler, IMAGE_SDR)
 						},
 						TextureId::User(x) => {
 							let masked = x & !(1 << 63);
 							let (image, hdr) = unsafe {
 								if masked != x {
-									(ImageId::from_raw(masked as _), false)
+									(ImageId::from_raw(masked as _), IMAGE_SDR)
 								} else {
 									let id = Res::<ImageView>::from_raw(masked as _);
-									(
-										pass.pass.get(id).id.unwrap(),
-										pass.pass.desc(id).format == vk::Format::A2B10G10R10_UNORM_PACK32,
-									)
+									let hdr = match pass.pass.desc(id).format {
+										vk::Format::A2B10G10R10_UNORM_PACK32 => IMAGE_PQ,
+										vk::Format::R16G16B16A16_SFLOAT => IMAGE_SCRGB,
+										_ => IMAGE_SDR,
+									};
+									(pass.pass.get(id).id.unwrap(), hdr)
 								}
 							};
 							(image, self.default_sampler, hdr)
@@ -312,7 +349,8 @@ impl Renderer {
 						&PushConstantsDynamic {
 							image,
 							sampler,
-							hdr_image: hdr_image as _,
+							hdr_image,
+							sdr_white: screen.sdr_white,
 						},
 					);
 					pass.draw_indexed(m.indices.len() as u32, 1, start_index, start_vertex, 0);
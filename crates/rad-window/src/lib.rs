@@ -5,6 +5,7 @@ use rad_graph::{
 	ash::{khr, vk},
 	device::{Device, Graphics, Queues, SyncPoint},
 	graph::SwapchainImage,
+	Error,
 	Result,
 };
 pub use winit;
@@ -59,9 +60,23 @@ impl<T: App> ApplicationHandler for AppWrapper<T> {
 		match event {
 			WindowEvent::RedrawRequested => {
 				let window = self.window.as_mut().unwrap();
-				let (image, id) = window.acquire().unwrap();
-				self.app.draw(window, image).unwrap();
-				let _ = window.present(id);
+				let res = window.acquire().and_then(|(image, id)| {
+					self.app.draw(window, image)?;
+					// An out of date swapchain is remade on the next resize, so only a lost device matters here.
+					match window.present(id) {
+						Err(Error::DeviceLost) => Err(Error::DeviceLost),
+						_ => Ok(()),
+					}
+				});
+				match Engine::get().global::<Device>().check_lost(res) {
+					Ok(()) => {},
+					// Nothing can be rendered anymore, so shut down cleanly instead of panicking.
+					Err(Error::DeviceLost) => {
+						self.app.device_lost();
+						el.exit();
+					},
+					Err(e) => panic!("failed to draw: {e}"),
+				}
 
 				tracy::frame!();
 			},
@@ -142,7 +157,16 @@ impl Window {
 		Ok(this)
 	}
 
-	pub fn hdr_enabled(&self) -> bool { self.format == vk::Format::A2B10G10R10_UNORM_PACK32 }
+	/// The format of the swapchain images.
+	pub fn format(&self) -> vk::Format { self.format }
+
+	/// If the swapchain is HDR10 or scRGB.
+	pub fn hdr_enabled(&self) -> bool {
+		matches!(
+			self.format,
+			vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::R16G16B16A16_SFLOAT
+		)
+	}
 
 	pub fn hdr_supported(&self) -> bool { self.hdr_supported }
 
@@ -225,9 +249,19 @@ impl Window {
 				surface_ext.get_physical_device_surface_capabilities(device.physical_device(), self.surface)?;
 			let formats = surface_ext.get_physical_device_surface_formats(device.physical_device(), self.surface)?;
 
-			let hdr_format = formats.iter().find(|x| {
-				x.format == vk::Format::A2B10G10R10_UNORM_PACK32 && x.color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT
-			});
+			// HDR10 is preferred, as scRGB needs twice the bandwidth.
+			let hdr_format = formats
+				.iter()
+				.find(|x| {
+					x.format == vk::Format::A2B10G10R10_UNORM_PACK32
+						&& x.color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT
+				})
+				.or_else(|| {
+					formats.iter().find(|x| {
+						x.format == vk::Format::R16G16B16A16_SFLOAT
+							&& x.color_space == vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
+					})
+				});
 			self.hdr_supported = hdr_format.is_some();
 
 			let (format, color_space) = self
@@ -345,4 +379,7 @@ pub trait App {
 	fn draw(&mut self, window: &mut Window, image: SwapchainImage) -> Result<()>;
 
 	fn event(&mut self, window: &mut Window, event: WindowEvent) -> Result<()>;
+
+	/// Called when the GPU device is lost, right before the event loop exits.
+	fn device_lost(&mut self) {}
 }
@@ -4,7 +4,7 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput, LitStr};
 
-#[proc_macro_derive(RadComponent, attributes(uuid))]
+#[proc_macro_derive(RadComponent, attributes(uuid, reflect))]
 pub fn component(input: TokenStream) -> TokenStream {
 	let inp: proc_macro2::TokenStream = input.clone().into();
 	let i = parse_macro_input!(input as DeriveInput);
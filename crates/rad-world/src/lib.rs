@@ -21,6 +21,7 @@ use rustc_hash::FxHashMap;
 pub use crate::tick::TickStage;
 use crate::{self as rad_world};
 
+pub mod name;
 pub mod serde;
 pub mod tick;
 pub mod transform;
@@ -67,6 +68,7 @@ impl Module for WorldModule {
 		engine.asset::<World>();
 
 		engine.component::<transform::Transform>();
+		engine.component::<name::Name>();
 	}
 }
 
@@ -79,6 +81,9 @@ pub trait RadComponent {
 	fn uuid_dyn(&self) -> Uuid;
 }
 
+/// Written before the format version of a world.
+const VERSIONED: u32 = u32::MAX;
+
 pub struct World {
 	inner: bevy_ecs::world::World,
 }
@@ -112,10 +117,24 @@ impl Asset for World {
 	fn load(mut data: Box<dyn AssetRead>) -> Result<Self, io::Error> {
 		let c = bincode::config::standard();
 
-		let count: u32 = bincode::decode_from_std_read(&mut data, c).map_err(map_dec_err)?;
+		// Worlds from before the format was versioned start with the entity count instead, which is never `VERSIONED`.
+		let first: u32 = bincode::decode_from_std_read(&mut data, c).map_err(map_dec_err)?;
+		let (version, count) = if first == VERSIONED {
+			let version: u32 = bincode::decode_from_std_read(&mut data, c).map_err(map_dec_err)?;
+			if version > serde::WORLD_VERSION {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("world saved in format {version}, which is newer than this version supports"),
+				));
+			}
+			let count: u32 = bincode::decode_from_std_read(&mut data, c).map_err(map_dec_err)?;
+			(version, count)
+		} else {
+			(0, first)
+		};
 		let mut inner = bevy_ecs::world::World::new();
 		for _ in 0..count {
-			serde::deserialize_entity(&mut data, &mut inner)?;
+			serde::deserialize_entity(&mut data, &mut inner, version)?;
 		}
 
 		Ok(Self { inner })
@@ -123,7 +142,13 @@ impl Asset for World {
 
 	fn save(&self, mut to: &mut dyn AssetWrite) -> Result<(), io::Error> {
 		let c = bincode::config::standard();
-		let count = self.inner.entities().len();
+		bincode::encode_into_std_write(VERSIONED, &mut to, c).map_err(map_enc_err)?;
+		bincode::encode_into_std_write(serde::WORLD_VERSION, &mut to, c).map_err(map_enc_err)?;
+		let count = self
+			.inner
+			.iter_entities()
+			.filter(|en| !en.contains::<serde::DoNotSerialize>())
+			.count() as u32;
 		bincode::encode_into_std_write(count, &mut to, c).map_err(map_enc_err)?;
 
 		for en in self.inner.iter_entities() {
@@ -0,0 +1,6 @@
+use crate::{rad_world, RadComponent};
+
+/// A human-readable name for an entity, shown in the editor.
+#[derive(Clone, Debug, PartialEq, RadComponent)]
+#[uuid("0b6a4f9e-5d2c-4e8b-9a1f-3c7d2e8f6b45")]
+pub struct Name(pub String);
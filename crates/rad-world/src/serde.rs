@@ -18,11 +18,13 @@ use bevy_reflect::{
 	DynamicVariant,
 	Map,
 	PartialReflect,
+	Reflect,
 	ReflectDeserialize,
 	ReflectFromReflect,
 	ReflectRef,
 	ReflectSerialize,
 	Set,
+	StructInfo,
 	TypeInfo,
 	TypeRegistration,
 	VariantInfo,
@@ -46,24 +48,48 @@ use crate::{ty_reg, uuid_to_ty, ReflectRadComponent};
 #[derive(Copy, Clone, Component)]
 pub struct DoNotSerialize;
 
+/// The world format written by `World::save`. Worlds from before version 1 have no header, and don't store how many
+/// fields each component has.
+pub(crate) const WORLD_VERSION: u32 = 1;
+
+/// Marks a component field as added in world format version `.0`, so older worlds don't store it. The field must also
+/// be `#[reflect(default)]`, which is what it loads as from those worlds.
+///
+/// Fields added since version 1 don't need this, as long as they're added after the existing ones.
+#[derive(Copy, Clone, Reflect)]
+pub struct Since(pub u32);
+
 pub fn serialize_entity(mut into: &mut dyn io::Write, world: &World, en: EntityRef) -> Result<(), io::Error> {
 	if en.contains::<DoNotSerialize>() {
 		return Ok(());
 	}
 
+	// Components that aren't `RadComponent`s, such as state added by systems, are not saved.
+	let comps: Vec<_> = en
+		.archetype()
+		.components()
+		.map(|comp| world.components().get_info(comp).unwrap())
+		.filter(|info| is_rad_component(info))
+		.collect();
+
 	let c = bincode::config::standard();
 	bincode::encode_into_std_write(en.id().index(), &mut into, c).map_err(map_enc_err)?;
-	let count = en.archetype().component_count() as u32;
+	let count = comps.len() as u32;
 	bincode::encode_into_std_write(count, &mut into, c).map_err(map_enc_err)?;
 
-	for comp in en.archetype().components() {
-		let info = world.components().get_info(comp).unwrap();
+	for info in comps {
 		serialize_component(&mut into, en, info)?;
 	}
 
 	Ok(())
 }
 
+fn is_rad_component(info: &ComponentInfo) -> bool {
+	info.type_id()
+		.and_then(|ty| ty_reg().get(ty))
+		.is_some_and(|reg| reg.data::<ReflectRadComponent>().is_some())
+}
+
 fn serialize_component(mut into: &mut dyn io::Write, en: EntityRef, info: &ComponentInfo) -> Result<(), io::Error> {
 	let c = bincode::config::standard();
 
@@ -99,7 +125,7 @@ fn serialize_component(mut into: &mut dyn io::Write, en: EntityRef, info: &Compo
 	Ok(())
 }
 
-pub fn deserialize_entity(mut from: &mut dyn io::Read, world: &mut World) -> Result<(), io::Error> {
+pub fn deserialize_entity(mut from: &mut dyn io::Read, world: &mut World, version: u32) -> Result<(), io::Error> {
 	let c = bincode::config::standard();
 	let id = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
 	#[allow(deprecated)]
@@ -107,15 +133,20 @@ pub fn deserialize_entity(mut from: &mut dyn io::Read, world: &mut World) -> Res
 	let count: u32 = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
 
 	for _ in 0..count {
-		deserialize_component(&mut from, &mut en)?;
+		deserialize_component(&mut from, &mut en, version)?;
 	}
 
 	Ok(())
 }
 
-fn deserialize_component(mut from: &mut dyn io::Read, en: &mut EntityWorldMut) -> Result<(), io::Error> {
+fn deserialize_component(mut from: &mut dyn io::Read, en: &mut EntityWorldMut, version: u32) -> Result<(), io::Error> {
 	let c = bincode::config::standard();
-	let comp: CompenentDecoder = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
+	let comp = if version == 0 {
+		bincode::decode_from_std_read::<CompenentDecoder<0>, _, _>(&mut from, c)
+	} else {
+		bincode::decode_from_std_read::<CompenentDecoder<WORLD_VERSION>, _, _>(&mut from, c)
+	}
+	.map_err(map_dec_err)?;
 	comp.refl.insert(en, comp.obj.as_partial_reflect(), ty_reg());
 
 	Ok(())
@@ -129,18 +160,25 @@ struct ComponentEncoder<'a> {
 impl Encode for ComponentEncoder<'_> {
 	fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
 		Encode::encode(self.uuid.as_bytes(), encoder)?;
+		// Stored so that fields can be added to components without breaking saved worlds.
+		let fields = match self.comp.reflect_ref() {
+			ReflectRef::Struct(x) => x.field_len() as u32,
+			_ => 0,
+		};
+		Encode::encode(&fields, encoder)?;
 		Encode::encode(&DynEncoder { val: self.comp }, encoder)?;
 
 		Ok(())
 	}
 }
 
-struct CompenentDecoder {
+/// Decodes a component saved in a world of format `VERSION`.
+struct CompenentDecoder<const VERSION: u32> {
 	refl: &'static ReflectComponent,
 	obj: Box<dyn PartialReflect>,
 }
 
-impl Decode for CompenentDecoder {
+impl<const VERSION: u32> Decode for CompenentDecoder<VERSION> {
 	fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
 		let uuid = Uuid::from_bytes(Decode::decode(decoder)?);
 		let id = uuid_to_ty(uuid).ok_or_else(|| DecodeError::Io {
@@ -158,12 +196,55 @@ impl Decode for CompenentDecoder {
 			),
 			additional: 0,
 		})?;
-		let obj = DynDecoder { reg }.decode(decoder)?;
+		let fields = if VERSION == 0 {
+			None
+		} else {
+			Some(u32::decode(decoder)? as usize)
+		};
+		let obj = match reg.type_info() {
+			TypeInfo::Struct(x) => decode_fields(reg, x, VERSION, fields, decoder)?,
+			_ => DynDecoder { reg }.decode(decoder)?,
+		};
 
 		Ok(Self { refl, obj })
 	}
 }
 
+/// Decode a component struct that was saved with `fields` fields, or if the world didn't store that, with the fields
+/// that existed in world format `version`. Fields that weren't saved take their `#[reflect(default)]`.
+fn decode_fields(
+	reg: &'static TypeRegistration, info: &StructInfo, version: u32, fields: Option<usize>, decoder: &mut impl Decoder,
+) -> Result<Box<dyn PartialReflect>, DecodeError> {
+	let invalid = |msg: &str| DecodeError::Io {
+		inner: io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("component (`{}`) {msg}", reg.type_info().type_path()),
+		),
+		additional: 0,
+	};
+
+	if fields.is_some_and(|x| x > info.field_len()) {
+		return Err(invalid("saved with more fields than it has, by a newer version"));
+	}
+	let mut s = DynamicStruct::default();
+	for (i, field) in info.iter().enumerate() {
+		let saved = match fields {
+			Some(x) => i < x,
+			None => field.get_attribute::<Since>().map_or(true, |x| x.0 <= version),
+		};
+		if saved {
+			let reg = ty_reg().get(field.ty().id()).unwrap();
+			s.insert_boxed(field.name(), DynDecoder { reg }.decode(decoder)?);
+		}
+	}
+
+	reg.data::<ReflectFromReflect>()
+		.ok_or_else(|| invalid("not unreflectable"))?
+		.from_reflect(&s)
+		.map(|x| x.into_partial_reflect())
+		.ok_or_else(|| invalid("missing fields without a `#[reflect(default)]`"))
+}
+
 struct DynEncoder<'a> {
 	val: &'a dyn PartialReflect,
 }
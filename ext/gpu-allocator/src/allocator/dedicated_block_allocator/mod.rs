@@ -127,6 +127,10 @@ impl SubAllocator for DedicatedBlockAllocator {
         self.allocated
     }
 
+    fn allocation_count(&self) -> usize {
+        usize::from(self.allocated != 0)
+    }
+
     fn supports_general_allocations(&self) -> bool {
         false
     }
@@ -410,6 +410,13 @@ impl SubAllocator for FreeListAllocator {
         self.allocated
     }
 
+    fn allocation_count(&self) -> usize {
+        self.chunks
+            .values()
+            .filter(|chunk| chunk.allocation_type != AllocationType::Free)
+            .count()
+    }
+
     fn supports_general_allocations(&self) -> bool {
         true
     }
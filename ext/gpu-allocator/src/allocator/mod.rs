@@ -137,6 +137,8 @@ pub(crate) trait SubAllocator: SubAllocatorBase + fmt::Debug + Sync + Send {
     fn supports_general_allocations(&self) -> bool;
     #[must_use]
     fn allocated(&self) -> u64;
+    #[must_use]
+    fn allocation_count(&self) -> usize;
 
     /// Helper function: reports if the suballocator is empty (meaning, having no allocations).
     #[must_use]
@@ -627,6 +627,20 @@ impl MemoryType {
 	}
 }
 
+/// The usage of a single memory type, see [`Allocator::memory_type_reports`].
+#[derive(Clone, Debug)]
+pub struct MemoryTypeReport {
+	pub memory_type_index: usize,
+	pub heap_index: usize,
+	pub memory_properties: vk::MemoryPropertyFlags,
+	/// Sum of the memory used by all allocations, in bytes.
+	pub allocated_bytes: u64,
+	/// Sum of the memory reserved by all memory blocks including unallocated regions, in bytes.
+	pub reserved_bytes: u64,
+	/// The number of live allocations.
+	pub allocation_count: usize,
+}
+
 pub struct Allocator {
 	pub(crate) memory_types: Vec<MemoryType>,
 	pub(crate) memory_heaps: Vec<vk::MemoryHeap>,
@@ -827,6 +841,25 @@ impl Allocator {
 			.map(|memory_type| memory_type.memory_type_index as _)
 	}
 
+	/// A cheaper alternative to [`Self::generate_report`] that only sums up every memory type, without reporting
+	/// individual allocations.
+	pub fn memory_type_reports(&self) -> Vec<MemoryTypeReport> {
+		self.memory_types
+			.iter()
+			.map(|memory_type| {
+				let blocks = memory_type.memory_blocks.iter().flatten();
+				MemoryTypeReport {
+					memory_type_index: memory_type.memory_type_index,
+					heap_index: memory_type.heap_index,
+					memory_properties: memory_type.memory_properties,
+					allocated_bytes: blocks.clone().map(|block| block.sub_allocator.allocated()).sum(),
+					reserved_bytes: blocks.clone().map(|block| block.size).sum(),
+					allocation_count: blocks.map(|block| block.sub_allocator.allocation_count()).sum(),
+				}
+			})
+			.collect()
+	}
+
 	pub fn generate_report(&self) -> AllocatorReport {
 		let mut allocations = vec![];
 		let mut blocks = vec![];